@@ -1,14 +1,40 @@
 //! Kode CLI 主程序
 
+mod mcp;
+
 use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+/// Kode 命令行
+#[derive(Parser)]
+#[command(name = "kode", version, about = "AI coding assistant")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// MCP 相关命令
+    Mcp {
+        #[command(subcommand)]
+        command: mcp::McpCommand,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 初始化日志
-    tracing_subscriber::fmt::init();
-
-    println!("Kode CLI v{}", env!("CARGO_PKG_VERSION"));
-    println!("项目骨架搭建完成！");
+    // 初始化日志（写到 stderr，stdout 可能承载 MCP 协议）
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
-    Ok(())
+    match Cli::parse().command {
+        Some(Command::Mcp { command }) => mcp::run(command).await,
+        None => {
+            println!("Kode CLI v{}", env!("CARGO_PKG_VERSION"));
+            println!("项目骨架搭建完成！");
+            Ok(())
+        }
+    }
 }
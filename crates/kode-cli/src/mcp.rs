@@ -0,0 +1,173 @@
+//! `kode mcp` 子命令
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use kode_core::agent::AgentLoader;
+use kode_core::config::types::{GlobalConfig, McpServerConfig};
+use kode_core::config::{
+    decide_mcprc_server, get_current_project_config, get_global_config, load_mcp_servers,
+};
+use kode_core::permission::{PermissionEngine, PermissionRules};
+use kode_services::mcp::{HttpTransportOptions, McpManager, McpServerStatus};
+use kode_tools::{
+    ApplyPatchTool, BashOutputTool, BashTool, FileWriteTool, GlobTool, GrepTool, KillBashTool,
+    McpServer, MultiEditTool, NotebookEditTool, NotebookReadTool, TodoReadTool, TodoWriteTool,
+    ToolContext, ToolRegistry, WebFetchTool,
+};
+use std::sync::Arc;
+
+/// `kode mcp` 子命令
+#[derive(Subcommand)]
+pub enum McpCommand {
+    /// 以 MCP 服务器模式运行，通过 stdio 提供内置工具
+    Serve {
+        /// 只提供此 Agent 的工具过滤器允许的工具
+        #[arg(long)]
+        agent: Option<String>,
+        /// 同时把可用的 Agent 暴露为 `agent_<name>` 工具
+        ///
+        /// 运行 Agent 需要模型后端，当前构建尚未包含，指定后启动时报错。
+        #[arg(long)]
+        agents: bool,
+    },
+    /// 列出配置的 MCP 服务器及其连接状态
+    List,
+    /// 批准当前目录 .mcprc 中的服务器，之后的会话会启动它
+    Approve {
+        /// 服务器名称
+        name: String,
+    },
+    /// 拒绝当前目录 .mcprc 中的服务器
+    Reject {
+        /// 服务器名称
+        name: String,
+    },
+}
+
+/// 执行 `kode mcp` 子命令
+pub async fn run(command: McpCommand) -> Result<()> {
+    match command {
+        McpCommand::Serve { agent, agents } => serve(agent, agents).await,
+        McpCommand::List => list().await,
+        McpCommand::Approve { name } => decide(&name, true).await,
+        McpCommand::Reject { name } => decide(&name, false).await,
+    }
+}
+
+/// 记录对 .mcprc 服务器的决定
+///
+/// 决定绑定到当前配置，.mcprc 中的命令、参数或环境变量变化后需要重新批准。
+async fn decide(name: &str, approved: bool) -> Result<()> {
+    let config = decide_mcprc_server(name, approved).await?;
+    let verb = if approved { "Approved" } else { "Rejected" };
+    println!("{} .mcprc server '{}': {}", verb, name, describe(&config));
+    Ok(())
+}
+
+/// 服务器配置的简短描述，让用户看清批准的是什么
+fn describe(config: &McpServerConfig) -> String {
+    match config {
+        McpServerConfig::Stdio(stdio) => {
+            let mut line = std::iter::once(stdio.command.as_str())
+                .chain(stdio.args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut env: Vec<_> = stdio.env.iter().flatten().map(|(key, _)| key).collect();
+            if !env.is_empty() {
+                env.sort();
+                line.push_str(&format!(
+                    " (env: {})",
+                    env.into_iter().cloned().collect::<Vec<_>>().join(", ")
+                ));
+            }
+            line
+        }
+        McpServerConfig::Sse(sse) => sse.url.clone(),
+        McpServerConfig::Http(http) => http.url.clone(),
+    }
+}
+
+async fn list() -> Result<()> {
+    let global = get_global_config().await?;
+    let servers = load_mcp_servers(None).await?;
+    if servers.is_empty() {
+        println!("No MCP servers configured.");
+        return Ok(());
+    }
+
+    let manager = Arc::new(
+        McpManager::new(servers)
+            .with_http_options(HttpTransportOptions::new().with_global_config(&global)),
+    );
+    manager.start().await;
+    let states = manager.status();
+    manager.shutdown().await;
+
+    for state in &states {
+        let mut line = format!(
+            "{} ({}, {}): {}",
+            state.name, state.scope, state.transport, state.status
+        );
+        if let Some(info) = &state.server_info {
+            line.push_str(&format!(" - {} {}", info.name, info.version));
+        }
+        println!("{}", line);
+    }
+    if states
+        .iter()
+        .any(|state| state.status == McpServerStatus::PendingApproval)
+    {
+        println!("\nServers from .mcprc start only after you run `kode mcp approve <name>`.");
+    }
+    Ok(())
+}
+
+async fn serve(agent: Option<String>, expose_agents: bool) -> Result<()> {
+    // 没有模型后端时 Agent 工具的调用必然失败，不把它们提供给客户端
+    if expose_agents {
+        bail!(
+            "`kode mcp serve --agents` is not supported yet: no model backend is available to run agents"
+        );
+    }
+
+    let global = get_global_config().await?;
+    let project = get_current_project_config().await?;
+    let cwd = std::env::current_dir().context("Cannot get current directory")?;
+
+    let rules = PermissionRules::from_configs(&global, &project);
+    let context = ToolContext::new(&cwd)
+        .with_project_config(project)
+        .with_permissions(Arc::new(PermissionEngine::new(&cwd, rules)));
+
+    let mut loader = AgentLoader::new()?;
+    let mut server = McpServer::new(builtin_tools(&global), context);
+    if let Some(name) = agent {
+        let agent = loader.load_agent(&name).await?;
+        let (filtered, warnings) = server.with_agent_filter(&agent);
+        for warning in warnings {
+            tracing::warn!("{}", warning);
+        }
+        server = filtered;
+    }
+    tracing::info!("Serving {} tools over MCP stdio", server.tools().len());
+    Arc::new(server).serve_stdio().await
+}
+
+/// 不依赖模型的内置工具
+fn builtin_tools(global: &GlobalConfig) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry.register(Arc::new(BashTool::new()));
+    registry.register(Arc::new(BashOutputTool::new()));
+    registry.register(Arc::new(KillBashTool::new()));
+    registry.register(Arc::new(FileWriteTool::new()));
+    registry.register(Arc::new(MultiEditTool::new()));
+    registry.register(Arc::new(ApplyPatchTool::new()));
+    registry.register(Arc::new(GlobTool::new()));
+    registry.register(Arc::new(GrepTool::new()));
+    registry.register(Arc::new(NotebookReadTool::new()));
+    registry.register(Arc::new(NotebookEditTool::new()));
+    registry.register(Arc::new(TodoReadTool::new()));
+    registry.register(Arc::new(TodoWriteTool::new()));
+    registry.register(Arc::new(WebFetchTool::new().with_global_config(global)));
+    registry
+}
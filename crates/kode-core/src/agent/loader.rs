@@ -178,6 +178,7 @@ impl AgentLoader {
                 name: "general-purpose".to_string(),
                 description: "General-purpose agent for researching complex questions, searching for code, and executing multi-step tasks".to_string(),
                 tools: crate::agent::types::ToolFilter::All,
+                disallowed_tools: Vec::new(),
                 model: None,
                 system_prompt: r#"You are a general-purpose agent. Given the user's task, use the tools available to complete it efficiently and thoroughly.
 
@@ -407,6 +408,7 @@ Guidelines:
 
         // 解析 tools 字段
         let tools = self.parse_tools_field(yaml.get("tools"));
+        let disallowed_tools = self.parse_disallowed_tools_field(&yaml);
 
         // 解析可选的 model_name 字段
         let model = yaml
@@ -425,6 +427,7 @@ Guidelines:
             name,
             description,
             tools,
+            disallowed_tools,
             model,
             system_prompt: markdown_body.to_string(),
             location: AgentLocation::Project, // 默认值，调用者会覆盖
@@ -458,6 +461,7 @@ Guidelines:
             .to_string();
 
         let tools = self.parse_tools_field(yaml.get("tools"));
+        let disallowed_tools = self.parse_disallowed_tools_field(&yaml);
 
         let model = yaml
             .get("model_name")
@@ -485,6 +489,7 @@ Guidelines:
             name,
             description,
             tools,
+            disallowed_tools,
             model,
             system_prompt,
             location: AgentLocation::Project, // 默认值，调用者会覆盖
@@ -529,6 +534,23 @@ Guidelines:
         }
     }
 
+    /// 解析 disallowed_tools 字段
+    ///
+    /// 支持 `disallowed_tools` 和 `disallowedTools`，值可以是字符串或字符串数组。
+    fn parse_disallowed_tools_field(&self, yaml: &Value) -> Vec<String> {
+        match yaml
+            .get("disallowed_tools")
+            .or_else(|| yaml.get("disallowedTools"))
+        {
+            Some(Value::String(s)) => vec![s.to_string()],
+            Some(Value::Sequence(arr)) => arr
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
     /// 清除缓存
     pub fn clear_cache(&mut self) {
         self.cache.clear();
@@ -657,6 +679,39 @@ Your job is to test things."#;
         assert!(agent.system_prompt.contains("test agent"));
     }
 
+    #[tokio::test]
+    async fn test_parse_agent_disallowed_tools() {
+        let loader = AgentLoader::new().unwrap();
+        let content = r#"---
+name: github-agent
+description: "Works with GitHub"
+tools:
+  - Grep
+  - mcp__github__*
+disallowed_tools:
+  - mcp__github__delete_*
+---
+
+You manage issues."#;
+
+        let agent = loader.parse_agent(content).unwrap();
+        assert_eq!(agent.disallowed_tools, vec!["mcp__github__delete_*"]);
+        assert!(agent.allows_tool("mcp__github__create_issue"));
+        assert!(!agent.allows_tool("mcp__github__delete_repo"));
+
+        let content = r#"---
+name: no-bash
+description: "Everything except Bash"
+disallowedTools: Bash
+---
+
+Prompt"#;
+        let agent = loader.parse_agent(content).unwrap();
+        assert!(matches!(agent.tools, ToolFilter::All));
+        assert!(!agent.allows_tool("Bash"));
+        assert!(agent.allows_tool("Grep"));
+    }
+
     #[tokio::test]
     async fn test_parse_agent_all_tools() {
         let loader = AgentLoader::new().unwrap();
@@ -33,9 +33,6 @@ use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
-#[cfg(test)]
-use serial_test::serial;
-
 /// 获取 Kode 配置目录
 ///
 /// 优先级:
@@ -306,7 +303,7 @@ mod tests {
         let id1 = generate_agent_id();
         let id2 = generate_agent_id();
         assert_ne!(id1, id2);
-        assert!(id1.len() > 0);
+        assert!(!id1.is_empty());
     }
 
     #[test]
@@ -36,6 +36,7 @@ pub enum AgentLocation {
 ///     name: "code-reviewer".to_string(),
 ///     description: "Reviews code for best practices".to_string(),
 ///     tools: ToolFilter::Specific(vec!["FileRead".to_string(), "Grep".to_string()]),
+///     disallowed_tools: vec![],
 ///     model: Some("claude-sonnet".to_string()),
 ///     system_prompt: "You are an expert code reviewer.".to_string(),
 ///     location: AgentLocation::User,
@@ -59,6 +60,13 @@ pub struct Agent {
     /// 定义 Agent 可以访问哪些工具。
     pub tools: ToolFilter,
 
+    /// 禁止使用的工具
+    ///
+    /// 对应 frontmatter 中的 `disallowed_tools` 字段，支持 `*` 通配符，
+    /// 优先级高于 `tools`。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disallowed_tools: Vec<String>,
+
     /// 可选的模型覆盖
     ///
     /// 如果指定，使用此模型而不是默认模型。
@@ -105,6 +113,7 @@ impl Agent {
             name,
             description,
             tools,
+            disallowed_tools: Vec::new(),
             model: None,
             system_prompt,
             location,
@@ -112,6 +121,36 @@ impl Agent {
         }
     }
 
+    /// 设置禁止使用的工具
+    pub fn with_disallowed_tools(mut self, tools: Vec<String>) -> Self {
+        self.disallowed_tools = tools;
+        self
+    }
+
+    /// 检查 Agent 是否可以使用指定工具
+    ///
+    /// 工具需要被 `tools` 允许，且不匹配 `disallowed_tools` 中的任何模式。
+    pub fn allows_tool(&self, tool_name: &str) -> bool {
+        self.tools.allows(tool_name)
+            && !self
+                .disallowed_tools
+                .iter()
+                .any(|pattern| wildcard_match(pattern, tool_name))
+    }
+
+    /// Agent 文件中引用的具体工具名（不含通配符模式）
+    ///
+    /// 用于检查 Agent 是否引用了不存在的工具。
+    pub fn referenced_tools(&self) -> impl Iterator<Item = &str> {
+        self.tools
+            .tools()
+            .unwrap_or_default()
+            .iter()
+            .chain(&self.disallowed_tools)
+            .map(String::as_str)
+            .filter(|name| !is_pattern(name))
+    }
+
     /// 设置模型覆盖
     pub fn with_model(mut self, model: String) -> Self {
         self.model = Some(model);
@@ -144,10 +183,14 @@ impl Agent {
 ///     "Grep".to_string(),
 /// ]);
 ///
+/// // 支持 `*` 通配符
+/// let github_tools = ToolFilter::Specific(vec!["mcp__github__*".to_string()]);
+///
 /// // 检查是否可以访问某个工具
 /// assert!(all_tools.allows("FileRead"));
 /// assert!(specific_tools.allows("FileRead"));
 /// assert!(!specific_tools.allows("Bash"));
+/// assert!(github_tools.allows("mcp__github__create_issue"));
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(untagged)]
@@ -160,7 +203,8 @@ pub enum ToolFilter {
 
     /// 访问指定的工具列表
     ///
-    /// 对应 TypeScript 中的字符串数组。
+    /// 对应 TypeScript 中的字符串数组。条目可以包含 `*` 通配符，
+    /// 例如 `mcp__github__*`。
     Specific(Vec<String>),
 }
 
@@ -177,7 +221,7 @@ impl ToolFilter {
     pub fn allows(&self, tool_name: &str) -> bool {
         match self {
             ToolFilter::All => true,
-            ToolFilter::Specific(tools) => tools.iter().any(|t| t == tool_name),
+            ToolFilter::Specific(tools) => tools.iter().any(|t| wildcard_match(t, tool_name)),
         }
     }
 
@@ -193,6 +237,33 @@ impl ToolFilter {
     }
 }
 
+/// 是否为包含通配符的模式
+fn is_pattern(name: &str) -> bool {
+    name.contains('*')
+}
+
+/// 匹配 `*` 通配符（`*` 匹配任意长度的字符）
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // 没有通配符，要求完全相同
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +325,48 @@ mod tests {
         assert_eq!(tools[1], "Grep");
     }
 
+    #[test]
+    fn test_wildcard_match() {
+        let cases = [
+            ("mcp__github__*", "mcp__github__create_issue", true),
+            ("mcp__github__*", "mcp__gitlab__create_issue", false),
+            ("*", "Bash", true),
+            ("File*", "FileRead", true),
+            ("*Edit", "MultiEdit", true),
+            ("*Edit", "EditNotes", false),
+            ("mcp__*__search", "mcp__docs__search", true),
+            ("Bash", "BashOutput", false),
+            ("a*a", "a", false),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(
+                wildcard_match(pattern, name),
+                expected,
+                "{} {}",
+                pattern,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_agent_allows_tool() {
+        let agent = Agent::new(
+            "reviewer".to_string(),
+            "Reviews code".to_string(),
+            ToolFilter::Specific(vec!["Grep".to_string(), "mcp__github__*".to_string()]),
+            "Prompt".to_string(),
+            AgentLocation::User,
+        )
+        .with_disallowed_tools(vec!["mcp__github__delete_*".to_string()]);
+
+        assert!(agent.allows_tool("Grep"));
+        assert!(agent.allows_tool("mcp__github__create_issue"));
+        assert!(!agent.allows_tool("mcp__github__delete_repo"));
+        assert!(!agent.allows_tool("Bash"));
+        assert_eq!(agent.referenced_tools().collect::<Vec<_>>(), vec!["Grep"]);
+    }
+
     #[test]
     fn test_tool_filter_default() {
         let filter: ToolFilter = Default::default();
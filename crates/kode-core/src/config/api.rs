@@ -243,7 +243,7 @@ mod tests {
         let config = get_global_config().await.unwrap();
         // 应该返回默认配置
         assert_eq!(config.num_startups, 0);
-        assert_eq!(config.verbose, false);
+        assert!(!config.verbose);
     }
 
     #[tokio::test]
@@ -256,9 +256,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_save_and_load_global_config() {
-        let mut config = GlobalConfig::default();
-        config.theme = Some("dark".to_string());
-        config.verbose = true;
+        let config = GlobalConfig {
+            theme: Some("dark".to_string()),
+            verbose: true,
+            ..Default::default()
+        };
 
         // 由于 get_config_file_path() 返回固定路径，我们直接测试 filter_default_fields 函数
         let filtered = filter_default_fields(&config);
@@ -270,8 +272,10 @@ mod tests {
 
     #[test]
     fn test_filter_default_fields() {
-        let mut config = GlobalConfig::default();
-        config.theme = Some("dark".to_string());
+        let config = GlobalConfig {
+            theme: Some("dark".to_string()),
+            ..Default::default()
+        };
 
         let filtered = filter_default_fields(&config);
 
@@ -284,7 +284,7 @@ mod tests {
 
     #[test]
     fn test_validate_and_repair_gpt5_profile() {
-        let mut profile = ModelProfile {
+        let profile = ModelProfile {
             name: "test-gpt5".to_string(),
             provider: ProviderType::Openai,
             model_name: "gpt-5".to_string(),
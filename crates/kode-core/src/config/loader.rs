@@ -204,7 +204,7 @@ mod tests {
         let config = loader.load(temp_file.path()).await.unwrap();
 
         assert_eq!(config.theme, Some("dark".to_string()));
-        assert_eq!(config.verbose, true);
+        assert!(config.verbose);
     }
 
     #[tokio::test]
@@ -216,7 +216,7 @@ mod tests {
             .unwrap();
 
         // 应该返回默认配置
-        assert_eq!(config.verbose, false);
+        assert!(!config.verbose);
         assert_eq!(config.num_startups, 0);
     }
 
@@ -225,8 +225,10 @@ mod tests {
         let temp_dir = tempfile::tempdir().unwrap();
         let config_path = temp_dir.path().join("config.json");
 
-        let mut config = GlobalConfig::default();
-        config.theme = Some("light".to_string());
+        let config = GlobalConfig {
+            theme: Some("light".to_string()),
+            ..Default::default()
+        };
 
         let loader = ConfigLoader::new();
         loader.save(&config, &config_path).await.unwrap();
@@ -240,8 +242,10 @@ mod tests {
         let temp_dir = tempfile::tempdir().unwrap();
         let config_path = temp_dir.path().join("config.json");
 
-        let mut global_config = GlobalConfig::default();
-        global_config.projects = Some(HashMap::new());
+        let mut global_config = GlobalConfig {
+            projects: Some(HashMap::new()),
+            ..Default::default()
+        };
 
         let project_config = ProjectConfig {
             allowed_tools: vec!["test_tool".to_string()],
@@ -1,48 +1,59 @@
 //! MCP (.mcprc) 文件支持
 //!
-//! 提供项目级 MCP 服务器配置功能。
-
-use crate::config::types::McpServerConfig;
+//! 提供项目级 MCP 服务器配置功能。`.mcprc` 随仓库分发，其中的服务器在用户批准前
+//! 不会启动：首次遇到时通过 [`McprcApprovalPrompt`] 询问（或由 `kode mcp approve` /
+//! `kode mcp reject` 决定），决定记录在项目配置的 `approved_mcprc_servers` /
+//! `rejected_mcprc_servers` 中，同时记录决定时的服务器配置。
+
+use crate::config::api::{
+    get_current_project_config, get_global_config, save_current_project_config,
+};
+use crate::config::types::{GlobalConfig, McpServerConfig, ProjectConfig};
 use crate::error::Error;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
+/// .mcprc 文件名
+pub const MCPRC_FILE_NAME: &str = ".mcprc";
+
 /// 获取 .mcprc 配置
 ///
-/// 读取当前目录的 .mcprc 文件，返回 MCP 服务器配置
-/// 使用 memoize 优化（基于文件内容）
+/// 读取当前目录的 .mcprc 文件，返回 MCP 服务器配置。
+/// 文件不存在或为空时返回空配置；内容无效时返回带行列号的解析错误。
 ///
 /// # Returns
 ///
 /// 返回 MCP 服务器配置的 JSON 字符串
 pub async fn get_mcprc_config() -> Result<String, Error> {
-    let mcprc_path = get_mcprc_path()?;
-
-    // 检查文件是否存在
-    if !mcprc_path.exists() {
-        let empty = serde_json::json!({});
-        return serde_json::to_string(&empty)
-            .map_err(|e| Error::ConfigError(format!("Failed to serialize: {}", e)));
-    }
-
-    // 读取文件内容（容忍读取错误）
-    let content = fs::read_to_string(&mcprc_path)
-        .await
-        .unwrap_or_else(|_| String::new());
+    let servers = McprcFile::current()?.load().await?;
+    serde_json::to_string(&servers)
+        .map_err(|e| Error::ConfigError(format!("Failed to serialize config: {}", e)))
+}
 
-    // 如果文件为空或读取失败，返回空配置
-    if content.trim().is_empty() {
-        let empty = serde_json::json!({});
-        return serde_json::to_string(&empty)
-            .map_err(|e| Error::ConfigError(format!("Failed to serialize: {}", e)));
-    }
+/// 列出当前目录 .mcprc 中的服务器（按名称排序）
+pub async fn list_mcprc_servers() -> Result<BTreeMap<String, McpServerConfig>, Error> {
+    McprcFile::current()?.load().await
+}
 
-    // 解析 JSON（容忍解析错误）
-    let config: serde_json::Value = serde_json::from_str(&content).unwrap_or(serde_json::json!({}));
+/// 向当前目录的 .mcprc 添加服务器
+///
+/// # Arguments
+///
+/// * `name` - 服务器名称
+/// * `server_config` - 服务器配置
+pub async fn add_mcprc_server(name: &str, server_config: McpServerConfig) -> Result<(), Error> {
+    McprcFile::current()?.add_server(name, server_config).await
+}
 
-    serde_json::to_string(&config)
-        .map_err(|e| Error::ConfigError(format!("Failed to serialize config: {}", e)))
+/// 从当前目录的 .mcprc 移除服务器
+///
+/// # Returns
+///
+/// 返回被移除的服务器配置
+pub async fn remove_mcprc_server(name: &str) -> Result<McpServerConfig, Error> {
+    McprcFile::current()?.remove_server(name).await
 }
 
 /// 清空 .mcprc 配置（仅用于测试）
@@ -64,105 +75,414 @@ pub async fn clear_mcprc_config_for_testing() -> Result<(), Error> {
     Ok(())
 }
 
-/// 添加 MCP 服务器配置（仅用于测试）
+/// 获取 .mcprc 文件路径
+fn get_mcprc_path() -> Result<PathBuf, Error> {
+    let current_dir = std::env::current_dir()
+        .map_err(|e| Error::ConfigError(format!("Cannot get current directory: {}", e)))?;
+
+    Ok(current_dir.join(MCPRC_FILE_NAME))
+}
+
+/// 严格解析 .mcprc 内容
 ///
-/// # Arguments
+/// 空白内容视为空配置；JSON 语法错误或服务器配置无效时返回
+/// [`Error::ConfigParseError`]，消息中包含出错的行号和列号。
+/// 服务器配置无效时定位到该服务器的名称处。
 ///
-/// * `name` - 服务器名称
-/// * `server_config` - 服务器配置
-///
-/// # Returns
+/// # Arguments
 ///
-/// 返回操作结果
-#[cfg(test)]
-pub async fn add_mcprc_server_for_testing(
-    name: String,
-    server_config: McpServerConfig,
-) -> Result<(), Error> {
-    let mut config: HashMap<String, McpServerConfig> = get_mcprc_config_hashmap().await?;
+/// * `path` - 文件路径（用于错误信息）
+/// * `content` - 文件内容
+pub fn parse_mcprc(path: &Path, content: &str) -> Result<BTreeMap<String, McpServerConfig>, Error> {
+    let parse_error = |line: usize, column: usize, message: String| Error::ConfigParseError {
+        path: path.to_path_buf(),
+        message: format!("line {}, column {}: {}", line, column, message),
+    };
 
-    config.insert(name, server_config);
+    if content.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
 
-    save_mcprc_config(&config).await?;
+    // 先检查语法和顶层结构，再逐个转换服务器配置
+    let raw: BTreeMap<String, serde_json::Value> = serde_json::from_str(content).map_err(|e| {
+        let message = e.to_string();
+        let suffix = format!(" at line {} column {}", e.line(), e.column());
+        let message = message
+            .strip_suffix(&suffix)
+            .unwrap_or(&message)
+            .to_string();
+        parse_error(e.line(), e.column(), message)
+    })?;
+
+    raw.into_iter()
+        .map(|(name, value)| match McpServerConfig::try_from(value) {
+            Ok(config) => Ok((name, config)),
+            Err(e) => {
+                let (line, column) = key_position(content, &name);
+                Err(parse_error(
+                    line,
+                    column,
+                    format!("invalid MCP server '{}': {}", name, e),
+                ))
+            }
+        })
+        .collect()
+}
 
-    Ok(())
+/// 服务器名称键在内容中的位置（行列号从 1 开始），找不到时返回 (1, 1)
+fn key_position(content: &str, name: &str) -> (usize, usize) {
+    let key = serde_json::to_string(name).unwrap_or_default();
+    let Some(offset) = content.find(&key) else {
+        return (1, 1);
+    };
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
 }
 
-/// 移除 MCP 服务器配置（仅用于测试）
-///
-/// # Arguments
-///
-/// * `name` - 服务器名称
-///
-/// # Returns
-///
-/// 返回操作结果
-#[cfg(test)]
-pub async fn remove_mcprc_server_for_testing(name: String) -> Result<(), Error> {
-    let mut config: HashMap<String, McpServerConfig> = get_mcprc_config_hashmap().await?;
+/// 项目目录中的 .mcprc 文件
+#[derive(Debug, Clone)]
+pub struct McprcFile {
+    path: PathBuf,
+}
 
-    if !config.contains_key(&name) {
-        return Err(Error::ConfigError(format!(
-            "MCP server '{}' not found in .mcprc config",
-            name
-        )));
+impl McprcFile {
+    /// 指定项目目录中的 .mcprc
+    pub fn new(project_dir: &Path) -> Self {
+        Self {
+            path: project_dir.join(MCPRC_FILE_NAME),
+        }
     }
 
-    config.remove(&name);
+    /// 当前目录中的 .mcprc
+    pub fn current() -> Result<Self, Error> {
+        Ok(Self {
+            path: get_mcprc_path()?,
+        })
+    }
 
-    save_mcprc_config(&config).await?;
+    /// 文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-    Ok(())
+    /// 读取全部服务器配置
+    ///
+    /// 文件不存在时返回空配置，读取或解析失败时返回错误。
+    pub async fn load(&self) -> Result<BTreeMap<String, McpServerConfig>, Error> {
+        match fs::read_to_string(&self.path).await {
+            Ok(content) => parse_mcprc(&self.path, &content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(Error::ConfigLoadError {
+                path: self.path.clone(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    /// 添加服务器，同名服务器已存在时报错
+    pub async fn add_server(
+        &self,
+        name: &str,
+        server_config: McpServerConfig,
+    ) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Err(Error::ConfigError(
+                "MCP server name must not be empty".to_string(),
+            ));
+        }
+        let mut servers = self.load().await?;
+        if servers.contains_key(name) {
+            return Err(Error::ConfigError(format!(
+                "MCP server '{}' already exists in {}",
+                name,
+                self.path.display()
+            )));
+        }
+        servers.insert(name.to_string(), server_config);
+        self.save(&servers).await
+    }
+
+    /// 移除服务器，返回被移除的配置
+    pub async fn remove_server(&self, name: &str) -> Result<McpServerConfig, Error> {
+        let mut servers = self.load().await?;
+        let removed = servers.remove(name).ok_or_else(|| {
+            Error::ConfigError(format!("MCP server '{}' not found in .mcprc config", name))
+        })?;
+        self.save(&servers).await?;
+        Ok(removed)
+    }
+
+    /// 保存服务器配置（2 空格缩进）
+    async fn save(&self, servers: &BTreeMap<String, McpServerConfig>) -> Result<(), Error> {
+        let content =
+            serde_json::to_string_pretty(servers).map_err(|e| Error::ConfigSaveError {
+                path: self.path.clone(),
+                message: e.to_string(),
+            })?;
+
+        fs::write(&self.path, content + "\n")
+            .await
+            .map_err(|e| Error::ConfigSaveError {
+                path: self.path.clone(),
+                message: e.to_string(),
+            })
+    }
 }
 
-/// 获取 .mcprc 文件路径
-fn get_mcprc_path() -> Result<PathBuf, Error> {
-    let current_dir = std::env::current_dir()
-        .map_err(|e| Error::ConfigError(format!("Cannot get current directory: {}", e)))?;
+/// .mcprc 服务器的批准状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McprcApproval {
+    /// 已批准
+    Approved,
+    /// 已拒绝
+    Rejected,
+    /// 尚未决定
+    Pending,
+}
+
+/// 查询 .mcprc 服务器的批准状态
+///
+/// 决定只对做出决定时的配置有效：命令、参数、环境变量等任何变化都会使服务器回到
+/// 尚未决定状态，防止已批准的名称被替换成其他命令。同时出现在两个列表中时按已拒绝处理。
+pub fn mcprc_server_status(
+    project: &ProjectConfig,
+    name: &str,
+    server_config: &McpServerConfig,
+) -> McprcApproval {
+    let decided = project
+        .decided_mcprc_configs
+        .as_ref()
+        .and_then(|configs| configs.get(name));
+    if decided != Some(server_config) {
+        return McprcApproval::Pending;
+    }
+    let contains =
+        |list: &Option<Vec<String>>| list.as_ref().is_some_and(|l| l.iter().any(|n| n == name));
+    if contains(&project.rejected_mcprc_servers) {
+        McprcApproval::Rejected
+    } else if contains(&project.approved_mcprc_servers) {
+        McprcApproval::Approved
+    } else {
+        McprcApproval::Pending
+    }
+}
 
-    Ok(current_dir.join(".mcprc"))
+/// 记录用户对 .mcprc 服务器当前配置的决定
+///
+/// 服务器从另一个列表中移除，因此之后可以改变决定。
+pub fn record_mcprc_decision(
+    project: &mut ProjectConfig,
+    name: &str,
+    server_config: &McpServerConfig,
+    approved: bool,
+) {
+    project
+        .decided_mcprc_configs
+        .get_or_insert_with(HashMap::new)
+        .insert(name.to_string(), server_config.clone());
+    let (add, remove) = if approved {
+        (
+            &mut project.approved_mcprc_servers,
+            &mut project.rejected_mcprc_servers,
+        )
+    } else {
+        (
+            &mut project.rejected_mcprc_servers,
+            &mut project.approved_mcprc_servers,
+        )
+    };
+    if let Some(list) = remove {
+        list.retain(|n| n != name);
+    }
+    let list = add.get_or_insert_with(Vec::new);
+    if !list.iter().any(|n| n == name) {
+        list.push(name.to_string());
+    }
 }
 
-/// 获取 .mcprc 配置为 HashMap
-#[allow(dead_code)]
-async fn get_mcprc_config_hashmap() -> Result<HashMap<String, McpServerConfig>, Error> {
-    let config_str = get_mcprc_config().await?;
-    let config: serde_json::Value = serde_json::from_str(&config_str)
-        .map_err(|e| Error::ConfigError(format!("Failed to parse config: {}", e)))?;
+/// .mcprc 服务器批准询问回调，由 UI 实现
+#[async_trait]
+pub trait McprcApprovalPrompt: Send + Sync {
+    /// 询问用户是否允许启动此服务器，返回 `true` 表示批准
+    async fn approve(&self, name: &str, server_config: &McpServerConfig) -> bool;
+}
 
-    if config.is_null() || !config.is_object() {
-        return Ok(HashMap::new());
+/// 对尚未决定的 .mcprc 服务器询问用户并记录决定
+///
+/// 没有询问回调（非交互模式）时不做记录，尚未决定的服务器保持不启动。
+///
+/// # Returns
+///
+/// 返回是否修改了项目配置
+pub async fn resolve_mcprc_approvals(
+    project: &mut ProjectConfig,
+    mcprc: &BTreeMap<String, McpServerConfig>,
+    prompt: Option<&dyn McprcApprovalPrompt>,
+) -> bool {
+    let Some(prompt) = prompt else {
+        return false;
+    };
+    let mut changed = false;
+    for (name, config) in mcprc {
+        if mcprc_server_status(project, name, config) == McprcApproval::Pending {
+            let approved = prompt.approve(name, config).await;
+            record_mcprc_decision(project, name, config, approved);
+            changed = true;
+        }
     }
+    changed
+}
 
-    let config_map: HashMap<String, McpServerConfig> = serde_json::from_value(config)
-        .map_err(|e| Error::ConfigError(format!("Failed to parse .mcprc config: {}", e)))?;
+/// 记录用户对当前目录 .mcprc 中某个服务器的决定并保存项目配置
+///
+/// # Returns
+///
+/// 返回被决定的服务器配置；服务器不在 .mcprc 中时返回错误
+pub async fn decide_mcprc_server(name: &str, approved: bool) -> Result<McpServerConfig, Error> {
+    let mcprc = list_mcprc_servers().await?;
+    let server_config = mcprc.get(name).ok_or_else(|| {
+        Error::ConfigError(format!("MCP server '{}' not found in .mcprc config", name))
+    })?;
+    let mut project = get_current_project_config().await?;
+    record_mcprc_decision(&mut project, name, server_config, approved);
+    save_current_project_config(&project).await?;
+    Ok(server_config.clone())
+}
 
-    Ok(config_map)
+/// MCP 服务器配置的来源
+///
+/// 按优先级从低到高排列：同名服务器以优先级高的来源为准。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum McpServerScope {
+    /// 全局配置中的 `mcp_servers`
+    Global,
+    /// 当前目录的 .mcprc
+    Mcprc,
+    /// 项目配置中的 `mcp_servers`
+    Project,
 }
 
-/// 保存 .mcprc 配置
-#[allow(dead_code)]
-async fn save_mcprc_config(config: &HashMap<String, McpServerConfig>) -> Result<(), Error> {
-    let mcprc_path = get_mcprc_path()?;
+impl std::fmt::Display for McpServerScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            McpServerScope::Global => write!(f, "global"),
+            McpServerScope::Mcprc => write!(f, ".mcprc"),
+            McpServerScope::Project => write!(f, "project"),
+        }
+    }
+}
 
-    // 序列化为 JSON（2 空格缩进）
-    let content = serde_json::to_string_pretty(config)
-        .map_err(|e| Error::ConfigError(format!("Failed to serialize .mcprc config: {}", e)))?;
+/// 合并后的 MCP 服务器及其来源
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopedMcpServer {
+    /// 配置来源
+    pub scope: McpServerScope,
+    /// 服务器配置
+    pub config: McpServerConfig,
+    /// 批准状态，只有 .mcprc 服务器可能不是 [`McprcApproval::Approved`]
+    pub approval: McprcApproval,
+}
 
-    // 写入文件
-    fs::write(&mcprc_path, content)
-        .await
-        .map_err(|e| Error::ConfigSaveError {
-            path: mcprc_path,
-            message: e.to_string(),
-        })?;
+impl ScopedMcpServer {
+    /// 是否可以启动
+    pub fn is_approved(&self) -> bool {
+        self.approval == McprcApproval::Approved
+    }
+}
 
-    Ok(())
+/// 按优先级合并所有来源的 MCP 服务器
+///
+/// 优先级为 全局 < .mcprc < 项目。未批准的 .mcprc 服务器不会覆盖全局配置中的同名服务器，
+/// 只在没有其他来源时出现在结果中，以便显示其待批准或已拒绝状态；
+/// 是否可以启动由 [`ScopedMcpServer::is_approved`] 决定。
+pub fn merge_mcp_servers(
+    global: &GlobalConfig,
+    project: &ProjectConfig,
+    mcprc: &BTreeMap<String, McpServerConfig>,
+) -> BTreeMap<String, ScopedMcpServer> {
+    let scoped = |scope, config: &McpServerConfig, approval| ScopedMcpServer {
+        scope,
+        config: config.clone(),
+        approval,
+    };
+
+    let mut servers = BTreeMap::new();
+    for (name, config) in global.mcp_servers.iter().flatten() {
+        servers.insert(
+            name.clone(),
+            scoped(McpServerScope::Global, config, McprcApproval::Approved),
+        );
+    }
+    for (name, config) in mcprc {
+        let approval = mcprc_server_status(project, name, config);
+        if approval == McprcApproval::Approved || !servers.contains_key(name) {
+            servers.insert(
+                name.clone(),
+                scoped(McpServerScope::Mcprc, config, approval),
+            );
+        }
+    }
+    for (name, config) in project.mcp_servers.iter().flatten() {
+        servers.insert(
+            name.clone(),
+            scoped(McpServerScope::Project, config, McprcApproval::Approved),
+        );
+    }
+    servers
+}
+
+/// 读取所有来源的 MCP 服务器
+///
+/// 读取当前目录的 .mcprc，对首次出现的服务器询问用户并保存决定，
+/// 再按 [`merge_mcp_servers`] 的优先级合并全局、.mcprc 与项目配置。
+pub async fn load_mcp_servers(
+    prompt: Option<&dyn McprcApprovalPrompt>,
+) -> Result<BTreeMap<String, ScopedMcpServer>, Error> {
+    let global = get_global_config().await?;
+    let mcprc = list_mcprc_servers().await?;
+    let mut project = get_current_project_config().await?;
+    if resolve_mcprc_approvals(&mut project, &mcprc, prompt).await {
+        save_current_project_config(&project).await?;
+    }
+    Ok(merge_mcp_servers(&global, &project, &mcprc))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::types::McpStdioServerConfig;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    fn stdio(command: &str) -> McpServerConfig {
+        McpServerConfig::Stdio(McpStdioServerConfig {
+            command: command.to_string(),
+            args: vec![],
+            env: None,
+        })
+    }
+
+    /// 按预设答案回答并记录被询问的服务器
+    struct ScriptedPrompt {
+        approve: Vec<&'static str>,
+        asked: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl McprcApprovalPrompt for ScriptedPrompt {
+        async fn approve(&self, name: &str, _server_config: &McpServerConfig) -> bool {
+            self.asked.lock().unwrap().push(name.to_string());
+            self.approve.contains(&name)
+        }
+    }
 
     #[tokio::test]
     async fn test_get_mcprc_config_empty() {
@@ -178,4 +498,250 @@ mod tests {
         let path = path.unwrap();
         assert!(path.ends_with(".mcprc"));
     }
+
+    #[test]
+    fn test_server_config_type_discriminator() {
+        let configs: HashMap<String, McpServerConfig> = serde_json::from_str(
+            r#"{
+                "legacy-stdio": {"command": "npx", "args": ["server"]},
+                "legacy-sse": {"url": "http://localhost:3000/sse"},
+                "tagged-sse": {"type": "sse", "url": "http://localhost:3000/sse", "command": "x"},
+                "http": {
+                    "type": "http",
+                    "url": "https://example.com/mcp",
+                    "headers": {"Authorization": "Bearer ${TOKEN}"}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(configs["legacy-stdio"], McpServerConfig::Stdio(_)));
+        assert!(matches!(configs["legacy-sse"], McpServerConfig::Sse(_)));
+        assert!(matches!(configs["tagged-sse"], McpServerConfig::Sse(_)));
+        match &configs["http"] {
+            McpServerConfig::Http(http) => {
+                assert_eq!(
+                    http.headers.as_ref().unwrap()["Authorization"],
+                    "Bearer ${TOKEN}"
+                )
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // 序列化时总是写出 type
+        let json = serde_json::to_value(&configs["legacy-stdio"]).unwrap();
+        assert_eq!(json["type"], "stdio");
+        let roundtrip: McpServerConfig = serde_json::from_value(json).unwrap();
+        assert!(matches!(roundtrip, McpServerConfig::Stdio(_)));
+
+        let err =
+            serde_json::from_str::<McpServerConfig>(r#"{"type": "websocket", "url": "ws://x"}"#)
+                .unwrap_err();
+        assert!(err.to_string().contains("websocket"));
+    }
+
+    #[test]
+    fn test_parse_mcprc_reports_position() {
+        let path = Path::new("/project/.mcprc");
+        assert!(parse_mcprc(path, "  \n").unwrap().is_empty());
+
+        let err = parse_mcprc(path, "{\n  \"a\": {\"command\": \"x\"},\n  oops\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse config from /project/.mcprc: line 3, column 3: key must be a string"
+        );
+
+        let err =
+            parse_mcprc(path, "{\n  \"a\": {\"type\": \"ws\", \"url\": \"x\"}\n}").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("line 2, column"), "{}", message);
+        assert!(message.contains("ws"), "{}", message);
+
+        assert!(matches!(
+            parse_mcprc(path, "[]"),
+            Err(Error::ConfigParseError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mcprc_file_add_remove_list() {
+        let dir = TempDir::new().unwrap();
+        let file = McprcFile::new(dir.path());
+        assert!(file.load().await.unwrap().is_empty());
+
+        file.add_server("zeta", stdio("z")).await.unwrap();
+        file.add_server("alpha", stdio("a")).await.unwrap();
+        let err = file.add_server("alpha", stdio("b")).await.unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        let servers = file.load().await.unwrap();
+        assert_eq!(servers.keys().collect::<Vec<_>>(), vec!["alpha", "zeta"]);
+        assert_eq!(servers["alpha"], stdio("a"));
+
+        assert_eq!(file.remove_server("zeta").await.unwrap(), stdio("z"));
+        assert!(file.remove_server("zeta").await.is_err());
+        assert_eq!(file.load().await.unwrap().len(), 1);
+
+        std::fs::write(file.path(), "{ not json").unwrap();
+        let err = file.load().await.unwrap_err();
+        assert!(err.to_string().contains("line 1, column 3"), "{}", err);
+    }
+
+    /// 合并后可以启动的服务器
+    fn approved(project: &ProjectConfig, mcprc: &BTreeMap<String, McpServerConfig>) -> Vec<String> {
+        merge_mcp_servers(&GlobalConfig::default(), project, mcprc)
+            .into_iter()
+            .filter(|(_, server)| server.is_approved())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mcprc_approval_flow() {
+        let mcprc = BTreeMap::from([
+            ("trusted".to_string(), stdio("trusted")),
+            ("sketchy".to_string(), stdio("sketchy")),
+            ("later".to_string(), stdio("later")),
+        ]);
+        let mut project = ProjectConfig {
+            mcp_servers: Some(HashMap::from([("own".to_string(), stdio("own"))])),
+            approved_mcprc_servers: Some(vec!["later".to_string()]),
+            rejected_mcprc_servers: Some(vec!["later".to_string()]),
+            decided_mcprc_configs: Some(HashMap::from([("later".to_string(), stdio("later"))])),
+            ..Default::default()
+        };
+
+        // 非交互模式：不记录，未批准的服务器不启动
+        assert!(!resolve_mcprc_approvals(&mut project, &mcprc, None).await);
+        assert_eq!(
+            mcprc_server_status(&project, "later", &mcprc["later"]),
+            McprcApproval::Rejected
+        );
+        assert_eq!(approved(&project, &mcprc), vec!["own"]);
+
+        let prompt = ScriptedPrompt {
+            approve: vec!["trusted"],
+            asked: Mutex::new(Vec::new()),
+        };
+        assert!(resolve_mcprc_approvals(&mut project, &mcprc, Some(&prompt)).await);
+        assert_eq!(*prompt.asked.lock().unwrap(), vec!["sketchy", "trusted"]);
+        assert_eq!(
+            mcprc_server_status(&project, "trusted", &mcprc["trusted"]),
+            McprcApproval::Approved
+        );
+        assert_eq!(
+            mcprc_server_status(&project, "sketchy", &mcprc["sketchy"]),
+            McprcApproval::Rejected
+        );
+
+        assert_eq!(approved(&project, &mcprc), vec!["own", "trusted"]);
+
+        // 已决定的服务器不再询问
+        assert!(!resolve_mcprc_approvals(&mut project, &mcprc, Some(&prompt)).await);
+        assert_eq!(prompt.asked.lock().unwrap().len(), 2);
+
+        // 改变决定时从另一个列表移除
+        record_mcprc_decision(&mut project, "sketchy", &mcprc["sketchy"], true);
+        assert_eq!(
+            mcprc_server_status(&project, "sketchy", &mcprc["sketchy"]),
+            McprcApproval::Approved
+        );
+        assert!(!project
+            .rejected_mcprc_servers
+            .as_ref()
+            .unwrap()
+            .contains(&"sketchy".to_string()));
+
+        // 已批准的名称换成其他命令、参数或环境变量后需要重新批准
+        let McpServerConfig::Stdio(trusted) = &mcprc["trusted"] else {
+            unreachable!()
+        };
+        let changed = [
+            stdio("curl"),
+            McpServerConfig::Stdio(McpStdioServerConfig {
+                args: vec!["--evil".to_string()],
+                ..trusted.clone()
+            }),
+            McpServerConfig::Stdio(McpStdioServerConfig {
+                env: Some(HashMap::from([("LD_PRELOAD".to_string(), "x".to_string())])),
+                ..trusted.clone()
+            }),
+        ];
+        for config in changed {
+            assert_eq!(
+                mcprc_server_status(&project, "trusted", &config),
+                McprcApproval::Pending
+            );
+            let mcprc = BTreeMap::from([("trusted".to_string(), config)]);
+            assert_eq!(approved(&project, &mcprc), vec!["own"]);
+        }
+
+        // 只记录了名称（旧版本的决定）时需要重新决定
+        project.decided_mcprc_configs = None;
+        assert_eq!(
+            mcprc_server_status(&project, "trusted", &mcprc["trusted"]),
+            McprcApproval::Pending
+        );
+    }
+
+    #[test]
+    fn test_merge_mcp_servers_precedence() {
+        let global = GlobalConfig {
+            mcp_servers: Some(HashMap::from([
+                ("shared".to_string(), stdio("global-shared")),
+                ("overridden".to_string(), stdio("global-overridden")),
+                ("shadowed".to_string(), stdio("global-shadowed")),
+            ])),
+            ..Default::default()
+        };
+        let mcprc = BTreeMap::from([
+            ("overridden".to_string(), stdio("mcprc-overridden")),
+            ("shadowed".to_string(), stdio("mcprc-shadowed")),
+            ("new".to_string(), stdio("mcprc-new")),
+            ("project".to_string(), stdio("mcprc-project")),
+        ]);
+        let project = ProjectConfig {
+            mcp_servers: Some(HashMap::from([("project".to_string(), stdio("project"))])),
+            approved_mcprc_servers: Some(vec!["overridden".to_string()]),
+            decided_mcprc_configs: Some(HashMap::from([(
+                "overridden".to_string(),
+                stdio("mcprc-overridden"),
+            )])),
+            ..Default::default()
+        };
+
+        let servers = merge_mcp_servers(&global, &project, &mcprc);
+        let summary: Vec<_> = servers
+            .iter()
+            .map(|(name, server)| {
+                let McpServerConfig::Stdio(config) = &server.config else {
+                    unreachable!()
+                };
+                (
+                    name.as_str(),
+                    server.scope,
+                    config.command.as_str(),
+                    server.is_approved(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("new", McpServerScope::Mcprc, "mcprc-new", false),
+                (
+                    "overridden",
+                    McpServerScope::Mcprc,
+                    "mcprc-overridden",
+                    true
+                ),
+                ("project", McpServerScope::Project, "project", true),
+                // 未批准的 .mcprc 服务器不能顶替用户自己的配置
+                ("shadowed", McpServerScope::Global, "global-shadowed", true),
+                ("shared", McpServerScope::Global, "global-shared", true),
+            ]
+        );
+        assert_eq!(servers["new"].approval, McprcApproval::Pending);
+        assert_eq!(McpServerScope::Mcprc.to_string(), ".mcprc");
+    }
 }
@@ -24,10 +24,15 @@ use std::collections::HashMap;
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
+/// use kode_core::config::get_global_config;
 /// use kode_core::config::migration::migrate_model_profiles_remove_id;
+///
+/// # async fn example() -> kode_core::error::Result<()> {
 /// let config = get_global_config().await?;
 /// let migrated = migrate_model_profiles_remove_id(config);
+/// # Ok(())
+/// # }
 /// ```
 pub fn migrate_model_profiles_remove_id(mut config: GlobalConfig) -> GlobalConfig {
     // 如果没有 model_profiles，直接返回
@@ -101,9 +106,13 @@ pub fn migrate_model_profiles_remove_id(mut config: GlobalConfig) -> GlobalConfi
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
 /// use kode_core::config::migration::enable_configs;
+///
+/// # async fn example() -> kode_core::error::Result<()> {
 /// enable_configs().await?;
+/// # Ok(())
+/// # }
 /// ```
 pub async fn enable_configs() -> Result<(), Error> {
     // 在 Rust 版本中，我们不需要像 TypeScript 那样控制配置读取的时序
@@ -129,23 +138,25 @@ mod tests {
 
     #[test]
     fn test_migrate_model_profiles_remove_id_with_profiles() {
-        let mut config = GlobalConfig::default();
-        config.model_profiles = Some(vec![ModelProfile {
-            name: "test-model".to_string(),
-            provider: ProviderType::Anthropic,
-            model_name: "claude-3-5-sonnet-20241022".to_string(),
-            base_url: None,
-            api_key: "sk-ant-test".to_string(),
-            max_tokens: 8192,
-            context_length: 200000,
-            reasoning_effort: None,
-            is_active: true,
-            created_at: 0,
-            last_used: None,
-            is_gpt5: None,
-            validation_status: None,
-            last_validation: None,
-        }]);
+        let config = GlobalConfig {
+            model_profiles: Some(vec![ModelProfile {
+                name: "test-model".to_string(),
+                provider: ProviderType::Anthropic,
+                model_name: "claude-3-5-sonnet-20241022".to_string(),
+                base_url: None,
+                api_key: "sk-ant-test".to_string(),
+                max_tokens: 8192,
+                context_length: 200000,
+                reasoning_effort: None,
+                is_active: true,
+                created_at: 0,
+                last_used: None,
+                is_gpt5: None,
+                validation_status: None,
+                last_validation: None,
+            }]),
+            ..Default::default()
+        };
 
         let migrated = migrate_model_profiles_remove_id(config);
         assert!(migrated.model_profiles.is_some());
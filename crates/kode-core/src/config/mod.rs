@@ -36,6 +36,7 @@ pub mod migration;
 pub use loader::ConfigLoader;
 pub use types::GlobalConfig;
 pub use types::ProjectConfig;
+pub use types::SandboxConfig;
 
 // 重新导出环境变量函数
 pub use env::{get_anthropic_api_key, get_config_file_path, get_openai_api_key};
@@ -64,13 +65,15 @@ pub use gpt5::{
 };
 
 // 重新导出 MCP 支持函数
-pub use mcp::get_mcprc_config;
+pub use mcp::{
+    add_mcprc_server, decide_mcprc_server, get_mcprc_config, list_mcprc_servers, load_mcp_servers,
+    merge_mcp_servers, remove_mcprc_server, McpServerScope, McprcApproval, McprcApprovalPrompt,
+    McprcFile, ScopedMcpServer,
+};
 
 // 测试专用函数（仅在测试时可用）
 #[cfg(test)]
-pub use mcp::{
-    add_mcprc_server_for_testing, clear_mcprc_config_for_testing, remove_mcprc_server_for_testing,
-};
+pub use mcp::clear_mcprc_config_for_testing;
 
 // 重新导出配置迁移函数
 pub use migration::{enable_configs, migrate_model_profiles_remove_id};
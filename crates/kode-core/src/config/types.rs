@@ -47,34 +47,92 @@ pub struct AccountInfo {
 }
 
 /// MCP 服务器配置 - STDIO 类型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct McpStdioServerConfig {
     /// 启动命令
     pub command: String,
     /// 命令参数
+    #[serde(default)]
     pub args: Vec<String>,
     /// 环境变量
     pub env: Option<HashMap<String, String>>,
 }
 
 /// MCP 服务器配置 - SSE 类型
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct McpSseServerConfig {
     /// 服务器 URL
     pub url: String,
+    /// 附加请求头，值中的 `${VAR}` 在连接时替换为环境变量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
 }
 
-/// MCP 服务器配置（联合类型）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// MCP 服务器配置 - Streamable HTTP 类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[serde(untagged)]
+pub struct McpHttpServerConfig {
+    /// 服务器端点 URL
+    pub url: String,
+    /// 附加请求头，值中的 `${VAR}` 在连接时替换为环境变量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
+}
+
+/// MCP 服务器配置（联合类型）
+///
+/// 以 `type` 字段区分传输方式。为兼容旧配置，缺少 `type` 时
+/// 带 `command` 的视为 stdio，带 `url` 的视为 SSE。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+#[serde(try_from = "serde_json::Value")]
 pub enum McpServerConfig {
     /// STDIO 传输
     Stdio(McpStdioServerConfig),
     /// SSE 传输
     Sse(McpSseServerConfig),
+    /// Streamable HTTP 传输
+    Http(McpHttpServerConfig),
+}
+
+/// 带 `type` 标签的 MCP 服务器配置，仅用于反序列化
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TaggedMcpServerConfig {
+    Stdio(McpStdioServerConfig),
+    Sse(McpSseServerConfig),
+    #[serde(alias = "streamable-http")]
+    Http(McpHttpServerConfig),
+}
+
+impl TryFrom<serde_json::Value> for McpServerConfig {
+    type Error = String;
+
+    fn try_from(mut value: serde_json::Value) -> Result<Self, Self::Error> {
+        let object = value
+            .as_object_mut()
+            .ok_or_else(|| "MCP server config must be an object".to_string())?;
+        if !object.contains_key("type") {
+            let inferred = if object.contains_key("command") {
+                "stdio"
+            } else if object.contains_key("url") {
+                "sse"
+            } else {
+                return Err("MCP server config needs a `type`, `command` or `url`".to_string());
+            };
+            object.insert("type".to_string(), inferred.into());
+        }
+
+        let tagged: TaggedMcpServerConfig =
+            serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Ok(match tagged {
+            TaggedMcpServerConfig::Stdio(config) => McpServerConfig::Stdio(config),
+            TaggedMcpServerConfig::Sse(config) => McpServerConfig::Sse(config),
+            TaggedMcpServerConfig::Http(config) => McpServerConfig::Http(config),
+        })
+    }
 }
 
 /// 模型提供商标识
@@ -169,13 +227,55 @@ pub struct ModelPointers {
     pub quick: Option<String>,
 }
 
+/// Bash 沙箱配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxConfig {
+    /// 是否启用沙箱
+    #[serde(default)]
+    pub enabled: bool,
+    /// 是否允许访问网络
+    #[serde(default)]
+    pub allow_network: bool,
+    /// 项目根目录之外额外允许写入的路径
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable_paths: Vec<String>,
+    /// 内核不支持沙箱时是否退回到不加沙箱执行
+    #[serde(default = "default_true")]
+    pub allow_unsandboxed_fallback: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_network: false,
+            writable_paths: Vec::new(),
+            allow_unsandboxed_fallback: true,
+        }
+    }
+}
+
 /// 项目配置
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectConfig {
-    /// 允许使用的工具列表
+    /// 允许使用的工具列表（权限规则）
     #[serde(default)]
     pub allowed_tools: Vec<String>,
+    /// 禁止使用的工具列表（权限规则）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_tools: Vec<String>,
+    /// 禁止 Bash 工具执行的命令（在内置禁止列表之外追加）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_commands: Vec<String>,
+    /// Bash 沙箱配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxConfig>,
     /// 上下文信息
     #[serde(default)]
     pub context: HashMap<String, String>,
@@ -197,6 +297,9 @@ pub struct ProjectConfig {
     pub approved_mcprc_servers: Option<Vec<String>>,
     /// 已拒绝的 mcprc 服务器
     pub rejected_mcprc_servers: Option<Vec<String>>,
+    /// 做出批准或拒绝决定时 mcprc 中的服务器配置，配置变化后需要重新决定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_mcprc_configs: Option<HashMap<String, McpServerConfig>>,
     /// 上次 API 调用时长
     pub last_api_duration: Option<u64>,
     /// 上次成本
@@ -273,6 +376,12 @@ pub struct GlobalConfig {
     pub default_model_name: Option<String>,
     /// 最后忽略的更新版本
     pub last_dismissed_update_version: Option<String>,
+    /// 用户级允许规则
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_tools: Vec<String>,
+    /// 用户级禁止规则
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_tools: Vec<String>,
 }
 
 /// 自定义 API key 响应
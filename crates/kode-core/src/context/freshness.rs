@@ -80,7 +80,8 @@ impl FileFreshnessService {
                 last_agent_edit: None,
             };
 
-            self.read_timestamps.insert(file_path.to_string(), timestamp);
+            self.read_timestamps
+                .insert(file_path.to_string(), timestamp);
             self.session_files.insert(file_path.to_string());
         }
     }
@@ -118,7 +119,8 @@ impl FileFreshnessService {
                     size,
                     last_agent_edit: Some(now),
                 };
-                self.read_timestamps.insert(file_path.to_string(), timestamp);
+                self.read_timestamps
+                    .insert(file_path.to_string(), timestamp);
             }
         }
 
@@ -200,10 +202,7 @@ impl FileFreshnessService {
 
         // 检查文件是否存在
         if !Path::new(file_path).exists() {
-            return Some(format!(
-                "Note: {} was deleted since last read.",
-                file_path
-            ));
+            return Some(format!("Note: {} was deleted since last read.", file_path));
         }
 
         // 检查修改时间
@@ -236,10 +235,7 @@ impl FileFreshnessService {
                 file_path
             ))
         } else {
-            Some(format!(
-                "Note: {} is no longer accessible.",
-                file_path
-            ))
+            Some(format!("Note: {} is no longer accessible.", file_path))
         }
     }
 
@@ -266,7 +262,7 @@ impl FileFreshnessService {
             .collect();
 
         // 按最后读取时间排序（最新的在前）
-        files.sort_by(|a, b| b.last_read.cmp(&a.last_read));
+        files.sort_by_key(|f| std::cmp::Reverse(f.last_read));
 
         files.truncate(max_files);
         files
@@ -326,7 +322,8 @@ impl FileFreshnessService {
     /// * `agent_id` - Agent ID
     /// * `file_path` - TODO 文件路径
     pub fn start_watching_todo_file(&mut self, agent_id: &str, file_path: &str) {
-        self.watched_todo_files.insert(agent_id.to_string(), file_path.to_string());
+        self.watched_todo_files
+            .insert(agent_id.to_string(), file_path.to_string());
 
         // 记录初始状态
         if Path::new(file_path).exists() {
@@ -334,6 +331,23 @@ impl FileFreshnessService {
         }
     }
 
+    /// 检查 TODO 文件是否被外部修改
+    ///
+    /// 只检查通过 [`start_watching_todo_file`](Self::start_watching_todo_file) 监控的文件。
+    /// 检测到外部修改后重新记录文件状态，同一次修改只提醒一次。
+    ///
+    /// # Arguments
+    /// * `agent_id` - Agent ID
+    ///
+    /// # Returns
+    /// 提醒消息（如果没有外部修改则返回 None）
+    pub fn check_todo_file(&mut self, agent_id: &str) -> Option<String> {
+        let file_path = self.watched_todo_files.get(agent_id)?.clone();
+        let reminder = self.generate_file_modification_reminder(&file_path)?;
+        self.record_file_read(&file_path);
+        Some(reminder)
+    }
+
     /// 停止监控 TODO 文件
     ///
     /// # Arguments
@@ -373,88 +387,83 @@ impl FileFreshnessService {
             "venv/",
         ];
 
-        !invalid_patterns.iter().any(|pattern| file_path.contains(pattern))
+        !invalid_patterns
+            .iter()
+            .any(|pattern| file_path.contains(pattern))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::{self, File};
+    use std::fs;
     use std::io::Write;
-    use std::path::PathBuf;
-
-    fn create_temp_file(content: &str) -> PathBuf {
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join(format!("test_file_{}.txt", uuid::Uuid::new_v4()));
+    use tempfile::NamedTempFile;
 
-        let mut file = File::create(&file_path).unwrap();
+    fn create_temp_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
         file.write_all(content.as_bytes()).unwrap();
+        file
+    }
 
-        file_path
+    fn timestamp(path: &str, last_read: u64) -> FileTimestamp {
+        FileTimestamp {
+            path: path.to_string(),
+            last_read,
+            last_modified: 0,
+            size: 0,
+            last_agent_edit: None,
+        }
     }
 
     #[test]
     fn test_record_file_read() {
         let mut service = FileFreshnessService::new();
         let temp_file = create_temp_file("Hello, World!");
-        let file_path = temp_file.to_str().unwrap();
+        let file_path = temp_file.path().to_str().unwrap();
 
         service.record_file_read(file_path);
 
         assert!(service.is_file_tracked(file_path));
         assert!(service.get_session_files().contains(&file_path.to_string()));
-
-        // 清理
-        fs::remove_file(file_path).ok();
     }
 
     #[test]
     fn test_check_file_freshness() {
         let mut service = FileFreshnessService::new();
         let temp_file = create_temp_file("Initial content");
-        let file_path = temp_file.to_str().unwrap();
+        let file_path = temp_file.path().to_str().unwrap();
 
         service.record_file_read(file_path);
 
         let status = service.check_file_freshness(file_path);
         assert!(status.is_fresh);
         assert!(!status.conflict);
-
-        // 清理
-        fs::remove_file(file_path).ok();
     }
 
     #[test]
     fn test_get_important_files() {
         let mut service = FileFreshnessService::new();
-        let temp_file1 = create_temp_file("File 1");
-        let temp_file2 = create_temp_file("File 2");
-
-        let path1 = temp_file1.to_str().unwrap();
-        let path2 = temp_file2.to_str().unwrap();
-
-        service.record_file_read(path1);
-        std::thread::sleep(std::time::Duration::from_millis(10));
-        service.record_file_read(path2);
+        // 临时目录位于 /tmp，会被 is_valid_for_recovery 过滤，因此直接构造读取记录
+        for (path, last_read) in [("src/a.rs", 1), ("src/b.rs", 2), ("/tmp/c.rs", 3)] {
+            service
+                .read_timestamps
+                .insert(path.to_string(), timestamp(path, last_read));
+        }
 
         let important = service.get_important_files(10);
         assert_eq!(important.len(), 2);
 
         // 最新的文件应该在前
-        assert_eq!(important[0].path, path2);
-        assert_eq!(important[1].path, path1);
-
-        // 清理
-        fs::remove_file(path1).ok();
-        fs::remove_file(path2).ok();
+        assert_eq!(important[0].path, "src/b.rs");
+        assert_eq!(important[1].path, "src/a.rs");
     }
 
     #[test]
     fn test_reset_session() {
         let mut service = FileFreshnessService::new();
         let temp_file = create_temp_file("Test");
-        let file_path = temp_file.to_str().unwrap();
+        let file_path = temp_file.path().to_str().unwrap();
 
         service.record_file_read(file_path);
         assert!(service.is_file_tracked(file_path));
@@ -462,16 +471,19 @@ mod tests {
         service.reset_session();
         assert!(!service.is_file_tracked(file_path));
         assert!(service.get_session_files().is_empty());
-
-        // 清理
-        fs::remove_file(file_path).ok();
     }
 
     #[test]
     fn test_is_valid_for_recovery() {
-        assert!(!FileFreshnessService::is_valid_for_recovery("node_modules/package.json"));
-        assert!(!FileFreshnessService::is_valid_for_recovery("/tmp/file.txt"));
-        assert!(!FileFreshnessService::is_valid_for_recovery("target/debug/test"));
+        assert!(!FileFreshnessService::is_valid_for_recovery(
+            "node_modules/package.json"
+        ));
+        assert!(!FileFreshnessService::is_valid_for_recovery(
+            "/tmp/file.txt"
+        ));
+        assert!(!FileFreshnessService::is_valid_for_recovery(
+            "target/debug/test"
+        ));
 
         assert!(FileFreshnessService::is_valid_for_recovery("src/main.rs"));
         assert!(FileFreshnessService::is_valid_for_recovery("README.md"));
@@ -488,8 +500,26 @@ mod tests {
             Some(&"/tmp/todo.md".to_string())
         );
 
+        assert_eq!(service.check_todo_file("agent-1"), None);
         service.stop_watching_todo_file("agent-1");
 
         assert_eq!(service.watched_todo_files.get("agent-1"), None);
     }
+
+    #[test]
+    fn test_check_todo_file_detects_external_edit() {
+        let mut service = FileFreshnessService::new();
+        let todo_file = create_temp_file("[]");
+        let path = todo_file.path().to_str().unwrap();
+
+        service.start_watching_todo_file("agent-1", path);
+        assert_eq!(service.check_todo_file("agent-1"), None);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(path, "[{}]").unwrap();
+        let reminder = service.check_todo_file("agent-1").unwrap();
+        assert!(reminder.contains("modified externally"));
+        // 同一次修改只提醒一次
+        assert_eq!(service.check_todo_file("agent-1"), None);
+    }
 }
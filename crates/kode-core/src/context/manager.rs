@@ -195,7 +195,11 @@ impl TokenCounter {
             ContentBlock::ToolUse(t) => {
                 t.tool_name.len() + t.tool_use_id.len() + t.parameters.to_string().len()
             }
-            ContentBlock::ToolResult(r) => r.tool_use_id.len() + r.content.len(),
+            ContentBlock::ToolResult(r) => {
+                r.tool_use_id.len()
+                    + r.content.len()
+                    + r.images.iter().map(|i| i.data.len()).sum::<usize>()
+            }
             ContentBlock::Image(i) => i.image_type.len() + i.media_type.len() + i.data.len(),
         }
     }
@@ -716,7 +720,8 @@ impl MessageContextManager {
     /// 是否需要压缩
     pub fn should_auto_compact_dynamic(&self) -> bool {
         let context_limit = Self::get_compression_model_context_limit();
-        let threshold = (context_limit as f64 * auto_compact_config::AUTO_COMPACT_THRESHOLD_RATIO) as usize;
+        let threshold =
+            (context_limit as f64 * auto_compact_config::AUTO_COMPACT_THRESHOLD_RATIO) as usize;
         self.current_tokens >= threshold
     }
 
@@ -792,6 +797,8 @@ impl MessageContextManager {
     /// 格式化系统提示词并注入上下文
     ///
     /// 合并系统提示词数组，注入上下文变量，生成提醒。
+    /// 提示词中的 `{{key}}` 占位符替换为对应值；未被占位符引用的条目
+    /// （如 MCP 资源内容）按键名排序，以 `<context name="key">` 块追加到末尾。
     ///
     /// # Arguments
     /// * `system_prompt` - 系统提示词数组
@@ -810,17 +817,29 @@ impl MessageContextManager {
             system_prompt.join("\n\n")
         };
 
-        // 注入上下文变量（简化实现）
-        let final_prompt = if context.is_empty() {
-            merged_prompt
-        } else {
-            // 将上下文变量注入到提示词中
-            let mut result = merged_prompt.clone();
-            for (key, value) in context.iter() {
-                result = result.replace(&format!("{{{{{}}}}}", key), value);
+        // 注入上下文变量
+        let mut final_prompt = merged_prompt.clone();
+        let mut unreferenced: Vec<(&String, &String)> = Vec::new();
+        for (key, value) in context.iter() {
+            let placeholder = format!("{{{{{}}}}}", key);
+            if merged_prompt.contains(&placeholder) {
+                final_prompt = final_prompt.replace(&placeholder, value);
+            } else {
+                unreferenced.push((key, value));
             }
-            result
-        };
+        }
+
+        unreferenced.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in unreferenced {
+            if !final_prompt.is_empty() {
+                final_prompt.push_str("\n\n");
+            }
+            final_prompt.push_str(&format!(
+                "<context name=\"{}\">\n{}\n</context>",
+                key,
+                value.trim_end()
+            ));
+        }
 
         // 生成提醒（简化实现，实际需要根据业务逻辑）
         let reminders = None;
@@ -922,7 +941,7 @@ mod tests {
             MessageContextManager::new(10).with_trimming_strategy(TrimmingStrategy::KeepRecent(3));
 
         for i in 0..10 {
-            manager.add_message(Message::user(&format!("Message {}", i)));
+            manager.add_message(Message::user(format!("Message {}", i)));
         }
 
         // 应该只保留最近 3 条
@@ -1037,7 +1056,7 @@ mod tests {
         // 添加超过 10 条消息以触发智能压缩
         // 使用较小的 token 限制确保触发裁剪
         for i in 0..15 {
-            manager.add_message(Message::user(&format!("Message {}: implement feature", i)));
+            manager.add_message(Message::user(format!("Message {}: implement feature", i)));
         }
 
         // 智能压缩应该创建摘要消息并保留最近消息
@@ -1093,12 +1112,32 @@ mod tests {
         let mut context = std::collections::HashMap::new();
         context.insert("name".to_string(), "Alice".to_string());
 
-        let (formatted, reminders) = MessageContextManager::format_system_prompt_with_context(&system_prompt, &context);
+        let (formatted, reminders) =
+            MessageContextManager::format_system_prompt_with_context(&system_prompt, &context);
         assert!(formatted.contains("helpful"));
         assert!(formatted.contains("concise"));
         assert!(reminders.is_none()); // 简化实现不生成提醒
     }
 
+    #[test]
+    fn test_format_system_prompt_appends_unreferenced_context() {
+        let system_prompt = vec!["Hello {{name}}.".to_string()];
+        let context = HashMap::from([
+            ("name".to_string(), "Alice".to_string()),
+            ("fake://readme".to_string(), "# Fake project\n".to_string()),
+            ("docs://api".to_string(), "API docs".to_string()),
+        ]);
+
+        let (formatted, _) =
+            MessageContextManager::format_system_prompt_with_context(&system_prompt, &context);
+        assert_eq!(
+            formatted,
+            "Hello Alice.\n\n\
+             <context name=\"docs://api\">\nAPI docs\n</context>\n\n\
+             <context name=\"fake://readme\">\n# Fake project\n</context>"
+        );
+    }
+
     #[test]
     fn test_add_line_numbers() {
         let content = "Line 1\nLine 2\nLine 3";
@@ -1139,6 +1178,7 @@ mod tests {
                 tool_use_id: "test-id".to_string(),
                 content: "result".to_string(),
                 is_error: false,
+                images: Vec::new(),
             })]);
 
         // 这个简单的判断可能会将工具结果消息视为进度消息
@@ -23,5 +23,15 @@ pub mod message;
 /// 上下文管理模块
 pub mod context;
 
+/// 权限系统模块
+pub mod permission;
+
+/// 查询循环模块
+pub mod query;
+
+/// 测试支持模块
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+
 // 重新导出常用类型
 pub use error::Result;
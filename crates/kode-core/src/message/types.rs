@@ -54,6 +54,9 @@ pub struct ToolResultBlock {
     /// 是否为错误结果
     #[serde(default)]
     pub is_error: bool,
+    /// 随结果返回的图片（例如 notebook 单元格的图片输出）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageBlock>,
 }
 
 /// 图片内容块
@@ -355,12 +358,12 @@ impl ProgressMessage {
     ///
     /// # Examples
     /// ```
-    /// use kode_core::message::ProgressMessage;
+    /// use kode_core::message::types::ProgressMessage;
     /// use kode_core::message::Message;
     /// use std::collections::HashSet;
     ///
     /// let msg = Message::assistant("Running command...");
-    /// let ids = HashSet::from(["tool-1", "tool-2"]);
+    /// let ids = HashSet::from(["tool-1".to_string(), "tool-2".to_string()]);
     ///
     /// let progress = ProgressMessage::new(
     ///     &msg,
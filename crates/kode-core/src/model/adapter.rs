@@ -5,6 +5,7 @@
 use crate::error::Result;
 use crate::message::Message;
 use async_trait::async_trait;
+use std::sync::Arc;
 
 use super::types::TokenUsage;
 
@@ -105,6 +106,17 @@ pub trait ModelAdapter: Send + Sync {
     }
 }
 
+/// 模型适配器提供者
+///
+/// 按名称解析模型适配器，供需要切换模型的场景（例如子 Agent 的模型覆盖）使用。
+pub trait ModelProvider: Send + Sync {
+    /// 获取模型适配器
+    ///
+    /// `model` 为 `None` 时返回默认模型；模型名无法解析时返回
+    /// [`Error::ModelNotConfigured`](crate::error::Error::ModelNotConfigured)。
+    fn adapter(&self, model: Option<&str>) -> Result<Arc<dyn ModelAdapter>>;
+}
+
 /// 模型配置
 ///
 /// 用于创建模型适配器的配置信息。
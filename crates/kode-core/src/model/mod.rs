@@ -6,6 +6,6 @@ pub mod adapter;
 pub mod streaming;
 pub mod types;
 
-pub use adapter::{ModelAdapter, ModelConfig, ModelResponse};
+pub use adapter::{ModelAdapter, ModelConfig, ModelProvider, ModelResponse};
 pub use streaming::StreamingResponse;
 pub use types::{StreamChunk, TokenUsage};
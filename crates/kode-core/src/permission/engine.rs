@@ -0,0 +1,808 @@
+//! 权限引擎
+//!
+//! 按作用域保存规则，结合权限模式评估每次工具调用；需要询问时调用 UI 实现的
+//! [`PermissionPrompt`]，并把“始终允许”的回答写回项目配置的 `allowed_tools`。
+
+use super::{AccessKind, PermissionMode, PermissionRequest, PermissionRule, PermissionScope};
+use super::{CommandTarget, PermissionTarget, FILE_EDIT_RULE};
+use crate::config::{
+    get_current_project_config, save_current_project_config, GlobalConfig, ProjectConfig,
+};
+use crate::error::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 单个作用域内的规则
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeRules {
+    /// 允许规则
+    pub allow: Vec<PermissionRule>,
+    /// 拒绝规则
+    pub deny: Vec<PermissionRule>,
+}
+
+impl ScopeRules {
+    /// 从配置中的字符串列表解析规则
+    ///
+    /// 无法解析的规则会被跳过并记录到 `invalid` 中。
+    fn parse(allow: &[String], deny: &[String], invalid: &mut Vec<String>) -> Self {
+        let mut parse = |rules: &[String]| {
+            rules
+                .iter()
+                .filter_map(|rule| match rule.parse() {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        invalid.push(format!("{}", e));
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            allow: parse(allow),
+            deny: parse(deny),
+        }
+    }
+
+    fn add_allow(&mut self, rule: PermissionRule) {
+        if !self.allow.contains(&rule) {
+            self.allow.push(rule);
+        }
+    }
+}
+
+/// 所有作用域的规则
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PermissionRules {
+    /// 用户级规则
+    pub user: ScopeRules,
+    /// 项目级规则
+    pub project: ScopeRules,
+    /// 会话级规则
+    pub session: ScopeRules,
+    /// 配置中无法解析的规则
+    pub invalid: Vec<String>,
+}
+
+impl PermissionRules {
+    /// 从全局配置和项目配置加载规则
+    pub fn from_configs(global: &GlobalConfig, project: &ProjectConfig) -> Self {
+        let mut invalid = Vec::new();
+        let user = ScopeRules::parse(&global.allowed_tools, &global.denied_tools, &mut invalid);
+        let project =
+            ScopeRules::parse(&project.allowed_tools, &project.denied_tools, &mut invalid);
+        Self {
+            user,
+            project,
+            session: ScopeRules::default(),
+            invalid,
+        }
+    }
+
+    /// 按优先级（会话、项目、用户）遍历作用域
+    fn scopes(&self) -> [(PermissionScope, &ScopeRules); 3] {
+        [
+            (PermissionScope::Session, &self.session),
+            (PermissionScope::Project, &self.project),
+            (PermissionScope::User, &self.user),
+        ]
+    }
+
+    fn allow_rules(&self) -> impl Iterator<Item = &PermissionRule> {
+        self.scopes()
+            .into_iter()
+            .flat_map(|(_, rules)| rules.allow.iter())
+    }
+}
+
+/// 权限评估结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionCheck {
+    /// 放行
+    Allow,
+    /// 拒绝
+    Deny {
+        /// 拒绝原因
+        reason: String,
+    },
+    /// 需要询问用户
+    Ask {
+        /// 询问原因
+        reason: String,
+        /// 用户选择“始终允许”时要添加的规则
+        suggestions: Vec<PermissionRule>,
+    },
+}
+
+/// 最终权限决定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionDecision {
+    /// 放行
+    Allow,
+    /// 拒绝
+    Deny {
+        /// 拒绝原因
+        reason: String,
+    },
+}
+
+impl PermissionDecision {
+    /// 是否放行
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, PermissionDecision::Allow)
+    }
+}
+
+/// 交给 UI 的询问内容
+#[derive(Debug, Clone)]
+pub struct PermissionAsk {
+    /// 权限请求
+    pub request: PermissionRequest,
+    /// 询问原因
+    pub reason: String,
+    /// 选择允许（本会话或始终）时将添加的规则
+    pub suggestions: Vec<PermissionRule>,
+}
+
+/// 用户对询问的回答
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionAnswer {
+    /// 仅允许这一次
+    AllowOnce,
+    /// 在本会话内允许
+    AllowForSession,
+    /// 始终允许（写入项目配置）
+    AlwaysAllow,
+    /// 拒绝
+    Deny,
+}
+
+/// 权限询问回调，由 UI 实现
+#[async_trait]
+pub trait PermissionPrompt: Send + Sync {
+    /// 询问用户是否允许此次工具调用
+    async fn ask(&self, ask: &PermissionAsk) -> PermissionAnswer;
+}
+
+/// “始终允许”规则的持久化
+#[async_trait]
+pub trait PermissionStore: Send + Sync {
+    /// 将规则追加到项目的 `allowed_tools`
+    async fn add_allowed_rules(&self, rules: &[PermissionRule]) -> Result<()>;
+}
+
+/// 写入当前项目配置的持久化实现
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProjectConfigStore;
+
+#[async_trait]
+impl PermissionStore for ProjectConfigStore {
+    async fn add_allowed_rules(&self, rules: &[PermissionRule]) -> Result<()> {
+        let mut config = get_current_project_config().await?;
+        for rule in rules {
+            let rule = rule.to_string();
+            if !config.allowed_tools.contains(&rule) {
+                config.allowed_tools.push(rule);
+            }
+        }
+        save_current_project_config(&config).await
+    }
+}
+
+/// 权限引擎
+///
+/// 在同一会话的所有工具调用之间共享。
+pub struct PermissionEngine {
+    root: PathBuf,
+    mode: Mutex<PermissionMode>,
+    rules: Mutex<PermissionRules>,
+    prompt: Option<Arc<dyn PermissionPrompt>>,
+    store: Option<Arc<dyn PermissionStore>>,
+}
+
+impl std::fmt::Debug for PermissionEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PermissionEngine")
+            .field("root", &self.root)
+            .field("mode", &self.mode())
+            .field("rules", &*lock(&self.rules))
+            .finish_non_exhaustive()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl PermissionEngine {
+    /// 创建权限引擎
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - 项目根目录，路径规则基于此解析
+    /// * `rules` - 初始规则
+    pub fn new(root: impl Into<PathBuf>, rules: PermissionRules) -> Self {
+        Self {
+            root: root.into(),
+            mode: Mutex::new(PermissionMode::Default),
+            rules: Mutex::new(rules),
+            prompt: None,
+            store: None,
+        }
+    }
+
+    /// 设置权限模式
+    pub fn with_mode(self, mode: PermissionMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// 设置询问回调
+    pub fn with_prompt(mut self, prompt: Arc<dyn PermissionPrompt>) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    /// 设置“始终允许”规则的持久化
+    pub fn with_store(mut self, store: Arc<dyn PermissionStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// 当前权限模式
+    pub fn mode(&self) -> PermissionMode {
+        *lock(&self.mode)
+    }
+
+    /// 切换权限模式
+    pub fn set_mode(&self, mode: PermissionMode) {
+        *lock(&self.mode) = mode;
+    }
+
+    /// 当前规则的快照
+    pub fn rules(&self) -> PermissionRules {
+        lock(&self.rules).clone()
+    }
+
+    /// 向指定作用域添加允许规则（只影响内存中的规则）
+    pub fn add_allow_rule(&self, scope: PermissionScope, rule: PermissionRule) {
+        let mut rules = lock(&self.rules);
+        match scope {
+            PermissionScope::User => rules.user.add_allow(rule),
+            PermissionScope::Project => rules.project.add_allow(rule),
+            PermissionScope::Session => rules.session.add_allow(rule),
+        }
+    }
+
+    /// 评估权限请求（不询问用户）
+    pub fn evaluate(&self, request: &PermissionRequest) -> PermissionCheck {
+        let rules = lock(&self.rules);
+
+        for (scope, scope_rules) in rules.scopes() {
+            if let Some(rule) = scope_rules
+                .deny
+                .iter()
+                .find(|rule| rule.matches_any(request, &self.root))
+            {
+                return PermissionCheck::Deny {
+                    reason: format!(
+                        "{} is denied by {} rule `{}`",
+                        request.tool_name, scope, rule
+                    ),
+                };
+            }
+        }
+
+        match self.mode() {
+            PermissionMode::Bypass => return PermissionCheck::Allow,
+            PermissionMode::Plan if request.access != AccessKind::ReadOnly => {
+                return PermissionCheck::Deny {
+                    reason: format!(
+                        "{} is not allowed in plan mode, which only permits read-only tools",
+                        request.tool_name
+                    ),
+                };
+            }
+            PermissionMode::AcceptEdits
+                if request.access == AccessKind::Edit && self.is_project_path(request) =>
+            {
+                return PermissionCheck::Allow;
+            }
+            _ => {}
+        }
+        if request.access == AccessKind::ReadOnly {
+            return PermissionCheck::Allow;
+        }
+
+        let suggestions = match &request.target {
+            PermissionTarget::Commands(commands) => {
+                // 每条非只读命令都需要被某条 allow 规则覆盖
+                let uncovered: Vec<&CommandTarget> = commands
+                    .iter()
+                    .filter(|command| {
+                        !command.read_only
+                            && !rules.allow_rules().any(|rule| {
+                                rule.matches(
+                                    &PermissionRequest {
+                                        target: PermissionTarget::Commands(
+                                            vec![(*command).clone()],
+                                        ),
+                                        ..request.clone()
+                                    },
+                                    &self.root,
+                                )
+                            })
+                    })
+                    .collect();
+                if uncovered.is_empty() && !commands.is_empty() {
+                    return PermissionCheck::Allow;
+                }
+                // 写文件的命令无法被带内容的规则覆盖，不给出建议
+                uncovered
+                    .into_iter()
+                    .filter(|command| !command.writes_file)
+                    .map(|command| PermissionRule::with_content(&request.tool_name, &command.line))
+                    .collect()
+            }
+            PermissionTarget::Paths(paths) if request.access == AccessKind::Edit => {
+                if rules
+                    .allow_rules()
+                    .any(|rule| rule.matches(request, &self.root))
+                {
+                    return PermissionCheck::Allow;
+                }
+                // 为每个未被覆盖的路径分别给出规则建议
+                paths
+                    .iter()
+                    .map(|path| PermissionRequest {
+                        target: PermissionTarget::Path(path.clone()),
+                        ..request.clone()
+                    })
+                    .filter(|single| {
+                        !rules
+                            .allow_rules()
+                            .any(|rule| rule.matches(single, &self.root))
+                    })
+                    .map(|single| self.suggest_rule(&single))
+                    .collect()
+            }
+            _ => {
+                if rules
+                    .allow_rules()
+                    .any(|rule| rule.matches(request, &self.root))
+                {
+                    return PermissionCheck::Allow;
+                }
+                vec![self.suggest_rule(request)]
+            }
+        };
+
+        PermissionCheck::Ask {
+            reason: request
+                .reason
+                .clone()
+                .unwrap_or_else(|| format!("{} requires permission", request.tool_name)),
+            suggestions,
+        }
+    }
+
+    /// 评估权限请求，需要时询问用户
+    ///
+    /// 没有设置询问回调时，需要询问的请求会被拒绝。
+    pub async fn check(&self, request: &PermissionRequest) -> Result<PermissionDecision> {
+        let (reason, suggestions) = match self.evaluate(request) {
+            PermissionCheck::Allow => return Ok(PermissionDecision::Allow),
+            PermissionCheck::Deny { reason } => return Ok(PermissionDecision::Deny { reason }),
+            PermissionCheck::Ask {
+                reason,
+                suggestions,
+            } => (reason, suggestions),
+        };
+
+        let Some(prompt) = &self.prompt else {
+            return Ok(PermissionDecision::Deny {
+                reason: format!("{} and no approval prompt is available", reason),
+            });
+        };
+        let ask = PermissionAsk {
+            request: request.clone(),
+            reason,
+            suggestions,
+        };
+
+        match prompt.ask(&ask).await {
+            PermissionAnswer::AllowOnce => {}
+            PermissionAnswer::AllowForSession => {
+                for rule in &ask.suggestions {
+                    self.add_allow_rule(PermissionScope::Session, rule.clone());
+                }
+            }
+            PermissionAnswer::AlwaysAllow => {
+                for rule in &ask.suggestions {
+                    self.add_allow_rule(PermissionScope::Project, rule.clone());
+                }
+                if let Some(store) = &self.store {
+                    store.add_allowed_rules(&ask.suggestions).await?;
+                }
+            }
+            PermissionAnswer::Deny => {
+                return Ok(PermissionDecision::Deny {
+                    reason: format!("Permission to use {} was denied", request.tool_name),
+                });
+            }
+        }
+        Ok(PermissionDecision::Allow)
+    }
+
+    /// 请求用户批准计划，批准后退出计划模式
+    ///
+    /// 不处于计划模式时直接放行；没有询问回调时拒绝，保持计划模式。
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_name` - 发起批准的工具名
+    /// * `plan` - 展示给用户的计划
+    pub async fn approve_plan(&self, tool_name: &str, plan: &str) -> PermissionDecision {
+        if self.mode() != PermissionMode::Plan {
+            return PermissionDecision::Allow;
+        }
+        let Some(prompt) = &self.prompt else {
+            return PermissionDecision::Deny {
+                reason:
+                    "Leaving plan mode requires user approval and no approval prompt is available"
+                        .to_string(),
+            };
+        };
+        let ask = PermissionAsk {
+            request: PermissionRequest::new(tool_name, AccessKind::Execute).with_reason(plan),
+            reason: format!("Approve this plan and leave plan mode?\n\n{}", plan),
+            suggestions: Vec::new(),
+        };
+        match prompt.ask(&ask).await {
+            PermissionAnswer::Deny => PermissionDecision::Deny {
+                reason: "The user rejected the plan; stay in plan mode and revise it".to_string(),
+            },
+            _ => {
+                self.set_mode(PermissionMode::Default);
+                PermissionDecision::Allow
+            }
+        }
+    }
+
+    /// 请求的目标路径是否位于项目根目录内
+    fn is_project_path(&self, request: &PermissionRequest) -> bool {
+        match &request.target {
+            PermissionTarget::Path(path) => is_within(path, &self.root),
+            PermissionTarget::Paths(paths) => {
+                !paths.is_empty() && paths.iter().all(|path| is_within(path, &self.root))
+            }
+            _ => false,
+        }
+    }
+
+    /// 为“始终允许”生成规则
+    fn suggest_rule(&self, request: &PermissionRequest) -> PermissionRule {
+        match &request.target {
+            PermissionTarget::Path(path) if request.access == AccessKind::Edit => {
+                let path = path.strip_prefix(&self.root).unwrap_or(path);
+                PermissionRule::with_content(FILE_EDIT_RULE, path.to_string_lossy())
+            }
+            PermissionTarget::Domain(domain) => {
+                PermissionRule::with_content(&request.tool_name, format!("domain:{}", domain))
+            }
+            _ => PermissionRule::tool(&request.tool_name),
+        }
+    }
+}
+
+/// 不访问文件系统地判断 `path` 是否位于 `root` 内（处理 `..`）
+fn is_within(path: &Path, root: &Path) -> bool {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized.starts_with(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ROOT: &str = "/project";
+
+    struct FixedPrompt {
+        answer: PermissionAnswer,
+        asked: AtomicUsize,
+    }
+
+    impl FixedPrompt {
+        fn new(answer: PermissionAnswer) -> Arc<Self> {
+            Arc::new(Self {
+                answer,
+                asked: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl PermissionPrompt for FixedPrompt {
+        async fn ask(&self, _ask: &PermissionAsk) -> PermissionAnswer {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            self.answer
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl PermissionStore for MemoryStore {
+        async fn add_allowed_rules(&self, rules: &[PermissionRule]) -> Result<()> {
+            lock(&self.0).extend(rules.iter().map(|r| r.to_string()));
+            Ok(())
+        }
+    }
+
+    fn engine(allow: &[&str], deny: &[&str]) -> PermissionEngine {
+        let project = ProjectConfig {
+            allowed_tools: allow.iter().map(|s| s.to_string()).collect(),
+            denied_tools: deny.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        PermissionEngine::new(
+            ROOT,
+            PermissionRules::from_configs(&GlobalConfig::default(), &project),
+        )
+    }
+
+    fn bash(commands: &[(&str, bool)]) -> PermissionRequest {
+        let access = if commands.iter().all(|(_, read_only)| *read_only) {
+            AccessKind::ReadOnly
+        } else {
+            AccessKind::Execute
+        };
+        PermissionRequest::new("Bash", access).with_target(PermissionTarget::Commands(
+            commands
+                .iter()
+                .map(|(line, read_only)| CommandTarget::new(*line, *read_only))
+                .collect(),
+        ))
+    }
+
+    fn edit(path: &str) -> PermissionRequest {
+        PermissionRequest::new("MultiEdit", AccessKind::Edit)
+            .with_target(PermissionTarget::Path(PathBuf::from(path)))
+    }
+
+    fn is_ask(check: &PermissionCheck) -> bool {
+        matches!(check, PermissionCheck::Ask { .. })
+    }
+
+    #[test]
+    fn test_rules_from_configs() {
+        let global = GlobalConfig {
+            allowed_tools: vec!["Grep".to_string()],
+            denied_tools: vec!["Bash(curl:*)".to_string()],
+            ..Default::default()
+        };
+        let project = ProjectConfig {
+            allowed_tools: vec!["Bash(npm test)".to_string(), "Bash(".to_string()],
+            ..Default::default()
+        };
+        let rules = PermissionRules::from_configs(&global, &project);
+        assert_eq!(rules.user.allow, vec![PermissionRule::tool("Grep")]);
+        assert_eq!(rules.user.deny.len(), 1);
+        assert_eq!(rules.project.allow.len(), 1);
+        assert_eq!(rules.invalid.len(), 1);
+    }
+
+    #[test]
+    fn test_default_mode() {
+        let engine = engine(
+            &["Bash(git commit:*)", "FileEdit(docs/**)"],
+            &["Bash(git push:*)"],
+        );
+
+        // 只读操作自动放行
+        assert_eq!(
+            engine.evaluate(&bash(&[("git status", true)])),
+            PermissionCheck::Allow
+        );
+        // 非只读命令需要 allow 规则覆盖
+        assert_eq!(
+            engine.evaluate(&bash(&[("git status", true), ("git commit -m x", false)])),
+            PermissionCheck::Allow
+        );
+        match engine.evaluate(&bash(&[("git commit -m x", false), ("npm install", false)])) {
+            PermissionCheck::Ask { suggestions, .. } => {
+                assert_eq!(suggestions, vec!["Bash(npm install)".parse().unwrap()]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // deny 规则命中任意子命令即拒绝
+        assert!(matches!(
+            engine.evaluate(&bash(&[("git status", true), ("git push origin", false)])),
+            PermissionCheck::Deny { .. }
+        ));
+
+        assert_eq!(
+            engine.evaluate(&edit("/project/docs/guide.md")),
+            PermissionCheck::Allow
+        );
+        match engine.evaluate(&edit("/project/src/main.rs")) {
+            PermissionCheck::Ask { suggestions, .. } => {
+                assert_eq!(suggestions[0].to_string(), "FileEdit(src/main.rs)");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_multiple_paths() {
+        let engine = engine(&["FileEdit(docs/**)"], &["FileEdit(secrets/**)"]);
+        let paths = |paths: &[&str]| {
+            PermissionRequest::new("ApplyPatch", AccessKind::Edit).with_target(
+                PermissionTarget::Paths(paths.iter().map(PathBuf::from).collect()),
+            )
+        };
+
+        // 每个路径都被 allow 规则覆盖才放行
+        assert_eq!(
+            engine.evaluate(&paths(&["/project/docs/a.md", "/project/docs/b.md"])),
+            PermissionCheck::Allow
+        );
+        match engine.evaluate(&paths(&["/project/docs/a.md", "/project/src/lib.rs"])) {
+            PermissionCheck::Ask { suggestions, .. } => {
+                assert_eq!(suggestions, vec!["FileEdit(src/lib.rs)".parse().unwrap()]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // deny 规则命中任意路径即拒绝
+        assert!(matches!(
+            engine.evaluate(&paths(&["/project/docs/a.md", "/project/secrets/key.pem"])),
+            PermissionCheck::Deny { .. }
+        ));
+
+        engine.set_mode(PermissionMode::AcceptEdits);
+        assert_eq!(
+            engine.evaluate(&paths(&["/project/src/a.rs", "/project/src/b.rs"])),
+            PermissionCheck::Allow
+        );
+        assert!(is_ask(&engine.evaluate(&paths(&[
+            "/project/src/a.rs",
+            "/project/../etc/hosts"
+        ]))));
+    }
+
+    #[test]
+    fn test_modes() {
+        let engine = engine(&[], &["FileEdit(secrets/**)"]);
+        let in_project = edit("/project/src/main.rs");
+        let outside = edit("/project/../etc/passwd");
+        let command = bash(&[("make", false)]);
+
+        engine.set_mode(PermissionMode::AcceptEdits);
+        assert_eq!(engine.evaluate(&in_project), PermissionCheck::Allow);
+        assert!(is_ask(&engine.evaluate(&outside)));
+        assert!(is_ask(&engine.evaluate(&command)));
+
+        engine.set_mode(PermissionMode::Plan);
+        assert!(matches!(
+            engine.evaluate(&in_project),
+            PermissionCheck::Deny { .. }
+        ));
+        assert_eq!(
+            engine.evaluate(&bash(&[("ls", true)])),
+            PermissionCheck::Allow
+        );
+
+        engine.set_mode(PermissionMode::Bypass);
+        assert_eq!(engine.evaluate(&command), PermissionCheck::Allow);
+        // deny 规则在 bypass 模式下依然生效
+        assert!(matches!(
+            engine.evaluate(&edit("/project/secrets/key.pem")),
+            PermissionCheck::Deny { .. }
+        ));
+
+        assert_eq!(
+            "accept-edits".parse::<PermissionMode>().unwrap(),
+            PermissionMode::AcceptEdits
+        );
+        assert!("yolo".parse::<PermissionMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_without_prompt_denies() {
+        let engine = engine(&[], &[]);
+        let decision = engine.check(&bash(&[("make", false)])).await.unwrap();
+        assert!(!decision.is_allowed());
+    }
+
+    #[tokio::test]
+    async fn test_session_answer() {
+        let prompt = FixedPrompt::new(PermissionAnswer::AllowForSession);
+        let engine = engine(&[], &[]).with_prompt(prompt.clone());
+        let request = bash(&[("make", false)]);
+
+        assert!(engine.check(&request).await.unwrap().is_allowed());
+        assert!(engine.check(&request).await.unwrap().is_allowed());
+        assert_eq!(prompt.asked.load(Ordering::SeqCst), 1);
+        assert_eq!(engine.rules().session.allow.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_always_allow_persists() {
+        let store = Arc::new(MemoryStore::default());
+        let engine = engine(&[], &[])
+            .with_prompt(FixedPrompt::new(PermissionAnswer::AlwaysAllow))
+            .with_store(store.clone());
+
+        assert!(engine
+            .check(&edit("/project/src/lib.rs"))
+            .await
+            .unwrap()
+            .is_allowed());
+        assert_eq!(*lock(&store.0), vec!["FileEdit(src/lib.rs)".to_string()]);
+        assert_eq!(engine.rules().project.allow.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deny_answer() {
+        let engine = engine(&[], &[]).with_prompt(FixedPrompt::new(PermissionAnswer::Deny));
+        let decision = engine.check(&edit("/project/a.rs")).await.unwrap();
+        assert_eq!(
+            decision,
+            PermissionDecision::Deny {
+                reason: "Permission to use MultiEdit was denied".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_plan() {
+        let no_prompt = engine(&[], &[]).with_mode(PermissionMode::Plan);
+        assert!(!no_prompt
+            .approve_plan("ExitPlanMode", "1. edit")
+            .await
+            .is_allowed());
+        assert_eq!(no_prompt.mode(), PermissionMode::Plan);
+
+        let rejecting = engine(&[], &[])
+            .with_mode(PermissionMode::Plan)
+            .with_prompt(FixedPrompt::new(PermissionAnswer::Deny));
+        assert!(!rejecting
+            .approve_plan("ExitPlanMode", "1. edit")
+            .await
+            .is_allowed());
+        assert_eq!(rejecting.mode(), PermissionMode::Plan);
+
+        let prompt = FixedPrompt::new(PermissionAnswer::AllowOnce);
+        let approving = engine(&[], &[])
+            .with_mode(PermissionMode::Plan)
+            .with_prompt(prompt.clone());
+        assert!(approving
+            .approve_plan("ExitPlanMode", "1. edit")
+            .await
+            .is_allowed());
+        assert_eq!(approving.mode(), PermissionMode::Default);
+        assert_eq!(prompt.asked.load(Ordering::SeqCst), 1);
+        // 退出计划模式后编辑恢复为按规则询问
+        assert!(is_ask(&approving.evaluate(&edit("/project/src/main.rs"))));
+        // 不在计划模式时无需批准
+        assert!(approving
+            .approve_plan("ExitPlanMode", "again")
+            .await
+            .is_allowed());
+        assert_eq!(prompt.asked.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,190 @@
+//! 权限系统
+//!
+//! 每次工具调用前，由工具层构造 [`PermissionRequest`]，交给 [`PermissionEngine`] 评估：
+//! 先匹配各作用域的 deny 规则，再按权限模式和 allow 规则决定放行、拒绝或询问用户。
+//!
+//! # 模块结构
+//!
+//! - [`rule`](rule): 规则语法与匹配
+//! - [`engine`](engine): 规则作用域、权限模式与询问回调
+
+pub mod engine;
+pub mod rule;
+
+pub use engine::{
+    PermissionAnswer, PermissionAsk, PermissionCheck, PermissionDecision, PermissionEngine,
+    PermissionPrompt, PermissionRules, PermissionStore, ProjectConfigStore, ScopeRules,
+};
+pub use rule::{PermissionRule, RuleParseError, FILE_EDIT_RULE};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// 工具调用的访问类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// 只读（读取文件、搜索、只读命令）
+    ReadOnly,
+    /// 编辑文件
+    Edit,
+    /// 执行命令或其他有副作用的操作
+    Execute,
+}
+
+/// 单条 shell 命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTarget {
+    /// 命令行
+    pub line: String,
+    /// 是否为只读命令
+    pub read_only: bool,
+    /// 去掉前导变量赋值和包装命令（`env`、`nohup`、`timeout` 等）后实际执行的命令，
+    /// 与 `line` 不同时用于匹配 deny 规则
+    pub effective_line: Option<String>,
+    /// 是否通过重定向写入文件，此时带内容的 allow 规则不匹配
+    pub writes_file: bool,
+}
+
+impl CommandTarget {
+    /// 创建命令目标
+    pub fn new(line: impl Into<String>, read_only: bool) -> Self {
+        Self {
+            line: line.into(),
+            read_only,
+            effective_line: None,
+            writes_file: false,
+        }
+    }
+
+    /// 设置实际执行的命令
+    pub fn with_effective_line(mut self, effective_line: impl Into<String>) -> Self {
+        let effective_line = effective_line.into();
+        self.effective_line = (effective_line != self.line).then_some(effective_line);
+        self
+    }
+
+    /// 标记命令会通过重定向写入文件
+    pub fn with_file_redirect(mut self) -> Self {
+        self.writes_file = true;
+        self
+    }
+}
+
+/// 权限规则匹配的对象
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PermissionTarget {
+    /// 只按工具名匹配
+    #[default]
+    None,
+    /// 文件路径（绝对路径）
+    Path(PathBuf),
+    /// 多个文件路径（绝对路径），例如一次修改多个文件的补丁
+    Paths(Vec<PathBuf>),
+    /// 拆分后的 shell 命令
+    Commands(Vec<CommandTarget>),
+    /// 网络请求的目标主机（小写），匹配 `WebFetch(domain:example.com)` 形式的规则
+    Domain(String),
+}
+
+/// 一次工具调用的权限请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRequest {
+    /// 工具名
+    pub tool_name: String,
+    /// 访问类型
+    pub access: AccessKind,
+    /// 规则匹配的对象
+    pub target: PermissionTarget,
+    /// 需要询问时展示给用户的原因
+    pub reason: Option<String>,
+}
+
+impl PermissionRequest {
+    /// 创建权限请求
+    pub fn new(tool_name: impl Into<String>, access: AccessKind) -> Self {
+        Self {
+            tool_name: tool_name.into(),
+            access,
+            target: PermissionTarget::None,
+            reason: None,
+        }
+    }
+
+    /// 设置规则匹配的对象
+    pub fn with_target(mut self, target: PermissionTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// 设置询问原因
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// 规则作用域
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionScope {
+    /// 用户级（全局配置）
+    User,
+    /// 项目级（项目配置）
+    Project,
+    /// 仅当前会话
+    Session,
+}
+
+impl fmt::Display for PermissionScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionScope::User => write!(f, "user"),
+            PermissionScope::Project => write!(f, "project"),
+            PermissionScope::Session => write!(f, "session"),
+        }
+    }
+}
+
+/// 权限模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionMode {
+    /// 只读操作自动放行，其余按规则放行或询问
+    #[default]
+    Default,
+    /// 在默认模式基础上自动放行项目内的文件编辑
+    AcceptEdits,
+    /// 只读模式：拒绝所有非只读操作
+    Plan,
+    /// 除 deny 规则外全部放行
+    Bypass,
+}
+
+impl fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionMode::Default => write!(f, "default"),
+            PermissionMode::AcceptEdits => write!(f, "accept-edits"),
+            PermissionMode::Plan => write!(f, "plan"),
+            PermissionMode::Bypass => write!(f, "bypass"),
+        }
+    }
+}
+
+impl FromStr for PermissionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(PermissionMode::Default),
+            "accept-edits" | "acceptEdits" => Ok(PermissionMode::AcceptEdits),
+            "plan" => Ok(PermissionMode::Plan),
+            "bypass" | "bypassPermissions" => Ok(PermissionMode::Bypass),
+            _ => Err(format!(
+                "Unknown permission mode '{}' (expected default, accept-edits, plan or bypass)",
+                s
+            )),
+        }
+    }
+}
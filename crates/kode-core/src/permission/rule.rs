@@ -0,0 +1,368 @@
+//! 权限规则
+//!
+//! 规则语法：
+//!
+//! - `Tool`：匹配该工具的所有调用，例如 `Grep`
+//! - `Bash(git status)`：只匹配完全相同的命令
+//! - `Bash(git status:*)`：匹配以 `git status` 开头的命令
+//! - `FileEdit(src/**)`：匹配对 `src/` 下文件的任意编辑（glob，相对于项目根目录）
+//! - `mcp__server__tool`：匹配 MCP 服务器的某个工具；`mcp__server` 匹配该服务器的所有工具
+
+use super::{AccessKind, PermissionRequest, PermissionTarget};
+use glob::{MatchOptions, Pattern};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// 代表所有文件编辑工具的规则名
+pub const FILE_EDIT_RULE: &str = "FileEdit";
+
+/// 权限规则解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleParseError(String);
+
+impl fmt::Display for RuleParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid permission rule: {}", self.0)
+    }
+}
+
+impl std::error::Error for RuleParseError {}
+
+/// 单条权限规则
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PermissionRule {
+    /// 工具名（或 `FileEdit`、`mcp__server`）
+    pub tool: String,
+    /// 括号中的规则内容
+    pub content: Option<String>,
+}
+
+impl PermissionRule {
+    /// 创建只按工具名匹配的规则
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self {
+            tool: name.into(),
+            content: None,
+        }
+    }
+
+    /// 创建带内容的规则
+    pub fn with_content(name: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool: name.into(),
+            content: Some(content.into()),
+        }
+    }
+
+    /// 规则名是否适用于该请求的工具
+    fn applies_to(&self, request: &PermissionRequest) -> bool {
+        if self.tool == request.tool_name {
+            return true;
+        }
+        if self.tool == FILE_EDIT_RULE {
+            return request.access == AccessKind::Edit;
+        }
+        // `mcp__server` 匹配该服务器下的所有工具
+        self.tool.starts_with("mcp__")
+            && self.content.is_none()
+            && request
+                .tool_name
+                .strip_prefix(self.tool.as_str())
+                .is_some_and(|rest| rest.starts_with("__"))
+    }
+
+    /// 规则是否匹配单条命令
+    pub fn matches_command(&self, command: &str) -> bool {
+        match &self.content {
+            None => true,
+            Some(content) => match content.strip_suffix(":*") {
+                Some(prefix) => {
+                    command == prefix
+                        || command
+                            .strip_prefix(prefix)
+                            .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+                }
+                None => command == content,
+            },
+        }
+    }
+
+    /// 规则是否匹配文件路径
+    ///
+    /// 相对模式基于 `root` 解析。
+    pub fn matches_path(&self, path: &Path, root: &Path) -> bool {
+        let Some(content) = &self.content else {
+            return true;
+        };
+        let Ok(pattern) = Pattern::new(content) else {
+            return false;
+        };
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        if Path::new(content).is_absolute() {
+            return pattern.matches_path_with(path, options);
+        }
+        path.strip_prefix(root)
+            .is_ok_and(|relative| pattern.matches_path_with(relative, options))
+    }
+
+    /// 规则是否匹配网络请求的目标主机
+    ///
+    /// 规则内容为 `domain:example.com`，`domain:*.example.com` 匹配所有子域名。
+    pub fn matches_domain(&self, domain: &str) -> bool {
+        let Some(content) = &self.content else {
+            return true;
+        };
+        let Some(pattern) = content.strip_prefix("domain:") else {
+            return false;
+        };
+        match pattern.strip_prefix("*.") {
+            Some(parent) => domain
+                .to_ascii_lowercase()
+                .strip_suffix(&parent.to_ascii_lowercase())
+                .is_some_and(|sub| sub.ends_with('.')),
+            None => domain.eq_ignore_ascii_case(pattern),
+        }
+    }
+
+    /// 规则是否匹配整个请求
+    ///
+    /// 多条命令或多个路径的请求要求每一项都匹配，用于判断 allow 规则。
+    pub fn matches(&self, request: &PermissionRequest, root: &Path) -> bool {
+        if !self.applies_to(request) {
+            return false;
+        }
+        match &request.target {
+            _ if self.content.is_none() => true,
+            PermissionTarget::None => false,
+            PermissionTarget::Path(path) => self.matches_path(path, root),
+            PermissionTarget::Domain(domain) => self.matches_domain(domain),
+            PermissionTarget::Paths(paths) => {
+                !paths.is_empty() && paths.iter().all(|path| self.matches_path(path, root))
+            }
+            // 重定向写文件的命令不受带内容的 allow 规则覆盖，避免 `Bash(echo:*)` 放行 `echo x > ~/.bashrc`
+            PermissionTarget::Commands(commands) => {
+                !commands.is_empty()
+                    && commands
+                        .iter()
+                        .all(|c| !c.writes_file && self.matches_command(&c.line))
+            }
+        }
+    }
+
+    /// 规则是否匹配请求的任意部分
+    ///
+    /// 只要有一条命令或一个路径匹配就算命中，用于判断 deny 规则。
+    pub fn matches_any(&self, request: &PermissionRequest, root: &Path) -> bool {
+        if !self.applies_to(request) {
+            return false;
+        }
+        match &request.target {
+            PermissionTarget::Commands(commands) if self.content.is_some() => {
+                commands.iter().any(|c| {
+                    self.matches_command(&c.line)
+                        || c.effective_line
+                            .as_deref()
+                            .is_some_and(|line| self.matches_command(line))
+                })
+            }
+            PermissionTarget::Paths(paths) if self.content.is_some() => {
+                paths.iter().any(|path| self.matches_path(path, root))
+            }
+            _ => self.matches(request, root),
+        }
+    }
+}
+
+impl FromStr for PermissionRule {
+    type Err = RuleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (tool, content) = match s.find('(') {
+            Some(open) => {
+                let content = s[open + 1..]
+                    .strip_suffix(')')
+                    .ok_or_else(|| RuleParseError(format!("missing closing ')' in `{}`", s)))?;
+                (&s[..open], Some(content.trim()))
+            }
+            None => (s, None),
+        };
+
+        let tool = tool.trim();
+        if tool.is_empty()
+            || !tool
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(RuleParseError(format!("invalid tool name in `{}`", s)));
+        }
+        match content {
+            Some("") => Err(RuleParseError(format!("empty rule content in `{}`", s))),
+            Some(content) => Ok(Self::with_content(tool, content)),
+            None => Ok(Self::tool(tool)),
+        }
+    }
+}
+
+impl fmt::Display for PermissionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.content {
+            Some(content) => write!(f, "{}({})", self.tool, content),
+            None => write!(f, "{}", self.tool),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permission::CommandTarget;
+    use std::path::PathBuf;
+
+    fn bash(commands: &[&str]) -> PermissionRequest {
+        PermissionRequest::new("Bash", AccessKind::Execute).with_target(PermissionTarget::Commands(
+            commands
+                .iter()
+                .map(|line| CommandTarget::new(*line, false))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let cases = [
+            ("Grep", "Grep", None),
+            ("Bash(git status:*)", "Bash", Some("git status:*")),
+            ("FileEdit( src/** )", "FileEdit", Some("src/**")),
+            (
+                "mcp__github__create_issue",
+                "mcp__github__create_issue",
+                None,
+            ),
+        ];
+        for (input, tool, content) in cases {
+            let rule: PermissionRule = input.parse().unwrap();
+            assert_eq!(rule.tool, tool, "{}", input);
+            assert_eq!(rule.content.as_deref(), content, "{}", input);
+        }
+        assert_eq!(
+            "Bash(npm test)"
+                .parse::<PermissionRule>()
+                .unwrap()
+                .to_string(),
+            "Bash(npm test)"
+        );
+
+        for invalid in ["", "Bash(", "Bash()", "(ls)", "Bad Name"] {
+            assert!(invalid.parse::<PermissionRule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_command_matching() {
+        let prefix: PermissionRule = "Bash(git status:*)".parse().unwrap();
+        assert!(prefix.matches_command("git status"));
+        assert!(prefix.matches_command("git status --short"));
+        assert!(!prefix.matches_command("git statusx"));
+        assert!(!prefix.matches_command("git push"));
+
+        let exact: PermissionRule = "Bash(npm test)".parse().unwrap();
+        assert!(exact.matches_command("npm test"));
+        assert!(!exact.matches_command("npm test -- --watch"));
+
+        // allow 需要全部命令匹配，deny 只需任意一条
+        let root = Path::new("/project");
+        let request = bash(&["git status", "rm -rf build"]);
+        assert!(!prefix.matches(&request, root));
+        assert!(prefix.matches_any(&request, root));
+        assert!(PermissionRule::tool("Bash").matches(&request, root));
+    }
+
+    #[test]
+    fn test_command_redirects_and_wrappers() {
+        let root = Path::new("/project");
+        let echo: PermissionRule = "Bash(echo:*)".parse().unwrap();
+        let redirected = PermissionRequest::new("Bash", AccessKind::Execute).with_target(
+            PermissionTarget::Commands(vec![
+                CommandTarget::new("echo pwn", false).with_file_redirect()
+            ]),
+        );
+        assert!(!echo.matches(&redirected, root));
+        assert!(echo.matches_any(&redirected, root));
+        assert!(PermissionRule::tool("Bash").matches(&redirected, root));
+
+        let rm: PermissionRule = "Bash(rm:*)".parse().unwrap();
+        let wrapped = PermissionRequest::new("Bash", AccessKind::Execute).with_target(
+            PermissionTarget::Commands(vec![CommandTarget::new("timeout 1 rm -rf build", false)
+                .with_effective_line("rm -rf build")]),
+        );
+        assert!(rm.matches_any(&wrapped, root));
+        assert!(!rm.matches(&wrapped, root));
+
+        let plain = CommandTarget::new("rm -rf build", false).with_effective_line("rm -rf build");
+        assert_eq!(plain.effective_line, None);
+    }
+
+    #[test]
+    fn test_domain_matching() {
+        let root = Path::new("/project");
+        let fetch = |domain: &str| {
+            PermissionRequest::new("WebFetch", AccessKind::Execute)
+                .with_target(PermissionTarget::Domain(domain.to_string()))
+        };
+
+        let exact: PermissionRule = "WebFetch(domain:docs.rs)".parse().unwrap();
+        assert!(exact.matches(&fetch("docs.rs"), root));
+        assert!(exact.matches(&fetch("DOCS.rs"), root));
+        assert!(!exact.matches(&fetch("evil-docs.rs"), root));
+        assert!(!exact.matches(&fetch("sub.docs.rs"), root));
+
+        let wildcard: PermissionRule = "WebFetch(domain:*.example.com)".parse().unwrap();
+        assert!(wildcard.matches(&fetch("api.example.com"), root));
+        assert!(!wildcard.matches(&fetch("example.com"), root));
+        assert!(!wildcard.matches(&fetch("badexample.com"), root));
+
+        let other: PermissionRule = "WebFetch(example.com)".parse().unwrap();
+        assert!(!other.matches(&fetch("example.com"), root));
+        assert!(PermissionRule::tool("WebFetch").matches(&fetch("example.com"), root));
+    }
+
+    #[test]
+    fn test_path_matching() {
+        let root = Path::new("/project");
+        let rule: PermissionRule = "FileEdit(src/**)".parse().unwrap();
+        let edit = |tool: &str, path: &str| {
+            PermissionRequest::new(tool, AccessKind::Edit)
+                .with_target(PermissionTarget::Path(PathBuf::from(path)))
+        };
+
+        assert!(rule.matches(&edit("MultiEdit", "/project/src/main.rs"), root));
+        assert!(rule.matches(&edit("FileWrite", "/project/src/a/b.rs"), root));
+        assert!(!rule.matches(&edit("FileWrite", "/project/Cargo.toml"), root));
+        assert!(!rule.matches(&edit("FileWrite", "/other/src/main.rs"), root));
+
+        let read = PermissionRequest::new("FileRead", AccessKind::ReadOnly).with_target(
+            PermissionTarget::Path(PathBuf::from("/project/src/main.rs")),
+        );
+        assert!(!rule.matches(&read, root));
+
+        let absolute: PermissionRule = "FileWrite(/tmp/*.log)".parse().unwrap();
+        assert!(absolute.matches(&edit("FileWrite", "/tmp/out.log"), root));
+    }
+
+    #[test]
+    fn test_mcp_matching() {
+        let root = Path::new("/project");
+        let request = PermissionRequest::new("mcp__github__create_issue", AccessKind::Execute);
+
+        assert!(PermissionRule::tool("mcp__github__create_issue").matches(&request, root));
+        assert!(PermissionRule::tool("mcp__github").matches(&request, root));
+        assert!(!PermissionRule::tool("mcp__git").matches(&request, root));
+        assert!(!PermissionRule::tool("mcp__gitlab").matches(&request, root));
+    }
+}
@@ -0,0 +1,623 @@
+//! 查询循环实现
+
+use super::{
+    QueryEvent, QueryOptions, QueryResult, ReminderSource, StopReason, ToolRunner,
+    TOOL_CANCELLED_MESSAGE,
+};
+use crate::context::MessageContextManager;
+use crate::error::{Error, Result};
+use crate::message::types::TokenUsage;
+use crate::message::{ContentBlock, Message, TextBlock, ToolResultBlock, ToolUseBlock};
+use crate::model::{ModelAdapter, StreamChunk, StreamingResponse};
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+/// 用量上限回调
+///
+/// 每轮模型输出结束后以该轮的助手消息调用（包含 `usage` 和 `cost_usd`），
+/// 返回 `false` 时不再发起新一轮请求。
+pub type UsageLimit = Arc<dyn Fn(&Message) -> bool + Send + Sync>;
+
+/// 查询循环
+///
+/// # Examples
+///
+/// ```ignore
+/// let engine = QueryEngine::new(adapter, runner)
+///     .with_events(events_tx)
+///     .with_cancellation(cancel.clone());
+///
+/// context.add_message(Message::user("Fix the failing test"));
+/// let result = engine.run(&mut context).await?;
+/// ```
+pub struct QueryEngine {
+    adapter: Arc<dyn ModelAdapter>,
+    tools: Arc<dyn ToolRunner>,
+    options: QueryOptions,
+    events: Option<UnboundedSender<QueryEvent>>,
+    cancel: CancellationToken,
+    usage_limit: Option<UsageLimit>,
+    reminders: Option<Arc<dyn ReminderSource>>,
+}
+
+/// 一轮模型输出
+#[derive(Default)]
+struct Turn {
+    blocks: Vec<ContentBlock>,
+    text: String,
+    tool_uses: Vec<ToolUseBlock>,
+    usage: Option<TokenUsage>,
+}
+
+impl Turn {
+    /// 把累积的文本作为一个文本块收尾
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.blocks.push(ContentBlock::Text(TextBlock { text }));
+        }
+    }
+
+    fn into_message(mut self) -> (Message, Vec<ToolUseBlock>) {
+        self.flush_text();
+        let mut message = Message::assistant("").with_blocks(self.blocks);
+        if let Some(usage) = self.usage {
+            message = message.with_usage(usage);
+        }
+        (message, self.tool_uses)
+    }
+
+    /// 丢弃工具调用，只保留已输出的文本；没有文本时返回 `None`
+    fn into_partial_message(mut self) -> Option<Message> {
+        self.blocks
+            .retain(|block| !matches!(block, ContentBlock::ToolUse(_)));
+        self.tool_uses.clear();
+        self.flush_text();
+        if self.blocks.is_empty() {
+            return None;
+        }
+        Some(self.into_message().0)
+    }
+}
+
+impl QueryEngine {
+    /// 创建查询循环
+    pub fn new(adapter: Arc<dyn ModelAdapter>, tools: Arc<dyn ToolRunner>) -> Self {
+        Self {
+            adapter,
+            tools,
+            options: QueryOptions::default(),
+            events: None,
+            cancel: CancellationToken::new(),
+            usage_limit: None,
+            reminders: None,
+        }
+    }
+
+    /// 设置查询选项
+    pub fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 设置最大轮数（至少为 1）
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.options.max_turns = max_turns.max(1);
+        self
+    }
+
+    /// 设置系统提示词
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.options.system_prompt = Some(system_prompt.into());
+        self
+    }
+
+    /// 设置事件通道
+    pub fn with_events(mut self, events: UnboundedSender<QueryEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// 设置取消令牌
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 设置用量上限回调
+    pub fn with_usage_limit(mut self, usage_limit: UsageLimit) -> Self {
+        self.usage_limit = Some(usage_limit);
+        self
+    }
+
+    /// 设置上下文提醒来源
+    pub fn with_reminders(mut self, reminders: Arc<dyn ReminderSource>) -> Self {
+        self.reminders = Some(reminders);
+        self
+    }
+
+    /// 查询选项
+    pub fn options(&self) -> &QueryOptions {
+        &self.options
+    }
+
+    /// 运行查询循环
+    ///
+    /// 每轮把 `context` 规范化后的消息发送给模型，并把助手回复和工具结果追加回 `context`。
+    /// 模型回复中没有工具调用、达到最大轮数或被取消时结束。
+    ///
+    /// # Errors
+    ///
+    /// 模型请求失败或流中出现错误事件时返回错误；已追加到 `context` 的消息保留。
+    pub async fn run(&self, context: &mut MessageContextManager) -> Result<QueryResult> {
+        let mut result = QueryResult {
+            messages: Vec::new(),
+            stop_reason: StopReason::EndTurn,
+            turns: 0,
+            usage: TokenUsage::default(),
+        };
+
+        result.stop_reason = loop {
+            if self.cancel.is_cancelled() {
+                break StopReason::Cancelled;
+            }
+            if result.turns >= self.options.max_turns {
+                break StopReason::MaxTurns;
+            }
+            result.turns += 1;
+            self.emit(QueryEvent::TurnStarted { turn: result.turns });
+
+            let request = self.adapter.stream_message(
+                context.normalize_messages_for_api(),
+                self.options.system_prompt.clone(),
+                self.options.max_tokens,
+            );
+            let stream = tokio::select! {
+                _ = self.cancel.cancelled() => break StopReason::Cancelled,
+                stream = request => stream?,
+            };
+
+            let (turn, cancelled) = self.collect_turn(stream).await?;
+            if let Some(usage) = &turn.usage {
+                add_usage(&mut result.usage, usage);
+            }
+            if cancelled {
+                // 流被中断时工具调用可能不完整，只保留已输出的文本
+                let usage = turn.usage.clone();
+                match turn.into_partial_message() {
+                    Some(message) => {
+                        self.within_usage_limit(&message);
+                        self.append(context, &mut result, message);
+                    }
+                    None => {
+                        if let Some(usage) = usage {
+                            self.within_usage_limit(&Message::assistant("").with_usage(usage));
+                        }
+                    }
+                }
+                break StopReason::Cancelled;
+            }
+            let (message, tool_uses) = turn.into_message();
+            let within_limit = self.within_usage_limit(&message);
+            self.append(context, &mut result, message);
+
+            if tool_uses.is_empty() {
+                break StopReason::EndTurn;
+            }
+
+            let (results, cancelled) = tokio::select! {
+                _ = self.cancel.cancelled() => (cancelled_results(&tool_uses), true),
+                results = self.tools.run(&tool_uses, &self.cancel) => (results, false),
+            };
+            for tool_result in &results {
+                self.emit(QueryEvent::ToolResult(tool_result.clone()));
+            }
+            let mut blocks: Vec<ContentBlock> =
+                results.into_iter().map(ContentBlock::ToolResult).collect();
+            if let Some(reminders) = &self.reminders {
+                blocks.extend(
+                    reminders
+                        .reminders()
+                        .into_iter()
+                        .map(|text| ContentBlock::Text(TextBlock { text })),
+                );
+            }
+            self.append(context, &mut result, Message::user("").with_blocks(blocks));
+
+            if cancelled || self.cancel.is_cancelled() {
+                break StopReason::Cancelled;
+            }
+            if !within_limit {
+                break StopReason::UsageLimit;
+            }
+        };
+
+        self.emit(QueryEvent::Finished {
+            stop_reason: result.stop_reason,
+            turns: result.turns,
+        });
+        Ok(result)
+    }
+
+    /// 读取一轮流式输出
+    ///
+    /// 返回累积的输出以及流是否被取消。
+    async fn collect_turn(&self, mut stream: StreamingResponse) -> Result<(Turn, bool)> {
+        let mut turn = Turn::default();
+        loop {
+            let chunk = tokio::select! {
+                _ = self.cancel.cancelled() => return Ok((turn, true)),
+                chunk = stream.next() => chunk,
+            };
+            let Some(chunk) = chunk else {
+                return Ok((turn, false));
+            };
+            match chunk? {
+                StreamChunk::ContentBlockStart { .. } => {}
+                StreamChunk::ContentBlockDelta { delta, .. } => {
+                    turn.text.push_str(&delta);
+                    self.emit(QueryEvent::TextDelta { text: delta });
+                }
+                StreamChunk::ContentBlockStop { .. } => turn.flush_text(),
+                StreamChunk::ToolUse {
+                    tool_name,
+                    tool_use_id,
+                    parameters,
+                } => {
+                    turn.flush_text();
+                    let tool_use = ToolUseBlock {
+                        tool_use_id,
+                        tool_name,
+                        parameters,
+                    };
+                    self.emit(QueryEvent::ToolUse(tool_use.clone()));
+                    turn.blocks.push(ContentBlock::ToolUse(tool_use.clone()));
+                    turn.tool_uses.push(tool_use);
+                }
+                StreamChunk::MessageStop { usage } => {
+                    turn.usage = Some(TokenUsage {
+                        input_tokens: Some(usage.input_tokens),
+                        output_tokens: Some(usage.output_tokens),
+                        ..Default::default()
+                    });
+                }
+                StreamChunk::Error { message } => return Err(Error::ModelStreamError(message)),
+            }
+        }
+    }
+
+    /// 用本轮的助手消息调用用量上限回调
+    fn within_usage_limit(&self, message: &Message) -> bool {
+        self.usage_limit
+            .as_ref()
+            .map_or(true, |usage_limit| usage_limit(message))
+    }
+
+    fn append(
+        &self,
+        context: &mut MessageContextManager,
+        result: &mut QueryResult,
+        message: Message,
+    ) {
+        context.add_message(message.clone());
+        self.emit(QueryEvent::Message(message.clone()));
+        result.messages.push(message);
+    }
+
+    fn emit(&self, event: QueryEvent) {
+        if let Some(events) = &self.events {
+            // 接收端关闭时忽略，查询照常进行
+            let _ = events.send(event);
+        }
+    }
+}
+
+fn cancelled_results(tool_uses: &[ToolUseBlock]) -> Vec<ToolResultBlock> {
+    tool_uses
+        .iter()
+        .map(|tool_use| ToolResultBlock {
+            tool_use_id: tool_use.tool_use_id.clone(),
+            content: TOOL_CANCELLED_MESSAGE.to_string(),
+            is_error: true,
+            images: Vec::new(),
+        })
+        .collect()
+}
+
+fn add_usage(total: &mut TokenUsage, usage: &TokenUsage) {
+    let add = |a: Option<usize>, b: Option<usize>| match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    };
+    total.input_tokens = add(total.input_tokens, usage.input_tokens);
+    total.output_tokens = add(total.output_tokens, usage.output_tokens);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageContent;
+    use crate::model::TokenUsage as ModelUsage;
+    use crate::testing::ScriptedAdapter;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc;
+
+    /// 记录调用并返回固定结果的工具执行器；`hang` 为真时永不返回
+    #[derive(Default)]
+    struct EchoRunner {
+        calls: Mutex<Vec<String>>,
+        hang: bool,
+    }
+
+    #[async_trait]
+    impl ToolRunner for EchoRunner {
+        async fn run(
+            &self,
+            tool_uses: &[ToolUseBlock],
+            _cancel: &CancellationToken,
+        ) -> Vec<ToolResultBlock> {
+            self.calls
+                .lock()
+                .unwrap()
+                .extend(tool_uses.iter().map(|t| t.tool_name.clone()));
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+            tool_uses
+                .iter()
+                .map(|t| ToolResultBlock {
+                    tool_use_id: t.tool_use_id.clone(),
+                    content: format!("ran {}", t.tool_name),
+                    is_error: false,
+                    images: Vec::new(),
+                })
+                .collect()
+        }
+    }
+
+    fn stop() -> StreamChunk {
+        StreamChunk::message_stop(ModelUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            total_tokens: Some(15),
+        })
+    }
+
+    fn tool_turn(id: &str) -> Vec<StreamChunk> {
+        vec![
+            StreamChunk::content_block_delta(0, "Checking"),
+            StreamChunk::content_block_stop(0),
+            StreamChunk::tool_use("Grep", id, json!({"pattern": "todo"})),
+            stop(),
+        ]
+    }
+
+    fn blocks(message: &Message) -> &[ContentBlock] {
+        match &message.content {
+            MessageContent::Blocks(blocks) => blocks,
+            MessageContent::Text(_) => panic!("expected blocks"),
+        }
+    }
+
+    fn context() -> MessageContextManager {
+        let mut context = MessageContextManager::new(0);
+        context.add_message(Message::user("Find the TODOs"));
+        context
+    }
+
+    #[tokio::test]
+    async fn test_text_reply_ends_turn() {
+        let adapter = ScriptedAdapter::new(vec![vec![
+            StreamChunk::content_block_delta(0, "Hello"),
+            StreamChunk::content_block_delta(0, " there"),
+            stop(),
+        ]]);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let engine = QueryEngine::new(adapter, Arc::new(EchoRunner::default())).with_events(tx);
+
+        let mut context = context();
+        let result = engine.run(&mut context).await.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::EndTurn);
+        assert_eq!(result.turns, 1);
+        assert_eq!(result.usage.output_tokens, Some(5));
+        assert_eq!(context.message_count(), 2);
+        assert_eq!(
+            blocks(&result.messages[0]),
+            [ContentBlock::Text(TextBlock {
+                text: "Hello there".to_string()
+            })]
+        );
+
+        drop(engine);
+        let mut deltas = String::new();
+        let mut finished = None;
+        while let Some(event) = rx.recv().await {
+            match event {
+                QueryEvent::TextDelta { text } => deltas.push_str(&text),
+                QueryEvent::Finished { stop_reason, .. } => finished = Some(stop_reason),
+                _ => {}
+            }
+        }
+        assert_eq!(deltas, "Hello there");
+        assert_eq!(finished, Some(StopReason::EndTurn));
+    }
+
+    #[tokio::test]
+    async fn test_tool_results_are_sent_back() {
+        let adapter = ScriptedAdapter::new(vec![
+            tool_turn("call-1"),
+            vec![StreamChunk::content_block_delta(0, "Found 3"), stop()],
+        ]);
+        let runner = Arc::new(EchoRunner::default());
+        let engine = QueryEngine::new(adapter.clone(), runner.clone());
+
+        let mut context = context();
+        let result = engine.run(&mut context).await.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::EndTurn);
+        assert_eq!(result.turns, 2);
+        assert_eq!(result.usage.input_tokens, Some(20));
+        assert_eq!(*runner.calls.lock().unwrap(), ["Grep"]);
+        // 助手消息（文本 + 工具调用）、工具结果、最终回复
+        assert_eq!(result.messages.len(), 3);
+        assert_eq!(blocks(&result.messages[0]).len(), 2);
+
+        let requests = adapter.requests();
+        assert_eq!(requests.len(), 2);
+        let last = requests[1].0.last().unwrap();
+        assert_eq!(last.role, crate::message::Role::User);
+        assert_eq!(
+            blocks(last),
+            [ContentBlock::ToolResult(ToolResultBlock {
+                tool_use_id: "call-1".to_string(),
+                content: "ran Grep".to_string(),
+                is_error: false,
+                images: Vec::new(),
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_turns() {
+        let adapter = ScriptedAdapter::new(vec![tool_turn("a"), tool_turn("b"), tool_turn("c")]);
+        let engine =
+            QueryEngine::new(adapter.clone(), Arc::new(EchoRunner::default())).with_max_turns(2);
+
+        let mut context = context();
+        let result = engine.run(&mut context).await.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::MaxTurns);
+        assert_eq!(result.turns, 2);
+        assert_eq!(adapter.requests().len(), 2);
+        // 最后一轮的工具结果仍然追加到上下文
+        assert_eq!(result.messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_mid_stream() {
+        let adapter = ScriptedAdapter::hanging(vec![
+            StreamChunk::content_block_delta(0, "Partial"),
+            StreamChunk::tool_use("Grep", "call-1", json!({})),
+        ]);
+        let cancel = CancellationToken::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let runner = Arc::new(EchoRunner::default());
+        let engine = QueryEngine::new(adapter, runner.clone())
+            .with_events(tx)
+            .with_cancellation(cancel.clone());
+
+        let mut context = context();
+        let (result, _) = tokio::join!(engine.run(&mut context), async {
+            while let Some(event) = rx.recv().await {
+                if matches!(event, QueryEvent::ToolUse(_)) {
+                    cancel.cancel();
+                    break;
+                }
+            }
+        });
+        let result = result.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+        assert!(runner.calls.lock().unwrap().is_empty());
+        // 只保留已输出的文本，不完整的工具调用被丢弃
+        assert_eq!(
+            blocks(&result.messages[0]),
+            [ContentBlock::Text(TextBlock {
+                text: "Partial".to_string()
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_mid_tool() {
+        let adapter = ScriptedAdapter::new(vec![tool_turn("call-1")]);
+        let cancel = CancellationToken::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let runner = Arc::new(EchoRunner {
+            hang: true,
+            ..Default::default()
+        });
+        let engine = QueryEngine::new(adapter, runner)
+            .with_events(tx)
+            .with_cancellation(cancel.clone());
+
+        let mut context = context();
+        let (result, _) = tokio::join!(engine.run(&mut context), async {
+            while let Some(event) = rx.recv().await {
+                if matches!(event, QueryEvent::ToolUse(_)) {
+                    cancel.cancel();
+                    break;
+                }
+            }
+        });
+        let result = result.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+        // 每个工具调用都有对应的取消结果，保证上下文可以继续使用
+        assert_eq!(
+            blocks(result.messages.last().unwrap()),
+            [ContentBlock::ToolResult(ToolResultBlock {
+                tool_use_id: "call-1".to_string(),
+                content: TOOL_CANCELLED_MESSAGE.to_string(),
+                is_error: true,
+                images: Vec::new(),
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_error() {
+        let adapter = ScriptedAdapter::new(vec![vec![
+            StreamChunk::content_block_delta(0, "Hi"),
+            StreamChunk::error("overloaded"),
+        ]]);
+        let engine = QueryEngine::new(adapter, Arc::new(EchoRunner::default()));
+
+        let err = engine.run(&mut context()).await.unwrap_err();
+        assert!(matches!(err, Error::ModelStreamError(message) if message == "overloaded"));
+    }
+
+    #[tokio::test]
+    async fn test_usage_limit() {
+        let adapter = ScriptedAdapter::new(vec![tool_turn("a"), tool_turn("b")]);
+        let engine = QueryEngine::new(adapter.clone(), Arc::new(EchoRunner::default()))
+            .with_usage_limit(Arc::new(|message| {
+                message
+                    .usage
+                    .as_ref()
+                    .is_some_and(|usage| usage.output_tokens < Some(5))
+            }));
+
+        let mut context = context();
+        let result = engine.run(&mut context).await.unwrap();
+
+        assert_eq!(result.stop_reason, StopReason::UsageLimit);
+        assert_eq!(adapter.requests().len(), 1);
+        // 本轮的工具调用仍然执行完毕
+        assert_eq!(result.messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reminders_follow_tool_results() {
+        struct Reminder;
+        impl ReminderSource for Reminder {
+            fn reminders(&self) -> Vec<String> {
+                vec!["<system-reminder>todo list is stale</system-reminder>".to_string()]
+            }
+        }
+
+        let adapter = ScriptedAdapter::new(vec![tool_turn("a"), vec![stop()]]);
+        let engine = QueryEngine::new(adapter, Arc::new(EchoRunner::default()))
+            .with_reminders(Arc::new(Reminder));
+
+        let result = engine.run(&mut context()).await.unwrap();
+        let blocks = blocks(&result.messages[1]);
+        assert!(matches!(blocks[0], ContentBlock::ToolResult(_)));
+        assert!(matches!(&blocks[1], ContentBlock::Text(t) if t.text.contains("stale")));
+    }
+}
@@ -0,0 +1,157 @@
+//! 查询循环
+//!
+//! 驱动一次用户请求的完整处理过程：把上下文发送给模型，收集流式返回的文本和工具调用，
+//! 执行工具并把结果追加回上下文，直到模型不再调用工具为止。
+//!
+//! # 模块结构
+//!
+//! - [`engine`](engine): 查询循环本身
+//!
+//! 工具的权限检查和执行由 [`ToolRunner`] 的实现负责（工具层的编排器），
+//! 查询循环只关心消息的流转、轮数限制和取消。
+
+pub mod engine;
+
+pub use engine::{QueryEngine, UsageLimit};
+
+use crate::message::{ContentBlock, Message, MessageContent, Role, ToolResultBlock, ToolUseBlock};
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+/// 默认最大轮数
+pub const DEFAULT_MAX_TURNS: usize = 50;
+
+/// 默认每轮最大输出 token 数
+pub const DEFAULT_MAX_TOKENS: usize = 8192;
+
+/// 工具调用被取消时返回的结果内容
+pub const TOOL_CANCELLED_MESSAGE: &str = "Tool execution was cancelled";
+
+/// 工具执行接口
+///
+/// 查询循环通过此 trait 执行一轮中的所有工具调用。
+#[async_trait]
+pub trait ToolRunner: Send + Sync {
+    /// 执行工具调用
+    ///
+    /// 实现需要在执行前检查权限，并为每个调用返回一个结果（按 `tool_uses` 顺序）；
+    /// 被拒绝或失败的调用返回 `is_error` 结果，而不是错误。
+    async fn run(
+        &self,
+        tool_uses: &[ToolUseBlock],
+        cancel: &CancellationToken,
+    ) -> Vec<ToolResultBlock>;
+}
+
+/// 上下文提醒来源
+///
+/// 每轮工具执行结束后调用，返回的提醒作为文本块追加到工具结果消息中，
+/// 例如待办列表长期未更新或文件被外部修改。
+pub trait ReminderSource: Send + Sync {
+    /// 当前需要插入上下文的提醒
+    fn reminders(&self) -> Vec<String>;
+}
+
+/// 查询选项
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// 最大轮数（每次模型调用算一轮）
+    pub max_turns: usize,
+    /// 每轮最大输出 token 数
+    pub max_tokens: usize,
+    /// 系统提示词
+    pub system_prompt: Option<String>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            max_turns: DEFAULT_MAX_TURNS,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            system_prompt: None,
+        }
+    }
+}
+
+/// 查询结束原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 模型回复中没有工具调用
+    EndTurn,
+    /// 达到最大轮数
+    MaxTurns,
+    /// 用量上限回调要求停止
+    UsageLimit,
+    /// 被取消
+    Cancelled,
+}
+
+/// 查询事件
+///
+/// 供 UI 实时展示查询进度。
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryEvent {
+    /// 新一轮开始（从 1 开始计数）
+    TurnStarted {
+        /// 轮数
+        turn: usize,
+    },
+    /// 模型输出的文本片段
+    TextDelta {
+        /// 文本内容
+        text: String,
+    },
+    /// 模型请求调用工具
+    ToolUse(ToolUseBlock),
+    /// 工具执行结果
+    ToolResult(ToolResultBlock),
+    /// 追加到上下文的完整消息
+    Message(Message),
+    /// 查询结束
+    Finished {
+        /// 结束原因
+        stop_reason: StopReason,
+        /// 实际执行的轮数
+        turns: usize,
+    },
+}
+
+/// 查询结果
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    /// 本次查询追加到上下文的消息
+    pub messages: Vec<Message>,
+    /// 结束原因
+    pub stop_reason: StopReason,
+    /// 实际执行的轮数
+    pub turns: usize,
+    /// 所有轮次累计的 token 使用情况
+    pub usage: crate::message::types::TokenUsage,
+}
+
+impl QueryResult {
+    /// 最后一条助手消息中的文本，多个文本块以换行连接
+    ///
+    /// 用于把子查询的最终回复作为结果返回。
+    pub fn final_text(&self) -> String {
+        let Some(message) = self
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::Assistant)
+        else {
+            return String::new();
+        };
+        match &message.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! 测试支持
+//!
+//! 本 crate 和下游 crate 的测试共用的替身。下游 crate 在 dev-dependencies 中
+//! 启用 `test-support` feature 后使用。
+
+use crate::error::{Error, Result};
+use crate::message::Message;
+use crate::model::{ModelAdapter, ModelResponse, StreamChunk, StreamingResponse};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// 按脚本逐轮返回流块的模型
+///
+/// 每次 `stream_message` 取出脚本中的下一轮，脚本用完后返回错误。
+/// 收到的请求（消息和系统提示词）都会被记录。
+#[derive(Default)]
+pub struct ScriptedAdapter {
+    /// 每轮的流块；`true` 表示发送完后挂起，模拟未结束的流
+    script: Mutex<VecDeque<(Vec<StreamChunk>, bool)>>,
+    requests: Mutex<Vec<(Vec<Message>, Option<String>)>>,
+}
+
+impl ScriptedAdapter {
+    /// 依次返回各轮流块的模型
+    pub fn new(turns: Vec<Vec<StreamChunk>>) -> Arc<Self> {
+        Arc::new(Self {
+            script: Mutex::new(turns.into_iter().map(|t| (t, false)).collect()),
+            requests: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 发送完流块后挂起的模型
+    pub fn hanging(chunks: Vec<StreamChunk>) -> Arc<Self> {
+        Arc::new(Self {
+            script: Mutex::new(VecDeque::from([(chunks, true)])),
+            requests: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 已收到的请求：每次请求的消息和系统提示词
+    pub fn requests(&self) -> Vec<(Vec<Message>, Option<String>)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ModelAdapter for ScriptedAdapter {
+    async fn send_message(
+        &self,
+        _messages: Vec<Message>,
+        _system_prompt: Option<String>,
+        _max_tokens: usize,
+    ) -> Result<ModelResponse> {
+        Err(Error::ModelRequestError("not scripted".to_string()))
+    }
+
+    async fn stream_message(
+        &self,
+        messages: Vec<Message>,
+        system_prompt: Option<String>,
+        _max_tokens: usize,
+    ) -> Result<StreamingResponse> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((messages, system_prompt));
+        let (chunks, hang) = self
+            .script
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| Error::ModelRequestError("script exhausted".to_string()))?;
+        let chunks = stream::iter(chunks.into_iter().map(Ok));
+        Ok(if hang {
+            StreamingResponse::new(Box::pin(chunks.chain(stream::pending())))
+        } else {
+            StreamingResponse::new(Box::pin(chunks))
+        })
+    }
+
+    fn model_name(&self) -> &str {
+        "scripted"
+    }
+}
@@ -0,0 +1,209 @@
+//! 测试用的最小 MCP 服务器
+//!
+//! 按行读取 stdin 上的 JSON-RPC 消息，提供几个行为可预测的工具和资源，
+//! 供 `kode-services` 的客户端测试启动。
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+fn send(message: Value) {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", message).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn text_result(id: &Value, text: &str, is_error: bool) {
+    send(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": { "content": [{ "type": "text", "text": text }], "isError": is_error }
+    }));
+}
+
+fn tool(name: &str, description: &str) -> Value {
+    json!({ "name": name, "description": description, "inputSchema": { "type": "object" } })
+}
+
+fn tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "echo",
+            "description": "Echo the message back",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "message": { "type": "string" } },
+                "required": ["message"]
+            }
+        }),
+        tool("greeting", "Return FAKE_MCP_GREETING"),
+        tool("fail", "Always report a tool error"),
+        tool(
+            "notify",
+            "Ping the client, then emit a list_changed notification",
+        ),
+        tool("sleep", "Sleep for `ms` milliseconds"),
+        tool("cancelled", "List request ids the client cancelled"),
+        tool("crash", "Exit without answering"),
+    ]
+}
+
+fn resources() -> Vec<Value> {
+    vec![
+        json!({ "uri": "fake://readme", "name": "README", "mimeType": "text/markdown" }),
+        json!({ "uri": "fake://counter", "name": "Counter", "description": "Bumped on subscribe" }),
+        json!({ "uri": "fake://logo", "name": "Logo", "mimeType": "image/png" }),
+    ]
+}
+
+fn main() {
+    eprintln!("fake server starting");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut initialized = false;
+    let mut cancelled: Vec<String> = Vec::new();
+    let mut counter = 0;
+
+    while let Some(Ok(line)) = lines.next() {
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        let method = message["method"].as_str().unwrap_or_default().to_string();
+        let id = message["id"].clone();
+        let params = message["params"].clone();
+
+        match method.as_str() {
+            "initialize" => send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "protocolVersion": params["protocolVersion"],
+                    "capabilities": {
+                        "tools": { "listChanged": true },
+                        "resources": { "subscribe": true }
+                    },
+                    "serverInfo": { "name": "fake-mcp-server", "version": "0.0.1" },
+                    "instructions": "Test server"
+                }
+            })),
+            "notifications/initialized" => initialized = true,
+            "notifications/cancelled" => cancelled.push(params["requestId"].to_string()),
+            "tools/list" => {
+                let all = tools();
+                let (page, next) = match params["cursor"].as_str() {
+                    Some("page2") => (all[3..].to_vec(), Value::Null),
+                    _ => (all[..3].to_vec(), json!("page2")),
+                };
+                let mut result = json!({ "tools": page });
+                if !next.is_null() {
+                    result["nextCursor"] = next;
+                }
+                send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+            }
+            "tools/call" => {
+                let args = &params["arguments"];
+                match params["name"].as_str().unwrap_or_default() {
+                    "echo" => text_result(&id, args["message"].as_str().unwrap_or_default(), false),
+                    "greeting" => {
+                        let greeting = std::env::var("FAKE_MCP_GREETING").unwrap_or_default();
+                        let state = if initialized {
+                            "initialized"
+                        } else {
+                            "uninitialized"
+                        };
+                        text_result(&id, &format!("{} ({})", greeting, state), false);
+                    }
+                    "fail" => text_result(&id, "tool failed on purpose", true),
+                    "notify" => {
+                        send(json!({ "jsonrpc": "2.0", "id": "srv-1", "method": "ping" }));
+                        let mut answered = false;
+                        for line in lines.by_ref() {
+                            let reply: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                            if reply["id"] == "srv-1" {
+                                answered = reply["result"] == json!({});
+                                break;
+                            }
+                        }
+                        send(json!({
+                            "jsonrpc": "2.0",
+                            "method": "notifications/tools/list_changed"
+                        }));
+                        let text = if answered {
+                            "client answered ping"
+                        } else {
+                            "client did not answer ping"
+                        };
+                        text_result(&id, text, false);
+                    }
+                    "sleep" => {
+                        let ms = args["ms"].as_u64().unwrap_or(0);
+                        std::thread::sleep(Duration::from_millis(ms));
+                        text_result(&id, "slept", false);
+                    }
+                    "cancelled" => text_result(&id, &cancelled.join(","), false),
+                    "crash" => {
+                        eprintln!("fake server crashing");
+                        std::process::exit(3);
+                    }
+                    other => send(json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32602, "message": format!("Unknown tool: {}", other) }
+                    })),
+                }
+            }
+            "resources/list" => {
+                let all = resources();
+                let (page, next) = match params["cursor"].as_str() {
+                    Some("page2") => (all[2..].to_vec(), Value::Null),
+                    _ => (all[..2].to_vec(), json!("page2")),
+                };
+                let mut result = json!({ "resources": page });
+                if !next.is_null() {
+                    result["nextCursor"] = next;
+                }
+                send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+            }
+            "resources/read" => {
+                let uri = params["uri"].as_str().unwrap_or_default();
+                let contents = match uri {
+                    "fake://readme" => json!([{
+                        "uri": uri, "mimeType": "text/markdown", "text": "# Fake project"
+                    }]),
+                    "fake://counter" => json!([{ "uri": uri, "text": counter.to_string() }]),
+                    "fake://logo" => {
+                        json!([{ "uri": uri, "mimeType": "image/png", "blob": "iVBORw0K" }])
+                    }
+                    _ => {
+                        send(json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": -32002, "message": format!("Resource not found: {}", uri) }
+                        }));
+                        continue;
+                    }
+                };
+                send(json!({ "jsonrpc": "2.0", "id": id, "result": { "contents": contents } }));
+            }
+            "resources/subscribe" => {
+                send(json!({ "jsonrpc": "2.0", "id": id, "result": {} }));
+                counter += 1;
+                send(json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/updated",
+                    "params": { "uri": params["uri"] }
+                }));
+            }
+            "resources/unsubscribe" => send(json!({ "jsonrpc": "2.0", "id": id, "result": {} })),
+            "ping" => send(json!({ "jsonrpc": "2.0", "id": id, "result": {} })),
+            _ if !id.is_null() => send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("Method not found: {}", method) }
+            })),
+            _ => {}
+        }
+    }
+}
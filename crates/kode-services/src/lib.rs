@@ -5,4 +5,5 @@
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
-// TODO: 添加服务模块
+/// MCP 客户端
+pub mod mcp;
@@ -0,0 +1,522 @@
+//! MCP 客户端
+//!
+//! 在任意 [`McpTransport`] 之上实现 JSON-RPC 请求/响应配对、握手与能力协商、
+//! 服务器通知分发以及请求超时。
+
+use super::http::HttpTransportOptions;
+use super::protocol::{
+    CallToolResult, ClientCapabilities, GetPromptResult, Implementation, InitializeParams,
+    InitializeResult, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    ListPromptsResult, ListResourcesResult, ListToolsResult, McpTool, Prompt, ReadResourceResult,
+    RequestId, Resource, ServerCapabilities, MCP_PROTOCOL_VERSION, METHOD_NOT_FOUND,
+    SUPPORTED_PROTOCOL_VERSIONS,
+};
+use super::sse::SseTransport;
+use super::stdio::StdioTransport;
+use super::streamable_http::StreamableHttpTransport;
+use super::transport::McpTransport;
+use anyhow::{anyhow, bail, Result};
+use kode_core::config::types::{McpServerConfig, McpStdioServerConfig};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot, watch};
+use tokio::task::JoinHandle;
+
+/// 默认请求超时
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 通知广播通道容量
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// 等待响应的请求，`None` 表示连接已关闭
+type Pending = Arc<Mutex<Option<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>>>;
+
+/// MCP 客户端
+pub struct McpClient {
+    name: String,
+    transport: Arc<dyn McpTransport>,
+    pending: Pending,
+    next_id: AtomicI64,
+    notifications: broadcast::Sender<JsonRpcNotification>,
+    request_timeout: Duration,
+    server: Option<InitializeResult>,
+    reader: JoinHandle<()>,
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl McpClient {
+    /// 在传输之上创建客户端（尚未握手）
+    pub fn new(name: impl Into<String>, transport: Arc<dyn McpTransport>) -> Self {
+        let name = name.into();
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        let closed = Arc::new(watch::Sender::new(false));
+        let reader = tokio::spawn(dispatch(
+            name.clone(),
+            transport.clone(),
+            pending.clone(),
+            notifications.clone(),
+            closed.clone(),
+        ));
+
+        Self {
+            name,
+            transport,
+            pending,
+            next_id: AtomicI64::new(1),
+            notifications,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            server: None,
+            reader,
+            closed,
+        }
+    }
+
+    /// 设置请求超时
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 在传输之上创建客户端并完成握手
+    pub async fn connect(
+        name: impl Into<String>,
+        transport: Arc<dyn McpTransport>,
+    ) -> Result<Self> {
+        let mut client = Self::new(name, transport);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// 启动 stdio 服务器进程并完成握手
+    pub async fn connect_stdio(
+        name: impl Into<String>,
+        config: &McpStdioServerConfig,
+    ) -> Result<Self> {
+        let transport = Arc::new(StdioTransport::spawn(config)?);
+        Self::connect(name, transport).await
+    }
+
+    /// 按服务器配置选择传输并完成握手
+    ///
+    /// `options` 只用于 SSE 与 Streamable HTTP 传输。
+    pub async fn connect_config(
+        name: impl Into<String>,
+        config: &McpServerConfig,
+        options: &HttpTransportOptions,
+    ) -> Result<Self> {
+        let transport: Arc<dyn McpTransport> = match config {
+            McpServerConfig::Stdio(config) => Arc::new(StdioTransport::spawn(config)?),
+            McpServerConfig::Sse(config) => Arc::new(SseTransport::connect(config, options).await?),
+            McpServerConfig::Http(config) => {
+                Arc::new(StreamableHttpTransport::new(config, options)?)
+            }
+        };
+        Self::connect(name, transport).await
+    }
+
+    /// 执行 initialize 握手
+    ///
+    /// 发送客户端信息与能力，校验服务器选择的协议版本，然后发送
+    /// `notifications/initialized`。
+    pub async fn initialize(&mut self) -> Result<&InitializeResult> {
+        let params = InitializeParams {
+            protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+            capabilities: ClientCapabilities::default(),
+            client_info: Implementation {
+                name: "kode".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        };
+        let result: InitializeResult = self
+            .request_typed("initialize", Some(serde_json::to_value(params)?))
+            .await?;
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&result.protocol_version.as_str()) {
+            let _ = self.transport.close().await;
+            bail!(
+                "MCP server '{}' uses unsupported protocol version {}",
+                self.name,
+                result.protocol_version
+            );
+        }
+
+        self.notify("notifications/initialized", None).await?;
+        Ok(self.server.insert(result))
+    }
+
+    /// 服务器名称（配置中的键）
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 握手结果，未初始化时为 `None`
+    pub fn server(&self) -> Option<&InitializeResult> {
+        self.server.as_ref()
+    }
+
+    /// 服务器声明的能力
+    pub fn capabilities(&self) -> Option<&ServerCapabilities> {
+        self.server.as_ref().map(|s| &s.capabilities)
+    }
+
+    /// 订阅服务器通知
+    pub fn subscribe(&self) -> broadcast::Receiver<JsonRpcNotification> {
+        self.notifications.subscribe()
+    }
+
+    /// 连接是否已关闭（服务器退出或调用了 [`McpClient::close`]）
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// 等待连接关闭
+    pub async fn closed(&self) {
+        let mut closed = self.closed.subscribe();
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+
+    /// 传输层诊断输出（stdio 服务器的 stderr）
+    pub fn diagnostics(&self) -> String {
+        self.transport.diagnostics()
+    }
+
+    /// 列出服务器提供的全部工具
+    ///
+    /// 自动跟随分页游标；服务器未声明工具能力时返回空列表。
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        if self.server_capabilities()?.tools.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let page: ListToolsResult = self.request_typed("tools/list", params).await?;
+            tools.extend(page.tools);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        Ok(tools)
+    }
+
+    /// 调用工具
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult> {
+        if self.server_capabilities()?.tools.is_none() {
+            bail!("MCP server '{}' does not provide tools", self.name);
+        }
+        self.request_typed(
+            "tools/call",
+            Some(json!({ "name": name, "arguments": arguments })),
+        )
+        .await
+    }
+
+    /// 列出服务器提供的全部资源
+    ///
+    /// 自动跟随分页游标；服务器未声明资源能力时返回空列表。
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        if self.server_capabilities()?.resources.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut resources = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let page: ListResourcesResult = self.request_typed("resources/list", params).await?;
+            resources.extend(page.resources);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        Ok(resources)
+    }
+
+    /// 读取资源
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult> {
+        if self.server_capabilities()?.resources.is_none() {
+            bail!("MCP server '{}' does not provide resources", self.name);
+        }
+        self.request_typed("resources/read", Some(json!({ "uri": uri })))
+            .await
+    }
+
+    /// 订阅资源变更
+    ///
+    /// 变更以 `notifications/resources/updated` 通知送达 [`McpClient::subscribe`] 的接收端。
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        self.ensure_resource_subscriptions()?;
+        self.request("resources/subscribe", Some(json!({ "uri": uri })))
+            .await?;
+        Ok(())
+    }
+
+    /// 取消资源订阅
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<()> {
+        self.ensure_resource_subscriptions()?;
+        self.request("resources/unsubscribe", Some(json!({ "uri": uri })))
+            .await?;
+        Ok(())
+    }
+
+    /// 列出服务器提供的全部提示词
+    ///
+    /// 自动跟随分页游标；服务器未声明提示词能力时返回空列表。
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        if self.server_capabilities()?.prompts.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut prompts = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.as_ref().map(|c| json!({ "cursor": c }));
+            let page: ListPromptsResult = self.request_typed("prompts/list", params).await?;
+            prompts.extend(page.prompts);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        Ok(prompts)
+    }
+
+    /// 以给定参数展开提示词
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        if self.server_capabilities()?.prompts.is_none() {
+            bail!("MCP server '{}' does not provide prompts", self.name);
+        }
+        self.request_typed(
+            "prompts/get",
+            Some(json!({ "name": name, "arguments": arguments })),
+        )
+        .await
+    }
+
+    /// 发送通知
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
+        let message = JsonRpcMessage::Notification(JsonRpcNotification::new(method, params));
+        self.transport.send(&message).await
+    }
+
+    /// 发送请求并等待结果
+    ///
+    /// 超时后向服务器发送 `notifications/cancelled` 并返回错误。
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id.clone(), tx),
+            None => return Err(self.closed_error()),
+        };
+
+        let message = JsonRpcMessage::Request(JsonRpcRequest::new(id.clone(), method, params));
+        if let Err(e) = self.transport.send(&message).await {
+            self.forget(&id);
+            return Err(e);
+        }
+
+        let response = match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(self.closed_error()),
+            Err(_) => {
+                self.forget(&id);
+                let _ = self
+                    .notify(
+                        "notifications/cancelled",
+                        Some(json!({ "requestId": id, "reason": "Request timed out" })),
+                    )
+                    .await;
+                bail!(
+                    "MCP request '{}' to server '{}' timed out after {}ms",
+                    method,
+                    self.name,
+                    self.request_timeout.as_millis()
+                );
+            }
+        };
+
+        if let Some(error) = response.error {
+            bail!(
+                "MCP server '{}' returned an error for '{}': {}",
+                self.name,
+                method,
+                error
+            );
+        }
+        Ok(response.result.unwrap_or(Value::Null))
+    }
+
+    /// 关闭连接并结束服务器进程
+    pub async fn close(&self) -> Result<()> {
+        let result = self.transport.close().await;
+        self.reader.abort();
+        self.pending.lock().unwrap().take();
+        self.closed.send_replace(true);
+        result
+    }
+
+    async fn request_typed<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<T> {
+        let value = self.request(method, params).await?;
+        serde_json::from_value(value).map_err(|e| {
+            anyhow!(
+                "Invalid '{}' response from MCP server '{}': {}",
+                method,
+                self.name,
+                e
+            )
+        })
+    }
+
+    fn server_capabilities(&self) -> Result<&ServerCapabilities> {
+        self.capabilities()
+            .ok_or_else(|| anyhow!("MCP server '{}' is not initialized", self.name))
+    }
+
+    fn ensure_resource_subscriptions(&self) -> Result<()> {
+        match &self.server_capabilities()?.resources {
+            Some(resources) if resources.subscribe => Ok(()),
+            _ => bail!(
+                "MCP server '{}' does not support resource subscriptions",
+                self.name
+            ),
+        }
+    }
+
+    fn forget(&self, id: &RequestId) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(id);
+        }
+    }
+
+    fn closed_error(&self) -> anyhow::Error {
+        let stderr = self.transport.diagnostics();
+        if stderr.trim().is_empty() {
+            anyhow!("MCP server '{}' closed the connection", self.name)
+        } else {
+            anyhow!(
+                "MCP server '{}' closed the connection. Server stderr:\n{}",
+                self.name,
+                stderr
+            )
+        }
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        // 读取任务持有传输的引用，结束它才能让传输（及子进程）被释放
+        self.reader.abort();
+    }
+}
+
+/// 读取服务器消息并分发：响应交给等待者，通知广播，服务器请求就地应答
+async fn dispatch(
+    name: String,
+    transport: Arc<dyn McpTransport>,
+    pending: Pending,
+    notifications: broadcast::Sender<JsonRpcNotification>,
+    closed: Arc<watch::Sender<bool>>,
+) {
+    while let Some(message) = transport.receive().await {
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let waiter = pending
+                    .lock()
+                    .unwrap()
+                    .as_mut()
+                    .and_then(|p| p.remove(&response.id));
+                match waiter {
+                    Some(tx) => {
+                        let _ = tx.send(response);
+                    }
+                    None => tracing::debug!(
+                        "Dropping response {} from MCP server {} with no pending request",
+                        response.id,
+                        name
+                    ),
+                }
+            }
+            JsonRpcMessage::Notification(notification) => {
+                let _ = notifications.send(notification);
+            }
+            JsonRpcMessage::Request(request) => {
+                let response = match request.method.as_str() {
+                    "ping" => JsonRpcResponse::success(request.id, json!({})),
+                    other => JsonRpcResponse::failure(
+                        request.id,
+                        METHOD_NOT_FOUND,
+                        format!("Method not found: {}", other),
+                    ),
+                };
+                if let Err(e) = transport.send(&JsonRpcMessage::Response(response)).await {
+                    tracing::warn!("Failed to answer MCP server {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    // 连接已关闭：丢弃所有等待者，使其立即失败
+    pending.lock().unwrap().take();
+    closed.send_replace(true);
+}
+
+/// 已连接 MCP 客户端的来源
+///
+/// 资源工具等需要访问全部服务器的组件通过它获取当前连接，
+/// 连接集合可以在会话中变化。
+pub trait McpClientProvider: Send + Sync {
+    /// 当前已连接的客户端
+    fn clients(&self) -> Vec<Arc<McpClient>>;
+
+    /// 按服务器名获取当前连接
+    ///
+    /// 服务器重启后返回新的连接，调用方不应长期持有返回的客户端。
+    fn client(&self, name: &str) -> Option<Arc<McpClient>> {
+        self.clients()
+            .into_iter()
+            .find(|client| client.name() == name)
+    }
+}
+
+impl McpClientProvider for Vec<Arc<McpClient>> {
+    fn clients(&self) -> Vec<Arc<McpClient>> {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spawn_failure() {
+        let config = McpStdioServerConfig {
+            command: "/nonexistent/kode-mcp-server".to_string(),
+            args: vec![],
+            env: None,
+        };
+        let err = McpClient::connect_stdio("missing", &config)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Failed to start MCP server"));
+    }
+}
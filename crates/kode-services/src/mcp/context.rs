@@ -0,0 +1,81 @@
+//! MCP 上下文资源
+//!
+//! 会话开始时读取 `mcp_context_uris` 中列出的资源，结果以 URI 为键交给
+//! `MessageContextManager::format_system_prompt_with_context` 注入系统提示词。
+
+use super::client::McpClient;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 加载得到的上下文
+#[derive(Debug, Clone, Default)]
+pub struct McpContext {
+    /// URI -> 资源文本
+    pub entries: HashMap<String, String>,
+    /// 未能加载的资源说明
+    pub errors: Vec<String>,
+}
+
+/// 从已连接的服务器读取上下文资源
+///
+/// 优先使用资源列表中包含该 URI 的服务器；都没有列出时依次尝试所有提供资源的服务器
+/// （资源模板生成的 URI 不会出现在列表中）。单个资源失败不影响其余资源。
+pub async fn load_context_uris(clients: &[Arc<McpClient>], uris: &[String]) -> McpContext {
+    let mut context = McpContext::default();
+    if uris.is_empty() {
+        return context;
+    }
+
+    let providers: Vec<&Arc<McpClient>> = clients
+        .iter()
+        .filter(|client| {
+            client
+                .capabilities()
+                .is_some_and(|caps| caps.resources.is_some())
+        })
+        .collect();
+
+    let mut listed: Vec<(&Arc<McpClient>, Vec<String>)> = Vec::new();
+    for client in &providers {
+        match client.list_resources().await {
+            Ok(resources) => {
+                listed.push((client, resources.into_iter().map(|r| r.uri).collect()));
+            }
+            Err(e) => tracing::warn!("Failed to list resources of '{}': {}", client.name(), e),
+        }
+    }
+
+    for uri in uris {
+        let owner = listed
+            .iter()
+            .find(|(_, listed_uris)| listed_uris.contains(uri))
+            .map(|(client, _)| *client);
+        let candidates = match owner {
+            Some(client) => vec![client],
+            None => providers.clone(),
+        };
+
+        match read_first(&candidates, uri).await {
+            Ok(text) => {
+                context.entries.insert(uri.clone(), text);
+            }
+            Err(e) => context.errors.push(format!(
+                "Failed to load MCP context resource {}: {}",
+                uri, e
+            )),
+        }
+    }
+    context
+}
+
+async fn read_first(candidates: &[&Arc<McpClient>], uri: &str) -> Result<String> {
+    let mut last_error = anyhow!("no connected MCP server provides resources");
+    for client in candidates {
+        match client.read_resource(uri).await {
+            Ok(result) => return Ok(result.to_text()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
@@ -0,0 +1,390 @@
+//! HTTP 传输公共部分
+//!
+//! SSE 与 Streamable HTTP 共用的连接选项、请求头解析和事件流读取。
+
+use super::protocol::JsonRpcMessage;
+use anyhow::{anyhow, bail, Context, Result};
+use eventsource_stream::{Event, Eventsource};
+use futures::StreamExt;
+use kode_core::config::GlobalConfig;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+/// 会话 ID 请求/响应头
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// 默认最大重连次数
+const DEFAULT_MAX_RECONNECTS: u32 = 5;
+
+/// 默认首次重连等待时间
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// 重连等待时间上限
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// 建立连接的超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP 类传输的连接选项
+#[derive(Debug, Clone)]
+pub struct HttpTransportOptions {
+    proxy: Option<String>,
+    max_reconnects: u32,
+    reconnect_delay: Duration,
+}
+
+impl Default for HttpTransportOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            max_reconnects: DEFAULT_MAX_RECONNECTS,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+        }
+    }
+}
+
+impl HttpTransportOptions {
+    /// 默认选项
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用全局配置中的代理
+    pub fn with_global_config(self, config: &GlobalConfig) -> Self {
+        match &config.proxy {
+            Some(proxy) => self.with_proxy(proxy),
+            None => self,
+        }
+    }
+
+    /// 通过代理连接
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 设置事件流断开后的重连次数和首次等待时间（之后按指数退避）
+    pub fn with_reconnect(mut self, max_reconnects: u32, delay: Duration) -> Self {
+        self.max_reconnects = max_reconnects;
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// 最大重连次数
+    pub fn max_reconnects(&self) -> u32 {
+        self.max_reconnects
+    }
+
+    /// 第 `attempt` 次重连前的等待时间，服务器通过 `retry:` 指定时以其为准
+    pub(super) fn reconnect_delay(&self, attempt: u32, server_retry: Option<Duration>) -> Duration {
+        server_retry
+            .unwrap_or_else(|| {
+                self.reconnect_delay
+                    .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            })
+            .min(MAX_RECONNECT_DELAY)
+    }
+
+    /// 构建 HTTP 客户端
+    pub(super) fn build_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?,
+            );
+        }
+        builder.build().context("Failed to build HTTP client")
+    }
+}
+
+/// 解析配置中的请求头，展开值中的 `${VAR}` 环境变量引用
+pub fn resolve_headers(headers: Option<&HashMap<String, String>>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers.into_iter().flatten() {
+        let expanded = expand_env(value)
+            .with_context(|| format!("Failed to resolve MCP header '{}'", name))?;
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid MCP header name '{}'", name))?;
+        let value = HeaderValue::from_str(&expanded)
+            .with_context(|| format!("Invalid value for MCP header '{}'", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// 展开 `${VAR}` 形式的环境变量引用
+fn expand_env(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow!("Unterminated '${{' in '{}'", value))?;
+        let var = &after[..end];
+        let resolved =
+            std::env::var(var).map_err(|_| anyhow!("Environment variable {} is not set", var))?;
+        result.push_str(&resolved);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// 事件流的可恢复状态
+#[derive(Debug, Default)]
+pub(super) struct StreamState {
+    /// 最近收到的事件 ID，重连时作为 `Last-Event-ID` 发送
+    pub last_event_id: String,
+    /// 服务器建议的重连等待时间
+    pub retry: Option<Duration>,
+}
+
+/// 逐个读取响应中的 SSE 事件，直到流结束或 `handle` 要求停止
+///
+/// 流正常结束或被中止时返回 `Ok`，传输错误时返回 `Err`。
+pub(super) async fn read_events<F>(
+    response: reqwest::Response,
+    state: &mut StreamState,
+    mut handle: F,
+) -> Result<()>
+where
+    F: FnMut(Event) -> ControlFlow<()>,
+{
+    let mut events = response.bytes_stream().eventsource();
+    while let Some(event) = events.next().await {
+        let event = event.map_err(|e| anyhow!("SSE stream error: {}", e))?;
+        if !event.id.is_empty() {
+            state.last_event_id = event.id.clone();
+        }
+        if let Some(retry) = event.retry {
+            state.retry = Some(retry);
+        }
+        if handle(event).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 解析单条消息或批量消息数组
+pub(super) fn parse_messages(data: &str) -> Result<Vec<JsonRpcMessage>> {
+    let value: serde_json::Value =
+        serde_json::from_str(data).context("Invalid JSON-RPC payload")?;
+    let messages = match value {
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<Vec<_>, _>>()?,
+        other => vec![serde_json::from_value(other)?],
+    };
+    Ok(messages)
+}
+
+/// 读取失败响应的正文作为错误信息
+pub(super) async fn status_error(response: reqwest::Response, context: &str) -> anyhow::Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let body = body.trim();
+    if body.is_empty() {
+        anyhow!("{} failed with HTTP {}", context, status)
+    } else {
+        anyhow!("{} failed with HTTP {}: {}", context, status, body)
+    }
+}
+
+/// 检查响应状态，失败时转为错误
+pub(super) async fn ensure_success(
+    response: reqwest::Response,
+    context: &str,
+) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(status_error(response, context).await)
+    }
+}
+
+/// 响应是否为事件流
+pub(super) fn is_event_stream(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("text/event-stream"))
+        .unwrap_or(false)
+}
+
+/// 确认事件流响应，否则报错
+pub(super) async fn expect_event_stream(
+    response: reqwest::Response,
+    context: &str,
+) -> Result<reqwest::Response> {
+    let response = ensure_success(response, context).await?;
+    if !is_event_stream(&response) {
+        bail!("{} did not return an event stream", context);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+pub(super) mod test_support {
+    //! 测试用的极简 HTTP 服务器工具，每个连接只处理一个请求
+
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    /// 收到的请求
+    #[derive(Debug, Clone)]
+    pub struct Request {
+        pub method: String,
+        pub target: String,
+        pub headers: HashMap<String, String>,
+        pub body: String,
+    }
+
+    impl Request {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers.get(name).map(|s| s.as_str())
+        }
+
+        /// 去掉代理请求中的 scheme 和主机部分
+        pub fn path(&self) -> &str {
+            match self.target.find("://") {
+                Some(i) => {
+                    let rest = &self.target[i + 3..];
+                    rest.find('/').map(|j| &rest[j..]).unwrap_or("/")
+                }
+                None => &self.target,
+            }
+        }
+
+        pub fn json(&self) -> serde_json::Value {
+            serde_json::from_str(&self.body).unwrap()
+        }
+    }
+
+    pub async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?.to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.ok()?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let length: usize = headers
+            .get("content-length")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.ok()?;
+        Some(Request {
+            method,
+            target,
+            headers,
+            body: String::from_utf8_lossy(&body).to_string(),
+        })
+    }
+
+    pub async fn respond(
+        stream: &mut TcpStream,
+        status: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    pub async fn respond_json(stream: &mut TcpStream, headers: &[(&str, &str)], body: &str) {
+        let mut all = vec![("Content-Type", "application/json")];
+        all.extend_from_slice(headers);
+        respond(stream, "200 OK", &all, body).await;
+    }
+
+    /// 发送事件流响应头，之后用 [`send_event`] 写入事件
+    pub async fn start_event_stream(stream: &mut TcpStream, headers: &[(&str, &str)]) {
+        let mut response = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n".to_string();
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+
+    pub async fn send_event(stream: &mut TcpStream, id: Option<&str>, event: &str, data: &str) {
+        let mut chunk = String::new();
+        if let Some(id) = id {
+            chunk.push_str(&format!("id: {}\n", id));
+        }
+        chunk.push_str(&format!("event: {}\ndata: {}\n\n", event, data));
+        let _ = stream.write_all(chunk.as_bytes()).await;
+        let _ = stream.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_headers_expands_env() {
+        std::env::set_var("KODE_TEST_MCP_HEADER_TOKEN", "s3cret");
+        let headers = HashMap::from([
+            (
+                "Authorization".to_string(),
+                "Bearer ${KODE_TEST_MCP_HEADER_TOKEN}".to_string(),
+            ),
+            ("X-Static".to_string(), "plain".to_string()),
+        ]);
+        let map = resolve_headers(Some(&headers)).unwrap();
+        assert_eq!(map["authorization"], "Bearer s3cret");
+        assert_eq!(map["x-static"], "plain");
+
+        let missing = HashMap::from([(
+            "Authorization".to_string(),
+            "Bearer ${KODE_TEST_MCP_HEADER_MISSING}".to_string(),
+        )]);
+        let err = resolve_headers(Some(&missing)).unwrap_err();
+        assert!(format!("{:#}", err).contains("KODE_TEST_MCP_HEADER_MISSING is not set"));
+    }
+
+    #[test]
+    fn test_reconnect_delay_backoff() {
+        let options = HttpTransportOptions::new().with_reconnect(5, Duration::from_millis(100));
+        assert_eq!(options.reconnect_delay(1, None), Duration::from_millis(100));
+        assert_eq!(options.reconnect_delay(3, None), Duration::from_millis(400));
+        assert_eq!(
+            options.reconnect_delay(2, Some(Duration::from_secs(1))),
+            Duration::from_secs(1)
+        );
+        assert_eq!(options.reconnect_delay(30, None), MAX_RECONNECT_DELAY);
+    }
+}
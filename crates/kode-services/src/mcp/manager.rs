@@ -0,0 +1,448 @@
+//! MCP 服务器生命周期管理
+//!
+//! [`McpManager`] 持有按来源合并后的全部服务器（见 [`merge_mcp_servers`]），
+//! 并行启动已批准的服务器并限制连接时间，跟踪每个服务器的状态，
+//! 按退避策略重启意外退出的 stdio 服务器，并在会话结束时统一关闭。
+//! `kode mcp list` 与 TUI 通过 [`McpManager::status`] 读取状态快照。
+//! 管理器实现 [`McpClientProvider`]，工具与资源每次调用时按名称取当前连接，重启后自动使用新连接。
+//!
+//! [`merge_mcp_servers`]: kode_core::config::merge_mcp_servers
+
+use super::client::{McpClient, McpClientProvider};
+use super::http::HttpTransportOptions;
+use super::protocol::Implementation;
+use anyhow::{bail, Result};
+use futures::future::join_all;
+use kode_core::config::types::McpServerConfig;
+use kode_core::config::{McpServerScope, McprcApproval, ScopedMcpServer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// 默认连接超时（含进程启动与握手）
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 服务器状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpServerStatus {
+    /// 已批准，尚未启动
+    Pending,
+    /// 正在连接
+    Connecting,
+    /// 已连接
+    Connected,
+    /// 服务器意外退出，正在进行第 `attempt` 次重启
+    Restarting {
+        /// 重启次数（从 1 开始）
+        attempt: u32,
+    },
+    /// 连接失败或放弃重启
+    Failed {
+        /// 错误信息
+        error: String,
+    },
+    /// .mcprc 服务器等待用户批准
+    PendingApproval,
+    /// .mcprc 服务器已被用户拒绝
+    Rejected,
+    /// 已关闭
+    Stopped,
+}
+
+impl fmt::Display for McpServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            McpServerStatus::Pending => write!(f, "pending"),
+            McpServerStatus::Connecting => write!(f, "connecting"),
+            McpServerStatus::Connected => write!(f, "connected"),
+            McpServerStatus::Restarting { attempt } => {
+                write!(f, "restarting (attempt {})", attempt)
+            }
+            McpServerStatus::Failed { error } => write!(f, "failed: {}", error),
+            McpServerStatus::PendingApproval => write!(f, "pending approval"),
+            McpServerStatus::Rejected => write!(f, "rejected"),
+            McpServerStatus::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// 单个服务器的状态快照
+#[derive(Debug, Clone)]
+pub struct McpServerState {
+    /// 服务器名称
+    pub name: String,
+    /// 配置来源
+    pub scope: McpServerScope,
+    /// 传输类型：`stdio`、`sse` 或 `http`
+    pub transport: &'static str,
+    /// 当前状态
+    pub status: McpServerStatus,
+    /// 握手时服务器报告的名称与版本
+    pub server_info: Option<Implementation>,
+    /// 本次会话中成功重启的次数
+    pub restarts: u32,
+}
+
+/// stdio 服务器意外退出后的重启策略
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// 连续重启的最大次数，超过后标记为失败
+    pub max_restarts: u32,
+    /// 首次重启前的等待时间，之后按指数退避
+    pub initial_delay: Duration,
+    /// 单次等待时间上限
+    pub max_delay: Duration,
+    /// 服务器稳定运行超过此时间后重新计算重启次数
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// 第 `attempt` 次重启前的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+}
+
+struct ServerEntry {
+    scope: McpServerScope,
+    config: McpServerConfig,
+    status: McpServerStatus,
+    client: Option<Arc<McpClient>>,
+    restarts: u32,
+}
+
+/// MCP 服务器管理器
+pub struct McpManager {
+    servers: Mutex<BTreeMap<String, ServerEntry>>,
+    options: HttpTransportOptions,
+    connect_timeout: Duration,
+    restart: RestartPolicy,
+    shutdown: CancellationToken,
+    supervisors: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl McpManager {
+    /// 由合并后的服务器配置创建管理器
+    ///
+    /// 未批准的 .mcprc 服务器只记录状态，不会启动。
+    pub fn new(servers: BTreeMap<String, ScopedMcpServer>) -> Self {
+        let servers = servers
+            .into_iter()
+            .map(|(name, server)| {
+                let status = match server.approval {
+                    McprcApproval::Approved => McpServerStatus::Pending,
+                    McprcApproval::Pending => McpServerStatus::PendingApproval,
+                    McprcApproval::Rejected => McpServerStatus::Rejected,
+                };
+                let entry = ServerEntry {
+                    scope: server.scope,
+                    config: server.config,
+                    status,
+                    client: None,
+                    restarts: 0,
+                };
+                (name, entry)
+            })
+            .collect();
+
+        Self {
+            servers: Mutex::new(servers),
+            options: HttpTransportOptions::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            restart: RestartPolicy::default(),
+            shutdown: CancellationToken::new(),
+            supervisors: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 设置 SSE / Streamable HTTP 传输的连接选项
+    pub fn with_http_options(mut self, options: HttpTransportOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 设置单个服务器的连接超时
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// 设置 stdio 服务器的重启策略
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart = policy;
+        self
+    }
+
+    /// 并行启动所有尚未启动的已批准服务器
+    ///
+    /// 每个服务器的连接受连接超时限制，失败只影响该服务器的状态。
+    /// 所有服务器都得到结果后返回。
+    pub async fn start(self: &Arc<Self>) {
+        let targets: Vec<(String, McpServerConfig)> = {
+            let mut servers = self.servers.lock().unwrap();
+            servers
+                .iter_mut()
+                .filter(|(_, entry)| entry.status == McpServerStatus::Pending)
+                .map(|(name, entry)| {
+                    entry.status = McpServerStatus::Connecting;
+                    (name.clone(), entry.config.clone())
+                })
+                .collect()
+        };
+
+        join_all(targets.into_iter().map(|(name, config)| async move {
+            let result = self.connect(&name, &config).await;
+            self.started(name, result).await;
+        }))
+        .await;
+    }
+
+    /// 所有服务器的状态快照，按名称排序
+    pub fn status(&self) -> Vec<McpServerState> {
+        self.servers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, entry)| McpServerState {
+                name: name.clone(),
+                scope: entry.scope,
+                transport: transport_name(&entry.config),
+                status: entry.status.clone(),
+                server_info: entry
+                    .client
+                    .as_ref()
+                    .and_then(|client| client.server())
+                    .map(|server| server.server_info.clone()),
+                restarts: entry.restarts,
+            })
+            .collect()
+    }
+
+    /// 关闭所有服务器
+    ///
+    /// 停止重启任务后并行关闭全部连接；之后管理器不再启动或重启任何服务器。
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+        let supervisors = std::mem::take(&mut *self.supervisors.lock().unwrap());
+        join_all(supervisors).await;
+
+        let clients: Vec<_> = {
+            let mut servers = self.servers.lock().unwrap();
+            servers
+                .values_mut()
+                .filter_map(|entry| {
+                    if matches!(
+                        entry.status,
+                        McpServerStatus::Connected
+                            | McpServerStatus::Connecting
+                            | McpServerStatus::Restarting { .. }
+                            | McpServerStatus::Pending
+                    ) {
+                        entry.status = McpServerStatus::Stopped;
+                    }
+                    entry.client.take()
+                })
+                .collect()
+        };
+        join_all(clients.iter().map(|client| async move {
+            if let Err(e) = client.close().await {
+                tracing::warn!("Failed to close MCP server {}: {}", client.name(), e);
+            }
+        }))
+        .await;
+    }
+
+    async fn connect(&self, name: &str, config: &McpServerConfig) -> Result<Arc<McpClient>> {
+        let connect = McpClient::connect_config(name, config, &self.options);
+        match tokio::time::timeout(self.connect_timeout, connect).await {
+            Ok(client) => Ok(Arc::new(client?)),
+            Err(_) => bail!(
+                "Timed out connecting to MCP server '{}' after {:?}",
+                name,
+                self.connect_timeout
+            ),
+        }
+    }
+
+    /// 记录首次连接的结果，成功时开始监视连接
+    async fn started(self: &Arc<Self>, name: String, result: Result<Arc<McpClient>>) {
+        let client = {
+            let mut servers = self.servers.lock().unwrap();
+            let Some(entry) = servers.get_mut(&name) else {
+                return;
+            };
+            if self.shutdown.is_cancelled() {
+                entry.status = McpServerStatus::Stopped;
+                result.ok()
+            } else {
+                match result {
+                    Ok(client) => {
+                        entry.status = McpServerStatus::Connected;
+                        entry.client = Some(client.clone());
+                        let supervisor = tokio::spawn(self.clone().supervise(name, client));
+                        self.supervisors.lock().unwrap().push(supervisor);
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to start MCP server {}: {:#}", name, e);
+                        entry.status = McpServerStatus::Failed {
+                            error: format!("{:#}", e),
+                        };
+                        None
+                    }
+                }
+            }
+        };
+        // 启动期间管理器已关闭：释放刚建立的连接
+        if let Some(client) = client {
+            let _ = client.close().await;
+        }
+    }
+
+    /// 等待连接关闭；stdio 服务器按重启策略重启，其他传输标记为失败
+    async fn supervise(self: Arc<Self>, name: String, mut client: Arc<McpClient>) {
+        let mut attempt = 0;
+        loop {
+            let connected_at = Instant::now();
+            tokio::select! {
+                _ = self.shutdown.cancelled() => return,
+                _ = client.closed() => {}
+            }
+
+            let mut error = exit_error(&client);
+            let Some(config) = self.update(&name, |entry| {
+                entry.client = None;
+                entry.config.clone()
+            }) else {
+                return;
+            };
+            if !matches!(config, McpServerConfig::Stdio(_)) {
+                tracing::warn!("MCP server {} disconnected: {}", name, error);
+                self.update(&name, |entry| {
+                    entry.status = McpServerStatus::Failed { error }
+                });
+                return;
+            }
+
+            if connected_at.elapsed() >= self.restart.reset_after {
+                attempt = 0;
+            }
+            client = loop {
+                attempt += 1;
+                if attempt > self.restart.max_restarts {
+                    tracing::warn!("Giving up on MCP server {}: {}", name, error);
+                    let error = format!(
+                        "{}; giving up after {} restart attempts",
+                        error, self.restart.max_restarts
+                    );
+                    self.update(&name, |entry| {
+                        entry.status = McpServerStatus::Failed { error }
+                    });
+                    return;
+                }
+
+                tracing::warn!(
+                    "Restarting MCP server {} (attempt {}): {}",
+                    name,
+                    attempt,
+                    error
+                );
+                self.update(&name, |entry| {
+                    entry.status = McpServerStatus::Restarting { attempt }
+                });
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    _ = tokio::time::sleep(self.restart.delay(attempt)) => {}
+                }
+                let result = tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    result = self.connect(&name, &config) => result,
+                };
+                match result {
+                    Ok(client) => break client,
+                    Err(e) => error = format!("{:#}", e),
+                }
+            };
+
+            self.update(&name, |entry| {
+                entry.status = McpServerStatus::Connected;
+                entry.client = Some(client.clone());
+                entry.restarts += 1;
+            });
+        }
+    }
+
+    fn update<T>(&self, name: &str, f: impl FnOnce(&mut ServerEntry) -> T) -> Option<T> {
+        self.servers.lock().unwrap().get_mut(name).map(f)
+    }
+}
+
+impl McpClientProvider for McpManager {
+    fn clients(&self) -> Vec<Arc<McpClient>> {
+        self.servers
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|entry| entry.client.clone())
+            .collect()
+    }
+
+    /// 按名称获取已连接的客户端，重启期间返回 `None`
+    fn client(&self, name: &str) -> Option<Arc<McpClient>> {
+        self.servers
+            .lock()
+            .unwrap()
+            .get(name)
+            .and_then(|entry| entry.client.clone())
+    }
+}
+
+fn transport_name(config: &McpServerConfig) -> &'static str {
+    match config {
+        McpServerConfig::Stdio(_) => "stdio",
+        McpServerConfig::Sse(_) => "sse",
+        McpServerConfig::Http(_) => "http",
+    }
+}
+
+/// 连接关闭的原因，附带服务器 stderr 的最后一行
+fn exit_error(client: &McpClient) -> String {
+    let diagnostics = client.diagnostics();
+    match diagnostics
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+    {
+        Some(line) => format!("server exited unexpectedly ({})", line.trim()),
+        None => "server exited unexpectedly".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_backoff() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(10), Duration::from_secs(30));
+    }
+}
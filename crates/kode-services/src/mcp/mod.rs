@@ -0,0 +1,29 @@
+//! MCP（Model Context Protocol）客户端
+//!
+//! 提供 JSON-RPC 协议类型、传输层抽象、stdio / SSE / Streamable HTTP 传输、客户端实现、
+//! 服务器生命周期管理、上下文资源加载以及提示词斜杠命令。
+
+pub mod client;
+pub mod context;
+pub mod http;
+pub mod manager;
+pub mod prompts;
+pub mod protocol;
+pub mod sse;
+pub mod stdio;
+pub mod streamable_http;
+pub mod transport;
+
+pub use client::{McpClient, McpClientProvider, DEFAULT_REQUEST_TIMEOUT};
+pub use context::{load_context_uris, McpContext};
+pub use http::HttpTransportOptions;
+pub use manager::{McpManager, McpServerState, McpServerStatus, RestartPolicy};
+pub use prompts::{McpPromptCommand, McpPromptCommands};
+pub use protocol::{
+    CallToolResult, GetPromptResult, InitializeResult, McpTool, Prompt, PromptArgument,
+    ReadResourceResult, Resource, ResourceContents, ServerCapabilities, ToolContent,
+};
+pub use sse::SseTransport;
+pub use stdio::StdioTransport;
+pub use streamable_http::StreamableHttpTransport;
+pub use transport::{DiagnosticLog, McpTransport};
@@ -0,0 +1,536 @@
+//! MCP 提示词斜杠命令
+//!
+//! 每个服务器发布的提示词注册为 `/<server>:<prompt>` 命令。命令参数可以按声明顺序
+//! 位置传入，也可以写成 `key=value`；校验通过后调用 `prompts/get`，
+//! 返回的消息转换为会话消息插入对话。
+
+use super::client::McpClientProvider;
+use super::protocol::{Prompt, PromptArgument, PromptMessage, PromptRole, ToolContent};
+use anyhow::{anyhow, bail, Result};
+use kode_core::message::{ContentBlock, ImageBlock, Message, TextBlock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// 单个提示词命令
+///
+/// 只保存服务器名，每次展开时从 `clients` 取当前连接，服务器重启后使用新连接。
+pub struct McpPromptCommand {
+    name: String,
+    server: String,
+    prompt: Prompt,
+    clients: Arc<dyn McpClientProvider>,
+}
+
+impl McpPromptCommand {
+    /// 包装 `clients` 中名为 `server` 的服务器返回的提示词定义
+    pub fn new(
+        server: impl Into<String>,
+        clients: Arc<dyn McpClientProvider>,
+        prompt: Prompt,
+    ) -> Self {
+        let server = server.into();
+        Self {
+            name: format!("{}:{}", server, prompt.name),
+            server,
+            prompt,
+            clients,
+        }
+    }
+
+    /// 命令名（不含前导 `/`）
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 所属服务器名
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// 提示词定义
+    pub fn prompt(&self) -> &Prompt {
+        &self.prompt
+    }
+
+    /// 用法说明，必填参数写作 `<name>`，可选参数写作 `[name]`
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for argument in &self.prompt.arguments {
+            if argument.required {
+                usage.push_str(&format!(" <{}>", argument.name));
+            } else {
+                usage.push_str(&format!(" [{}]", argument.name));
+            }
+        }
+        usage
+    }
+
+    /// 解析并校验命令参数
+    ///
+    /// `key=value` 形式的参数按名称匹配，其余参数按声明顺序填入尚未赋值的参数。
+    /// 含空格的值可以用单引号或双引号包裹。
+    pub fn parse_arguments(&self, input: &str) -> Result<HashMap<String, String>> {
+        let declared = &self.prompt.arguments;
+        let mut values: HashMap<String, String> = HashMap::new();
+        let mut positional = Vec::new();
+
+        for token in split_arguments(input)? {
+            match token.split_once('=') {
+                Some((key, value)) if is_argument_key(key) => {
+                    if !declared.iter().any(|a| a.name == key) {
+                        bail!(
+                            "Unknown argument '{}' for /{}. Usage: {}",
+                            key,
+                            self.name,
+                            self.usage()
+                        );
+                    }
+                    if values.insert(key.to_string(), value.to_string()).is_some() {
+                        bail!("Argument '{}' given more than once", key);
+                    }
+                }
+                _ => positional.push(token),
+            }
+        }
+
+        let mut open = declared
+            .iter()
+            .filter(|a| !values.contains_key(&a.name))
+            .collect::<Vec<&PromptArgument>>()
+            .into_iter();
+        for value in positional {
+            let Some(argument) = open.next() else {
+                bail!(
+                    "Too many arguments for /{}. Usage: {}",
+                    self.name,
+                    self.usage()
+                );
+            };
+            values.insert(argument.name.clone(), value);
+        }
+
+        let missing: Vec<&str> = declared
+            .iter()
+            .filter(|a| a.required && !values.contains_key(&a.name))
+            .map(|a| a.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "Missing required argument(s) {} for /{}. Usage: {}",
+                missing.join(", "),
+                self.name,
+                self.usage()
+            );
+        }
+        Ok(values)
+    }
+
+    /// 解析参数并展开提示词为会话消息
+    pub async fn expand(&self, input: &str) -> Result<Vec<Message>> {
+        let arguments = self.parse_arguments(input)?;
+        let client = self
+            .clients
+            .client(&self.server)
+            .ok_or_else(|| anyhow!("MCP server '{}' is not connected", self.server))?;
+        let result = client.get_prompt(&self.prompt.name, &arguments).await?;
+        Ok(result.messages.iter().map(to_message).collect())
+    }
+}
+
+/// 全部服务器的提示词命令
+#[derive(Default)]
+pub struct McpPromptCommands {
+    commands: BTreeMap<String, McpPromptCommand>,
+}
+
+impl McpPromptCommands {
+    /// 读取所有服务器的提示词
+    ///
+    /// 单个服务器读取失败只记录警告，不影响其他服务器。
+    /// `clients` 通常是 `McpManager`，命令执行时使用服务器的当前连接。
+    pub async fn load(clients: Arc<dyn McpClientProvider>) -> Self {
+        let mut commands = Self::default();
+        for client in clients.clients() {
+            match client.list_prompts().await {
+                Ok(prompts) => {
+                    for prompt in prompts {
+                        commands.insert(McpPromptCommand::new(
+                            client.name(),
+                            clients.clone(),
+                            prompt,
+                        ));
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "Failed to list prompts of MCP server '{}': {}",
+                    client.name(),
+                    e
+                ),
+            }
+        }
+        commands
+    }
+
+    /// 注册命令，同名命令被替换
+    pub fn insert(&mut self, command: McpPromptCommand) {
+        self.commands.insert(command.name.clone(), command);
+    }
+
+    /// 按名称（不含前导 `/`）查找命令
+    pub fn get(&self, name: &str) -> Option<&McpPromptCommand> {
+        self.commands.get(name)
+    }
+
+    /// 按名称排序的全部命令
+    pub fn commands(&self) -> impl Iterator<Item = &McpPromptCommand> {
+        self.commands.values()
+    }
+
+    /// 是否没有任何命令
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// 执行一行斜杠命令输入
+    ///
+    /// 输入不是已注册的 MCP 提示词命令时返回 `None`，交给其他命令处理。
+    pub async fn execute(&self, line: &str) -> Option<Result<Vec<Message>>> {
+        let line = line.trim_start().strip_prefix('/')?;
+        let (name, input) = match line.split_once(char::is_whitespace) {
+            Some((name, input)) => (name, input),
+            None => (line, ""),
+        };
+        let command = self.get(name)?;
+        Some(command.expand(input).await)
+    }
+}
+
+/// 可以作为 `key=value` 键的名称
+fn is_argument_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// 按空白拆分参数，支持单双引号和双引号内的 `\` 转义
+fn split_arguments(input: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => match chars.next() {
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        bail!("Unterminated quote in arguments");
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// 把提示词消息转换为会话消息
+fn to_message(message: &PromptMessage) -> Message {
+    let block = match &message.content {
+        ToolContent::Text { text } => ContentBlock::Text(TextBlock { text: text.clone() }),
+        ToolContent::Image { data, mime_type } => ContentBlock::Image(ImageBlock {
+            image_type: "base64".to_string(),
+            media_type: mime_type.clone(),
+            data: data.clone(),
+        }),
+        ToolContent::Resource { resource } => {
+            let uri = resource["uri"].as_str().unwrap_or("unknown");
+            let mime_type = resource["mimeType"].as_str().unwrap_or_default();
+            match (resource["text"].as_str(), resource["blob"].as_str()) {
+                (Some(text), _) => ContentBlock::Text(TextBlock {
+                    text: format!("[Resource: {}]\n{}", uri, text),
+                }),
+                (None, Some(blob)) if mime_type.starts_with("image/") => {
+                    ContentBlock::Image(ImageBlock {
+                        image_type: "base64".to_string(),
+                        media_type: mime_type.to_string(),
+                        data: blob.to_string(),
+                    })
+                }
+                _ => ContentBlock::Text(TextBlock {
+                    text: format!("[Binary resource: {} ({})]", uri, mime_type),
+                }),
+            }
+        }
+        ToolContent::Unknown => ContentBlock::Text(TextBlock {
+            text: "[Unsupported content]".to_string(),
+        }),
+    };
+
+    match (message.role, block) {
+        (PromptRole::User, ContentBlock::Text(TextBlock { text })) => Message::user(text),
+        (PromptRole::Assistant, ContentBlock::Text(TextBlock { text })) => Message::assistant(text),
+        (PromptRole::User, block) => Message::user("").with_blocks(vec![block]),
+        (PromptRole::Assistant, block) => Message::assistant("").with_blocks(vec![block]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::client::McpClient;
+    use super::super::protocol::{JsonRpcMessage, JsonRpcResponse};
+    use super::super::transport::McpTransport;
+    use super::*;
+    use async_trait::async_trait;
+    use kode_core::message::{MessageContent, Role};
+    use serde_json::{json, Value};
+    use tokio::sync::{mpsc, Mutex};
+
+    /// 内存中的假 MCP 服务器，只提供提示词
+    struct PromptServer {
+        tx: mpsc::UnboundedSender<JsonRpcMessage>,
+        rx: Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    }
+
+    impl PromptServer {
+        fn get(params: &Value) -> Value {
+            let args = &params["arguments"];
+            json!({
+                "description": "Review a change",
+                "messages": [
+                    {
+                        "role": "user",
+                        "content": {
+                            "type": "text",
+                            "text": format!("Review {} focusing on {}", args["pr"].as_str().unwrap(),
+                                args["focus"].as_str().unwrap_or("everything"))
+                        }
+                    },
+                    {
+                        "role": "assistant",
+                        "content": { "type": "text", "text": "Sure, fetching the diff." }
+                    },
+                    {
+                        "role": "user",
+                        "content": {
+                            "type": "resource",
+                            "resource": { "uri": "git://diff", "mimeType": "text/x-diff", "text": "+added" }
+                        }
+                    },
+                    {
+                        "role": "user",
+                        "content": { "type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png" }
+                    }
+                ]
+            })
+        }
+    }
+
+    #[async_trait]
+    impl McpTransport for PromptServer {
+        async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+            let JsonRpcMessage::Request(request) = message else {
+                return Ok(());
+            };
+            let params = request.params.clone().unwrap_or_default();
+            let result = match request.method.as_str() {
+                "initialize" => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": { "prompts": {} },
+                    "serverInfo": { "name": "fake", "version": "1.0" }
+                }),
+                "prompts/list" => json!({
+                    "prompts": [
+                        {
+                            "name": "review",
+                            "description": "Review a pull request",
+                            "arguments": [
+                                { "name": "pr", "required": true },
+                                { "name": "focus" }
+                            ]
+                        },
+                        { "name": "standup" }
+                    ]
+                }),
+                "prompts/get" => Self::get(&params),
+                _ => json!({}),
+            };
+            let _ = self
+                .tx
+                .send(JsonRpcMessage::Response(JsonRpcResponse::success(
+                    request.id.clone(),
+                    result,
+                )));
+            Ok(())
+        }
+
+        async fn receive(&self) -> Option<JsonRpcMessage> {
+            self.rx.lock().await.recv().await
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn connect() -> Arc<McpClient> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let server = Arc::new(PromptServer {
+            tx,
+            rx: Mutex::new(rx),
+        });
+        Arc::new(McpClient::connect("github", server).await.unwrap())
+    }
+
+    async fn load() -> McpPromptCommands {
+        McpPromptCommands::load(Arc::new(vec![connect().await])).await
+    }
+
+    /// 可以替换连接的客户端来源，模拟服务器重启
+    struct Restartable(std::sync::Mutex<Option<Arc<McpClient>>>);
+
+    impl McpClientProvider for Restartable {
+        fn clients(&self) -> Vec<Arc<McpClient>> {
+            self.0.lock().unwrap().iter().cloned().collect()
+        }
+    }
+
+    fn text(message: &Message) -> &str {
+        match &message.content {
+            MessageContent::Text(text) => text,
+            MessageContent::Blocks(_) => panic!("expected text content"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompts_registered_as_commands() {
+        let commands = load().await;
+        let names: Vec<_> = commands.commands().map(|c| c.name()).collect();
+        assert_eq!(names, vec!["github:review", "github:standup"]);
+
+        let review = commands.get("github:review").unwrap();
+        assert_eq!(review.server(), "github");
+        assert_eq!(review.usage(), "/github:review <pr> [focus]");
+    }
+
+    #[tokio::test]
+    async fn test_parse_arguments() {
+        let commands = load().await;
+        let review = commands.get("github:review").unwrap();
+        let args = |input: &str| review.parse_arguments(input);
+
+        assert_eq!(
+            args("42 security").unwrap(),
+            HashMap::from([
+                ("pr".to_string(), "42".to_string()),
+                ("focus".to_string(), "security".to_string()),
+            ])
+        );
+        assert_eq!(
+            args("focus=\"error handling\" 42").unwrap(),
+            HashMap::from([
+                ("pr".to_string(), "42".to_string()),
+                ("focus".to_string(), "error handling".to_string()),
+            ])
+        );
+        // 键不是合法参数名时整体作为位置参数
+        assert_eq!(
+            args("https://x.test/pr?id=7").unwrap()["pr"],
+            "https://x.test/pr?id=7"
+        );
+
+        let err = args("").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Missing required argument(s) pr for /github:review. Usage: /github:review <pr> [focus]"
+        );
+        assert!(args("1 2 3")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Too many arguments"));
+        assert!(args("pr=1 owner=me")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unknown argument 'owner'"));
+        assert!(args("pr=1 pr=2")
+            .unwrap_err()
+            .to_string()
+            .contains("more than once"));
+        assert!(args("'unterminated").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_expands_messages() {
+        let commands = load().await;
+        assert!(commands.execute("/help").await.is_none());
+        assert!(commands.execute("not a command").await.is_none());
+
+        let messages = commands
+            .execute("/github:review 42 focus=tests")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].role, Role::User);
+        assert_eq!(text(&messages[0]), "Review 42 focusing on tests");
+        assert_eq!(messages[1].role, Role::Assistant);
+        assert_eq!(text(&messages[1]), "Sure, fetching the diff.");
+        assert_eq!(text(&messages[2]), "[Resource: git://diff]\n+added");
+        match &messages[3].content {
+            MessageContent::Blocks(blocks) => {
+                assert!(matches!(&blocks[0], ContentBlock::Image(i) if i.media_type == "image/png"))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let err = commands
+            .execute("/github:review")
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Missing required argument"));
+    }
+
+    #[tokio::test]
+    async fn test_restart_uses_new_client() {
+        let first = connect().await;
+        let clients = Arc::new(Restartable(std::sync::Mutex::new(Some(first.clone()))));
+        let commands = McpPromptCommands::load(clients.clone()).await;
+
+        // 服务器退出，重启完成前执行报告未连接
+        first.close().await.unwrap();
+        *clients.0.lock().unwrap() = None;
+        let err = commands
+            .execute("/github:standup")
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "MCP server 'github' is not connected");
+
+        // 重启后已注册的命令使用新连接
+        *clients.0.lock().unwrap() = Some(connect().await);
+        let messages = commands.execute("/github:review 7").await.unwrap().unwrap();
+        assert_eq!(text(&messages[0]), "Review 7 focusing on everything");
+    }
+}
@@ -0,0 +1,507 @@
+//! MCP 协议类型
+//!
+//! JSON-RPC 2.0 消息封装以及 MCP 握手、工具相关的请求/响应结构。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// JSON-RPC 版本
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// 客户端支持的 MCP 协议版本
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// 可以协商的协议版本（按新旧排列）
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", MCP_PROTOCOL_VERSION];
+
+/// 方法未找到错误码
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// 参数无效错误码
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// 请求 ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    /// 数字 ID
+    Number(i64),
+    /// 字符串 ID
+    String(String),
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestId::Number(n) => write!(f, "{}", n),
+            RequestId::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// JSON-RPC 请求
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcRequest {
+    /// 协议版本，固定为 "2.0"
+    pub jsonrpc: String,
+    /// 请求 ID
+    pub id: RequestId,
+    /// 方法名
+    pub method: String,
+    /// 参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcRequest {
+    /// 创建请求
+    pub fn new(id: RequestId, method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// JSON-RPC 通知（无 ID，不需要响应）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcNotification {
+    /// 协议版本，固定为 "2.0"
+    pub jsonrpc: String,
+    /// 方法名
+    pub method: String,
+    /// 参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcNotification {
+    /// 创建通知
+    pub fn new(method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// JSON-RPC 错误对象
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    /// 错误码
+    pub code: i64,
+    /// 错误消息
+    pub message: String,
+    /// 附加数据
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+/// JSON-RPC 响应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcResponse {
+    /// 协议版本，固定为 "2.0"
+    pub jsonrpc: String,
+    /// 对应请求的 ID
+    pub id: RequestId,
+    /// 成功结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// 错误
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    /// 创建成功响应
+    pub fn success(id: RequestId, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// 创建错误响应
+    pub fn failure(id: RequestId, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+                data: None,
+            }),
+        }
+    }
+}
+
+/// 任意 JSON-RPC 消息
+///
+/// 变体顺序决定反序列化优先级：同时带 `id` 和 `method` 的是请求，
+/// 只有 `method` 的是通知，其余带 `id` 的是响应。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    /// 请求
+    Request(JsonRpcRequest),
+    /// 通知
+    Notification(JsonRpcNotification),
+    /// 响应
+    Response(JsonRpcResponse),
+}
+
+/// 客户端或服务器的实现信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Implementation {
+    /// 名称
+    pub name: String,
+    /// 版本
+    pub version: String,
+}
+
+/// 客户端能力
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientCapabilities {
+    /// 根目录能力
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Value>,
+    /// 采样能力
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Value>,
+}
+
+/// 列表变更能力
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListChangedCapability {
+    /// 列表变化时是否发送通知
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+/// 资源能力
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesCapability {
+    /// 是否支持订阅
+    #[serde(default)]
+    pub subscribe: bool,
+    /// 列表变化时是否发送通知
+    #[serde(default)]
+    pub list_changed: bool,
+}
+
+/// 服务器能力
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    /// 工具
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<ListChangedCapability>,
+    /// 资源
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourcesCapability>,
+    /// 提示词
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<ListChangedCapability>,
+    /// 日志
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Value>,
+}
+
+/// initialize 请求参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    /// 协议版本
+    pub protocol_version: String,
+    /// 客户端能力
+    pub capabilities: ClientCapabilities,
+    /// 客户端信息
+    pub client_info: Implementation,
+}
+
+/// initialize 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    /// 服务器选择的协议版本
+    pub protocol_version: String,
+    /// 服务器能力
+    #[serde(default)]
+    pub capabilities: ServerCapabilities,
+    /// 服务器信息
+    pub server_info: Implementation,
+    /// 服务器提供的使用说明
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+}
+
+/// MCP 工具定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    /// 工具名
+    pub name: String,
+    /// 描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 输入参数的 JSON Schema
+    #[serde(default)]
+    pub input_schema: Value,
+}
+
+/// tools/list 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListToolsResult {
+    /// 工具列表
+    pub tools: Vec<McpTool>,
+    /// 下一页游标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// 工具返回的内容块
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ToolContent {
+    /// 文本
+    Text {
+        /// 文本内容
+        text: String,
+    },
+    /// 图片
+    #[serde(rename_all = "camelCase")]
+    Image {
+        /// base64 数据
+        data: String,
+        /// MIME 类型
+        mime_type: String,
+    },
+    /// 嵌入资源
+    Resource {
+        /// 资源内容
+        resource: Value,
+    },
+    /// 未识别的内容类型
+    #[serde(other)]
+    Unknown,
+}
+
+/// tools/call 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    /// 内容块
+    #[serde(default)]
+    pub content: Vec<ToolContent>,
+    /// 是否为工具执行错误
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+/// MCP 资源
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    /// 资源 URI
+    pub uri: String,
+    /// 名称
+    #[serde(default)]
+    pub name: String,
+    /// 描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// MIME 类型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// resources/list 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResourcesResult {
+    /// 资源列表
+    pub resources: Vec<Resource>,
+    /// 下一页游标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// 资源内容（文本或 base64 二进制）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    /// 资源 URI
+    pub uri: String,
+    /// MIME 类型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// 文本内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// base64 编码的二进制内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+/// resources/read 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadResourceResult {
+    /// 内容（一个 URI 可能对应多段内容）
+    pub contents: Vec<ResourceContents>,
+}
+
+impl ReadResourceResult {
+    /// 以文本形式呈现全部内容，二进制内容以占位说明代替
+    pub fn to_text(&self) -> String {
+        self.contents
+            .iter()
+            .map(|content| match (&content.text, &content.mime_type) {
+                (Some(text), _) => text.clone(),
+                (None, mime_type) => format!(
+                    "[Binary resource: {} ({})]",
+                    content.uri,
+                    mime_type.as_deref().unwrap_or("unknown type")
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// 提示词参数定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptArgument {
+    /// 参数名
+    pub name: String,
+    /// 描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 是否必填
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// MCP 提示词
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prompt {
+    /// 提示词名
+    pub name: String,
+    /// 描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 参数列表（按声明顺序接收位置参数）
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// prompts/list 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPromptsResult {
+    /// 提示词列表
+    pub prompts: Vec<Prompt>,
+    /// 下一页游标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// 提示词消息角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptRole {
+    /// 用户
+    User,
+    /// 助手
+    Assistant,
+}
+
+/// 提示词展开后的单条消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptMessage {
+    /// 角色
+    pub role: PromptRole,
+    /// 内容
+    pub content: ToolContent,
+}
+
+/// prompts/get 响应结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetPromptResult {
+    /// 描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 消息
+    pub messages: Vec<PromptMessage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_discrimination() {
+        let request: JsonRpcMessage =
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).unwrap();
+        assert!(matches!(request, JsonRpcMessage::Request(_)));
+
+        let notification: JsonRpcMessage = serde_json::from_value(
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        )
+        .unwrap();
+        assert!(matches!(notification, JsonRpcMessage::Notification(_)));
+
+        let response: JsonRpcMessage =
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": "a", "result": {}})).unwrap();
+        match response {
+            JsonRpcMessage::Response(r) => {
+                assert_eq!(r.id, RequestId::String("a".to_string()));
+                assert_eq!(r.result, Some(json!({})));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_content_roundtrip() {
+        let result: CallToolResult = serde_json::from_value(json!({
+            "content": [
+                {"type": "text", "text": "hi"},
+                {"type": "image", "data": "AAAA", "mimeType": "image/png"},
+                {"type": "audio", "data": "xx"}
+            ],
+            "isError": true
+        }))
+        .unwrap();
+        assert!(result.is_error);
+        assert_eq!(
+            result.content[0],
+            ToolContent::Text {
+                text: "hi".to_string()
+            }
+        );
+        assert_eq!(
+            result.content[1],
+            ToolContent::Image {
+                data: "AAAA".to_string(),
+                mime_type: "image/png".to_string()
+            }
+        );
+        assert_eq!(result.content[2], ToolContent::Unknown);
+    }
+}
@@ -0,0 +1,429 @@
+//! SSE 传输（MCP 2024-11-05 HTTP+SSE）
+//!
+//! 通过 GET 建立事件流，服务器先以 `endpoint` 事件告知消息提交地址（其中带会话 ID），
+//! 之后客户端把消息 POST 到该地址，响应从事件流返回。事件流断开后自动重连，
+//! 并携带 `Last-Event-ID` 以便服务器补发。
+
+use super::http::{
+    ensure_success, expect_event_stream, parse_messages, read_events, resolve_headers,
+    HttpTransportOptions, StreamState,
+};
+use super::protocol::JsonRpcMessage;
+use super::transport::{DiagnosticLog, McpTransport};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use kode_core::config::types::McpSseServerConfig;
+use reqwest::header::{HeaderMap, ACCEPT};
+use std::ops::ControlFlow;
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// 等待 `endpoint` 事件的时间
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SSE 传输
+pub struct SseTransport {
+    client: reqwest::Client,
+    headers: HeaderMap,
+    endpoint: watch::Receiver<Option<String>>,
+    incoming: Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    listener: StdMutex<Option<JoinHandle<()>>>,
+    log: DiagnosticLog,
+}
+
+impl SseTransport {
+    /// 建立事件流并等待服务器告知消息端点
+    pub async fn connect(
+        config: &McpSseServerConfig,
+        options: &HttpTransportOptions,
+    ) -> Result<Self> {
+        let client = options.build_client()?;
+        let headers = resolve_headers(config.headers.as_ref())?;
+        let log = DiagnosticLog::default();
+        let (endpoint_tx, mut endpoint) = watch::channel(None);
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let listener = tokio::spawn(listen(
+            Listener {
+                client: client.clone(),
+                url: config.url.clone(),
+                headers: headers.clone(),
+                options: options.clone(),
+                log: log.clone(),
+            },
+            endpoint_tx,
+            tx,
+        ));
+
+        let connected = tokio::time::timeout(ENDPOINT_TIMEOUT, endpoint.wait_for(Option::is_some))
+            .await
+            .map(|r| r.is_ok());
+        if connected != Ok(true) {
+            listener.abort();
+            let reason = match connected {
+                Err(_) => "timed out waiting for endpoint event".to_string(),
+                _ => log.contents(),
+            };
+            return Err(anyhow!(
+                "Failed to connect to MCP SSE server {}: {}",
+                config.url,
+                reason
+            ));
+        }
+
+        Ok(Self {
+            client,
+            headers,
+            endpoint,
+            incoming: Mutex::new(rx),
+            listener: StdMutex::new(Some(listener)),
+            log,
+        })
+    }
+
+    /// 当前的消息提交地址
+    pub fn endpoint(&self) -> Option<String> {
+        self.endpoint.borrow().clone()
+    }
+}
+
+#[async_trait]
+impl McpTransport for SseTransport {
+    async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+        let endpoint = self
+            .endpoint()
+            .ok_or_else(|| anyhow!("MCP SSE transport is not connected"))?;
+        let response = self
+            .client
+            .post(&endpoint)
+            .headers(self.headers.clone())
+            .json(message)
+            .send()
+            .await
+            .with_context(|| format!("Failed to post to MCP endpoint {}", endpoint))?;
+        ensure_success(response, "MCP message post").await?;
+        Ok(())
+    }
+
+    async fn receive(&self) -> Option<JsonRpcMessage> {
+        self.incoming.lock().await.recv().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            listener.abort();
+        }
+        Ok(())
+    }
+
+    fn diagnostics(&self) -> String {
+        self.log.contents()
+    }
+}
+
+impl Drop for SseTransport {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            listener.abort();
+        }
+    }
+}
+
+/// 事件流监听所需的连接信息
+struct Listener {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    options: HttpTransportOptions,
+    log: DiagnosticLog,
+}
+
+/// 维持事件流：分发消息，断开后按退避策略重连
+///
+/// 首次连接失败不重试，由 [`SseTransport::connect`] 报告错误。
+async fn listen(
+    listener: Listener,
+    endpoint_tx: watch::Sender<Option<String>>,
+    tx: mpsc::UnboundedSender<JsonRpcMessage>,
+) {
+    let Listener {
+        client,
+        url,
+        headers,
+        options,
+        log,
+    } = listener;
+    let base = reqwest::Url::parse(&url).ok();
+    let mut state = StreamState::default();
+    let mut failures = 0;
+
+    loop {
+        let mut request = client
+            .get(&url)
+            .headers(headers.clone())
+            .header(ACCEPT, "text/event-stream");
+        if !state.last_event_id.is_empty() {
+            request = request.header("Last-Event-ID", state.last_event_id.clone());
+        }
+
+        let mut received = false;
+        let result = match request.send().await {
+            Ok(response) => match expect_event_stream(response, "SSE connection").await {
+                Ok(response) => {
+                    read_events(response, &mut state, |event| {
+                        match event.event.as_str() {
+                            "endpoint" => {
+                                let endpoint = match &base {
+                                    Some(base) => base
+                                        .join(event.data.trim())
+                                        .map(|u| u.to_string())
+                                        .unwrap_or_else(|_| event.data.trim().to_string()),
+                                    None => event.data.trim().to_string(),
+                                };
+                                endpoint_tx.send_replace(Some(endpoint));
+                            }
+                            "" | "message" => match parse_messages(&event.data) {
+                                Ok(messages) => {
+                                    received = true;
+                                    for message in messages {
+                                        if tx.send(message).is_err() {
+                                            return ControlFlow::Break(());
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    log.push(format!("Ignoring malformed SSE message: {}", e))
+                                }
+                            },
+                            _ => {}
+                        }
+                        ControlFlow::Continue(())
+                    })
+                    .await
+                }
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+
+        match result {
+            Ok(()) => log.push("SSE stream closed by server"),
+            Err(e) => log.push(format!("{:#}", e)),
+        }
+        if tx.is_closed() || endpoint_tx.borrow().is_none() {
+            return;
+        }
+
+        // 只有真正收到过消息才重置退避，避免服务器反复建连即断时无限重试
+        if received {
+            failures = 0;
+        }
+        failures += 1;
+        if failures > options.max_reconnects() {
+            log.push(format!(
+                "Giving up on SSE stream after {} reconnect attempts",
+                options.max_reconnects()
+            ));
+            return;
+        }
+        let delay = options.reconnect_delay(failures, state.retry);
+        log.push(format!(
+            "Reconnecting SSE stream in {}ms (attempt {})",
+            delay.as_millis(),
+            failures
+        ));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::client::McpClient;
+    use super::super::http::test_support::*;
+    use super::super::protocol::ToolContent;
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// 假 SSE 服务器的共享状态
+    #[derive(Default)]
+    struct Server {
+        /// 当前事件流连接
+        stream: Mutex<Option<TcpStream>>,
+        /// 每次 GET 收到的请求
+        connects: StdMutex<Vec<Request>>,
+        /// 已发出的事件，重连时按 Last-Event-ID 补发
+        events: StdMutex<Vec<String>>,
+    }
+
+    impl Server {
+        async fn emit(&self, data: &serde_json::Value) {
+            let mut stream = self.stream.lock().await;
+            let id = {
+                let mut events = self.events.lock().unwrap();
+                events.push(data.to_string());
+                events.len().to_string()
+            };
+            if let Some(stream) = stream.as_mut() {
+                send_event(stream, Some(&id), "message", &data.to_string()).await;
+            }
+        }
+
+        async fn attach(&self, mut socket: TcpStream, last_event_id: Option<&str>) {
+            let mut stream = self.stream.lock().await;
+            start_event_stream(&mut socket, &[]).await;
+            send_event(&mut socket, None, "endpoint", "/messages?session_id=abc").await;
+            let skip: usize = last_event_id.and_then(|id| id.parse().ok()).unwrap_or(0);
+            let missed: Vec<String> = self.events.lock().unwrap()[skip..].to_vec();
+            for (i, data) in missed.iter().enumerate() {
+                let id = (skip + i + 1).to_string();
+                send_event(&mut socket, Some(&id), "message", data).await;
+            }
+            *stream = Some(socket);
+        }
+    }
+
+    /// 启动假服务器；`drop_first_stream` 为真时在第一个请求处理后断开事件流
+    async fn start_server(drop_first_stream: bool) -> (String, Arc<Server>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        let server = Arc::new(Server::default());
+        let state = server.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let Some(request) = read_request(&mut socket).await else {
+                        return;
+                    };
+                    if request.header("authorization") != Some("Bearer sse-token") {
+                        respond(&mut socket, "401 Unauthorized", &[], "missing token").await;
+                        return;
+                    }
+                    match request.method.as_str() {
+                        "GET" => {
+                            state.connects.lock().unwrap().push(request.clone());
+                            state.attach(socket, request.header("last-event-id")).await;
+                        }
+                        "POST" => {
+                            assert_eq!(request.path(), "/messages?session_id=abc");
+                            respond(&mut socket, "202 Accepted", &[], "").await;
+                            let message = request.json();
+                            let Some(id) = message.get("id").cloned() else {
+                                return;
+                            };
+                            let result = match message["method"].as_str().unwrap() {
+                                "initialize" => json!({
+                                    "protocolVersion": "2024-11-05",
+                                    "capabilities": { "tools": {} },
+                                    "serverInfo": { "name": "sse-server", "version": "1.0" }
+                                }),
+                                "tools/call" => json!({
+                                    "content": [{
+                                        "type": "text",
+                                        "text": message["params"]["arguments"]["message"]
+                                    }]
+                                }),
+                                _ => json!({}),
+                            };
+                            state
+                                .emit(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+                                .await;
+                            if drop_first_stream && state.connects.lock().unwrap().len() == 1 {
+                                // 断开事件流，迫使客户端重连
+                                state.stream.lock().await.take();
+                            }
+                        }
+                        _ => respond(&mut socket, "405 Method Not Allowed", &[], "").await,
+                    }
+                });
+            }
+        });
+        (url, server)
+    }
+
+    fn config(url: &str) -> McpSseServerConfig {
+        std::env::set_var("KODE_TEST_MCP_SSE_TOKEN", "sse-token");
+        McpSseServerConfig {
+            url: url.to_string(),
+            headers: Some(HashMap::from([(
+                "Authorization".to_string(),
+                "Bearer ${KODE_TEST_MCP_SSE_TOKEN}".to_string(),
+            )])),
+        }
+    }
+
+    fn fast_reconnect() -> HttpTransportOptions {
+        HttpTransportOptions::new().with_reconnect(3, Duration::from_millis(20))
+    }
+
+    #[tokio::test]
+    async fn test_sse_handshake_and_call() {
+        let (url, _server) = start_server(false).await;
+        let transport = SseTransport::connect(&config(&url), &fast_reconnect())
+            .await
+            .unwrap();
+        assert_eq!(
+            transport.endpoint().unwrap(),
+            url.replace("/sse", "/messages?session_id=abc")
+        );
+
+        let client = McpClient::connect("sse", Arc::new(transport))
+            .await
+            .unwrap();
+        assert_eq!(client.server().unwrap().server_info.name, "sse-server");
+        let result = client
+            .call_tool("echo", json!({ "message": "over sse" }))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.content,
+            vec![ToolContent::Text {
+                text: "over sse".to_string()
+            }]
+        );
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sse_reconnects_with_last_event_id() {
+        let (url, server) = start_server(true).await;
+        let transport = SseTransport::connect(&config(&url), &fast_reconnect())
+            .await
+            .unwrap();
+        let client = McpClient::connect("sse", Arc::new(transport))
+            .await
+            .unwrap();
+
+        // 初始化响应（事件 1）之后事件流被断开，客户端应带着 Last-Event-ID 重连
+        let result = client
+            .call_tool("echo", json!({ "message": "after reconnect" }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+
+        let connects = server.connects.lock().unwrap().clone();
+        assert!(connects.len() >= 2);
+        assert_eq!(connects[0].header("last-event-id"), None);
+        assert_eq!(connects[1].header("last-event-id"), Some("1"));
+        assert!(client.diagnostics().contains("Reconnecting SSE stream"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_connect_reports_http_errors() {
+        let (url, _server) = start_server(false).await;
+        let config = McpSseServerConfig { url, headers: None };
+        let err = SseTransport::connect(&config, &fast_reconnect())
+            .await
+            .err()
+            .unwrap();
+        let message = err.to_string();
+        assert!(message.contains("HTTP 401"), "{}", message);
+        assert!(message.contains("missing token"), "{}", message);
+    }
+}
@@ -0,0 +1,154 @@
+//! 子进程 stdio 传输
+//!
+//! 启动 MCP 服务器进程，按行收发 JSON-RPC 消息，并保留最近的 stderr 输出用于诊断。
+
+use super::protocol::JsonRpcMessage;
+use super::transport::{DiagnosticLog, McpTransport};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use kode_core::config::types::McpStdioServerConfig;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{mpsc, Mutex};
+
+/// 标准输出关闭后等待 stderr 读完的时间
+const STDERR_DRAIN: Duration = Duration::from_millis(500);
+
+/// 关闭 stdin 后等待进程自行退出的时间
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+
+/// 子进程 stdio 传输
+pub struct StdioTransport {
+    command: String,
+    stdin: Mutex<Option<ChildStdin>>,
+    incoming: Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    child: Mutex<Option<Child>>,
+    stderr: DiagnosticLog,
+}
+
+impl StdioTransport {
+    /// 按配置启动服务器进程
+    ///
+    /// 配置中的环境变量叠加在当前进程环境之上。进程随传输一起释放时会被终止。
+    pub fn spawn(config: &McpStdioServerConfig) -> Result<Self> {
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(env) = &config.env {
+            command.envs(env);
+        }
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start MCP server '{}'", config.command))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("MCP server has no stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("MCP server has no stdout"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("MCP server has no stderr"))?;
+
+        let stderr_log = DiagnosticLog::default();
+        let buffer = stderr_log.clone();
+        let name = config.command.clone();
+        let stderr_task = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::debug!("[{} stderr] {}", name, line);
+                buffer.push(line);
+            }
+        });
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let name = config.command.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<JsonRpcMessage>(&line) {
+                    Ok(message) => {
+                        if tx.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Ignoring malformed message from MCP server {}: {}", name, e)
+                    }
+                }
+            }
+            // 等 stderr 读完再报告连接关闭，确保诊断信息完整
+            let _ = tokio::time::timeout(STDERR_DRAIN, stderr_task).await;
+        });
+
+        Ok(Self {
+            command: config.command.clone(),
+            stdin: Mutex::new(Some(stdin)),
+            incoming: Mutex::new(rx),
+            child: Mutex::new(Some(child)),
+            stderr: stderr_log,
+        })
+    }
+
+    /// 服务器最近输出到 stderr 的内容
+    pub fn stderr_output(&self) -> String {
+        self.stderr.contents()
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+
+        let mut stdin = self.stdin.lock().await;
+        let stdin = stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("MCP server '{}' is closed", self.command))?;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .with_context(|| format!("Failed to write to MCP server '{}'", self.command))?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn receive(&self) -> Option<JsonRpcMessage> {
+        self.incoming.lock().await.recv().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        // 先关闭 stdin，给服务器正常退出的机会
+        self.stdin.lock().await.take();
+
+        let Some(mut child) = self.child.lock().await.take() else {
+            return Ok(());
+        };
+        if tokio::time::timeout(CLOSE_GRACE, child.wait())
+            .await
+            .is_err()
+        {
+            child.start_kill()?;
+            child.wait().await?;
+        }
+        Ok(())
+    }
+
+    fn diagnostics(&self) -> String {
+        self.stderr_output()
+    }
+}
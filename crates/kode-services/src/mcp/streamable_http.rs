@@ -0,0 +1,596 @@
+//! Streamable HTTP 传输（MCP 2025-03-26）
+//!
+//! 每条消息 POST 到同一个端点，服务器以 JSON 或事件流应答。服务器在握手时通过
+//! `Mcp-Session-Id` 分配会话，之后的请求都要带上。事件流中途断开时，用
+//! `Last-Event-ID` 发起 GET 恢复；握手完成后另开一条 GET 事件流接收服务器主动消息。
+
+use super::http::{
+    ensure_success, expect_event_stream, is_event_stream, parse_messages, read_events,
+    resolve_headers, HttpTransportOptions, StreamState, SESSION_HEADER,
+};
+use super::protocol::{JsonRpcMessage, JsonRpcResponse, RequestId};
+use super::transport::{DiagnosticLog, McpTransport};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use eventsource_stream::Event;
+use kode_core::config::types::McpHttpServerConfig;
+use reqwest::header::{HeaderMap, ACCEPT};
+use reqwest::StatusCode;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// 事件流在返回响应前断开且无法恢复时使用的错误码
+const STREAM_CLOSED: i64 = -32000;
+
+/// Streamable HTTP 传输
+pub struct StreamableHttpTransport {
+    connection: Connection,
+    incoming: Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    tasks: StdMutex<Vec<JoinHandle<()>>>,
+    listening: AtomicBool,
+    closed: watch::Sender<bool>,
+}
+
+impl StreamableHttpTransport {
+    /// 创建传输（首次发送时才建立连接）
+    pub fn new(config: &McpHttpServerConfig, options: &HttpTransportOptions) -> Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            connection: Connection {
+                client: options.build_client()?,
+                url: config.url.clone(),
+                headers: resolve_headers(config.headers.as_ref())?,
+                options: options.clone(),
+                session: Arc::new(StdMutex::new(None)),
+                tx,
+                log: DiagnosticLog::default(),
+            },
+            incoming: Mutex::new(rx),
+            tasks: StdMutex::new(Vec::new()),
+            listening: AtomicBool::new(false),
+            closed: watch::channel(false).0,
+        })
+    }
+
+    /// 服务器分配的会话 ID
+    pub fn session_id(&self) -> Option<String> {
+        self.connection.session()
+    }
+
+    fn spawn(&self, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|t| !t.is_finished());
+        tasks.push(tokio::spawn(task));
+    }
+}
+
+#[async_trait]
+impl McpTransport for StreamableHttpTransport {
+    async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+        if *self.closed.borrow() {
+            bail!("MCP server at {} is closed", self.connection.url);
+        }
+
+        let session = self.connection.session();
+        let mut request = self
+            .connection
+            .client
+            .post(&self.connection.url)
+            .headers(self.connection.headers.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        if let Some(session) = &session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach MCP server at {}", self.connection.url))?;
+
+        if let Some(id) = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.connection.session.lock().unwrap() = Some(id.to_string());
+        }
+        if response.status() == StatusCode::NOT_FOUND && session.is_some() {
+            self.connection.session.lock().unwrap().take();
+            bail!(
+                "MCP session {} expired on {}; reconnect to start a new session",
+                session.unwrap_or_default(),
+                self.connection.url
+            );
+        }
+        let response = ensure_success(response, "MCP request").await?;
+
+        if response.status() != StatusCode::ACCEPTED {
+            if is_event_stream(&response) {
+                let request_id = match message {
+                    JsonRpcMessage::Request(request) => Some(request.id.clone()),
+                    _ => None,
+                };
+                self.spawn(
+                    self.connection
+                        .clone()
+                        .stream_response(response, request_id),
+                );
+            } else {
+                let body = response.text().await?;
+                if !body.trim().is_empty() {
+                    for message in parse_messages(&body)? {
+                        let _ = self.connection.tx.send(message);
+                    }
+                }
+            }
+        }
+
+        // 握手完成后再打开服务器消息流
+        if let JsonRpcMessage::Notification(notification) = message {
+            if notification.method == "notifications/initialized"
+                && !self.listening.swap(true, Ordering::SeqCst)
+            {
+                self.spawn(self.connection.clone().listen());
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(&self) -> Option<JsonRpcMessage> {
+        let mut closed = self.closed.subscribe();
+        let mut incoming = self.incoming.lock().await;
+        tokio::select! {
+            message = incoming.recv() => message,
+            _ = closed.wait_for(|closed| *closed) => None,
+        }
+    }
+
+    async fn close(&self) -> Result<()> {
+        if self.closed.send_replace(true) {
+            return Ok(());
+        }
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+
+        // 通知服务器结束会话；服务器不支持时忽略
+        let session = self.connection.session.lock().unwrap().take();
+        if let Some(session) = session {
+            let request = self
+                .connection
+                .client
+                .delete(&self.connection.url)
+                .headers(self.connection.headers.clone())
+                .header(SESSION_HEADER, session);
+            if let Err(e) = request.send().await {
+                self.connection
+                    .log
+                    .push(format!("Failed to end MCP session: {}", e));
+            }
+        }
+        Ok(())
+    }
+
+    fn diagnostics(&self) -> String {
+        self.connection.log.contents()
+    }
+}
+
+impl Drop for StreamableHttpTransport {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// 后台任务共享的连接信息
+#[derive(Clone)]
+struct Connection {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    options: HttpTransportOptions,
+    session: Arc<StdMutex<Option<String>>>,
+    tx: mpsc::UnboundedSender<JsonRpcMessage>,
+    log: DiagnosticLog,
+}
+
+impl Connection {
+    fn session(&self) -> Option<String> {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// 打开 GET 事件流，服务器不提供时（405）返回 `None`
+    async fn open_stream(&self, last_event_id: &str) -> Result<Option<reqwest::Response>> {
+        let mut request = self
+            .client
+            .get(&self.url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "text/event-stream");
+        if let Some(session) = self.session() {
+            request = request.header(SESSION_HEADER, session);
+        }
+        if !last_event_id.is_empty() {
+            request = request.header("Last-Event-ID", last_event_id);
+        }
+        let response = request.send().await?;
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(None);
+        }
+        expect_event_stream(response, "MCP event stream")
+            .await
+            .map(Some)
+    }
+
+    /// 分发一个事件中的消息，返回其中是否包含 `request_id` 的响应
+    fn deliver(&self, event: &Event, request_id: Option<&RequestId>) -> bool {
+        if !matches!(event.event.as_str(), "" | "message") || event.data.is_empty() {
+            return false;
+        }
+        let mut answered = false;
+        match parse_messages(&event.data) {
+            Ok(messages) => {
+                for message in messages {
+                    if let (JsonRpcMessage::Response(response), Some(id)) = (&message, request_id) {
+                        answered |= &response.id == id;
+                    }
+                    let _ = self.tx.send(message);
+                }
+            }
+            Err(e) => self
+                .log
+                .push(format!("Ignoring malformed SSE message: {}", e)),
+        }
+        answered
+    }
+
+    /// 读取 POST 返回的事件流；在收到响应前断开时用 `Last-Event-ID` 恢复
+    async fn stream_response(self, response: reqwest::Response, request_id: Option<RequestId>) {
+        let mut state = StreamState::default();
+        let mut answered = false;
+        let mut result = read_events(response, &mut state, |event| {
+            answered = self.deliver(&event, request_id.as_ref());
+            if answered {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .await;
+
+        let Some(request_id) = request_id else {
+            return;
+        };
+        let mut attempt = 0;
+        while !answered {
+            if let Err(e) = &result {
+                self.log.push(format!("{:#}", e));
+            }
+            attempt += 1;
+            if state.last_event_id.is_empty() || attempt > self.options.max_reconnects() {
+                self.log.push(format!(
+                    "Response stream for request {} closed before a response arrived",
+                    request_id
+                ));
+                let _ = self
+                    .tx
+                    .send(JsonRpcMessage::Response(JsonRpcResponse::failure(
+                        request_id,
+                        STREAM_CLOSED,
+                        "Response stream closed before a response arrived",
+                    )));
+                return;
+            }
+
+            let delay = self.options.reconnect_delay(attempt, state.retry);
+            self.log.push(format!(
+                "Resuming response stream after event {} in {}ms",
+                state.last_event_id,
+                delay.as_millis()
+            ));
+            tokio::time::sleep(delay).await;
+            result = match self.open_stream(&state.last_event_id).await {
+                Ok(Some(response)) => {
+                    read_events(response, &mut state, |event| {
+                        answered = self.deliver(&event, Some(&request_id));
+                        if answered {
+                            ControlFlow::Break(())
+                        } else {
+                            ControlFlow::Continue(())
+                        }
+                    })
+                    .await
+                }
+                Ok(None) => Err(anyhow!("Server does not support stream resumption")),
+                Err(e) => Err(e),
+            };
+        }
+    }
+
+    /// 维持接收服务器主动消息的 GET 事件流，断开后按退避策略重连
+    async fn listen(self) {
+        let mut state = StreamState::default();
+        let mut failures = 0;
+        loop {
+            let mut received = false;
+            let result = match self.open_stream(&state.last_event_id).await {
+                Ok(None) => return,
+                Ok(Some(response)) => {
+                    read_events(response, &mut state, |event| {
+                        received = true;
+                        self.deliver(&event, None);
+                        ControlFlow::Continue(())
+                    })
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => self.log.push("MCP event stream closed by server"),
+                Err(e) => self.log.push(format!("{:#}", e)),
+            }
+            if self.tx.is_closed() {
+                return;
+            }
+            if received {
+                failures = 0;
+            }
+            failures += 1;
+            if failures > self.options.max_reconnects() {
+                self.log.push(format!(
+                    "Giving up on MCP event stream after {} reconnect attempts",
+                    self.options.max_reconnects()
+                ));
+                return;
+            }
+            tokio::time::sleep(self.options.reconnect_delay(failures, state.retry)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::client::McpClient;
+    use super::super::http::test_support::*;
+    use super::super::protocol::ToolContent;
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// 假 Streamable HTTP 服务器记录的请求
+    #[derive(Default)]
+    struct Server {
+        requests: StdMutex<Vec<Request>>,
+    }
+
+    impl Server {
+        fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn response(id: &serde_json::Value, result: serde_json::Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+    }
+
+    fn text_result(text: &str) -> serde_json::Value {
+        json!({ "content": [{ "type": "text", "text": text }] })
+    }
+
+    /// 启动假服务器
+    ///
+    /// - initialize 以 JSON 应答并分配会话
+    /// - `echo` 以事件流应答
+    /// - `resume` 先发一个带 ID 的进度通知后断开，响应在带 Last-Event-ID 的 GET 上补发
+    /// - 不带 Last-Event-ID 的 GET 返回 405
+    async fn start_server() -> (String, Arc<Server>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let server = Arc::new(Server::default());
+        let state = server.clone();
+        let pending_resume: Arc<StdMutex<Option<serde_json::Value>>> = Arc::default();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let state = state.clone();
+                let pending_resume = pending_resume.clone();
+                tokio::spawn(async move {
+                    let Some(request) = read_request(&mut socket).await else {
+                        return;
+                    };
+                    state.requests.lock().unwrap().push(request.clone());
+                    if request.header("x-api-key") != Some("http-key") {
+                        respond(&mut socket, "401 Unauthorized", &[], "bad key").await;
+                        return;
+                    }
+
+                    match request.method.as_str() {
+                        "GET" => match request.header("last-event-id") {
+                            Some("e1") => {
+                                let id = pending_resume.lock().unwrap().take().unwrap();
+                                start_event_stream(&mut socket, &[]).await;
+                                send_event(
+                                    &mut socket,
+                                    Some("e2"),
+                                    "message",
+                                    &response(&id, text_result("resumed")),
+                                )
+                                .await;
+                            }
+                            _ => respond(&mut socket, "405 Method Not Allowed", &[], "").await,
+                        },
+                        "DELETE" => respond(&mut socket, "200 OK", &[], "").await,
+                        "POST" => {
+                            let message = request.json();
+                            let method = message["method"].as_str().unwrap_or_default();
+                            if method != "initialize"
+                                && request.header("mcp-session-id") != Some("session-1")
+                            {
+                                respond(&mut socket, "400 Bad Request", &[], "no session").await;
+                                return;
+                            }
+                            let id = message["id"].clone();
+                            match method {
+                                "initialize" => {
+                                    let body = response(
+                                        &id,
+                                        json!({
+                                            "protocolVersion": "2025-03-26",
+                                            "capabilities": { "tools": {} },
+                                            "serverInfo": { "name": "http-server", "version": "1.0" }
+                                        }),
+                                    );
+                                    respond_json(
+                                        &mut socket,
+                                        &[("Mcp-Session-Id", "session-1")],
+                                        &body,
+                                    )
+                                    .await;
+                                }
+                                "tools/call" => {
+                                    let tool = message["params"]["name"].as_str().unwrap();
+                                    start_event_stream(&mut socket, &[]).await;
+                                    if tool == "resume" {
+                                        *pending_resume.lock().unwrap() = Some(id);
+                                        send_event(
+                                            &mut socket,
+                                            Some("e1"),
+                                            "message",
+                                            &json!({
+                                                "jsonrpc": "2.0",
+                                                "method": "notifications/progress",
+                                                "params": { "progress": 1 }
+                                            })
+                                            .to_string(),
+                                        )
+                                        .await;
+                                    } else {
+                                        let text = message["params"]["arguments"]["message"]
+                                            .as_str()
+                                            .unwrap();
+                                        send_event(
+                                            &mut socket,
+                                            None,
+                                            "message",
+                                            &response(&id, text_result(text)),
+                                        )
+                                        .await;
+                                    }
+                                }
+                                _ => respond(&mut socket, "202 Accepted", &[], "").await,
+                            }
+                        }
+                        _ => respond(&mut socket, "405 Method Not Allowed", &[], "").await,
+                    }
+                });
+            }
+        });
+        (url, server)
+    }
+
+    fn config(url: &str) -> McpHttpServerConfig {
+        std::env::set_var("KODE_TEST_MCP_HTTP_KEY", "http-key");
+        McpHttpServerConfig {
+            url: url.to_string(),
+            headers: Some(HashMap::from([(
+                "X-Api-Key".to_string(),
+                "${KODE_TEST_MCP_HTTP_KEY}".to_string(),
+            )])),
+        }
+    }
+
+    fn options() -> HttpTransportOptions {
+        HttpTransportOptions::new().with_reconnect(3, Duration::from_millis(20))
+    }
+
+    fn text(content: &[ToolContent]) -> &str {
+        match &content[0] {
+            ToolContent::Text { text } => text,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_and_streamed_responses() {
+        let (url, server) = start_server().await;
+        let transport = Arc::new(StreamableHttpTransport::new(&config(&url), &options()).unwrap());
+        let client = McpClient::connect("http", transport.clone()).await.unwrap();
+        assert_eq!(transport.session_id().as_deref(), Some("session-1"));
+        assert_eq!(client.server().unwrap().protocol_version, "2025-03-26");
+
+        let result = client
+            .call_tool("echo", json!({ "message": "streamed" }))
+            .await
+            .unwrap();
+        assert_eq!(text(&result.content), "streamed");
+
+        client.close().await.unwrap();
+        assert_eq!(transport.session_id(), None);
+        let requests = server.requests();
+        let delete = requests.iter().find(|r| r.method == "DELETE").unwrap();
+        assert_eq!(delete.header("mcp-session-id"), Some("session-1"));
+    }
+
+    #[tokio::test]
+    async fn test_resumes_interrupted_response_stream() {
+        let (url, server) = start_server().await;
+        let transport = Arc::new(StreamableHttpTransport::new(&config(&url), &options()).unwrap());
+        let client = McpClient::connect("http", transport).await.unwrap();
+        let mut notifications = client.subscribe();
+
+        let result = client.call_tool("resume", json!({})).await.unwrap();
+        assert_eq!(text(&result.content), "resumed");
+        assert_eq!(
+            notifications.recv().await.unwrap().method,
+            "notifications/progress"
+        );
+
+        let resumed = server
+            .requests()
+            .into_iter()
+            .find(|r| r.header("last-event-id").is_some())
+            .unwrap();
+        assert_eq!(resumed.method, "GET");
+        assert_eq!(resumed.header("last-event-id"), Some("e1"));
+        assert_eq!(resumed.header("mcp-session-id"), Some("session-1"));
+        assert!(client.diagnostics().contains("Resuming response stream"));
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        // 假服务器同时充当 HTTP 代理：请求行中是完整的目标 URL
+        let (url, server) = start_server().await;
+        let proxy = url.trim_end_matches("/mcp").to_string();
+        let options = options().with_global_config(&kode_core::config::GlobalConfig {
+            proxy: Some(proxy),
+            ..Default::default()
+        });
+        let transport = Arc::new(
+            StreamableHttpTransport::new(&config("http://mcp.invalid/mcp"), &options).unwrap(),
+        );
+        let client = McpClient::connect("proxied", transport).await.unwrap();
+        assert_eq!(client.server().unwrap().server_info.name, "http-server");
+
+        let first = server.requests().into_iter().next().unwrap();
+        assert_eq!(first.target, "http://mcp.invalid/mcp");
+        assert_eq!(first.path(), "/mcp");
+    }
+
+    #[tokio::test]
+    async fn test_http_errors_are_reported() {
+        let (url, _server) = start_server().await;
+        let config = McpHttpServerConfig { url, headers: None };
+        let transport = Arc::new(StreamableHttpTransport::new(&config, &options()).unwrap());
+        let err = McpClient::connect("http", transport).await.err().unwrap();
+        let message = err.to_string();
+        assert!(message.contains("HTTP 401"), "{}", message);
+        assert!(message.contains("bad key"), "{}", message);
+    }
+}
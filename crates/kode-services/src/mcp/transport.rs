@@ -0,0 +1,55 @@
+//! MCP 传输层抽象
+//!
+//! 客户端只依赖 [`McpTransport`]，具体的进程 stdio、SSE 等传输各自实现。
+
+use super::protocol::JsonRpcMessage;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// MCP 消息传输
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// 发送一条消息
+    async fn send(&self, message: &JsonRpcMessage) -> Result<()>;
+
+    /// 接收下一条消息，连接关闭时返回 `None`
+    ///
+    /// 同一时刻只应有一个接收者。
+    async fn receive(&self) -> Option<JsonRpcMessage>;
+
+    /// 关闭连接并释放底层资源
+    async fn close(&self) -> Result<()>;
+
+    /// 用于诊断的附加输出（例如服务器进程的 stderr）
+    fn diagnostics(&self) -> String {
+        String::new()
+    }
+}
+
+/// 诊断日志保留的行数上限
+const MAX_DIAGNOSTIC_LINES: usize = 200;
+
+/// 有界的诊断日志，保留最近的若干行
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticLog {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl DiagnosticLog {
+    /// 追加一行，超出上限时丢弃最早的行
+    pub fn push(&self, line: impl Into<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == MAX_DIAGNOSTIC_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.into());
+    }
+
+    /// 全部内容，按行连接
+    pub fn contents(&self) -> String {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().cloned().collect::<Vec<_>>().join("\n")
+    }
+}
@@ -0,0 +1,408 @@
+//! 基于 stdio 假服务器的 MCP 客户端集成测试
+//!
+//! 假服务器是本 crate 的 `fake_mcp_server` 二进制（src/bin/fake_mcp_server.rs），
+//! 通过 `CARGO_BIN_EXE_fake_mcp_server` 定位。
+
+use kode_core::config::types::{McpServerConfig, McpStdioServerConfig};
+use kode_core::config::{McpServerScope, McprcApproval, ScopedMcpServer};
+use kode_services::mcp::protocol::{CallToolResult, ToolContent, MCP_PROTOCOL_VERSION};
+use kode_services::mcp::{
+    load_context_uris, McpClient, McpClientProvider, McpManager, McpServerStatus, RestartPolicy,
+};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 假服务器的启动配置，通过环境变量传入问候语
+fn fake_server() -> McpStdioServerConfig {
+    McpStdioServerConfig {
+        command: env!("CARGO_BIN_EXE_fake_mcp_server").to_string(),
+        args: vec![],
+        env: Some(HashMap::from([(
+            "FAKE_MCP_GREETING".to_string(),
+            "hello from env".to_string(),
+        )])),
+    }
+}
+
+async fn connect() -> McpClient {
+    McpClient::connect_stdio("fake", &fake_server())
+        .await
+        .unwrap()
+}
+
+fn text(result: &CallToolResult) -> String {
+    result
+        .content
+        .iter()
+        .filter_map(|c| match c {
+            ToolContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_initialize_handshake() {
+    let client = connect().await;
+    let server = client.server().unwrap();
+    assert_eq!(server.server_info.name, "fake-mcp-server");
+    assert_eq!(server.protocol_version, MCP_PROTOCOL_VERSION);
+    assert!(client.capabilities().unwrap().tools.is_some());
+    assert!(client.capabilities().unwrap().prompts.is_none());
+
+    // 服务器在收到 initialized 通知后才会回显环境变量
+    let result = client.call_tool("greeting", json!({})).await.unwrap();
+    assert_eq!(text(&result), "hello from env (initialized)");
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_tools_follows_pagination() {
+    let client = connect().await;
+    let tools = client.list_tools().await.unwrap();
+    let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "echo",
+            "greeting",
+            "fail",
+            "notify",
+            "sleep",
+            "cancelled",
+            "crash"
+        ]
+    );
+    assert_eq!(tools[0].input_schema["required"], json!(["message"]));
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_call_tool_results_and_errors() {
+    let client = connect().await;
+
+    let result = client
+        .call_tool("echo", json!({ "message": "hi" }))
+        .await
+        .unwrap();
+    assert!(!result.is_error);
+    assert_eq!(text(&result), "hi");
+
+    let result = client.call_tool("fail", json!({})).await.unwrap();
+    assert!(result.is_error);
+    assert_eq!(text(&result), "tool failed on purpose");
+
+    let err = client.call_tool("missing", json!({})).await.unwrap_err();
+    assert!(err.to_string().contains("Unknown tool: missing"), "{}", err);
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_notifications_and_server_requests() {
+    let client = connect().await;
+    let mut notifications = client.subscribe();
+
+    // notify 工具先向客户端发 ping 请求，收到应答后再发通知
+    let result = client.call_tool("notify", json!({})).await.unwrap();
+    assert_eq!(text(&result), "client answered ping");
+
+    let notification = notifications.recv().await.unwrap();
+    assert_eq!(notification.method, "notifications/tools/list_changed");
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_resources_list_read_and_subscribe() {
+    let client = connect().await;
+
+    let resources = client.list_resources().await.unwrap();
+    let uris: Vec<_> = resources.iter().map(|r| r.uri.as_str()).collect();
+    assert_eq!(uris, vec!["fake://readme", "fake://counter", "fake://logo"]);
+    assert_eq!(resources[0].mime_type.as_deref(), Some("text/markdown"));
+
+    let readme = client.read_resource("fake://readme").await.unwrap();
+    assert_eq!(readme.to_text(), "# Fake project");
+    let logo = client.read_resource("fake://logo").await.unwrap();
+    assert_eq!(logo.contents[0].blob.as_deref(), Some("iVBORw0K"));
+    assert_eq!(logo.to_text(), "[Binary resource: fake://logo (image/png)]");
+    let err = client.read_resource("fake://missing").await.unwrap_err();
+    assert!(err.to_string().contains("Resource not found"), "{}", err);
+
+    let mut notifications = client.subscribe();
+    client.subscribe_resource("fake://counter").await.unwrap();
+    let notification = notifications.recv().await.unwrap();
+    assert_eq!(notification.method, "notifications/resources/updated");
+    assert_eq!(notification.params.unwrap()["uri"], "fake://counter");
+    let counter = client.read_resource("fake://counter").await.unwrap();
+    assert_eq!(counter.to_text(), "1");
+    client.unsubscribe_resource("fake://counter").await.unwrap();
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_request_timeout_cancels_and_recovers() {
+    let client = connect()
+        .await
+        .with_request_timeout(Duration::from_millis(200));
+
+    let err = client
+        .call_tool("sleep", json!({ "ms": 600 }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("timed out after 200ms"), "{}", err);
+
+    // 迟到的响应被丢弃，后续请求不受影响
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let client = client.with_request_timeout(Duration::from_secs(5));
+    let result = client
+        .call_tool("echo", json!({ "message": "still alive" }))
+        .await
+        .unwrap();
+    assert_eq!(text(&result), "still alive");
+
+    // 超时的请求（ID 2，ID 1 为 initialize）已通知服务器取消
+    let result = client.call_tool("cancelled", json!({})).await.unwrap();
+    assert_eq!(text(&result), "2");
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_crash_reports_stderr() {
+    let client = connect().await;
+    assert!(!client.is_closed());
+    let err = client.call_tool("crash", json!({})).await.unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("closed the connection"), "{}", message);
+    assert!(message.contains("fake server crashing"), "{}", message);
+    assert!(client.diagnostics().contains("fake server starting"));
+    tokio::time::timeout(Duration::from_secs(5), client.closed())
+        .await
+        .unwrap();
+    assert!(client.is_closed());
+
+    // 关闭后的请求立即失败
+    let err = client.list_tools().await.unwrap_err();
+    assert!(err.to_string().contains("closed the connection"));
+}
+
+#[tokio::test]
+async fn test_close_terminates_server() {
+    let client = connect().await;
+    client.close().await.unwrap();
+    assert!(client.is_closed());
+    let err = client
+        .call_tool("echo", json!({ "message": "x" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("closed"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_load_context_uris() {
+    let client = Arc::new(
+        McpClient::connect_stdio("fake", &fake_server())
+            .await
+            .unwrap(),
+    );
+    let uris = vec!["fake://readme".to_string(), "fake://missing".to_string()];
+
+    let context = load_context_uris(std::slice::from_ref(&client), &uris).await;
+    assert_eq!(
+        context.entries,
+        HashMap::from([("fake://readme".to_string(), "# Fake project".to_string())])
+    );
+    assert_eq!(context.errors.len(), 1);
+    assert!(context.errors[0].contains("fake://missing"));
+    assert!(context.errors[0].contains("Resource not found"));
+
+    let context = load_context_uris(&[], &uris).await;
+    assert!(context.entries.is_empty());
+    assert!(context.errors[0].contains("no connected MCP server"));
+    client.close().await.unwrap();
+}
+
+fn stdio(command: &str, args: &[&str]) -> McpServerConfig {
+    McpServerConfig::Stdio(McpStdioServerConfig {
+        command: command.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        env: None,
+    })
+}
+
+fn server(
+    scope: McpServerScope,
+    config: McpServerConfig,
+    approval: McprcApproval,
+) -> ScopedMcpServer {
+    ScopedMcpServer {
+        scope,
+        config,
+        approval,
+    }
+}
+
+fn status_of(manager: &McpManager, name: &str) -> McpServerStatus {
+    manager
+        .status()
+        .into_iter()
+        .find(|state| state.name == name)
+        .unwrap()
+        .status
+}
+
+async fn wait_for(manager: &McpManager, name: &str, done: impl Fn(&McpServerStatus) -> bool) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !done(&status_of(manager, name)) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("{}: {}", name, status_of(manager, name)));
+}
+
+#[tokio::test]
+async fn test_start_status_and_shutdown() {
+    let servers = BTreeMap::from([
+        (
+            "fake".to_string(),
+            server(
+                McpServerScope::Project,
+                McpServerConfig::Stdio(fake_server()),
+                McprcApproval::Approved,
+            ),
+        ),
+        (
+            "missing".to_string(),
+            server(
+                McpServerScope::Global,
+                stdio("/nonexistent/kode-mcp-server", &[]),
+                McprcApproval::Approved,
+            ),
+        ),
+        (
+            "silent".to_string(),
+            server(
+                McpServerScope::Global,
+                stdio("sleep", &["10"]),
+                McprcApproval::Approved,
+            ),
+        ),
+        (
+            "unapproved".to_string(),
+            server(
+                McpServerScope::Mcprc,
+                stdio("/nonexistent/unapproved", &[]),
+                McprcApproval::Pending,
+            ),
+        ),
+        (
+            "rejected".to_string(),
+            server(
+                McpServerScope::Mcprc,
+                stdio("/nonexistent/rejected", &[]),
+                McprcApproval::Rejected,
+            ),
+        ),
+    ]);
+    let manager =
+        Arc::new(McpManager::new(servers).with_connect_timeout(Duration::from_millis(500)));
+    assert_eq!(status_of(&manager, "fake"), McpServerStatus::Pending);
+
+    let started = Instant::now();
+    manager.start().await;
+    // 并行启动：总耗时由最慢的服务器（超时）决定
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let states = manager.status();
+    let names: Vec<_> = states.iter().map(|state| state.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["fake", "missing", "rejected", "silent", "unapproved"]
+    );
+    assert_eq!(states[0].status, McpServerStatus::Connected);
+    assert_eq!(states[0].scope, McpServerScope::Project);
+    assert_eq!(states[0].transport, "stdio");
+    assert_eq!(
+        states[0].server_info.as_ref().unwrap().name,
+        "fake-mcp-server"
+    );
+    let McpServerStatus::Failed { error } = &states[1].status else {
+        panic!("{:?}", states[1].status);
+    };
+    assert!(error.contains("Failed to start MCP server"), "{}", error);
+    assert_eq!(states[2].status, McpServerStatus::Rejected);
+    let McpServerStatus::Failed { error } = &states[3].status else {
+        panic!("{:?}", states[3].status);
+    };
+    assert!(error.contains("Timed out connecting"), "{}", error);
+    assert_eq!(states[4].status, McpServerStatus::PendingApproval);
+    assert_eq!(states[4].status.to_string(), "pending approval");
+
+    let clients = manager.clients();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].name(), "fake");
+
+    manager.shutdown().await;
+    assert!(clients[0].is_closed());
+    assert!(manager.clients().is_empty());
+    assert_eq!(status_of(&manager, "fake"), McpServerStatus::Stopped);
+    assert!(matches!(
+        status_of(&manager, "missing"),
+        McpServerStatus::Failed { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_restarts_crashed_stdio_server() {
+    let servers = BTreeMap::from([(
+        "fake".to_string(),
+        server(
+            McpServerScope::Global,
+            McpServerConfig::Stdio(fake_server()),
+            McprcApproval::Approved,
+        ),
+    )]);
+    let manager = Arc::new(McpManager::new(servers).with_restart_policy(RestartPolicy {
+        max_restarts: 1,
+        initial_delay: Duration::from_millis(10),
+        ..Default::default()
+    }));
+    manager.start().await;
+
+    let first = manager.client("fake").unwrap();
+    assert!(first.call_tool("crash", json!({})).await.is_err());
+    wait_for(&manager, "fake", |status| {
+        *status == McpServerStatus::Connected && manager.client("fake").is_some()
+    })
+    .await;
+
+    let second = manager.client("fake").unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    let result = second
+        .call_tool("echo", json!({ "message": "back" }))
+        .await
+        .unwrap();
+    assert!(!result.is_error);
+    assert_eq!(manager.status()[0].restarts, 1);
+
+    // 超过最大重启次数后放弃
+    assert!(second.call_tool("crash", json!({})).await.is_err());
+    wait_for(&manager, "fake", |status| {
+        matches!(status, McpServerStatus::Failed { .. })
+    })
+    .await;
+    let McpServerStatus::Failed { error } = status_of(&manager, "fake") else {
+        unreachable!()
+    };
+    assert!(error.contains("fake server crashing"), "{}", error);
+    assert!(
+        error.contains("giving up after 1 restart attempts"),
+        "{}",
+        error
+    );
+    assert!(manager.clients().is_empty());
+
+    manager.shutdown().await;
+}
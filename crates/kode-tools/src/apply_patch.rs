@@ -0,0 +1,1212 @@
+//! ApplyPatch 工具
+//!
+//! 应用 unified diff 或 `*** Begin Patch` 信封格式的多文件补丁，
+//! 适合更擅长输出补丁而不是精确字符串替换的模型。
+//!
+//! 每个 hunk 按上下文定位：先精确匹配，再依次忽略行尾空白、首尾空白，
+//! 仍找不到时去掉 hunk 两端最多 [`MAX_FUZZ`] 行上下文重试。
+//! 所有文件的新内容先在内存中计算，任何 hunk 失败都不会写入；
+//! 写入过程中出错会恢复已经写入的文件。
+
+use crate::file_utils::{self, normalize_path, LineEnding};
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::permission::{AccessKind, PermissionRequest, PermissionTarget};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// ApplyPatch 工具名
+pub const APPLY_PATCH_TOOL_NAME: &str = "ApplyPatch";
+
+/// 定位失败时最多去掉的两端上下文行数
+pub const MAX_FUZZ: usize = 2;
+
+/// 错误信息中最多展示的 hunk 行数
+const MAX_REPORTED_LINES: usize = 8;
+
+const DESCRIPTION: &str = "Apply a patch to one or more files atomically. Accepts a unified \
+diff (as produced by `git diff` or `diff -u`) or the envelope format:\n\
+*** Begin Patch\n\
+*** Add File: path/to/new.rs\n\
++line\n\
+*** Update File: path/to/file.rs\n\
+*** Move to: path/to/renamed.rs (optional)\n\
+@@ optional line to anchor the hunk, e.g. a function signature\n\
+ context line\n\
+-removed line\n\
++added line\n\
+*** Delete File: path/to/old.rs\n\
+*** End Patch\n\
+Hunks are located by their context lines, so line numbers may be approximate. Include about \
+three lines of context around each change. If any hunk fails, no file is modified.";
+
+/// hunk 中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    /// 上下文（新旧内容中都存在）
+    Context(String),
+    /// 删除的行
+    Remove(String),
+    /// 新增的行
+    Add(String),
+}
+
+/// 一个 hunk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hunk {
+    /// unified diff 中旧文件的起始行（从 1 开始），用于在多处匹配时选择最近的一处
+    pub old_start: Option<usize>,
+    /// 信封格式中 `@@` 后的锚点行，hunk 从锚点之后开始查找
+    pub anchor: Option<String>,
+    /// hunk 内容
+    pub lines: Vec<HunkLine>,
+    /// 必须匹配文件末尾（`*** End of File`）
+    pub end_of_file: bool,
+    /// 旧内容末尾没有换行
+    pub old_no_newline: bool,
+    /// 新内容末尾没有换行
+    pub new_no_newline: bool,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// 去掉两端最多 `fuzz` 行上下文，返回去掉的前导行数和剩余的行
+    fn trimmed(&self, fuzz: usize) -> (usize, &[HunkLine]) {
+        let leading = self
+            .lines
+            .iter()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count();
+        let trailing = self
+            .lines
+            .iter()
+            .rev()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count();
+        let start = leading.min(fuzz);
+        let end = self.lines.len() - trailing.min(fuzz);
+        if start >= end {
+            return (0, &self.lines);
+        }
+        (start, &self.lines[start..end])
+    }
+
+    /// 用于错误信息的描述
+    fn label(&self, index: usize) -> String {
+        match (&self.anchor, self.old_start) {
+            (Some(anchor), _) => format!("hunk {} (@@ {})", index + 1, anchor),
+            (None, Some(line)) => format!("hunk {} (line {})", index + 1, line),
+            (None, None) => format!("hunk {}", index + 1),
+        }
+    }
+}
+
+/// 单个文件的修改
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// 新建文件
+    Add {
+        /// 文件路径
+        path: String,
+        /// 文件内容
+        content: String,
+    },
+    /// 删除文件
+    Delete {
+        /// 文件路径
+        path: String,
+    },
+    /// 修改（并可选地重命名）文件
+    Update {
+        /// 文件路径
+        path: String,
+        /// 重命名后的路径
+        move_to: Option<String>,
+        /// 要应用的 hunk
+        hunks: Vec<Hunk>,
+    },
+}
+
+impl FileChange {
+    /// 涉及的所有路径
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            FileChange::Add { path, .. } | FileChange::Delete { path } => vec![path],
+            FileChange::Update { path, move_to, .. } => {
+                let mut paths = vec![path.as_str()];
+                paths.extend(move_to.as_deref());
+                paths
+            }
+        }
+    }
+}
+
+/// 解析补丁
+///
+/// 以 `*** Begin Patch` 开头的按信封格式解析，否则按 unified diff 解析。
+pub fn parse_patch(patch: &str) -> Result<Vec<FileChange>> {
+    let changes = if patch.trim_start().starts_with("*** Begin Patch") {
+        parse_envelope(patch)?
+    } else {
+        parse_unified(patch)?
+    };
+    if changes.is_empty() {
+        bail!("Patch contains no file changes");
+    }
+    Ok(changes)
+}
+
+/// 解析 hunk 内容行，返回是否识别
+fn push_hunk_line(hunk: &mut Hunk, line: &str) -> bool {
+    match line.chars().next() {
+        Some(' ') => hunk.lines.push(HunkLine::Context(line[1..].to_string())),
+        Some('-') => hunk.lines.push(HunkLine::Remove(line[1..].to_string())),
+        Some('+') => hunk.lines.push(HunkLine::Add(line[1..].to_string())),
+        // 有些工具会去掉空上下文行的前导空格
+        None => hunk.lines.push(HunkLine::Context(String::new())),
+        Some('\\') => match hunk.lines.last() {
+            Some(HunkLine::Remove(_)) => hunk.old_no_newline = true,
+            Some(HunkLine::Add(_)) => hunk.new_no_newline = true,
+            Some(HunkLine::Context(_)) => {
+                hunk.old_no_newline = true;
+                hunk.new_no_newline = true;
+            }
+            None => {}
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn parse_envelope(patch: &str) -> Result<Vec<FileChange>> {
+    let mut lines = patch
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .peekable();
+    while lines.peek().is_some_and(|line| line.trim().is_empty()) {
+        lines.next();
+    }
+    lines.next(); // *** Begin Patch
+
+    let mut changes = Vec::new();
+    let mut ended = false;
+    while let Some(line) = lines.next() {
+        if line.trim() == "*** End Patch" {
+            ended = true;
+            break;
+        }
+        if let Some(path) = line.strip_prefix("*** Add File: ") {
+            let mut content = String::new();
+            while let Some(next) = lines.next_if(|l| !l.starts_with("*** ")) {
+                let text = next.strip_prefix('+').ok_or_else(|| {
+                    anyhow!("Line in added file {} must start with '+': {}", path, next)
+                })?;
+                content.push_str(text);
+                content.push('\n');
+            }
+            changes.push(FileChange::Add {
+                path: path.trim().to_string(),
+                content,
+            });
+        } else if let Some(path) = line.strip_prefix("*** Delete File: ") {
+            changes.push(FileChange::Delete {
+                path: path.trim().to_string(),
+            });
+        } else if let Some(path) = line.strip_prefix("*** Update File: ") {
+            let move_to = lines
+                .next_if(|l| l.starts_with("*** Move to: "))
+                .map(|l| l["*** Move to: ".len()..].trim().to_string());
+            let mut hunks: Vec<Hunk> = Vec::new();
+            while let Some(next) =
+                lines.next_if(|l| !l.starts_with("*** ") || l.trim() == "*** End of File")
+            {
+                if next.trim() == "*** End of File" {
+                    if let Some(hunk) = hunks.last_mut() {
+                        hunk.end_of_file = true;
+                    }
+                    continue;
+                }
+                if let Some(anchor) = next.strip_prefix("@@") {
+                    let anchor = anchor.trim();
+                    hunks.push(Hunk {
+                        anchor: (!anchor.is_empty()).then(|| anchor.to_string()),
+                        ..Default::default()
+                    });
+                    continue;
+                }
+                if hunks.is_empty() {
+                    hunks.push(Hunk::default());
+                }
+                let hunk = hunks.last_mut().expect("pushed above");
+                if !push_hunk_line(hunk, next) {
+                    bail!("Invalid line in update of {}: {}", path, next);
+                }
+            }
+            hunks.retain(|hunk| !hunk.lines.is_empty());
+            if hunks.is_empty() && move_to.is_none() {
+                bail!("Update of {} contains no hunks", path);
+            }
+            changes.push(FileChange::Update {
+                path: path.trim().to_string(),
+                move_to,
+                hunks,
+            });
+        } else if !line.trim().is_empty() {
+            bail!("Unexpected line in patch: {}", line);
+        }
+    }
+    if !ended {
+        bail!("Patch is missing `*** End Patch`");
+    }
+    Ok(changes)
+}
+
+/// `--- a/path\t2024-01-01` 中的路径
+fn diff_path(header: &str, prefix: &str) -> String {
+    let path = header.split('\t').next().unwrap_or(header).trim();
+    path.strip_prefix(prefix).unwrap_or(path).to_string()
+}
+
+/// 解析 `@@ -l,s +l,s @@`，返回旧起始行和新旧行数
+fn parse_range_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.strip_prefix("@@ ")?.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let part = part?.strip_prefix(sign)?;
+        match part.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = range(parts.next(), '-')?;
+    let (_, new_len) = range(parts.next(), '+')?;
+    Some((old_start, old_len, new_len))
+}
+
+fn parse_unified(patch: &str) -> Result<Vec<FileChange>> {
+    let lines: Vec<&str> = patch.lines().map(|l| l.trim_end_matches('\r')).collect();
+    let mut changes = Vec::new();
+    let mut rename: (Option<String>, Option<String>) = (None, None);
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("diff --git ") {
+            // 上一段只有重命名、没有 hunk
+            if let (Some(from), Some(to)) = rename.clone() {
+                changes.push(pure_rename(from, to));
+            }
+            rename = (None, None);
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            rename.0 = Some(from.to_string());
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            rename.1 = Some(to.to_string());
+        } else if line.starts_with("--- ")
+            && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+        {
+            let old = diff_path(&line[4..], "a/");
+            let new = diff_path(&lines[i + 1][4..], "b/");
+            rename = (None, None);
+            i += 2;
+
+            let mut hunks = Vec::new();
+            while i < lines.len() && lines[i].starts_with("@@") {
+                let header = lines[i];
+                let range = parse_range_header(header);
+                let mut hunk = Hunk {
+                    old_start: range.map(|(start, _, _)| start),
+                    ..Default::default()
+                };
+                i += 1;
+                let (mut old_left, mut new_left) =
+                    range.map_or((usize::MAX, usize::MAX), |(_, o, n)| (o, n));
+                while i < lines.len()
+                    && (old_left > 0 || new_left > 0 || lines[i].starts_with('\\'))
+                {
+                    let line = lines[i];
+                    if range.is_none()
+                        && (line.starts_with("@@")
+                            || line.starts_with("--- ")
+                            || line.starts_with("diff "))
+                    {
+                        break;
+                    }
+                    if !push_hunk_line(&mut hunk, line) {
+                        if range.is_none() {
+                            break;
+                        }
+                        bail!("Invalid line in hunk `{}` of {}: {}", header, new, line);
+                    }
+                    match hunk.lines.last() {
+                        _ if line.starts_with('\\') => {}
+                        Some(HunkLine::Context(_)) => {
+                            old_left = old_left.saturating_sub(1);
+                            new_left = new_left.saturating_sub(1);
+                        }
+                        Some(HunkLine::Remove(_)) => old_left = old_left.saturating_sub(1),
+                        Some(HunkLine::Add(_)) => new_left = new_left.saturating_sub(1),
+                        None => {}
+                    }
+                    i += 1;
+                }
+                if old_left != usize::MAX && (old_left > 0 || new_left > 0) {
+                    bail!("Hunk `{}` of {} is truncated", header, new);
+                }
+                hunks.push(hunk);
+            }
+
+            let change = if old == "/dev/null" {
+                let mut content = String::new();
+                for hunk in &hunks {
+                    for line in &hunk.lines {
+                        if let HunkLine::Add(text) = line {
+                            content.push_str(text);
+                            content.push('\n');
+                        }
+                    }
+                }
+                if hunks.iter().any(|hunk| hunk.new_no_newline) {
+                    content.pop();
+                }
+                FileChange::Add { path: new, content }
+            } else if new == "/dev/null" {
+                FileChange::Delete { path: old }
+            } else {
+                FileChange::Update {
+                    move_to: (old != new).then(|| new.clone()),
+                    path: old,
+                    hunks,
+                }
+            };
+            changes.push(change);
+            continue;
+        }
+        i += 1;
+    }
+    if let (Some(from), Some(to)) = rename {
+        changes.push(pure_rename(from, to));
+    }
+    Ok(changes)
+}
+
+fn pure_rename(from: String, to: String) -> FileChange {
+    FileChange::Update {
+        path: from,
+        move_to: Some(to),
+        hunks: Vec::new(),
+    }
+}
+
+/// 行比较的宽松程度
+#[derive(Clone, Copy)]
+enum Strictness {
+    Exact,
+    TrimEnd,
+    Trim,
+}
+
+impl Strictness {
+    fn eq(self, a: &str, b: &str) -> bool {
+        match self {
+            Strictness::Exact => a == b,
+            Strictness::TrimEnd => a.trim_end() == b.trim_end(),
+            Strictness::Trim => a.trim() == b.trim(),
+        }
+    }
+}
+
+/// 在 `lines[from..]` 中查找 `pattern`，有多处时返回离 `hint` 最近的一处
+fn find_sequence(
+    lines: &[String],
+    pattern: &[&str],
+    from: usize,
+    hint: Option<usize>,
+    end_of_file: bool,
+) -> Option<usize> {
+    if pattern.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - pattern.len();
+    if from > last {
+        return None;
+    }
+    for strictness in [Strictness::Exact, Strictness::TrimEnd, Strictness::Trim] {
+        let matches = |start: usize| {
+            pattern
+                .iter()
+                .zip(&lines[start..])
+                .all(|(p, l)| strictness.eq(l, p))
+        };
+        if end_of_file {
+            if matches(last) {
+                return Some(last);
+            }
+            continue;
+        }
+        let found = (from..=last)
+            .filter(|&start| matches(start))
+            .min_by_key(|&start| hint.map_or(0, |hint| start.abs_diff(hint)));
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// hunk 应用失败
+#[derive(Debug)]
+struct HunkError {
+    label: String,
+    reason: String,
+}
+
+/// 把 hunk 应用到文本（LF 换行），返回新文本
+///
+/// 失败的 hunk 被跳过并收集起来，以便一次报告所有问题。
+fn apply_hunks(content: &str, hunks: &[Hunk]) -> std::result::Result<String, Vec<HunkError>> {
+    let mut trailing_newline = content.is_empty() || content.ends_with('\n');
+    let body = content.strip_suffix('\n').unwrap_or(content);
+    let mut lines: Vec<String> = if body.is_empty() && content.len() <= 1 {
+        Vec::new()
+    } else {
+        body.split('\n').map(String::from).collect()
+    };
+
+    let mut errors = Vec::new();
+    let mut cursor = 0;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let fail = |reason: String| HunkError {
+            label: hunk.label(index),
+            reason,
+        };
+        let mut from = cursor;
+        if let Some(anchor) = &hunk.anchor {
+            match lines
+                .iter()
+                .skip(from)
+                .position(|line| line.trim() == anchor.trim())
+            {
+                Some(pos) => from += pos + 1,
+                None => {
+                    errors.push(fail(format!("anchor line not found: {}", anchor)));
+                    continue;
+                }
+            }
+        }
+        let hint = hunk.old_start.map(|line| line.saturating_sub(1));
+
+        if hunk.old_lines().is_empty() {
+            // 纯新增：插入到锚点之后、指定行之后或文件末尾
+            let at = match (&hunk.anchor, hunk.old_start) {
+                (Some(_), _) if !hunk.end_of_file => from,
+                (None, Some(line)) if !hunk.end_of_file => line.min(lines.len()),
+                _ => lines.len(),
+            };
+            let added: Vec<String> = hunk
+                .lines
+                .iter()
+                .filter_map(|line| match line {
+                    HunkLine::Add(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect();
+            cursor = at + added.len();
+            lines.splice(at..at, added);
+            if hunk.new_no_newline {
+                trailing_newline = false;
+            }
+            continue;
+        }
+
+        let located = (0..=MAX_FUZZ).find_map(|fuzz| {
+            let (skipped, trimmed) = hunk.trimmed(fuzz);
+            let pattern: Vec<&str> = trimmed
+                .iter()
+                .filter_map(|line| match line {
+                    HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                    HunkLine::Add(_) => None,
+                })
+                .collect();
+            find_sequence(
+                &lines,
+                &pattern,
+                from,
+                hint.map(|hint| hint + skipped),
+                hunk.end_of_file && fuzz == 0,
+            )
+            .map(|start| (start, trimmed))
+        });
+        let Some((start, trimmed)) = located else {
+            let old = hunk.old_lines();
+            let mut expected: Vec<String> = old
+                .iter()
+                .take(MAX_REPORTED_LINES)
+                .map(|line| format!("    {}", line))
+                .collect();
+            if old.len() > MAX_REPORTED_LINES {
+                expected.push(format!(
+                    "    ... ({} more lines)",
+                    old.len() - MAX_REPORTED_LINES
+                ));
+            }
+            errors.push(fail(format!(
+                "could not find these lines{}:\n{}",
+                if from > 0 {
+                    format!(" after line {}", from)
+                } else {
+                    String::new()
+                },
+                expected.join("\n")
+            )));
+            continue;
+        };
+
+        // 上下文行保留文件中的原文，只替换删除和新增的行
+        let mut replacement = Vec::new();
+        let mut offset = start;
+        for line in trimmed {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[offset].clone());
+                    offset += 1;
+                }
+                HunkLine::Remove(_) => offset += 1,
+                HunkLine::Add(text) => replacement.push(text.clone()),
+            }
+        }
+        let touches_end = offset == lines.len();
+        cursor = start + replacement.len();
+        lines.splice(start..offset, replacement);
+        if touches_end {
+            if hunk.new_no_newline {
+                trailing_newline = false;
+            } else if hunk.old_no_newline {
+                trailing_newline = true;
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut result = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Deserialize)]
+struct ApplyPatchParams {
+    patch: String,
+}
+
+/// 补丁中一个文件的最终状态
+struct Staged {
+    /// 新内容，`None` 表示删除
+    content: Option<String>,
+    /// 应用前的内容，`None` 表示原本不存在
+    original: Option<String>,
+}
+
+/// ApplyPatch 工具
+#[derive(Debug, Default)]
+pub struct ApplyPatchTool;
+
+impl ApplyPatchTool {
+    /// 创建新的 ApplyPatch 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 补丁中文件的当前内容（优先使用已暂存的内容）
+async fn current_content(
+    staged: &BTreeMap<PathBuf, Staged>,
+    path: &PathBuf,
+) -> Result<Option<String>> {
+    match staged.get(path) {
+        Some(entry) => Ok(entry.content.clone()),
+        None => file_utils::read_existing(path).await,
+    }
+}
+
+/// 暂存文件的新内容，首次暂存时记录原内容用于回滚
+async fn set(
+    staged: &mut BTreeMap<PathBuf, Staged>,
+    path: &PathBuf,
+    content: Option<String>,
+) -> Result<()> {
+    match staged.get_mut(path) {
+        Some(entry) => entry.content = content,
+        None => {
+            let original = file_utils::read_existing(path).await?;
+            staged.insert(path.clone(), Staged { content, original });
+        }
+    }
+    Ok(())
+}
+
+/// 在内存中计算所有文件的新内容
+///
+/// 任何文件或 hunk 失败都返回包含所有失败原因的错误。
+async fn stage(
+    changes: &[FileChange],
+    context: &ToolContext,
+) -> Result<(BTreeMap<PathBuf, Staged>, Vec<String>)> {
+    let mut staged = BTreeMap::new();
+    let mut summary = Vec::new();
+    let mut errors = Vec::new();
+
+    for change in changes {
+        for path in change.paths() {
+            file_utils::check_write_allowed(&context.resolve_path(path), context)?;
+        }
+        match change {
+            FileChange::Add { path, content } => {
+                let resolved = context.resolve_path(path);
+                if current_content(&staged, &resolved).await?.is_some() {
+                    errors.push(format!("{}: file already exists", path));
+                    continue;
+                }
+                set(&mut staged, &resolved, Some(content.clone())).await?;
+                summary.push(format!("A {}", path));
+            }
+            FileChange::Delete { path } => {
+                let resolved = context.resolve_path(path);
+                if current_content(&staged, &resolved).await?.is_none() {
+                    errors.push(format!("{}: file does not exist", path));
+                    continue;
+                }
+                set(&mut staged, &resolved, None).await?;
+                summary.push(format!("D {}", path));
+            }
+            FileChange::Update {
+                path,
+                move_to,
+                hunks,
+            } => {
+                let resolved = context.resolve_path(path);
+                let Some(original) = current_content(&staged, &resolved).await? else {
+                    errors.push(format!("{}: file does not exist", path));
+                    continue;
+                };
+                let line_ending = LineEnding::detect(&original);
+                let updated = match apply_hunks(&LineEnding::normalize(&original), hunks) {
+                    Ok(updated) => line_ending.apply(&updated),
+                    Err(failures) => {
+                        errors.extend(failures.into_iter().map(|failure| {
+                            format!("{}: {} failed: {}", path, failure.label, failure.reason)
+                        }));
+                        continue;
+                    }
+                };
+                match move_to {
+                    Some(target) if context.resolve_path(target) != resolved => {
+                        let target_resolved = context.resolve_path(target);
+                        if current_content(&staged, &target_resolved).await?.is_some() {
+                            errors
+                                .push(format!("{}: rename target {} already exists", path, target));
+                            continue;
+                        }
+                        set(&mut staged, &resolved, None).await?;
+                        set(&mut staged, &target_resolved, Some(updated)).await?;
+                        summary.push(format!("R {} -> {}", path, target));
+                    }
+                    _ => {
+                        set(&mut staged, &resolved, Some(updated)).await?;
+                        summary.push(format!("M {}", path));
+                    }
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Patch was not applied; no files were changed.\n{}",
+            errors
+                .iter()
+                .map(|error| format!("- {}", error))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+    Ok((staged, summary))
+}
+
+/// 写入或删除单个文件
+async fn write_staged(path: &PathBuf, content: Option<&str>) -> Result<()> {
+    match content {
+        Some(content) => file_utils::write_atomic(path, content).await,
+        None => match tokio::fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete {}", path.display())),
+        },
+    }
+}
+
+/// 写入所有暂存的文件，出错时恢复已经写入的文件
+async fn commit(staged: &BTreeMap<PathBuf, Staged>) -> Result<()> {
+    let mut written = Vec::new();
+    for (path, entry) in staged {
+        if entry.content == entry.original {
+            continue;
+        }
+        if let Err(e) = write_staged(path, entry.content.as_deref()).await {
+            for (path, original) in written.into_iter().rev() {
+                let _ = write_staged(path, original).await;
+            }
+            return Err(e.context("Patch was rolled back"));
+        }
+        written.push((path, entry.original.as_deref()));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        APPLY_PATCH_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "patch": {
+                        "type": "string",
+                        "description": "The unified diff or `*** Begin Patch` envelope to apply"
+                    }
+                },
+                "required": ["patch"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: ApplyPatchParams =
+            serde_json::from_value(params).context("Invalid ApplyPatch parameters")?;
+        let changes = parse_patch(&params.patch)?;
+        let (staged, summary) = stage(&changes, context).await?;
+        commit(&staged).await?;
+        for path in staged.keys() {
+            file_utils::record_write(path, context);
+        }
+
+        Ok(ToolResult::new(format!(
+            "Applied {} change(s):\n{}",
+            summary.len(),
+            summary.join("\n")
+        )))
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    fn permission_request(&self, params: &Value, context: &ToolContext) -> PermissionRequest {
+        let request = PermissionRequest::new(self.name(), AccessKind::Edit);
+        let Some(changes) = params
+            .get("patch")
+            .and_then(Value::as_str)
+            .and_then(|patch| parse_patch(patch).ok())
+        else {
+            return request;
+        };
+        let mut paths: Vec<PathBuf> = changes
+            .iter()
+            .flat_map(FileChange::paths)
+            .map(|path| normalize_path(&context.resolve_path(path)))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        match paths.len() {
+            1 => request.with_target(PermissionTarget::Path(paths.remove(0))),
+            _ => request.with_target(PermissionTarget::Paths(paths)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn update(hunks: &[Hunk], content: &str) -> String {
+        apply_hunks(content, hunks).unwrap()
+    }
+
+    fn hunk(lines: &[&str]) -> Hunk {
+        let mut hunk = Hunk::default();
+        for line in lines {
+            assert!(push_hunk_line(&mut hunk, line));
+        }
+        hunk
+    }
+
+    #[test]
+    fn test_parse_envelope() {
+        let patch = "*** Begin Patch
+*** Add File: src/new.rs
++fn new() {}
+*** Update File: src/lib.rs
+*** Move to: src/core.rs
+@@ fn main() {
+ let a = 1;
+-let b = 2;
++let b = 3;
+*** End of File
+*** Delete File: src/old.rs
+*** End Patch";
+        let changes = parse_patch(patch).unwrap();
+        assert_eq!(
+            changes[0],
+            FileChange::Add {
+                path: "src/new.rs".to_string(),
+                content: "fn new() {}\n".to_string()
+            }
+        );
+        let FileChange::Update {
+            path,
+            move_to,
+            hunks,
+        } = &changes[1]
+        else {
+            panic!("expected update");
+        };
+        assert_eq!(path, "src/lib.rs");
+        assert_eq!(move_to.as_deref(), Some("src/core.rs"));
+        assert_eq!(hunks[0].anchor.as_deref(), Some("fn main() {"));
+        assert!(hunks[0].end_of_file);
+        assert_eq!(
+            hunks[0].lines,
+            [
+                HunkLine::Context("let a = 1;".to_string()),
+                HunkLine::Remove("let b = 2;".to_string()),
+                HunkLine::Add("let b = 3;".to_string()),
+            ]
+        );
+        assert_eq!(
+            changes[2],
+            FileChange::Delete {
+                path: "src/old.rs".to_string()
+            }
+        );
+
+        assert!(parse_patch("*** Begin Patch\n*** Delete File: a\n")
+            .unwrap_err()
+            .to_string()
+            .contains("End Patch"));
+    }
+
+    #[test]
+    fn test_parse_unified_git_diff() {
+        let patch = "diff --git a/src/a.rs b/src/a.rs
+index 83db48f..bf269f4 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,3 @@ fn main() {
+ one
+--- two
++++ two
+ three
+diff --git a/old.txt b/renamed.txt
+similarity index 100%
+rename from old.txt
+rename to renamed.txt
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1,2 @@
++hello
++world
+\\ No newline at end of file
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+        let changes = parse_patch(patch).unwrap();
+        assert_eq!(changes.len(), 4);
+        let FileChange::Update { path, hunks, .. } = &changes[0] else {
+            panic!("expected update");
+        };
+        assert_eq!(path, "src/a.rs");
+        assert_eq!(hunks[0].old_start, Some(1));
+        // 以 `---` / `+++` 开头的内容行按行数归入 hunk
+        assert_eq!(hunks[0].lines[1], HunkLine::Remove("-- two".to_string()));
+        assert_eq!(hunks[0].lines[2], HunkLine::Add("++ two".to_string()));
+        assert_eq!(
+            changes[1],
+            FileChange::Update {
+                path: "old.txt".to_string(),
+                move_to: Some("renamed.txt".to_string()),
+                hunks: Vec::new()
+            }
+        );
+        assert_eq!(
+            changes[2],
+            FileChange::Add {
+                path: "new.txt".to_string(),
+                content: "hello\nworld".to_string()
+            }
+        );
+        assert_eq!(
+            changes[3],
+            FileChange::Delete {
+                path: "gone.txt".to_string()
+            }
+        );
+
+        assert!(parse_patch("just some text").is_err());
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        let content = "fn a() {\n    let x = 1;   \n    x\n}\n";
+        // 忽略行尾空白，上下文保留文件原文
+        assert_eq!(
+            update(
+                &[hunk(&[
+                    " fn a() {",
+                    "-    let x = 1;",
+                    "+    let x = 2;",
+                    "     x"
+                ])],
+                content
+            ),
+            "fn a() {\n    let x = 2;\n    x\n}\n"
+        );
+        // 忽略缩进差异
+        assert_eq!(
+            update(
+                &[hunk(&[" fn a() {", "-let x = 1;", "+    let y = 1;"])],
+                content
+            ),
+            "fn a() {\n    let y = 1;\n    x\n}\n"
+        );
+        // 上下文有一行对不上时去掉两端上下文重试
+        assert_eq!(
+            update(
+                &[hunk(&[
+                    " fn b() {",
+                    "-    let x = 1;",
+                    "+    let x = 3;",
+                    "     x"
+                ])],
+                content
+            ),
+            "fn a() {\n    let x = 3;\n    x\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_context_uses_line_hint() {
+        let content = "x\nvalue\ny\nx\nvalue\ny\n";
+        let mut second = hunk(&[" x", "-value", "+changed", " y"]);
+        second.old_start = Some(4);
+        assert_eq!(update(&[second], content), "x\nvalue\ny\nx\nchanged\ny\n");
+
+        // 行号偏移时仍然选择最近的匹配
+        let mut shifted = hunk(&[" x", "-value", "+changed", " y"]);
+        shifted.old_start = Some(2);
+        assert_eq!(update(&[shifted], content), "x\nchanged\ny\nx\nvalue\ny\n");
+
+        // 锚点之后查找
+        let mut anchored = hunk(&["-value", "+changed"]);
+        anchored.anchor = Some("y".to_string());
+        assert_eq!(update(&[anchored], content), "x\nvalue\ny\nx\nchanged\ny\n");
+    }
+
+    #[test]
+    fn test_pure_additions_and_newlines() {
+        let mut append = hunk(&["+tail"]);
+        append.end_of_file = true;
+        assert_eq!(update(&[append], "a\nb\n"), "a\nb\ntail\n");
+
+        let mut insert = hunk(&["+first"]);
+        insert.old_start = Some(0);
+        assert_eq!(update(&[insert], "a\n"), "first\na\n");
+
+        let mut no_newline = hunk(&[" a", "-b", "+c"]);
+        no_newline.new_no_newline = true;
+        assert_eq!(update(&[no_newline], "a\nb\n"), "a\nc");
+        assert_eq!(update(&[hunk(&[" a", "-b", "+c"])], "a\nb"), "a\nc");
+    }
+
+    #[test]
+    fn test_failed_hunks_are_reported() {
+        let errors = apply_hunks(
+            "one\ntwo\nthree\n",
+            &[
+                hunk(&[" one", "-two", "+2"]),
+                hunk(&[" four", "-five", "+5", " six"]),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].label, "hunk 2");
+        assert_eq!(
+            errors[0].reason,
+            "could not find these lines after line 2:\n    four\n    five\n    six"
+        );
+    }
+
+    fn setup() -> (TempDir, ToolContext) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "mod a;\r\nmod b;\r\n").unwrap();
+        std::fs::write(dir.path().join("src/old.rs"), "old\n").unwrap();
+        let context = ToolContext::new(dir.path());
+        for path in ["src/lib.rs", "src/old.rs"] {
+            context
+                .freshness()
+                .record_file_read(&dir.path().join(path).to_string_lossy());
+        }
+        (dir, context)
+    }
+
+    #[tokio::test]
+    async fn test_apply_multi_file_patch() {
+        let (dir, context) = setup();
+        let patch = "*** Begin Patch
+*** Update File: src/lib.rs
+ mod a;
+-mod b;
++mod c;
+*** Add File: src/c.rs
++pub fn c() {}
+*** Update File: src/old.rs
+*** Move to: src/renamed.rs
+-old
++new
+*** End Patch";
+        let result = ApplyPatchTool::new()
+            .execute(json!({"patch": patch}), &context)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.output,
+            "Applied 3 change(s):\nM src/lib.rs\nA src/c.rs\nR src/old.rs -> src/renamed.rs"
+        );
+
+        let read = |path: &str| std::fs::read_to_string(dir.path().join(path)).unwrap();
+        // 保留 CRLF 换行
+        assert_eq!(read("src/lib.rs"), "mod a;\r\nmod c;\r\n");
+        assert_eq!(read("src/c.rs"), "pub fn c() {}\n");
+        assert_eq!(read("src/renamed.rs"), "new\n");
+        assert!(!dir.path().join("src/old.rs").exists());
+        for path in ["src/lib.rs", "src/c.rs", "src/renamed.rs"] {
+            let path = dir.path().join(path);
+            assert!(context.freshness().is_file_tracked(&path.to_string_lossy()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_patch_changes_nothing() {
+        let (dir, context) = setup();
+        let patch = "--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ mod a;
+-mod b;
++mod c;
+--- a/src/old.rs
++++ b/src/old.rs
+@@ -10,2 +10,2 @@
+ missing
+-line
++changed
+--- /dev/null
++++ b/src/lib.rs
+@@ -0,0 +1 @@
++duplicate
+";
+        let err = ApplyPatchTool::new()
+            .execute(json!({"patch": patch}), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Patch was not applied; no files were changed.\n\
+- src/old.rs: hunk 1 (line 10) failed: could not find these lines:\n    missing\n    line\n\
+- src/lib.rs: file already exists"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/lib.rs")).unwrap(),
+            "mod a;\r\nmod b;\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_stale_and_outside_paths() {
+        let (dir, context) = setup();
+        let file = dir.path().join("src/old.rs");
+        context
+            .freshness()
+            .record_file_read(&file.to_string_lossy());
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(&file, "changed elsewhere\n").unwrap();
+
+        let patch = "*** Begin Patch\n*** Delete File: src/old.rs\n*** End Patch";
+        let err = ApplyPatchTool::new()
+            .execute(json!({"patch": patch}), &context)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("modified since it was last read"));
+
+        let patch = "*** Begin Patch\n*** Add File: ../escape.txt\n+x\n*** End Patch";
+        let err = ApplyPatchTool::new()
+            .execute(json!({"patch": patch}), &context)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside the working directory"));
+    }
+
+    #[test]
+    fn test_permission_request_lists_every_path() {
+        let context = ToolContext::new("/project");
+        let tool = ApplyPatchTool::new();
+        let patch = "*** Begin Patch\n*** Update File: b.rs\n*** Move to: c.rs\n-x\n+y\n\
+*** Delete File: a.rs\n*** End Patch";
+        let request = tool.permission_request(&json!({"patch": patch}), &context);
+        assert_eq!(request.access, AccessKind::Edit);
+        assert_eq!(
+            request.target,
+            PermissionTarget::Paths(vec![
+                PathBuf::from("/project/a.rs"),
+                PathBuf::from("/project/b.rs"),
+                PathBuf::from("/project/c.rs"),
+            ])
+        );
+
+        let single = "*** Begin Patch\n*** Delete File: a.rs\n*** End Patch";
+        assert_eq!(
+            tool.permission_request(&json!({"patch": single}), &context)
+                .target,
+            PermissionTarget::Path(PathBuf::from("/project/a.rs"))
+        );
+    }
+}
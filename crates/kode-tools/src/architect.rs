@@ -0,0 +1,583 @@
+//! Architect 工具与计划模式
+//!
+//! Architect 工具在 `reasoning` 模型上运行只读的规划对话，返回结构化的实现计划，
+//! 需要在项目配置中设置 `enableArchitectTool` 才能使用。
+//!
+//! 计划模式（[`PermissionMode::Plan`]）下所有非只读工具都会被拒绝，
+//! 直到模型调用 ExitPlanMode 并由用户批准计划。
+
+use crate::{Tool, ToolContext, ToolOrchestrator, ToolRegistry, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::config::types::ModelPointers;
+use kode_core::context::MessageContextManager;
+use kode_core::message::Message;
+use kode_core::model::ModelProvider;
+use kode_core::permission::{PermissionDecision, PermissionEngine, PermissionMode};
+use kode_core::query::{QueryEngine, StopReason};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+
+/// Architect 工具名
+pub const ARCHITECT_TOOL_NAME: &str = "Architect";
+
+/// ExitPlanMode 工具名
+pub const EXIT_PLAN_MODE_TOOL_NAME: &str = "ExitPlanMode";
+
+/// 规划对话的最大轮数
+pub const ARCHITECT_MAX_TURNS: usize = 20;
+
+const ARCHITECT_DESCRIPTION: &str = "Ask a software architect to analyze the codebase and \
+produce a step-by-step implementation plan. The architect can only read files and search; it \
+never edits anything. Use it before large or risky changes.";
+
+const ARCHITECT_SYSTEM_PROMPT: &str = "You are an expert software architect. Investigate the \
+codebase with the read-only tools available to you, then answer with an implementation plan \
+in exactly this format:
+
+## Summary
+One or two paragraphs describing the approach.
+
+## Steps
+1. A concrete, self-contained step.
+2. ...
+
+## Files
+- path/to/file that will change
+
+## Risks
+- Anything that could go wrong or needs a decision.
+
+Do not write code or make any changes.";
+
+const EXIT_PLAN_MODE_DESCRIPTION: &str = "Present the final implementation plan to the user \
+and ask to leave plan mode. Only call this when the plan is complete; mutating tools stay \
+blocked until the user approves it.";
+
+/// 结构化的实现计划
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImplementationPlan {
+    /// 方案概述
+    pub summary: String,
+    /// 实现步骤
+    pub steps: Vec<String>,
+    /// 涉及的文件
+    pub files: Vec<String>,
+    /// 风险与待决事项
+    pub risks: Vec<String>,
+}
+
+impl ImplementationPlan {
+    /// 从模型回复中解析计划
+    ///
+    /// 按 `## Summary` / `## Steps` / `## Files` / `## Risks` 分节；没有任何步骤时返回 `None`。
+    pub fn parse(text: &str) -> Option<Self> {
+        #[derive(Clone, Copy)]
+        enum Section {
+            None,
+            Summary,
+            Steps,
+            Files,
+            Risks,
+        }
+
+        let mut plan = Self::default();
+        let mut section = Section::None;
+        let mut summary = Vec::new();
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if let Some(heading) = trimmed.strip_prefix("## ") {
+                section = match heading.trim().to_lowercase().as_str() {
+                    "summary" => Section::Summary,
+                    "steps" => Section::Steps,
+                    "files" => Section::Files,
+                    "risks" => Section::Risks,
+                    _ => Section::None,
+                };
+                continue;
+            }
+            match section {
+                Section::None => {}
+                Section::Summary => summary.push(trimmed),
+                Section::Steps => push_item(&mut plan.steps, trimmed),
+                Section::Files => push_item(&mut plan.files, trimmed),
+                Section::Risks => push_item(&mut plan.risks, trimmed),
+            }
+        }
+        plan.summary = summary.join("\n").trim().to_string();
+        (!plan.steps.is_empty()).then_some(plan)
+    }
+}
+
+/// 追加列表项；去掉 `1.`、`-`、`*` 前缀，未带前缀的行并入上一项
+fn push_item(items: &mut Vec<String>, line: &str) {
+    if line.is_empty() {
+        return;
+    }
+    let numbered = line
+        .split_once(". ")
+        .filter(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        .map(|(_, rest)| rest);
+    let bulleted = line.strip_prefix("- ").or_else(|| line.strip_prefix("* "));
+    match numbered.or(bulleted) {
+        Some(item) => items.push(item.trim().to_string()),
+        None => match items.last_mut() {
+            Some(last) => {
+                last.push(' ');
+                last.push_str(line);
+            }
+            None => items.push(line.to_string()),
+        },
+    }
+}
+
+impl fmt::Display for ImplementationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "## Summary\n{}\n", self.summary)?;
+        writeln!(f, "## Steps")?;
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, step)?;
+        }
+        if !self.files.is_empty() {
+            writeln!(f, "\n## Files")?;
+            for file in &self.files {
+                writeln!(f, "- {}", file)?;
+            }
+        }
+        if !self.risks.is_empty() {
+            writeln!(f, "\n## Risks")?;
+            for risk in &self.risks {
+                writeln!(f, "- {}", risk)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Architect 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchitectParams {
+    /// 需要规划的任务
+    pub prompt: String,
+    /// 额外的背景信息
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+/// Architect 工具
+pub struct ArchitectTool {
+    registry: ToolRegistry,
+    models: Arc<dyn ModelProvider>,
+    model: Option<String>,
+}
+
+impl ArchitectTool {
+    /// 创建 Architect 工具
+    ///
+    /// 规划对话只能使用 `registry` 中的只读工具（不包括 Architect 自身和 ExitPlanMode）。
+    pub fn new(registry: ToolRegistry, models: Arc<dyn ModelProvider>) -> Self {
+        let mut planning = ToolRegistry::new();
+        let read_only = registry.read_only();
+        for name in read_only.list() {
+            if name != ARCHITECT_TOOL_NAME && name != EXIT_PLAN_MODE_TOOL_NAME {
+                planning.register(read_only.get(&name).expect("listed tool"));
+            }
+        }
+        Self {
+            registry: planning,
+            models,
+            model: None,
+        }
+    }
+
+    /// 使用模型指针中的 `reasoning` 模型
+    pub fn with_model_pointers(mut self, pointers: &ModelPointers) -> Self {
+        self.model = pointers.reasoning.clone();
+        self
+    }
+
+    /// 项目是否启用了 Architect 工具
+    pub fn is_enabled(context: &ToolContext) -> bool {
+        context.project_config.enable_architect_tool == Some(true)
+    }
+}
+
+#[async_trait]
+impl Tool for ArchitectTool {
+    fn name(&self) -> &str {
+        ARCHITECT_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        ARCHITECT_DESCRIPTION
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: ARCHITECT_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "prompt": {
+                        "type": "string",
+                        "description": "The technical request or change to plan"
+                    },
+                    "context": {
+                        "type": "string",
+                        "description": "Optional background from the current conversation"
+                    }
+                },
+                "required": ["prompt"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        if !Self::is_enabled(context) {
+            bail!(
+                "The Architect tool is disabled for this project \
+(enable it with `kode config set enableArchitectTool true`)"
+            );
+        }
+        let params: ArchitectParams =
+            serde_json::from_value(params).context("Invalid Architect parameters")?;
+        let adapter = self.models.adapter(self.model.as_deref())?;
+
+        // 规划对话始终处于计划模式，沿用当前的 deny 规则
+        let permissions =
+            PermissionEngine::new(context.working_dir.clone(), context.permissions.rules())
+                .with_mode(PermissionMode::Plan);
+        let mut planning_context = context
+            .clone()
+            .with_permissions(Arc::new(permissions))
+            .with_cancellation(context.cancel.child_token());
+        planning_context.progress = None;
+        let cancel = planning_context.cancel.clone();
+        let runner = ToolOrchestrator::new(self.registry.clone()).runner(planning_context);
+
+        let engine = QueryEngine::new(adapter, Arc::new(runner))
+            .with_system_prompt(ARCHITECT_SYSTEM_PROMPT)
+            .with_max_turns(ARCHITECT_MAX_TURNS)
+            .with_cancellation(cancel);
+        let mut conversation = MessageContextManager::new(0);
+        let prompt = match &params.context {
+            Some(extra) => format!("{}\n\nContext:\n{}", params.prompt, extra),
+            None => params.prompt.clone(),
+        };
+        conversation.add_message(Message::user(prompt));
+
+        let result = engine
+            .run(&mut conversation)
+            .await
+            .context("Architect failed")?;
+        if result.stop_reason == StopReason::Cancelled {
+            bail!("Architect was cancelled");
+        }
+
+        let text = result.final_text();
+        Ok(ToolResult::new(match ImplementationPlan::parse(&text) {
+            Some(plan) => plan.to_string(),
+            // 模型没有按格式回答时原样返回
+            None => text,
+        }))
+    }
+}
+
+/// ExitPlanMode 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExitPlanModeParams {
+    /// 交给用户批准的计划
+    pub plan: String,
+}
+
+/// ExitPlanMode 工具
+#[derive(Debug, Default)]
+pub struct ExitPlanModeTool;
+
+impl ExitPlanModeTool {
+    /// 创建新的 ExitPlanMode 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for ExitPlanModeTool {
+    fn name(&self) -> &str {
+        EXIT_PLAN_MODE_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        EXIT_PLAN_MODE_DESCRIPTION
+    }
+
+    // 批准由 `approve_plan` 单独询问，计划模式下也必须能调用
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: EXIT_PLAN_MODE_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "plan": {
+                        "type": "string",
+                        "description": "The implementation plan to present to the user (markdown)"
+                    }
+                },
+                "required": ["plan"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: ExitPlanModeParams =
+            serde_json::from_value(params).context("Invalid ExitPlanMode parameters")?;
+        match context
+            .permissions
+            .approve_plan(self.name(), &params.plan)
+            .await
+        {
+            PermissionDecision::Allow => Ok(ToolResult::new(
+                "The user approved the plan. Plan mode is off; you can start implementing it.",
+            )),
+            PermissionDecision::Deny { reason } => bail!(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_core::config::ProjectConfig;
+    use kode_core::message::ToolResultBlock;
+    use kode_core::message::{ContentBlock, MessageContent};
+    use kode_core::model::{ModelAdapter, StreamChunk};
+    use kode_core::permission::{
+        PermissionAnswer, PermissionAsk, PermissionPrompt, PermissionRules,
+    };
+    use kode_core::testing::ScriptedAdapter;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    struct Provider {
+        adapter: Arc<ScriptedAdapter>,
+        requested: Mutex<Vec<Option<String>>>,
+    }
+
+    impl ModelProvider for Provider {
+        fn adapter(&self, model: Option<&str>) -> kode_core::Result<Arc<dyn ModelAdapter>> {
+            self.requested.lock().unwrap().push(model.map(String::from));
+            Ok(self.adapter.clone())
+        }
+    }
+
+    struct Echo(&'static str, bool);
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "echo"
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.1
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema {
+                name: self.0.to_string(),
+                description: "echo".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn execute(&self, _params: Value, _context: &ToolContext) -> Result<ToolResult> {
+            Ok(ToolResult::new(format!("{} ran", self.0)))
+        }
+    }
+
+    struct FixedPrompt(PermissionAnswer);
+
+    #[async_trait]
+    impl PermissionPrompt for FixedPrompt {
+        async fn ask(&self, _ask: &PermissionAsk) -> PermissionAnswer {
+            self.0
+        }
+    }
+
+    const PLAN: &str = "Some preamble.
+
+## Summary
+Add a cache in front of the parser.
+
+## Steps
+1. Add a `Cache` struct.
+2. Wire it into `parse`,
+   keeping the old path as fallback.
+
+## Files
+- src/cache.rs
+- src/parser.rs
+
+## Risks
+- Stale entries after edits.";
+
+    fn setup(script: Vec<Vec<StreamChunk>>) -> (ArchitectTool, Arc<Provider>) {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(Echo("Grep", true)));
+        registry.register(Arc::new(Echo("Edit", false)));
+        registry.register(Arc::new(ExitPlanModeTool::new()));
+        let provider = Arc::new(Provider {
+            adapter: ScriptedAdapter::new(script),
+            requested: Mutex::new(Vec::new()),
+        });
+        let pointers = ModelPointers {
+            reasoning: Some("think-model".to_string()),
+            ..Default::default()
+        };
+        let tool = ArchitectTool::new(registry, provider.clone()).with_model_pointers(&pointers);
+        (tool, provider)
+    }
+
+    fn enabled(dir: &TempDir) -> ToolContext {
+        ToolContext::new(dir.path()).with_project_config(ProjectConfig {
+            enable_architect_tool: Some(true),
+            ..Default::default()
+        })
+    }
+
+    fn tool_results(message: &Message) -> Vec<ToolResultBlock> {
+        match &message.content {
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolResult(r) => Some(r.clone()),
+                    _ => None,
+                })
+                .collect(),
+            MessageContent::Text(_) => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_plan() {
+        let plan = ImplementationPlan::parse(PLAN).unwrap();
+        assert_eq!(plan.summary, "Add a cache in front of the parser.");
+        assert_eq!(
+            plan.steps,
+            [
+                "Add a `Cache` struct.",
+                "Wire it into `parse`, keeping the old path as fallback."
+            ]
+        );
+        assert_eq!(plan.files, ["src/cache.rs", "src/parser.rs"]);
+        assert_eq!(plan.risks, ["Stale entries after edits."]);
+        assert_eq!(ImplementationPlan::parse(&plan.to_string()), Some(plan));
+
+        assert_eq!(ImplementationPlan::parse("## Summary\nNo steps here"), None);
+    }
+
+    #[tokio::test]
+    async fn test_architect_plans_with_read_only_tools() {
+        let (tool, provider) = setup(vec![
+            vec![
+                StreamChunk::tool_use("Grep", "g", json!({})),
+                StreamChunk::tool_use("Edit", "e", json!({})),
+            ],
+            vec![StreamChunk::content_block_delta(0, PLAN)],
+        ]);
+        assert_eq!(tool.registry.list(), ["Grep"]);
+
+        let dir = TempDir::new().unwrap();
+        let result = tool
+            .execute(json!({"prompt": "Speed up parsing"}), &enabled(&dir))
+            .await
+            .unwrap();
+
+        assert!(result.output.starts_with("## Summary\nAdd a cache"));
+        assert!(result.output.contains("2. Wire it into `parse`"));
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            [Some("think-model".to_string())]
+        );
+
+        let requests = provider.adapter.requests();
+        assert_eq!(requests[0].1.as_deref(), Some(ARCHITECT_SYSTEM_PROMPT));
+        let results = tool_results(requests[1].0.last().unwrap());
+        assert_eq!(results[0].content, "Grep ran");
+        assert!(results[1].is_error);
+        assert!(results[1].content.contains("Unknown tool: Edit"));
+    }
+
+    #[tokio::test]
+    async fn test_architect_returns_unstructured_reply() {
+        let (tool, _) = setup(vec![vec![StreamChunk::content_block_delta(
+            0,
+            "Just refactor it.",
+        )]]);
+        let dir = TempDir::new().unwrap();
+        let result = tool
+            .execute(json!({"prompt": "x"}), &enabled(&dir))
+            .await
+            .unwrap();
+        assert_eq!(result.output, "Just refactor it.");
+    }
+
+    #[tokio::test]
+    async fn test_architect_disabled() {
+        let (tool, provider) = setup(Vec::new());
+        let dir = TempDir::new().unwrap();
+        let err = tool
+            .execute(json!({"prompt": "x"}), &ToolContext::new(dir.path()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("enableArchitectTool"));
+        assert!(provider.requested.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exit_plan_mode() {
+        let dir = TempDir::new().unwrap();
+        let plan_mode = |prompt: Option<PermissionAnswer>| {
+            let engine = PermissionEngine::new(dir.path(), PermissionRules::default())
+                .with_mode(PermissionMode::Plan);
+            let engine = match prompt {
+                Some(answer) => engine.with_prompt(Arc::new(FixedPrompt(answer))),
+                None => engine,
+            };
+            ToolContext::new(dir.path()).with_permissions(Arc::new(engine))
+        };
+        let tool = ExitPlanModeTool::new();
+        let params = json!({"plan": "1. Edit main.rs"});
+
+        let context = plan_mode(None);
+        let err = tool.execute(params.clone(), &context).await.unwrap_err();
+        assert!(err.to_string().contains("requires user approval"));
+        assert_eq!(context.permissions.mode(), PermissionMode::Plan);
+
+        let context = plan_mode(Some(PermissionAnswer::Deny));
+        let err = tool.execute(params.clone(), &context).await.unwrap_err();
+        assert!(err.to_string().contains("rejected the plan"));
+        assert_eq!(context.permissions.mode(), PermissionMode::Plan);
+
+        let context = plan_mode(Some(PermissionAnswer::AllowOnce));
+        let result = tool.execute(params, &context).await.unwrap();
+        assert!(result.output.contains("approved"));
+        assert_eq!(context.permissions.mode(), PermissionMode::Default);
+    }
+}
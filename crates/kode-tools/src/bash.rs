@@ -0,0 +1,611 @@
+//! Bash 工具
+//!
+//! 在会话的持久 shell 中执行命令，支持超时、取消、输出截断和后台执行。
+//! 后台任务通过 BashOutput 轮询输出，通过 KillBash 终止。
+
+use crate::bash_safety::{CommandCategory, CommandPolicy, SafetyVerdict};
+use crate::sandbox::{self, SandboxMode};
+use crate::shell::{CommandOutput, CommandStatus, RunOptions, DEFAULT_MAX_OUTPUT};
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::permission::{AccessKind, CommandTarget, PermissionRequest, PermissionTarget};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const DESCRIPTION: &str = "Execute a command in a persistent shell session. The working \
+directory and exported environment variables persist between calls. Commands time out after \
+2 minutes by default (maximum 10 minutes). Set run_in_background to start a long-running \
+command and poll its output later with BashOutput.";
+
+const OUTPUT_DESCRIPTION: &str = "Retrieve new output from a background command started with \
+Bash. Returns only output produced since the last check, together with the command status.";
+
+const KILL_DESCRIPTION: &str = "Terminate a background command started with Bash.";
+
+/// 默认超时（毫秒）
+pub const DEFAULT_TIMEOUT_MS: u64 = 120_000;
+
+/// 最大超时（毫秒）
+pub const MAX_TIMEOUT_MS: u64 = 600_000;
+
+#[derive(Debug, Deserialize)]
+struct BashParams {
+    command: String,
+    #[serde(default)]
+    timeout: Option<u64>,
+    #[serde(default)]
+    run_in_background: bool,
+    #[serde(default)]
+    merge_stderr: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BashIdParams {
+    bash_id: String,
+}
+
+/// Bash 工具
+#[derive(Debug, Default)]
+pub struct BashTool;
+
+impl BashTool {
+    /// 创建新的 Bash 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 将命令输出格式化为工具结果文本
+fn format_output(output: &CommandOutput, timeout_ms: u64) -> String {
+    let mut sections = Vec::new();
+
+    let stdout = output.stdout.trim_end();
+    if !stdout.is_empty() {
+        sections.push(stdout.to_string());
+    }
+    let stderr = output.stderr.trim_end();
+    if !stderr.is_empty() {
+        sections.push(format!("STDERR:\n{}", stderr));
+    }
+
+    match output.status {
+        CommandStatus::Exited(0) => {}
+        CommandStatus::Exited(code) => sections.push(format!("Exit code: {}", code)),
+        CommandStatus::ShellExited(code) => {
+            if let Some(code) = code {
+                sections.push(format!("Exit code: {}", code));
+            }
+            sections.push(
+                "Shell exited. A new shell will be started in the project directory for the \
+                 next command."
+                    .to_string(),
+            );
+        }
+        CommandStatus::TimedOut => sections.push(format!(
+            "Command timed out after {} ms and was terminated. The shell was restarted; the \
+             working directory and environment were reset.",
+            timeout_ms
+        )),
+        CommandStatus::Cancelled => sections.push(
+            "Command was cancelled. The shell was restarted; the working directory and \
+             environment were reset."
+                .to_string(),
+        ),
+    }
+
+    if sections.is_empty() {
+        "(No output)".to_string()
+    } else {
+        sections.join("\n")
+    }
+}
+
+#[async_trait]
+impl Tool for BashTool {
+    fn name(&self) -> &str {
+        "Bash"
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The command to execute"
+                    },
+                    "timeout": {
+                        "type": "integer",
+                        "description": "Optional timeout in milliseconds (max 600000)"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "Short description of what the command does"
+                    },
+                    "run_in_background": {
+                        "type": "boolean",
+                        "description": "Run the command in the background and return a bash_id \
+                                        for use with BashOutput"
+                    },
+                    "merge_stderr": {
+                        "type": "boolean",
+                        "description": "Interleave stderr with stdout (default false)"
+                    }
+                },
+                "required": ["command"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: BashParams =
+            serde_json::from_value(params).context("Invalid Bash parameters")?;
+        if params.command.trim().is_empty() {
+            bail!("command must not be empty");
+        }
+
+        // 禁止列表在任何权限模式下都生效
+        let analysis =
+            CommandPolicy::from_project_config(&context.project_config).analyze(&params.command);
+        if let SafetyVerdict::Deny { reason } = analysis.verdict {
+            bail!("Command blocked: {}", reason);
+        }
+
+        let sandbox = SandboxMode::resolve(
+            context.project_config.sandbox.as_ref(),
+            &context.working_dir,
+        )?;
+        let notice = match &sandbox {
+            SandboxMode::Fallback { reason } => Some(format!(
+                "Warning: sandbox unavailable ({}); the command ran without it.",
+                reason
+            )),
+            SandboxMode::Disabled | SandboxMode::Enabled(_) => None,
+        };
+        let with_notice = |output: String| match &notice {
+            Some(notice) => format!("{}\n{}", notice, output),
+            None => output,
+        };
+
+        if params.run_in_background {
+            let id = context
+                .shell
+                .spawn_background(
+                    &params.command,
+                    &context.working_dir,
+                    params.merge_stderr,
+                    sandbox.policy(),
+                )
+                .await?;
+            return Ok(ToolResult::new(with_notice(format!(
+                "Command running in background with ID: {}. Use BashOutput to check its output.",
+                id
+            ))));
+        }
+
+        let timeout_ms = params
+            .timeout
+            .unwrap_or(DEFAULT_TIMEOUT_MS)
+            .min(MAX_TIMEOUT_MS);
+        let options = RunOptions {
+            timeout: Duration::from_millis(timeout_ms),
+            merge_stderr: params.merge_stderr,
+            max_output: DEFAULT_MAX_OUTPUT,
+            sandbox: sandbox.policy().cloned(),
+        };
+        let output = context
+            .shell
+            .run(
+                &params.command,
+                &context.working_dir,
+                &options,
+                &context.cancel,
+            )
+            .await?;
+
+        let formatted = format_output(&output, timeout_ms);
+        if let Some(violation) = sandbox
+            .policy()
+            .and_then(|policy| sandbox::detect_violation(&output, policy, &context.working_dir))
+        {
+            bail!("Sandbox violation: {}\n{}", violation, formatted);
+        }
+        Ok(ToolResult::new(with_notice(formatted)))
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    /// 按子命令构造权限请求，使 `Bash(git status:*)` 等规则逐条匹配
+    fn permission_request(&self, params: &Value, context: &ToolContext) -> PermissionRequest {
+        let command = params
+            .get("command")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let analysis = CommandPolicy::from_project_config(&context.project_config).analyze(command);
+
+        let commands: Vec<CommandTarget> = if analysis.commands.is_empty() {
+            // 无法解析时把整条命令作为一个需要批准的目标，可能含重定向时不让 allow 规则覆盖
+            let target = CommandTarget::new(command.trim(), false);
+            vec![if command.contains('>') {
+                target.with_file_redirect()
+            } else {
+                target
+            }]
+        } else {
+            analysis
+                .commands
+                .iter()
+                .map(|c| {
+                    let target = CommandTarget::new(
+                        c.command.command_line(),
+                        c.category == CommandCategory::ReadOnly,
+                    )
+                    .with_effective_line(c.command.effective_command_line());
+                    if c.command.writes_file() {
+                        target.with_file_redirect()
+                    } else {
+                        target
+                    }
+                })
+                .collect()
+        };
+        let access = if analysis.is_read_only() {
+            AccessKind::ReadOnly
+        } else {
+            AccessKind::Execute
+        };
+
+        let request = PermissionRequest::new(self.name(), access)
+            .with_target(PermissionTarget::Commands(commands));
+        match analysis.verdict {
+            SafetyVerdict::Ask { reason } | SafetyVerdict::Deny { reason } => {
+                request.with_reason(reason)
+            }
+            SafetyVerdict::Allow => request,
+        }
+    }
+}
+
+/// BashOutput 工具：轮询后台命令的输出
+#[derive(Debug, Default)]
+pub struct BashOutputTool;
+
+impl BashOutputTool {
+    /// 创建新的 BashOutput 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn bash_id_schema(name: &str, description: &str) -> ToolSchema {
+    ToolSchema {
+        name: name.to_string(),
+        description: description.to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "bash_id": {
+                    "type": "string",
+                    "description": "The ID of the background command"
+                }
+            },
+            "required": ["bash_id"]
+        }),
+    }
+}
+
+#[async_trait]
+impl Tool for BashOutputTool {
+    fn name(&self) -> &str {
+        "BashOutput"
+    }
+
+    fn description(&self) -> &str {
+        OUTPUT_DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        bash_id_schema(self.name(), OUTPUT_DESCRIPTION)
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: BashIdParams =
+            serde_json::from_value(params).context("Invalid BashOutput parameters")?;
+        let snapshot = context
+            .shell
+            .poll(&params.bash_id, DEFAULT_MAX_OUTPUT)
+            .ok_or_else(|| anyhow!("No background job with ID {}", params.bash_id))?;
+
+        let mut output = format!("Status: {}", snapshot.status);
+        let stdout = snapshot.stdout.trim_end();
+        if !stdout.is_empty() {
+            output.push_str(&format!("\n{}", stdout));
+        }
+        let stderr = snapshot.stderr.trim_end();
+        if !stderr.is_empty() {
+            output.push_str(&format!("\nSTDERR:\n{}", stderr));
+        }
+        Ok(ToolResult::new(output))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+/// KillBash 工具：终止后台命令
+#[derive(Debug, Default)]
+pub struct KillBashTool;
+
+impl KillBashTool {
+    /// 创建新的 KillBash 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for KillBashTool {
+    fn name(&self) -> &str {
+        "KillBash"
+    }
+
+    fn description(&self) -> &str {
+        KILL_DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        bash_id_schema(self.name(), KILL_DESCRIPTION)
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: BashIdParams =
+            serde_json::from_value(params).context("Invalid KillBash parameters")?;
+        let status = context.shell.kill(&params.bash_id)?;
+        Ok(ToolResult::new(format!(
+            "Background job {} {}",
+            params.bash_id, status
+        )))
+    }
+
+    /// 终止进程有副作用，与执行命令一样需要权限（计划模式下拒绝）
+    fn requires_permission(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_core::config::{GlobalConfig, ProjectConfig};
+    use kode_core::permission::{
+        PermissionCheck, PermissionEngine, PermissionMode, PermissionRules,
+    };
+    use tempfile::TempDir;
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn test_format_output() {
+        let output = CommandOutput {
+            stdout: "hello\n".to_string(),
+            stderr: "warning\n".to_string(),
+            status: CommandStatus::Exited(2),
+            truncated: false,
+        };
+        assert_eq!(
+            format_output(&output, DEFAULT_TIMEOUT_MS),
+            "hello\nSTDERR:\nwarning\nExit code: 2"
+        );
+
+        let output = CommandOutput {
+            stdout: String::new(),
+            stderr: String::new(),
+            status: CommandStatus::Exited(0),
+            truncated: false,
+        };
+        assert_eq!(format_output(&output, DEFAULT_TIMEOUT_MS), "(No output)");
+    }
+
+    #[tokio::test]
+    async fn test_execute_in_working_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("marker.txt"), "").unwrap();
+        let context = ToolContext::new(dir.path());
+
+        let result = BashTool::new()
+            .execute(json!({"command": "ls"}), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "marker.txt");
+        assert!(BashTool::new().requires_permission());
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_denied_command() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path()).with_project_config(ProjectConfig {
+            denied_commands: vec!["touch".to_string()],
+            ..Default::default()
+        });
+
+        let err = BashTool::new()
+            .execute(json!({"command": "ls && touch created"}), &context)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("blocked"));
+        assert!(!dir.path().join("created").exists());
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_sandbox_config() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path()).with_project_config(ProjectConfig {
+            sandbox: Some(kode_core::config::SandboxConfig {
+                enabled: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let result = BashTool::new()
+            .execute(json!({"command": "touch inside && echo done"}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.ends_with("done"));
+        assert!(dir.path().join("inside").exists());
+        if sandbox::support().is_err() {
+            assert!(result.output.starts_with("Warning: sandbox unavailable"));
+        }
+    }
+
+    #[test]
+    fn test_permission_request() {
+        let context = ToolContext::new("/project");
+        let request = BashTool::new()
+            .permission_request(&json!({"command": "git status && npm install"}), &context);
+        assert_eq!(request.access, AccessKind::Execute);
+        assert_eq!(
+            request.target,
+            PermissionTarget::Commands(vec![
+                CommandTarget::new("git status", true),
+                CommandTarget::new("npm install", false),
+            ])
+        );
+        assert!(request.reason.unwrap().contains("npm install"));
+
+        let request = BashTool::new().permission_request(&json!({"command": "ls -la"}), &context);
+        assert_eq!(request.access, AccessKind::ReadOnly);
+    }
+
+    #[test]
+    fn test_permission_rules_see_redirects_and_wrappers() {
+        let context = ToolContext::new("/project");
+        let engine = PermissionEngine::new(
+            "/project",
+            PermissionRules::from_configs(
+                &GlobalConfig::default(),
+                &ProjectConfig {
+                    allowed_tools: vec!["Bash(echo:*)".to_string()],
+                    denied_tools: vec!["Bash(rm:*)".to_string()],
+                    ..Default::default()
+                },
+            ),
+        );
+        let check = |command: &str| {
+            engine.evaluate(
+                &BashTool::new().permission_request(&json!({ "command": command }), &context),
+            )
+        };
+
+        assert_eq!(check("echo hello 2>&1"), PermissionCheck::Allow);
+        assert_eq!(check("echo hello 2>/dev/null"), PermissionCheck::Allow);
+        match check("echo pwn > ~/.bashrc") {
+            PermissionCheck::Ask { suggestions, .. } => assert!(suggestions.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+        for command in [
+            "rm -rf build",
+            "env rm -rf build",
+            "nohup rm -rf build",
+            "timeout 1 rm -rf build",
+            "FOO=1 rm -rf build",
+        ] {
+            assert!(
+                matches!(check(command), PermissionCheck::Deny { .. }),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_kill_bash_requires_permission() {
+        let context = ToolContext::new("/project");
+        let request =
+            KillBashTool::new().permission_request(&json!({"bash_id": "bash_1"}), &context);
+        assert_eq!(request.access, AccessKind::Execute);
+
+        let engine = PermissionEngine::new("/project", PermissionRules::default());
+        assert!(matches!(
+            engine.evaluate(&request),
+            PermissionCheck::Ask { .. }
+        ));
+        engine.set_mode(PermissionMode::Plan);
+        match engine.evaluate(&request) {
+            PermissionCheck::Deny { reason } => assert!(reason.contains("plan mode"), "{}", reason),
+            other => panic!("unexpected {:?}", other),
+        }
+        // BashOutput 只读取输出，计划模式下仍然允许
+        let request =
+            BashOutputTool::new().permission_request(&json!({"bash_id": "bash_1"}), &context);
+        assert_eq!(engine.evaluate(&request), PermissionCheck::Allow);
+    }
+
+    #[tokio::test]
+    async fn test_execute_timeout() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path());
+        let result = BashTool::new()
+            .execute(json!({"command": "sleep 30", "timeout": 100}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.contains("timed out after 100 ms"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_cancelled_via_context() {
+        let dir = TempDir::new().unwrap();
+        let cancel = CancellationToken::new();
+        let context = ToolContext::new(dir.path()).with_cancellation(cancel.clone());
+        cancel.cancel();
+
+        let result = BashTool::new()
+            .execute(json!({"command": "sleep 30"}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.contains("cancelled"));
+    }
+
+    #[tokio::test]
+    async fn test_background_tools() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path());
+
+        let result = BashTool::new()
+            .execute(
+                json!({"command": "sleep 30", "run_in_background": true}),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(result.output.contains("bash_1"));
+
+        let status = BashOutputTool::new()
+            .execute(json!({"bash_id": "bash_1"}), &context)
+            .await
+            .unwrap();
+        assert!(status.output.starts_with("Status: running"));
+
+        let killed = KillBashTool::new()
+            .execute(json!({"bash_id": "bash_1"}), &context)
+            .await
+            .unwrap();
+        assert_eq!(killed.output, "Background job bash_1 killed");
+
+        assert!(BashOutputTool::new()
+            .execute(json!({"bash_id": "bash_9"}), &context)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,2329 @@
+//! Bash 命令安全分析
+//!
+//! Bash 工具执行命令之前，先把复合命令（`&&`、`||`、`|`、`;`、子 shell、
+//! 命令替换、重定向）拆分为简单命令，再把每个简单命令分类为只读、修改、
+//! 网络或破坏性操作，并对照禁止列表给出判定，供权限系统决定是否需要用户确认。
+//!
+//! 解析器只识别与安全相关的结构，不做变量展开；无法解析的命令一律要求确认。
+
+use kode_core::config::ProjectConfig;
+use std::fmt;
+
+/// 内置禁止执行的命令
+///
+/// 每一项是以空格分隔的模式：第一个词匹配程序名，其余每个词都必须匹配
+/// 某个参数（顺序不限）。每个词都支持 glob 通配符。
+pub const DEFAULT_DENIED_COMMANDS: &[&str] = &[
+    "sudo",
+    "su",
+    "doas",
+    "shutdown",
+    "reboot",
+    "halt",
+    "poweroff",
+    "mkfs*",
+    "fdisk",
+    "parted",
+    "wipefs",
+    "dd of=/dev/*",
+];
+
+/// 嵌套解析（命令替换、`bash -c`、`eval`）的最大深度
+const MAX_NESTING: usize = 8;
+
+/// 只读命令
+const READ_ONLY_COMMANDS: &[&str] = &[
+    ":",
+    "[",
+    "[[",
+    "ack",
+    "ag",
+    "basename",
+    "bat",
+    "cal",
+    "cat",
+    "cd",
+    "cksum",
+    "cmp",
+    "column",
+    "comm",
+    "cut",
+    "date",
+    "declare",
+    "df",
+    "diff",
+    "dirname",
+    "du",
+    "echo",
+    "egrep",
+    "env",
+    "eza",
+    "exa",
+    "exit",
+    "expand",
+    "export",
+    "false",
+    "fd",
+    "fgrep",
+    "file",
+    "fmt",
+    "fold",
+    "for",
+    "free",
+    "grep",
+    "groups",
+    "head",
+    "help",
+    "hexdump",
+    "history",
+    "hostname",
+    "id",
+    "info",
+    "jq",
+    "join",
+    "less",
+    "local",
+    "locate",
+    "ls",
+    "lsof",
+    "man",
+    "md5sum",
+    "more",
+    "nl",
+    "od",
+    "paste",
+    "pgrep",
+    "popd",
+    "printenv",
+    "printf",
+    "ps",
+    "pushd",
+    "pwd",
+    "read",
+    "readlink",
+    "readonly",
+    "realpath",
+    "rev",
+    "rg",
+    "seq",
+    "set",
+    "sha1sum",
+    "sha256sum",
+    "sha512sum",
+    "shift",
+    "sleep",
+    "stat",
+    "strings",
+    "tac",
+    "tail",
+    "test",
+    "tldr",
+    "tree",
+    "true",
+    "type",
+    "uname",
+    "unexpand",
+    "uniq",
+    "unset",
+    "uptime",
+    "vmstat",
+    "wait",
+    "wc",
+    "whereis",
+    "which",
+    "whoami",
+    "xxd",
+    "yq",
+];
+
+/// 只读命令中会写文件或执行其他程序的选项
+///
+/// 带这些选项时命令不再按只读处理。短选项同时匹配合并写法（`-Hx`）和紧跟的值（`-ofile`），
+/// 长选项同时匹配 `--opt=value`。
+const UNSAFE_READ_ONLY_OPTIONS: &[(&str, &[&str])] = &[
+    ("bat", &["--pager"]),
+    ("date", &["-s", "--set"]),
+    ("fd", &["-x", "--exec", "-X", "--exec-batch"]),
+    ("history", &["-a", "-c", "-d", "-n", "-r", "-s", "-w"]),
+    ("info", &["-o", "--output"]),
+    ("less", &["-o", "-O", "--log-file", "--LOG-FILE"]),
+    ("man", &["-P", "--pager", "-H", "--html"]),
+    // `-v` 把结果赋给任意 shell 变量（包括 PATH）
+    ("printf", &["-v"]),
+    ("rg", &["--pre"]),
+    ("tree", &["-o"]),
+    ("yq", &["-i", "--inplace"]),
+];
+
+/// 会改变后续命令执行方式的环境变量
+///
+/// 在持久 shell 中设置它们可以让之后的只读命令执行任意程序。
+const SENSITIVE_VARIABLES: &[&str] = &[
+    "BASH_ENV",
+    "CDPATH",
+    "EDITOR",
+    "ENV",
+    "IFS",
+    "LESSCLOSE",
+    "LESSOPEN",
+    "MANPAGER",
+    "PAGER",
+    "PATH",
+    "PROMPT_COMMAND",
+    "PS4",
+    "RIPGREP_CONFIG_PATH",
+    "SHELLOPTS",
+    "BASHOPTS",
+    "VISUAL",
+];
+
+/// 同上，按前缀匹配
+const SENSITIVE_VARIABLE_PREFIXES: &[&str] = &["DYLD_", "GIT_", "LD_"];
+
+/// 网络命令
+const NETWORK_COMMANDS: &[&str] = &[
+    "aria2c",
+    "axel",
+    "curl",
+    "dig",
+    "ftp",
+    "gh",
+    "host",
+    "http",
+    "httpie",
+    "https",
+    "links",
+    "lynx",
+    "nc",
+    "ncat",
+    "netcat",
+    "npx",
+    "nslookup",
+    "ping",
+    "rsync",
+    "scp",
+    "sftp",
+    "ssh",
+    "telnet",
+    "traceroute",
+    "w3m",
+    "wget",
+    "whois",
+    "xh",
+];
+
+/// 破坏性命令
+const DESTRUCTIVE_COMMANDS: &[&str] = &[
+    "dd", "kill", "killall", "pkill", "rm", "rmdir", "shred", "truncate", "unlink",
+];
+
+/// 会执行 `-c` 参数中脚本的 shell
+const SHELLS: &[&str] = &["bash", "dash", "fish", "ksh", "sh", "zsh"];
+
+/// 解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl ParseError {
+    fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// 重定向
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// 重定向操作符（包含文件描述符前缀，例如 `2>`）
+    pub operator: String,
+    /// 重定向目标
+    pub target: String,
+}
+
+impl Redirect {
+    /// 是否会写入文件
+    ///
+    /// 输入重定向、文件描述符复制以及写入 `/dev/null` 等设备不算写文件。
+    pub fn writes_file(&self) -> bool {
+        let operator = self
+            .operator
+            .trim_start_matches(|c: char| c.is_ascii_digit());
+        if operator.starts_with('<') && operator != "<>" {
+            return false;
+        }
+        if operator.ends_with('&')
+            && (self.target == "-" || self.target.chars().all(|c| c.is_ascii_digit()))
+        {
+            return false;
+        }
+        !matches!(
+            self.target.as_str(),
+            "/dev/null" | "/dev/stdout" | "/dev/stderr" | "/dev/tty"
+        )
+    }
+}
+
+/// 简单命令（不含控制操作符的单个命令）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// 去掉引号后的单词（包括前导变量赋值）
+    pub words: Vec<String>,
+    /// 重定向
+    pub redirects: Vec<Redirect>,
+}
+
+impl SimpleCommand {
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.redirects.is_empty()
+    }
+
+    /// 命令文本（单词以空格连接，不含重定向），用于权限规则的前缀匹配
+    pub fn command_line(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// 去掉前导变量赋值和包装命令后实际执行的命令文本，用于匹配 deny 规则
+    pub fn effective_command_line(&self) -> String {
+        effective_words(&self.words).join(" ")
+    }
+
+    /// 是否有写入文件的重定向
+    pub fn writes_file(&self) -> bool {
+        self.redirects.iter().any(Redirect::writes_file)
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redirects = self
+            .redirects
+            .iter()
+            .map(|r| format!("{}{}", r.operator, r.target));
+        let parts: Vec<String> = self.words.iter().cloned().chain(redirects).collect();
+        f.write_str(&parts.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word { text: String, quoted: bool },
+    Operator(&'static str),
+    Redirect(String),
+    Newline,
+    OpenParen,
+    CloseParen,
+}
+
+/// 等待读取正文的 here-document
+#[derive(Debug)]
+struct Heredoc {
+    delimiter: String,
+    /// 结束符未加引号时正文中的命令替换会被执行
+    expand: bool,
+    /// `<<-` 会去掉正文行首的 tab
+    strip_tabs: bool,
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+    /// 命令替换中解析出的命令
+    nested: Vec<SimpleCommand>,
+    heredocs: Vec<Heredoc>,
+}
+
+impl Lexer {
+    fn new(input: &str, depth: usize) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            depth,
+            nested: Vec::new(),
+            heredocs: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        loop {
+            match self.peek() {
+                None => return Ok(None),
+                Some(' ' | '\t' | '\r') => self.pos += 1,
+                Some('\\') if self.peek_at(1) == Some('\n') => self.pos += 2,
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(_) => break,
+            }
+        }
+
+        let c = self.peek().unwrap_or_default();
+        let token = match c {
+            '\n' => {
+                self.pos += 1;
+                self.read_heredoc_bodies()?;
+                Token::Newline
+            }
+            '&' if self.starts_with("&&") => self.operator("&&"),
+            '&' if self.starts_with("&>>") => self.redirect(String::new(), "&>>"),
+            '&' if self.starts_with("&>") => self.redirect(String::new(), "&>"),
+            '&' => self.operator("&"),
+            '|' if self.starts_with("||") => self.operator("||"),
+            '|' if self.starts_with("|&") => self.operator("|&"),
+            '|' => self.operator("|"),
+            ';' => self.operator(";"),
+            '(' => {
+                self.pos += 1;
+                Token::OpenParen
+            }
+            ')' => {
+                self.pos += 1;
+                Token::CloseParen
+            }
+            '<' | '>' if self.peek_at(1) != Some('(') => self.lex_redirect(String::new()),
+            '0'..='9' => {
+                let digits: String = self.chars[self.pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                let after = self.peek_at(digits.len());
+                if matches!(after, Some('<' | '>')) && self.peek_at(digits.len() + 1) != Some('(') {
+                    self.pos += digits.len();
+                    self.lex_redirect(digits)
+                } else {
+                    self.lex_word()?
+                }
+            }
+            _ => self.lex_word()?,
+        };
+        Ok(Some(token))
+    }
+
+    fn operator(&mut self, operator: &'static str) -> Token {
+        self.pos += operator.len();
+        Token::Operator(operator)
+    }
+
+    fn redirect(&mut self, prefix: String, operator: &str) -> Token {
+        self.pos += operator.chars().count();
+        Token::Redirect(prefix + operator)
+    }
+
+    fn lex_redirect(&mut self, prefix: String) -> Token {
+        const OPERATORS: &[&str] = &["<<<", "<<-", "<<", "<>", "<&", "<", ">>", ">&", ">|", ">"];
+        let operator = OPERATORS
+            .iter()
+            .find(|op| self.starts_with(op))
+            .copied()
+            .unwrap_or(">");
+        self.redirect(prefix, operator)
+    }
+
+    fn lex_word(&mut self) -> Result<Token, ParseError> {
+        let mut text = String::new();
+        let mut quoted = false;
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '(' | ')' => break,
+                '<' | '>' => {
+                    if self.peek_at(1) != Some('(') {
+                        break;
+                    }
+                    // 进程替换 <(...) / >(...)
+                    self.pos += 1;
+                    let inner = self.capture_parens()?;
+                    self.parse_nested(&inner)?;
+                    text.push(c);
+                    text.push_str(&format!("({})", inner));
+                }
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\n') => self.pos += 1,
+                        Some(next) => {
+                            text.push(next);
+                            quoted = true;
+                            self.pos += 1;
+                        }
+                        None => {}
+                    }
+                }
+                '\'' => {
+                    quoted = true;
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            None => return Err(ParseError::new("unterminated single quote")),
+                            Some('\'') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some(ch) => {
+                                text.push(ch);
+                                self.pos += 1;
+                            }
+                        }
+                    }
+                }
+                '"' => {
+                    quoted = true;
+                    self.pos += 1;
+                    self.lex_double_quoted(&mut text)?;
+                }
+                '$' => self.lex_dollar(&mut text)?,
+                '`' => self.lex_backtick(&mut text)?,
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(Token::Word { text, quoted })
+    }
+
+    fn lex_double_quoted(&mut self, text: &mut String) -> Result<(), ParseError> {
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::new("unterminated double quote")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(next) = self.peek() {
+                        match next {
+                            '"' | '\\' | '$' | '`' => text.push(next),
+                            '\n' => {}
+                            _ => {
+                                text.push('\\');
+                                text.push(next);
+                            }
+                        }
+                        self.pos += 1;
+                    }
+                }
+                Some('$') => self.lex_dollar(text)?,
+                Some('`') => self.lex_backtick(text)?,
+                Some(ch) => {
+                    text.push(ch);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn lex_dollar(&mut self, text: &mut String) -> Result<(), ParseError> {
+        if self.starts_with("$((") {
+            // 算术展开，内容按字面保留
+            self.pos += 1;
+            let inner = self.capture_parens()?;
+            text.push_str(&format!("$({})", inner));
+        } else if self.starts_with("$(") {
+            self.pos += 1;
+            let inner = self.capture_parens()?;
+            self.parse_nested(&inner)?;
+            text.push_str(&format!("$({})", inner));
+        } else if self.starts_with("${") {
+            let start = self.pos;
+            let mut depth = 0;
+            loop {
+                match self.peek() {
+                    None => return Err(ParseError::new("unterminated parameter expansion")),
+                    Some('{') => depth += 1,
+                    Some('}') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            self.pos += 1;
+                            break;
+                        }
+                    }
+                    Some(_) => {}
+                }
+                self.pos += 1;
+            }
+            text.extend(&self.chars[start..self.pos]);
+        } else {
+            text.push('$');
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn lex_backtick(&mut self, text: &mut String) -> Result<(), ParseError> {
+        self.pos += 1;
+        let mut inner = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::new("unterminated backquote")),
+                Some('`') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\\')
+                    if self
+                        .peek_at(1)
+                        .is_some_and(|c| matches!(c, '`' | '\\' | '$')) =>
+                {
+                    inner.push(self.peek_at(1).unwrap_or_default());
+                    self.pos += 2;
+                }
+                Some(ch) => {
+                    inner.push(ch);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.parse_nested(&inner)?;
+        text.push_str(&format!("`{}`", inner));
+        Ok(())
+    }
+
+    /// 从当前的 `(` 开始读取到与之匹配的 `)`，返回括号内的原始文本
+    fn capture_parens(&mut self) -> Result<String, ParseError> {
+        let start = self.pos + 1;
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let inner = self.chars[start..self.pos].iter().collect();
+                        self.pos += 1;
+                        return Ok(inner);
+                    }
+                }
+                '\\' => self.pos += 1,
+                '\'' => {
+                    self.pos += 1;
+                    while self.peek().is_some_and(|c| c != '\'') {
+                        self.pos += 1;
+                    }
+                }
+                '"' => {
+                    self.pos += 1;
+                    while let Some(c) = self.peek() {
+                        match c {
+                            '"' => break,
+                            '\\' => self.pos += 2,
+                            _ => self.pos += 1,
+                        }
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(ParseError::new("unterminated command substitution"))
+    }
+
+    fn parse_nested(&mut self, script: &str) -> Result<(), ParseError> {
+        if self.depth >= MAX_NESTING {
+            return Err(ParseError::new("command substitution is nested too deeply"));
+        }
+        let commands = parse(script, self.depth + 1)?;
+        self.nested.extend(commands);
+        Ok(())
+    }
+
+    /// 跳过换行后的 here-document 正文，并解析其中会被执行的命令替换
+    fn read_heredoc_bodies(&mut self) -> Result<(), ParseError> {
+        for heredoc in std::mem::take(&mut self.heredocs) {
+            let mut body = String::new();
+            while self.peek().is_some() {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+                let line: String = self.chars[start..self.pos].iter().collect();
+                self.pos += 1;
+
+                let check = if heredoc.strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    line.as_str()
+                };
+                if check == heredoc.delimiter {
+                    break;
+                }
+                body.push_str(&line);
+                body.push('\n');
+            }
+            if heredoc.expand {
+                self.scan_substitutions(&body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scan_substitutions(&mut self, text: &str) -> Result<(), ParseError> {
+        let mut lexer = Lexer::new(text, self.depth);
+        let mut scratch = String::new();
+        while let Some(c) = lexer.peek() {
+            match c {
+                '\\' => lexer.pos += 2,
+                '$' => lexer.lex_dollar(&mut scratch)?,
+                '`' => lexer.lex_backtick(&mut scratch)?,
+                _ => lexer.pos += 1,
+            }
+        }
+        self.nested.extend(lexer.nested);
+        Ok(())
+    }
+}
+
+/// 在命令开头出现、本身不执行任何操作的 shell 关键字
+fn is_opening_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "{" | "!" | "if" | "then" | "else" | "elif" | "do" | "while" | "until"
+    )
+}
+
+/// 结束复合命令的 shell 关键字
+fn is_closing_keyword(word: &str) -> bool {
+    matches!(word, "}" | "fi" | "done" | "esac")
+}
+
+/// 将命令行拆分为简单命令
+///
+/// 子 shell、命令组、控制结构以及命令替换（`$(...)`、反引号、`<(...)`、
+/// 未加引号结束符的 here-document 正文）中的命令都会展开到结果中。
+pub fn split_command(input: &str) -> Result<Vec<SimpleCommand>, ParseError> {
+    parse(input, 0)
+}
+
+fn parse(input: &str, depth: usize) -> Result<Vec<SimpleCommand>, ParseError> {
+    let mut lexer = Lexer::new(input, depth);
+    let mut commands = Vec::new();
+    let mut current = SimpleCommand::default();
+    let mut parens = 0usize;
+    // 上一个 token 之后是否可以出现控制操作符
+    let mut can_end = false;
+    // 上一个控制操作符（`&&`、`||`、`|`）是否要求后面还有命令
+    let mut need_command = false;
+
+    let flush = |commands: &mut Vec<SimpleCommand>, current: &mut SimpleCommand| {
+        if !current.is_empty() {
+            commands.push(std::mem::take(current));
+        }
+    };
+
+    while let Some(token) = lexer.next_token()? {
+        match token {
+            Token::Word { text, quoted } => {
+                if current.is_empty() && !quoted {
+                    if is_opening_keyword(&text) {
+                        can_end = false;
+                        continue;
+                    }
+                    if is_closing_keyword(&text) {
+                        can_end = true;
+                        continue;
+                    }
+                }
+                current.words.push(text);
+                can_end = true;
+                need_command = false;
+            }
+            Token::Redirect(operator) => {
+                let Some(Token::Word { text, quoted }) = lexer.next_token()? else {
+                    return Err(ParseError::new(format!(
+                        "missing redirection target after `{}`",
+                        operator
+                    )));
+                };
+                let base = operator.trim_start_matches(|c: char| c.is_ascii_digit());
+                if base == "<<" || base == "<<-" {
+                    lexer.heredocs.push(Heredoc {
+                        delimiter: text.clone(),
+                        expand: !quoted,
+                        strip_tabs: base == "<<-",
+                    });
+                }
+                current.redirects.push(Redirect {
+                    operator,
+                    target: text,
+                });
+                can_end = true;
+                need_command = false;
+            }
+            Token::Operator(operator) => {
+                if !can_end {
+                    return Err(ParseError::new(format!(
+                        "syntax error near unexpected token `{}`",
+                        operator
+                    )));
+                }
+                flush(&mut commands, &mut current);
+                can_end = false;
+                need_command = matches!(operator, "&&" | "||" | "|" | "|&");
+            }
+            Token::Newline => {
+                flush(&mut commands, &mut current);
+                can_end = false;
+            }
+            Token::OpenParen => {
+                if !current.is_empty() {
+                    return Err(ParseError::new("syntax error near unexpected token `('"));
+                }
+                parens += 1;
+                can_end = false;
+            }
+            Token::CloseParen => {
+                if parens == 0 || need_command {
+                    return Err(ParseError::new("syntax error near unexpected token `)'"));
+                }
+                flush(&mut commands, &mut current);
+                parens -= 1;
+                can_end = true;
+            }
+        }
+    }
+
+    if parens > 0 {
+        return Err(ParseError::new("unbalanced parentheses"));
+    }
+    if need_command {
+        return Err(ParseError::new("command ends with an operator"));
+    }
+    flush(&mut commands, &mut current);
+    commands.extend(lexer.nested);
+    Ok(commands)
+}
+
+/// 命令类别（按风险从低到高排序）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandCategory {
+    /// 只读：不修改文件系统，也不访问网络
+    ReadOnly,
+    /// 修改文件系统或执行任意代码
+    Mutating,
+    /// 访问网络
+    Network,
+    /// 删除数据或不可逆地丢弃修改
+    Destructive,
+}
+
+impl fmt::Display for CommandCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CommandCategory::ReadOnly => "read-only",
+            CommandCategory::Mutating => "mutating",
+            CommandCategory::Network => "network",
+            CommandCategory::Destructive => "destructive",
+        })
+    }
+}
+
+/// 安全判定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SafetyVerdict {
+    /// 所有子命令都是只读的，可以直接执行
+    Allow,
+    /// 需要用户确认
+    Ask {
+        /// 需要确认的原因
+        reason: String,
+    },
+    /// 禁止执行
+    Deny {
+        /// 禁止的原因
+        reason: String,
+    },
+}
+
+/// 已分类的简单命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzedCommand {
+    /// 简单命令
+    pub command: SimpleCommand,
+    /// 类别
+    pub category: CommandCategory,
+}
+
+/// 命令分析结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandAnalysis {
+    /// 拆分出的简单命令及其类别
+    pub commands: Vec<AnalyzedCommand>,
+    /// 总体类别（所有子命令中风险最高者）
+    pub category: CommandCategory,
+    /// 判定
+    pub verdict: SafetyVerdict,
+}
+
+impl CommandAnalysis {
+    /// 是否为只读命令
+    pub fn is_read_only(&self) -> bool {
+        self.verdict == SafetyVerdict::Allow
+    }
+}
+
+/// 禁止规则
+#[derive(Debug, Clone)]
+struct DenyRule {
+    pattern: String,
+    words: Vec<glob::Pattern>,
+}
+
+impl DenyRule {
+    fn parse(pattern: &str) -> Option<Self> {
+        let words: Vec<glob::Pattern> = pattern
+            .split_whitespace()
+            .map(|word| {
+                glob::Pattern::new(word)
+                    .or_else(|_| glob::Pattern::new(&glob::Pattern::escape(word)))
+                    .ok()
+            })
+            .collect::<Option<_>>()?;
+        if words.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pattern: pattern.trim().to_string(),
+            words,
+        })
+    }
+
+    fn matches(&self, words: &[String]) -> bool {
+        let Some((program, args)) = words.split_first() else {
+            return false;
+        };
+        let Some((first, required)) = self.words.split_first() else {
+            return false;
+        };
+        first.matches(program_name(program))
+            && required
+                .iter()
+                .all(|pattern| args.iter().any(|arg| pattern.matches(arg)))
+    }
+}
+
+/// 单个命令的评估结果
+#[derive(Debug, Clone)]
+struct Assessment {
+    category: CommandCategory,
+    denied: Option<String>,
+}
+
+impl Assessment {
+    fn new(category: CommandCategory) -> Self {
+        Self {
+            category,
+            denied: None,
+        }
+    }
+
+    fn denied(reason: String) -> Self {
+        Self {
+            category: CommandCategory::Destructive,
+            denied: Some(reason),
+        }
+    }
+
+    fn raise(&mut self, category: CommandCategory) {
+        self.category = self.category.max(category);
+    }
+
+    fn merge(&mut self, other: Assessment) {
+        self.raise(other.category);
+        if self.denied.is_none() {
+            self.denied = other.denied;
+        }
+    }
+}
+
+/// Bash 命令安全策略
+#[derive(Debug, Clone)]
+pub struct CommandPolicy {
+    deny: Vec<DenyRule>,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandPolicy {
+    /// 创建使用内置禁止列表的策略
+    pub fn new() -> Self {
+        Self {
+            deny: DEFAULT_DENIED_COMMANDS
+                .iter()
+                .filter_map(|p| DenyRule::parse(p))
+                .collect(),
+        }
+    }
+
+    /// 追加禁止规则
+    pub fn with_denied_commands<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.deny.extend(
+            patterns
+                .into_iter()
+                .filter_map(|p| DenyRule::parse(p.as_ref())),
+        );
+        self
+    }
+
+    /// 根据项目配置创建策略（内置列表加上 `deniedCommands`）
+    pub fn from_project_config(config: &ProjectConfig) -> Self {
+        Self::new().with_denied_commands(&config.denied_commands)
+    }
+
+    /// 分析命令
+    pub fn analyze(&self, command: &str) -> CommandAnalysis {
+        let commands = match split_command(command) {
+            Ok(commands) => commands,
+            Err(e) => {
+                return CommandAnalysis {
+                    commands: Vec::new(),
+                    category: CommandCategory::Mutating,
+                    verdict: SafetyVerdict::Ask {
+                        reason: format!("Could not parse command: {}", e),
+                    },
+                }
+            }
+        };
+
+        let mut denied = None;
+        let mut riskiest: Option<&SimpleCommand> = None;
+        let mut analyzed = Vec::with_capacity(commands.len());
+        let mut category = CommandCategory::ReadOnly;
+        for command in &commands {
+            let assessment = self.assess(&command.words, &command.redirects, 0);
+            if denied.is_none() {
+                denied = assessment.denied;
+            }
+            if riskiest.is_none() || assessment.category > category {
+                riskiest = Some(command);
+                category = category.max(assessment.category);
+            }
+            analyzed.push(AnalyzedCommand {
+                command: command.clone(),
+                category: assessment.category,
+            });
+        }
+
+        let verdict = match (denied, riskiest) {
+            (Some(reason), _) => SafetyVerdict::Deny { reason },
+            (None, Some(command)) if category > CommandCategory::ReadOnly => SafetyVerdict::Ask {
+                reason: format!("`{}` is a {} command", command, category),
+            },
+            _ => SafetyVerdict::Allow,
+        };
+
+        CommandAnalysis {
+            commands: analyzed,
+            category,
+            verdict,
+        }
+    }
+
+    fn deny_rule_for(&self, words: &[String]) -> Option<&DenyRule> {
+        self.deny.iter().find(|rule| rule.matches(words))
+    }
+
+    fn assess(&self, words: &[String], redirects: &[Redirect], depth: usize) -> Assessment {
+        let effective = effective_words(words);
+        for candidate in [words, effective] {
+            if let Some(rule) = self.deny_rule_for(candidate) {
+                return Assessment::denied(format!(
+                    "`{}` is blocked by the deny list (`{}`)",
+                    candidate.join(" "),
+                    rule.pattern
+                ));
+            }
+        }
+
+        let mut assessment = Assessment::new(CommandCategory::ReadOnly);
+        if redirects.iter().any(Redirect::writes_file) {
+            assessment.raise(CommandCategory::Mutating);
+        }
+        // 前导赋值与 `env` 的赋值参数
+        let prefix = &words[..words.len() - effective.len()];
+        if prefix.iter().any(|word| sets_sensitive_variable(word)) {
+            assessment.raise(CommandCategory::Mutating);
+        }
+
+        let Some((program, args)) = effective.split_first() else {
+            return assessment;
+        };
+        let name = program_name(program);
+
+        if let Some(target) = catastrophic_rm_target(name, args) {
+            return Assessment::denied(format!("Refusing to recursively delete `{}`", target));
+        }
+
+        if let Some(script) = nested_script(name, args) {
+            match parse(&script, depth + 1) {
+                Ok(commands) if depth < MAX_NESTING => {
+                    for command in commands {
+                        assessment.merge(self.assess(
+                            &command.words,
+                            &command.redirects,
+                            depth + 1,
+                        ));
+                    }
+                }
+                _ => assessment.raise(CommandCategory::Mutating),
+            }
+            return assessment;
+        }
+
+        if name == "find" {
+            for inner in find_exec_commands(args) {
+                if depth < MAX_NESTING {
+                    assessment.merge(self.assess(inner, &[], depth + 1));
+                }
+            }
+        }
+
+        assessment.raise(classify_program(name, args));
+        assessment
+    }
+}
+
+/// 程序名（去掉路径前缀）
+fn program_name(word: &str) -> &str {
+    word.rsplit('/').next().unwrap_or(word)
+}
+
+/// 是否为变量赋值（`NAME=value`）
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// 是否为对 [`SENSITIVE_VARIABLES`] 的赋值
+fn sets_sensitive_variable(word: &str) -> bool {
+    if !is_assignment(word) {
+        return false;
+    }
+    let name = word.split_once('=').map_or(word, |(name, _)| name);
+    SENSITIVE_VARIABLES.contains(&name)
+        || SENSITIVE_VARIABLE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// 包装命令需要跳过的单词数（包括包装命令本身），非包装命令返回 `None`
+fn wrapper_skip(name: &str, args: &[String]) -> Option<usize> {
+    let options_with_values: &[&str] = match name {
+        "env" => &["-u", "--unset", "-C", "--chdir"],
+        "nice" => &["-n", "--adjustment"],
+        "ionice" => &["-c", "-n", "-p"],
+        "timeout" => &["-s", "--signal", "-k", "--kill-after"],
+        "stdbuf" => &["-i", "-o", "-e"],
+        "watch" => &["-n", "--interval"],
+        "xargs" => &[
+            "-n",
+            "-I",
+            "-P",
+            "-d",
+            "-L",
+            "-s",
+            "-E",
+            "-a",
+            "--max-args",
+            "--replace",
+            "--max-procs",
+            "--delimiter",
+        ],
+        "nohup" | "command" | "builtin" | "exec" | "time" | "caffeinate" => &[],
+        _ => return None,
+    };
+
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if name == "env" && (is_assignment(arg) || arg == "-") {
+            i += 1;
+            continue;
+        }
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if !arg.starts_with('-') {
+            break;
+        }
+        i += 1;
+        if options_with_values.contains(&arg.as_str()) {
+            i += 1;
+        }
+    }
+    if name == "timeout" {
+        // 超时时长
+        i += 1;
+    }
+    Some(1 + i)
+}
+
+/// 去掉前导变量赋值和包装命令（`env`、`nohup`、`timeout`、`xargs` 等），
+/// 返回实际执行的命令。包装命令后面没有命令时返回包装命令本身。
+fn effective_words(words: &[String]) -> &[String] {
+    let mut rest = words;
+    loop {
+        while rest.first().is_some_and(|w| is_assignment(w)) {
+            rest = &rest[1..];
+        }
+        let Some((program, args)) = rest.split_first() else {
+            return rest;
+        };
+        match wrapper_skip(program_name(program), args) {
+            Some(skip) if skip < rest.len() => rest = &rest[skip..],
+            _ => return rest,
+        }
+    }
+}
+
+/// `rm -r` 删除根目录或家目录时返回目标
+fn catastrophic_rm_target<'a>(name: &str, args: &'a [String]) -> Option<&'a str> {
+    if name != "rm" {
+        return None;
+    }
+    let recursive = args.iter().any(|a| {
+        a == "--recursive" || (a.starts_with('-') && !a.starts_with("--") && a.contains(['r', 'R']))
+    });
+    if !recursive {
+        return None;
+    }
+    args.iter()
+        .map(String::as_str)
+        .filter(|a| !a.starts_with('-'))
+        .find(|a| {
+            matches!(
+                a.trim_end_matches('*').trim_end_matches('/'),
+                "" | "~" | "$HOME" | "${HOME}"
+            )
+        })
+}
+
+/// `bash -c SCRIPT`、`eval ARGS` 等会执行的内嵌脚本
+fn nested_script(name: &str, args: &[String]) -> Option<String> {
+    if name == "eval" {
+        return Some(args.join(" "));
+    }
+    if SHELLS.contains(&name) {
+        let flag = args
+            .iter()
+            .position(|a| a.starts_with('-') && !a.starts_with("--") && a.contains('c'))?;
+        return args.get(flag + 1).cloned();
+    }
+    None
+}
+
+/// `find -exec` / `-execdir` / `-ok` 执行的命令
+fn find_exec_commands(args: &[String]) -> Vec<&[String]> {
+    let mut commands = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if matches!(args[i].as_str(), "-exec" | "-execdir" | "-ok" | "-okdir") {
+            let start = i + 1;
+            let end = args[start..]
+                .iter()
+                .position(|a| a == ";" || a == "+")
+                .map_or(args.len(), |p| start + p);
+            commands.push(&args[start..end]);
+            i = end;
+        }
+        i += 1;
+    }
+    commands
+}
+
+fn has_any(args: &[String], flags: &[&str]) -> bool {
+    args.iter().any(|a| flags.contains(&a.as_str()))
+}
+
+/// `--` 之前是否出现某个选项
+///
+/// 长选项匹配 `--opt` 与 `--opt=value`；短选项匹配合并写法与紧跟的值（`-Hx`、`-ofile`）。
+fn has_option(args: &[String], option: &str) -> bool {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| match option.strip_prefix("--") {
+            Some(long) => arg
+                .strip_prefix("--")
+                .and_then(|a| a.strip_prefix(long))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('=')),
+            None => {
+                let short = &option[1..];
+                arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains(short)
+            }
+        })
+}
+
+fn has_any_option(args: &[String], options: &[&str]) -> bool {
+    options.iter().any(|option| has_option(args, option))
+}
+
+/// 位置参数（跳过选项以及 `options_with_values` 中选项单独给出的值）
+fn positional_args<'a>(args: &'a [String], options_with_values: &[&str]) -> Vec<&'a String> {
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            positional.extend(iter);
+            break;
+        }
+        if arg.starts_with('-') && arg != "-" {
+            if options_with_values.contains(&arg.as_str()) {
+                iter.next();
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+    positional
+}
+
+/// sed 的脚本参数；脚本来自文件（`-f`）时无法检查，返回 `None`
+fn sed_scripts(args: &[String]) -> Option<Vec<&str>> {
+    let mut scripts = Vec::new();
+    let mut positional = None;
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        i += 1;
+        if arg == "--" {
+            positional = positional.or(args.get(i).map(String::as_str));
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some(("expression", script)) => scripts.push(script),
+                Some(("file", _)) => return None,
+                Some(_) => {}
+                None => match long {
+                    "expression" => {
+                        scripts.push(args.get(i)?.as_str());
+                        i += 1;
+                    }
+                    "file" => return None,
+                    "line-length" => i += 1,
+                    _ => {}
+                },
+            }
+        } else if let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) {
+            for (pos, flag) in flags.char_indices() {
+                let attached = &flags[pos + 1..];
+                match flag {
+                    'e' => {
+                        if attached.is_empty() {
+                            scripts.push(args.get(i)?.as_str());
+                            i += 1;
+                        } else {
+                            scripts.push(attached);
+                        }
+                        break;
+                    }
+                    'f' => return None,
+                    'l' => {
+                        if attached.is_empty() {
+                            i += 1;
+                        }
+                        break;
+                    }
+                    // `-i` 可以紧跟备份后缀
+                    'i' => break,
+                    _ => {}
+                }
+            }
+        } else if positional.is_none() {
+            positional = Some(arg.as_str());
+        }
+    }
+    if scripts.is_empty() {
+        scripts.extend(positional);
+    }
+    Some(scripts)
+}
+
+/// 跳过以 `delimiter` 结尾的部分（处理反斜杠转义），返回其后的位置
+fn skip_delimited(chars: &[char], mut i: usize, delimiter: char) -> usize {
+    while let Some(&c) = chars.get(i) {
+        if c == '\\' {
+            i += 2;
+            continue;
+        }
+        i += 1;
+        if c == delimiter {
+            return i;
+        }
+    }
+    chars.len()
+}
+
+/// sed 脚本是否写文件（`w`、`W`、`s///w`）或执行命令（`e`、`s///e`）
+fn sed_script_is_unsafe(script: &str) -> bool {
+    let chars: Vec<char> = script.chars().collect();
+    let skip_to = |i: usize, stops: &[char]| {
+        chars[i..]
+            .iter()
+            .position(|c| stops.contains(c))
+            .map_or(chars.len(), |p| i + p)
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        // 跳过命令分隔符与地址（行号、`$`、`/regex/`、`\cregexc` 及其 `I`/`M` 修饰）
+        match chars[i] {
+            c if c.is_whitespace() || c.is_ascii_digit() => i += 1,
+            ';' | '{' | '}' | ',' | '!' | '$' | '~' | '+' | 'I' | 'M' => i += 1,
+            '/' => i = skip_delimited(&chars, i + 1, '/'),
+            '\\' if i + 1 < chars.len() => i = skip_delimited(&chars, i + 2, chars[i + 1]),
+            command => {
+                i += 1;
+                match command {
+                    'e' | 'w' | 'W' => return true,
+                    's' | 'y' => {
+                        let Some(&delimiter) = chars.get(i) else {
+                            break;
+                        };
+                        i = skip_delimited(&chars, i + 1, delimiter);
+                        i = skip_delimited(&chars, i, delimiter);
+                        if command == 's' {
+                            let end = skip_to(i, &[';', '\n', '}', ' ', '\t']);
+                            if chars[i..end].iter().any(|f| matches!(f, 'w' | 'e')) {
+                                return true;
+                            }
+                            i = end;
+                        }
+                    }
+                    // 文本、文件名与标签延续到行尾
+                    'a' | 'i' | 'c' | 'r' | 'R' | ':' => i = skip_to(i, &['\n']),
+                    'b' | 't' | 'T' => i = skip_to(i, &[';', '\n']),
+                    _ => {}
+                }
+            }
+        }
+    }
+    false
+}
+
+/// awk 的程序文本；程序来自文件或加载扩展时无法检查，返回 `None`
+fn awk_program(args: &[String]) -> Option<&str> {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        i += 1;
+        if arg == "--" {
+            return args.get(i).map(String::as_str);
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            return Some(arg);
+        }
+        let (name, attached) = match arg.strip_prefix("--") {
+            Some(long) => match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            },
+            None => (arg.get(1..2).unwrap_or_default(), arg.len() > 2),
+        };
+        match name {
+            "f" | "file" | "E" | "exec" | "i" | "include" | "l" | "load" => return None,
+            "F" | "field-separator" | "v" | "assign" if !attached => i += 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// awk 程序是否执行命令（`system()`、管道）或写文件（`>`）
+fn awk_program_is_unsafe(program: &str) -> bool {
+    program.contains("system") || program.contains('|') || program.contains('>')
+}
+
+/// 跳过选项后的第一个参数（子命令）及其后的参数
+fn subcommand<'a>(
+    args: &'a [String],
+    options_with_values: &[&str],
+) -> Option<(&'a str, &'a [String])> {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if !arg.starts_with('-') {
+            return Some((arg.as_str(), &args[i + 1..]));
+        }
+        i += if options_with_values.contains(&arg.as_str()) {
+            2
+        } else {
+            1
+        };
+    }
+    None
+}
+
+fn classify_program(name: &str, args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match name {
+        "git" => classify_git(args),
+        "cargo" => classify_cargo(args),
+        "npm" | "pnpm" | "yarn" | "bun" => classify_js_package_manager(name, args),
+        "pip" | "pip3" | "uv" | "pipx" | "poetry" => classify_python_package_manager(args),
+        "go" => classify_go(args),
+        "docker" | "podman" => classify_docker(args),
+        "brew" | "apt" | "apt-get" | "dnf" | "yum" => classify_system_package_manager(args),
+        "sed" => {
+            let in_place = args.iter().any(|a| {
+                a.starts_with("--in-place")
+                    || (a.starts_with('-') && !a.starts_with("--") && a.contains('i'))
+            });
+            let safe_scripts = sed_scripts(args)
+                .is_some_and(|scripts| !scripts.into_iter().any(sed_script_is_unsafe));
+            if in_place || !safe_scripts {
+                Mutating
+            } else {
+                ReadOnly
+            }
+        }
+        "sort" => {
+            if args
+                .iter()
+                .any(|a| a.starts_with("-o") || a.starts_with("--output"))
+            {
+                Mutating
+            } else {
+                ReadOnly
+            }
+        }
+        "awk" | "gawk" | "mawk" => match awk_program(args) {
+            Some(program) if !awk_program_is_unsafe(program) => ReadOnly,
+            _ => Mutating,
+        },
+        "export" | "declare" | "readonly" | "local" => {
+            if args.iter().any(|a| sets_sensitive_variable(a)) {
+                Mutating
+            } else {
+                ReadOnly
+            }
+        }
+        // `+!cmd` 与 `+|cmd` 启动时执行 shell 命令
+        "less"
+            if args
+                .iter()
+                .any(|a| a.starts_with('+') && a.contains(['!', '|'])) =>
+        {
+            Mutating
+        }
+        "tee" => {
+            if args
+                .iter()
+                .filter(|a| !a.starts_with('-'))
+                .all(|a| a == "/dev/null")
+            {
+                ReadOnly
+            } else {
+                Mutating
+            }
+        }
+        "find" => {
+            if has_any(args, &["-delete"]) {
+                Destructive
+            } else if has_any(args, &["-fprint", "-fprint0", "-fprintf", "-fls"]) {
+                Mutating
+            } else {
+                ReadOnly
+            }
+        }
+        // 只剩包装命令本身，例如 `xargs` 默认执行 echo
+        "xargs" => ReadOnly,
+        // 读入指定的变量（可以是 PATH 等）；不带变量名时只设置 REPLY
+        "read" if args.iter().any(|a| !a.starts_with('-')) => Mutating,
+        // 第二个位置参数是输出文件
+        "uniq" if positional_args(args, &["-f", "-s", "-w"]).len() > 1 => Mutating,
+        "xxd"
+            if positional_args(
+                args,
+                &[
+                    "-c",
+                    "-cols",
+                    "-g",
+                    "-groupsize",
+                    "-l",
+                    "-len",
+                    "-n",
+                    "-name",
+                    "-o",
+                    "-s",
+                    "-seek",
+                ],
+            )
+            .len()
+                > 1 =>
+        {
+            Mutating
+        }
+        _ if name.starts_with("mkfs") => Destructive,
+        _ if READ_ONLY_COMMANDS.contains(&name) => {
+            let unsafe_options = UNSAFE_READ_ONLY_OPTIONS
+                .iter()
+                .find(|(command, _)| *command == name)
+                .is_some_and(|(_, options)| has_any_option(args, options));
+            if unsafe_options {
+                Mutating
+            } else {
+                ReadOnly
+            }
+        }
+        _ if NETWORK_COMMANDS.contains(&name) => Network,
+        _ if DESTRUCTIVE_COMMANDS.contains(&name) => Destructive,
+        _ => Mutating,
+    }
+}
+
+fn classify_git(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    let Some((sub, rest)) = subcommand(args, &["-C", "-c", "--git-dir", "--work-tree"]) else {
+        return ReadOnly;
+    };
+    // `-c core.fsmonitor=cmd`、`-c core.pager=cmd` 等配置可以让任何子命令执行程序
+    let global = &args[..args.len() - rest.len() - 1];
+    if has_any_option(global, &["-c", "--config-env", "--exec-path"]) {
+        return Mutating;
+    }
+    let has = |flags: &[&str]| has_any(rest, flags);
+    let positional = rest.iter().filter(|a| !a.starts_with('-')).count();
+
+    match sub {
+        "diff" | "log" | "show" | "whatchanged" | "format-patch"
+            if has_option(rest, "--output") =>
+        {
+            Mutating
+        }
+        "grep" if has_any_option(rest, &["-O", "--open-files-in-pager"]) => Mutating,
+        "status" | "log" | "diff" | "show" | "rev-parse" | "ls-files" | "ls-tree" | "blame"
+        | "describe" | "shortlog" | "grep" | "cat-file" | "rev-list" | "show-ref"
+        | "whatchanged" | "help" | "version" | "merge-base" | "name-rev" | "for-each-ref"
+        | "check-ignore" | "count-objects" => ReadOnly,
+        "fetch" | "pull" | "clone" | "ls-remote" | "submodule" => Network,
+        "push" => {
+            let forced = has(&[
+                "--force",
+                "-f",
+                "--force-with-lease",
+                "--delete",
+                "-d",
+                "--mirror",
+            ]) || rest
+                .iter()
+                .any(|a| a.starts_with('+') || a.starts_with(':'));
+            if forced {
+                Destructive
+            } else {
+                Network
+            }
+        }
+        "reset" if has(&["--hard", "--merge", "--keep"]) => Destructive,
+        "clean" if has(&["-n", "--dry-run"]) => ReadOnly,
+        "clean" => Destructive,
+        "checkout" if has(&["--", ".", "-f", "--force"]) => Destructive,
+        "restore" if has(&["--staged", "-S"]) && !has(&["--worktree", "-W"]) => Mutating,
+        "restore" => Destructive,
+        "switch" if has(&["--discard-changes", "-f", "--force"]) => Destructive,
+        "branch" => {
+            if has(&["-D"]) || (has(&["-d", "--delete"]) && has(&["-f", "--force"])) {
+                Destructive
+            } else {
+                let modifies = has(&[
+                    "-d",
+                    "--delete",
+                    "-m",
+                    "-M",
+                    "-c",
+                    "-C",
+                    "-u",
+                    "--set-upstream-to",
+                    "--unset-upstream",
+                ]);
+                let lists = has(&[
+                    "--list",
+                    "-l",
+                    "--contains",
+                    "--merged",
+                    "--no-merged",
+                    "--points-at",
+                ]);
+                // 带分支名且不是列出分支时会创建分支
+                if modifies || (positional > 0 && !lists) {
+                    Mutating
+                } else {
+                    ReadOnly
+                }
+            }
+        }
+        "tag" => {
+            if positional == 0 || has(&["-l", "--list"]) {
+                ReadOnly
+            } else {
+                Mutating
+            }
+        }
+        "remote" => match rest.first().map(String::as_str) {
+            None | Some("-v" | "--verbose" | "show" | "get-url") => ReadOnly,
+            Some(_) => Mutating,
+        },
+        "config" => {
+            let writes = has_any_option(
+                rest,
+                &[
+                    "--unset",
+                    "--unset-all",
+                    "--add",
+                    "--replace-all",
+                    "--rename-section",
+                    "--remove-section",
+                    "--edit",
+                    "-e",
+                ],
+            ) || matches!(
+                rest.iter()
+                    .find(|a| !a.starts_with('-'))
+                    .map(String::as_str),
+                Some("set" | "unset" | "edit" | "rename-section" | "remove-section")
+            );
+            if writes {
+                Mutating
+            } else if has(&["--get", "--get-all", "--get-regexp", "--list", "-l"])
+                || positional <= 1
+            {
+                ReadOnly
+            } else {
+                Mutating
+            }
+        }
+        "stash" => match rest.first().map(String::as_str) {
+            Some("list" | "show") => ReadOnly,
+            Some("drop" | "clear") => Destructive,
+            _ => Mutating,
+        },
+        "reflog" => match rest.first().map(String::as_str) {
+            Some("expire" | "delete") => Destructive,
+            _ => ReadOnly,
+        },
+        "filter-branch" | "filter-repo" => Destructive,
+        "update-ref" if has(&["-d"]) => Destructive,
+        _ => Mutating,
+    }
+}
+
+fn classify_cargo(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &["-Z", "--config", "--color"]) {
+        None => ReadOnly,
+        Some((
+            "metadata" | "tree" | "version" | "help" | "locate-project" | "pkgid"
+            | "verify-project",
+            _,
+        )) => ReadOnly,
+        Some(("fmt", rest)) if has_any(rest, &["--check"]) => ReadOnly,
+        Some((
+            "search" | "install" | "publish" | "login" | "owner" | "yank" | "fetch" | "update"
+            | "add",
+            _,
+        )) => Network,
+        Some(_) => Mutating,
+    }
+}
+
+fn classify_js_package_manager(name: &str, args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &[]) {
+        // `yarn` 和 `bun` 不带参数时会安装依赖
+        None if matches!(name, "yarn" | "bun") => Network,
+        None => ReadOnly,
+        Some(("ls" | "list" | "why" | "help" | "root" | "bin" | "prefix", _)) => ReadOnly,
+        Some((
+            "install" | "i" | "add" | "ci" | "update" | "up" | "upgrade" | "publish" | "login"
+            | "view" | "info" | "outdated" | "audit" | "search" | "dlx" | "exec" | "create" | "x",
+            _,
+        )) => Network,
+        Some(_) => Mutating,
+    }
+}
+
+fn classify_python_package_manager(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &[]) {
+        None => ReadOnly,
+        Some(("list" | "show" | "freeze" | "check" | "help" | "tree", _)) => ReadOnly,
+        Some((
+            "install" | "download" | "add" | "sync" | "lock" | "update" | "search" | "run",
+            _,
+        )) => Network,
+        Some(_) => Mutating,
+    }
+}
+
+fn classify_go(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &[]) {
+        None => ReadOnly,
+        Some(("version" | "env" | "list" | "doc" | "vet" | "help", _)) => ReadOnly,
+        Some(("get" | "install", _)) => Network,
+        Some(("mod", rest)) if has_any(rest, &["download", "tidy"]) => Network,
+        Some(_) => Mutating,
+    }
+}
+
+fn classify_docker(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &["-H", "--host", "--context", "-c"]) {
+        None => ReadOnly,
+        Some((
+            "ps" | "images" | "inspect" | "logs" | "version" | "info" | "top" | "stats" | "history"
+            | "port",
+            _,
+        )) => ReadOnly,
+        Some(("pull" | "push" | "login" | "search", _)) => Network,
+        Some(("rm" | "rmi" | "kill", _)) => Destructive,
+        Some(("system" | "volume" | "image" | "container" | "network", rest))
+            if has_any(rest, &["prune", "rm"]) =>
+        {
+            Destructive
+        }
+        Some(_) => Mutating,
+    }
+}
+
+fn classify_system_package_manager(args: &[String]) -> CommandCategory {
+    use CommandCategory::*;
+
+    match subcommand(args, &[]) {
+        None => ReadOnly,
+        Some(("list" | "info" | "show" | "policy" | "--version", _)) => ReadOnly,
+        Some(("install" | "update" | "upgrade" | "search" | "fetch" | "download", _)) => Network,
+        Some(("remove" | "uninstall" | "purge" | "autoremove", _)) => Destructive,
+        Some(_) => Mutating,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use CommandCategory::*;
+
+    fn displays(input: &str) -> Vec<String> {
+        split_command(input)
+            .unwrap_or_else(|e| panic!("failed to parse {:?}: {}", input, e))
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_split_command() {
+        let cases: &[(&str, &[&str])] = &[
+            ("ls -la", &["ls -la"]),
+            ("git status && git diff", &["git status", "git diff"]),
+            ("cat a | grep b | wc -l", &["cat a", "grep b", "wc -l"]),
+            ("a; b || c & d", &["a", "b", "c", "d"]),
+            ("a |& b", &["a", "b"]),
+            ("a\nb\n\nc", &["a", "b", "c"]),
+            ("(cd src && ls) > out.txt", &["cd src", "ls", ">out.txt"]),
+            ("{ echo a; echo b; } > log", &["echo a", "echo b", ">log"]),
+            ("echo \"a && b\"", &["echo a && b"]),
+            ("echo 'x;y' \"|\"", &["echo x;y |"]),
+            ("echo a\\;b", &["echo a;b"]),
+            (
+                "echo $(rm -rf build)",
+                &["echo $(rm -rf build)", "rm -rf build"],
+            ),
+            ("echo `whoami`", &["echo `whoami`", "whoami"]),
+            ("echo \"$(date)\"", &["echo $(date)", "date"]),
+            (
+                "echo $(echo $(id))",
+                &["echo $(echo $(id))", "echo $(id)", "id"],
+            ),
+            ("echo $((1 + 2))", &["echo $((1 + 2))"]),
+            ("echo ${HOME:-/tmp}", &["echo ${HOME:-/tmp}"]),
+            (
+                "diff <(ls a) <(ls b)",
+                &["diff <(ls a) <(ls b)", "ls a", "ls b"],
+            ),
+            ("ls 2>&1 | head", &["ls 2>&1", "head"]),
+            ("ls &> log", &["ls &>log"]),
+            ("ls 2>/dev/null >> out", &["ls 2>/dev/null >>out"]),
+            ("a \\\n  && b", &["a", "b"]),
+            ("# comment\nls # trailing", &["ls"]),
+            ("FOO=1 make", &["FOO=1 make"]),
+            (
+                "if [ -f x ]; then cat x; else echo no; fi",
+                &["[ -f x ]", "cat x", "echo no"],
+            ),
+            (
+                "while true; do sleep 1; done && echo ok",
+                &["true", "sleep 1", "echo ok"],
+            ),
+            ("! grep -q x f", &["grep -q x f"]),
+            (
+                "cat <<EOF\nhello; rm x\nEOF\necho done",
+                &["cat <<EOF", "echo done"],
+            ),
+            ("cat <<EOF\n$(rm x)\nEOF", &["cat <<EOF", "rm x"]),
+            ("cat <<'EOF'\n$(rm x)\nEOF", &["cat <<EOF"]),
+            ("cat <<-EOF\n\tbody\n\tEOF\nls", &["cat <<-EOF", "ls"]),
+            ("grep x <<< \"$(ls)\"", &["grep x <<<$(ls)", "ls"]),
+            ("", &[]),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(&displays(input), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_split_command_errors() {
+        let cases = [
+            "echo 'unterminated",
+            "echo \"unterminated",
+            "echo `unterminated",
+            "a &&",
+            "| b",
+            "a && && b",
+            "; a",
+            "(a",
+            "a)",
+            "echo $(ls",
+            "ls >",
+            "f() { :; }",
+        ];
+        for input in cases {
+            assert!(split_command(input).is_err(), "expected error: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_redirect_writes_file() {
+        let cases = [
+            (">", "out.txt", true),
+            (">>", "out.txt", true),
+            ("2>", "err.log", true),
+            ("&>", "all.log", true),
+            (">", "/dev/null", false),
+            ("2>&", "1", false),
+            (">&", "file", true),
+            ("<", "in.txt", false),
+            ("<<<", "text", false),
+            ("<>", "file", true),
+        ];
+        for (operator, target, expected) in cases {
+            let redirect = Redirect {
+                operator: operator.to_string(),
+                target: target.to_string(),
+            };
+            assert_eq!(redirect.writes_file(), expected, "{}{}", operator, target);
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        let cases: &[(&str, CommandCategory)] = &[
+            // 只读
+            ("ls -la", ReadOnly),
+            ("cat README.md | grep foo | wc -l", ReadOnly),
+            ("git status", ReadOnly),
+            ("git log --oneline -5", ReadOnly),
+            ("git diff HEAD~1", ReadOnly),
+            ("git -C sub status", ReadOnly),
+            ("git --no-pager show HEAD", ReadOnly),
+            ("git branch", ReadOnly),
+            ("git branch -a", ReadOnly),
+            ("git branch --list 'feat*'", ReadOnly),
+            ("git tag", ReadOnly),
+            ("git remote -v", ReadOnly),
+            ("git stash list", ReadOnly),
+            ("git config --get user.name", ReadOnly),
+            ("git config user.name", ReadOnly),
+            ("git clean -n", ReadOnly),
+            ("find . -name '*.rs'", ReadOnly),
+            ("find . -name x -exec grep foo {} \\;", ReadOnly),
+            ("rg TODO src", ReadOnly),
+            ("sed -n 1,10p file", ReadOnly),
+            ("sort file | uniq -c", ReadOnly),
+            ("awk '{print $1}' file", ReadOnly),
+            ("echo hi 2>/dev/null", ReadOnly),
+            ("ls > /dev/null 2>&1", ReadOnly),
+            ("cd src && ls", ReadOnly),
+            ("FOO=1 env", ReadOnly),
+            ("env", ReadOnly),
+            ("cargo tree", ReadOnly),
+            ("cargo fmt --check", ReadOnly),
+            ("npm ls", ReadOnly),
+            ("docker ps", ReadOnly),
+            ("echo $(pwd)", ReadOnly),
+            ("jq .name package.json", ReadOnly),
+            ("test -f x && cat x", ReadOnly),
+            ("ls | xargs -n1 echo", ReadOnly),
+            ("ls | xargs", ReadOnly),
+            ("/bin/ls", ReadOnly),
+            ("tee /dev/null", ReadOnly),
+            ("for f in a b; do echo $f; done", ReadOnly),
+            ("bash -c 'ls | wc -l'", ReadOnly),
+            ("export FOO=bar", ReadOnly),
+            ("", ReadOnly),
+            // 修改
+            ("touch a", Mutating),
+            ("mkdir -p a/b", Mutating),
+            ("echo hi > out.txt", Mutating),
+            ("echo hi >> out.txt", Mutating),
+            ("ls &> log", Mutating),
+            ("(ls) > out", Mutating),
+            ("cat a | tee b", Mutating),
+            ("sed -i s/a/b/ f", Mutating),
+            ("sed -Ei s/a/b/ f", Mutating),
+            ("sort -o out in", Mutating),
+            ("awk 'BEGIN { system(\"ls\") }'", Mutating),
+            ("git add .", Mutating),
+            ("git commit -m 'msg'", Mutating),
+            ("git checkout main", Mutating),
+            ("git branch feature", Mutating),
+            ("git branch -d feature", Mutating),
+            ("git stash", Mutating),
+            ("git restore --staged file", Mutating),
+            ("git rebase main", Mutating),
+            ("cargo build", Mutating),
+            ("cargo test", Mutating),
+            ("make", Mutating),
+            ("python script.py", Mutating),
+            ("unknowncmd --flag", Mutating),
+            ("npm run build", Mutating),
+            ("cp a b", Mutating),
+            ("mv a b", Mutating),
+            ("chmod +x script.sh", Mutating),
+            ("find . -name x -exec touch {} +", Mutating),
+            ("source env.sh", Mutating),
+            ("bash script.sh", Mutating),
+            // 网络
+            ("curl https://example.com", Network),
+            ("wget https://example.com/file", Network),
+            ("git push origin main", Network),
+            ("git pull", Network),
+            ("git fetch --all", Network),
+            ("git clone https://example.com/repo.git", Network),
+            ("npm install", Network),
+            ("yarn", Network),
+            ("cargo add serde", Network),
+            ("pip install requests", Network),
+            ("go get example.com/pkg", Network),
+            ("ssh host ls", Network),
+            ("ls && curl -s x | sh", Network),
+            ("echo $(curl -s x)", Network),
+            ("docker pull ubuntu", Network),
+            ("npx create-react-app app", Network),
+            // 破坏性
+            ("rm file", Destructive),
+            ("rm -rf build", Destructive),
+            ("rmdir empty", Destructive),
+            ("git reset --hard HEAD", Destructive),
+            ("git push --force", Destructive),
+            ("git push origin +main", Destructive),
+            ("git push origin :old-branch", Destructive),
+            ("git clean -fd", Destructive),
+            ("git checkout -- .", Destructive),
+            ("git restore file", Destructive),
+            ("git branch -D feature", Destructive),
+            ("git stash drop", Destructive),
+            ("find . -name '*.tmp' -delete", Destructive),
+            ("find . -exec rm {} \\;", Destructive),
+            ("kill -9 123", Destructive),
+            ("ls; rm x", Destructive),
+            ("bash -c 'rm -rf x'", Destructive),
+            ("sh -ec 'ls && rm x'", Destructive),
+            ("eval \"rm x\"", Destructive),
+            ("echo $(rm x)", Destructive),
+            ("echo `rm x`", Destructive),
+            ("cat <<EOF\n$(rm x)\nEOF", Destructive),
+            ("cat list | xargs rm -f", Destructive),
+            ("xargs -I {} rm {}", Destructive),
+            ("env FOO=1 rm x", Destructive),
+            ("timeout 5 rm x", Destructive),
+            ("nohup rm x &", Destructive),
+            ("nice -n 10 rm x", Destructive),
+            ("docker rm container", Destructive),
+            ("docker system prune -a", Destructive),
+            ("dd if=a of=b", Destructive),
+            ("shred secret.txt", Destructive),
+            ("truncate -s 0 log", Destructive),
+        ];
+
+        let policy = CommandPolicy::new();
+        for (command, expected) in cases {
+            let analysis = policy.analyze(command);
+            assert_eq!(analysis.category, *expected, "command: {:?}", command);
+            assert!(
+                !matches!(analysis.verdict, SafetyVerdict::Deny { .. }),
+                "unexpectedly denied: {:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_only_commands_with_unsafe_options() {
+        let cases: &[(&str, CommandCategory)] = &[
+            // 执行命令或写文件的选项
+            ("fd -e rs -x rm", Mutating),
+            ("fd -Hx touch", Mutating),
+            ("fd --exec-batch=wc", Mutating),
+            ("rg --pre ./evil.sh TODO", Mutating),
+            ("rg --pre=./evil.sh TODO", Mutating),
+            ("man -P 'sh -c id' ls", Mutating),
+            ("man --pager=cmd ls", Mutating),
+            ("yq -i '.a = 1' file.yaml", Mutating),
+            ("yq --inplace '.a = 1' file.yaml", Mutating),
+            ("tree -o out.txt", Mutating),
+            ("date -s '2020-01-01'", Mutating),
+            ("date --set=tomorrow", Mutating),
+            ("less '+!id' file", Mutating),
+            ("less '+|cmd' file", Mutating),
+            ("less -o log file", Mutating),
+            ("export PATH=/tmp/evil:$PATH", Mutating),
+            ("export LD_PRELOAD=/tmp/x.so", Mutating),
+            ("declare -x PAGER=cmd", Mutating),
+            ("PATH=/tmp/evil", Mutating),
+            ("PATH=/tmp/evil ls", Mutating),
+            ("env PAGER=cmd man ls", Mutating),
+            ("GIT_PAGER=cmd git log", Mutating),
+            // 给任意变量赋值、写文件或执行分页程序
+            ("printf -v PATH /tmp/evil", Mutating),
+            ("printf -v out '%s' x", Mutating),
+            ("read PATH <<< /tmp/evil", Mutating),
+            ("read -r line < file", Mutating),
+            ("uniq a.txt b.txt", Mutating),
+            ("uniq -c -f 1 a.txt b.txt", Mutating),
+            ("xxd a.bin out.hex", Mutating),
+            ("xxd -r dump.hex out.bin", Mutating),
+            ("history -w notes.txt", Mutating),
+            ("history -a", Mutating),
+            ("history -r notes.txt", Mutating),
+            ("history -c", Mutating),
+            ("history -d 5", Mutating),
+            ("bat --pager 'sh -c id' x", Mutating),
+            ("bat --pager=cmd x", Mutating),
+            ("info -o out.txt ls", Mutating),
+            ("info --output=out.txt ls", Mutating),
+            // sed 的 e / w 指令
+            ("sed e file", Mutating),
+            ("sed '1e id' file", Mutating),
+            ("sed -n '/x/w out.txt' file", Mutating),
+            ("sed 's/a/b/w out.txt' file", Mutating),
+            ("sed 's/a/id/e' file", Mutating),
+            ("sed -e p -e 'W out' file", Mutating),
+            ("sed --expression='$w out' file", Mutating),
+            ("sed -f script.sed file", Mutating),
+            ("sed -ne 's/x/y/ep' file", Mutating),
+            // awk 的管道与 system()
+            ("awk '{ print | \"sh\" }' file", Mutating),
+            ("awk 'BEGIN { \"id\" | getline x }'", Mutating),
+            ("awk 'BEGIN { system (\"id\") }'", Mutating),
+            ("awk -f prog.awk file", Mutating),
+            ("gawk -i inplace '{print}' file", Mutating),
+            // git
+            ("git -c core.fsmonitor=cmd status", Mutating),
+            ("git -c core.pager=cmd log", Mutating),
+            ("git --config-env=core.pager=X log", Mutating),
+            ("git config --unset user.name", Mutating),
+            ("git config --unset-all remote.origin.fetch", Mutating),
+            ("git config --add a.b c", Mutating),
+            ("git config --remove-section alias", Mutating),
+            ("git config edit", Mutating),
+            ("git diff --output=patch.diff", Mutating),
+            ("git log --output patch.txt", Mutating),
+            ("git grep --open-files-in-pager=vim foo", Mutating),
+            // 不受影响的只读用法
+            ("fd -e rs", ReadOnly),
+            ("printf '%s\\n' x", ReadOnly),
+            ("read", ReadOnly),
+            ("read -r", ReadOnly),
+            ("uniq a.txt", ReadOnly),
+            ("uniq -f 1 -c a.txt", ReadOnly),
+            ("xxd a.bin", ReadOnly),
+            ("xxd -l 16 -s 0x10 a.bin", ReadOnly),
+            ("history", ReadOnly),
+            ("history 10", ReadOnly),
+            ("bat -n x", ReadOnly),
+            ("info ls", ReadOnly),
+            ("rg --pre-glob '*.gz' TODO", ReadOnly),
+            ("man ls", ReadOnly),
+            ("yq '.a' file.yaml", ReadOnly),
+            ("tree -L 2", ReadOnly),
+            ("date -u", ReadOnly),
+            ("less +G file", ReadOnly),
+            ("export FOO=bar", ReadOnly),
+            ("sed -n '1,10p' file", ReadOnly),
+            ("sed 's/a/b/g' file", ReadOnly),
+            ("sed -e 's/x/y/' -e '/foo/d' file", ReadOnly),
+            ("sed -n '/error/Ip' file", ReadOnly),
+            ("sed '/^a/i text' file", ReadOnly),
+            ("awk -F: '{print $1}' /etc/passwd", ReadOnly),
+            ("awk -F'|' '{print $2}' file", ReadOnly),
+            ("git -C sub log", ReadOnly),
+            ("git config --get user.name", ReadOnly),
+            ("git diff --stat", ReadOnly),
+        ];
+
+        let policy = CommandPolicy::new();
+        for (command, expected) in cases {
+            assert_eq!(
+                policy.analyze(command).category,
+                *expected,
+                "command: {:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_deny_list() {
+        let cases = [
+            "sudo rm x",
+            "sudo -u nobody ls",
+            "ls && sudo reboot",
+            "env sudo ls",
+            "FOO=1 sudo ls",
+            "/usr/bin/sudo ls",
+            "echo $(sudo id)",
+            "bash -c 'sudo ls'",
+            "xargs sudo rm",
+            "su - root",
+            "mkfs.ext4 /dev/sda1",
+            "dd if=/dev/zero of=/dev/sda bs=1M",
+            "shutdown -h now",
+            "rm -rf /",
+            "rm -fr ~",
+            "rm -r -f $HOME",
+            "rm -rf /*",
+            "rm --recursive ~/",
+        ];
+        let policy = CommandPolicy::new();
+        for command in cases {
+            assert!(
+                matches!(policy.analyze(command).verdict, SafetyVerdict::Deny { .. }),
+                "expected deny: {:?}",
+                command
+            );
+        }
+
+        // 出现在参数中的禁止命令不算
+        for command in [
+            "echo sudo",
+            "grep reboot log",
+            "rm -rf ./build",
+            "dd if=a of=b",
+        ] {
+            assert!(
+                !matches!(policy.analyze(command).verdict, SafetyVerdict::Deny { .. }),
+                "unexpectedly denied: {:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_deny_list() {
+        let policy = CommandPolicy::from_project_config(&ProjectConfig {
+            denied_commands: vec!["git push --force".to_string(), "npm publish".to_string()],
+            ..Default::default()
+        });
+
+        let cases = [
+            ("git push --force origin", true),
+            ("git push origin main --force", true),
+            ("git push origin main", false),
+            ("npm publish", true),
+            ("NPM_TOKEN=x npm publish --access public", true),
+            ("npm install", false),
+            ("sudo ls", true),
+        ];
+        for (command, denied) in cases {
+            assert_eq!(
+                matches!(policy.analyze(command).verdict, SafetyVerdict::Deny { .. }),
+                denied,
+                "command: {:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let policy = CommandPolicy::new();
+
+        let analysis = policy.analyze("git status && ls");
+        assert_eq!(analysis.verdict, SafetyVerdict::Allow);
+        assert!(analysis.is_read_only());
+        assert_eq!(analysis.commands.len(), 2);
+
+        let analysis = policy.analyze("ls && rm -rf build && git push");
+        assert_eq!(
+            analysis.verdict,
+            SafetyVerdict::Ask {
+                reason: "`rm -rf build` is a destructive command".to_string()
+            }
+        );
+        let categories: Vec<_> = analysis.commands.iter().map(|c| c.category).collect();
+        assert_eq!(categories, vec![ReadOnly, Destructive, Network]);
+
+        let analysis = policy.analyze("sudo ls");
+        assert_eq!(
+            analysis.verdict,
+            SafetyVerdict::Deny {
+                reason: "`sudo ls` is blocked by the deny list (`sudo`)".to_string()
+            }
+        );
+
+        let analysis = policy.analyze("echo 'unterminated");
+        assert!(matches!(
+            analysis.verdict,
+            SafetyVerdict::Ask { ref reason } if reason.starts_with("Could not parse command")
+        ));
+        assert!(!analysis.is_read_only());
+    }
+
+    #[test]
+    fn test_command_line() {
+        let commands = split_command("FOO=1 git status --short > out").unwrap();
+        assert_eq!(commands[0].command_line(), "FOO=1 git status --short");
+    }
+}
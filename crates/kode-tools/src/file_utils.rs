@@ -0,0 +1,415 @@
+//! 文件操作辅助函数
+//!
+//! 写文件类工具（MultiEdit、FileWrite 等）共享的检查与原子写入逻辑：
+//! 新鲜度检查、权限检查、换行符保持以及临时文件 + rename 写入。
+
+use crate::ToolContext;
+use anyhow::{anyhow, bail, Context, Result};
+use kode_core::permission::{AccessKind, PermissionRequest, PermissionTarget};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// 换行符风格
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Unix 风格 (`\n`)
+    Lf,
+    /// Windows 风格 (`\r\n`)
+    CrLf,
+}
+
+impl LineEnding {
+    /// 检测文本使用的换行符
+    ///
+    /// 以第一个换行符为准；没有换行符时视为 LF。
+    pub fn detect(content: &str) -> Self {
+        match content.find('\n') {
+            Some(idx) if idx > 0 && content.as_bytes()[idx - 1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    /// 将文本规范化为 LF
+    pub fn normalize(content: &str) -> String {
+        content.replace("\r\n", "\n")
+    }
+
+    /// 将 LF 文本转换为当前换行符风格
+    pub fn apply(&self, content: &str) -> String {
+        match self {
+            LineEnding::Lf => content.to_string(),
+            LineEnding::CrLf => LineEnding::normalize(content).replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// 词法规范化路径（处理 `.` 和 `..`，不访问文件系统）
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+/// 解析路径中已存在部分的符号链接
+///
+/// 对最近的已存在祖先做 canonicalize，再拼接其后尚不存在的部分，
+/// 使指向工作目录外的符号链接无法绕过边界检查。
+pub(crate) fn resolve_existing(path: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let mut existing = path.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(mut resolved) = existing.canonicalize() {
+            resolved.extend(missing.iter().rev());
+            return resolved;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path,
+        }
+    }
+}
+
+/// 检查路径是否位于工作目录内
+///
+/// 比较前解析已存在部分的符号链接。
+pub fn is_within_working_dir(path: &Path, context: &ToolContext) -> bool {
+    let root = resolve_existing(&context.working_dir);
+    resolve_existing(&context.resolve_path(path)).starts_with(root)
+}
+
+fn ensure_not_symlink(path: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => bail!(
+            "Permission denied: {} is a symbolic link; refusing to write through it",
+            path.display()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// 写入前检查
+///
+/// 所有写文件工具在修改磁盘之前必须通过此检查：
+/// - 目标必须位于工作目录内（解析符号链接后）
+/// - 目标不能是符号链接、目录或只读文件
+/// - 已存在的文件必须在本会话中读取过（或由工具写入过），且之后没有被外部修改
+///
+/// # Arguments
+///
+/// * `path` - 已解析的绝对路径
+/// * `context` - 工具上下文
+pub fn check_write_allowed(path: &Path, context: &ToolContext) -> Result<()> {
+    if !is_within_working_dir(path, context) {
+        bail!(
+            "Permission denied: {} is outside the working directory {}",
+            path.display(),
+            context.working_dir.display()
+        );
+    }
+    ensure_not_symlink(path)?;
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        // 新文件不需要先读取
+        Err(_) => return Ok(()),
+    };
+    if metadata.is_dir() {
+        bail!("{} is a directory, not a file", path.display());
+    }
+    if metadata.permissions().readonly() {
+        bail!("Permission denied: {} is read-only", path.display());
+    }
+
+    let key = path.to_string_lossy();
+    let freshness = context.freshness();
+    if !freshness.is_file_tracked(&key) {
+        bail!(
+            "{} has not been read yet. Read it first before writing to it.",
+            path.display()
+        );
+    }
+    if freshness.check_file_freshness(&key).conflict {
+        bail!(
+            "{} has been modified since it was last read. Read it again before writing to it.",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// 读取已存在的文本文件
+///
+/// 返回 `None` 表示文件不存在。
+pub async fn read_existing(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!(e)).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// 原子写入文件
+///
+/// 先写入同目录下的临时文件并 sync，再 rename 覆盖目标文件，
+/// 保证目标文件要么是旧内容，要么是完整的新内容。
+/// 会自动创建父目录，并保留原文件的权限位。目标是符号链接时拒绝写入，
+/// 以免 rename 把链接替换成普通文件。
+pub async fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    ensure_not_symlink(path)?;
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid file path: {}", path.display()))?;
+    fs::create_dir_all(parent)
+        .await
+        .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid file path: {}", path.display()))?
+        .to_string_lossy();
+    let temp_path = parent.join(format!(".{}.kode-tmp-{}", file_name, uuid::Uuid::new_v4()));
+
+    let result = write_temp_and_rename(path, &temp_path, content).await;
+    if result.is_err() {
+        // 失败时清理临时文件，目标文件保持不变
+        fs::remove_file(&temp_path).await.ok();
+    }
+    result
+}
+
+async fn write_temp_and_rename(path: &Path, temp_path: &Path, content: &str) -> Result<()> {
+    let original_permissions = fs::metadata(path).await.ok().map(|m| m.permissions());
+
+    let mut file = fs::File::create(temp_path)
+        .await
+        .with_context(|| format!("Failed to create temporary file {}", temp_path.display()))?;
+    file.write_all(content.as_bytes())
+        .await
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    file.sync_all()
+        .await
+        .with_context(|| format!("Failed to sync {}", temp_path.display()))?;
+    drop(file);
+
+    if let Some(permissions) = original_permissions {
+        fs::set_permissions(temp_path, permissions)
+            .await
+            .with_context(|| format!("Failed to preserve permissions of {}", path.display()))?;
+    }
+
+    fs::rename(temp_path, path)
+        .await
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+/// 构造文件编辑工具的权限请求
+///
+/// 目标为参数中 `file_path` 规范化后的绝对路径，供 `FileEdit(...)` 等规则匹配。
+pub fn edit_permission_request(
+    tool_name: &str,
+    params: &Value,
+    context: &ToolContext,
+) -> PermissionRequest {
+    let request = PermissionRequest::new(tool_name, AccessKind::Edit);
+    match params.get("file_path").and_then(Value::as_str) {
+        Some(path) => request.with_target(PermissionTarget::Path(normalize_path(
+            &context.resolve_path(path),
+        ))),
+        None => request,
+    }
+}
+
+/// 记录工具写入，更新新鲜度服务
+pub fn record_write(path: &Path, context: &ToolContext) {
+    context
+        .freshness()
+        .record_file_edit(&path.to_string_lossy());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_line_ending_detect() {
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("no newline"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("\nfirst"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_line_ending_roundtrip() {
+        let lf = LineEnding::normalize("a\r\nb\r\n");
+        assert_eq!(lf, "a\nb\n");
+        assert_eq!(LineEnding::CrLf.apply(&lf), "a\r\nb\r\n");
+        assert_eq!(LineEnding::CrLf.apply("a\r\nb\n"), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply(&lf), "a\nb\n");
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/a/b/../c/./d")),
+            PathBuf::from("/a/c/d")
+        );
+    }
+
+    #[test]
+    fn test_is_within_working_dir() {
+        let context = ToolContext::new("/project");
+        assert!(is_within_working_dir(Path::new("src/main.rs"), &context));
+        assert!(is_within_working_dir(Path::new("/project/a.txt"), &context));
+        assert!(!is_within_working_dir(
+            Path::new("../other/a.txt"),
+            &context
+        ));
+        assert!(!is_within_working_dir(Path::new("/etc/passwd"), &context));
+    }
+
+    #[test]
+    fn test_check_write_allowed_rejects_stale_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = ToolContext::new(temp_dir.path());
+        let file = temp_dir.path().join("a.txt");
+        std::fs::write(&file, "one").unwrap();
+
+        // 从未读取过的已存在文件不能直接覆盖；新文件不受限制
+        let err = check_write_allowed(&file, &context).unwrap_err();
+        assert!(err.to_string().contains("has not been read yet"), "{}", err);
+        assert!(check_write_allowed(&temp_dir.path().join("new.txt"), &context).is_ok());
+
+        context
+            .freshness()
+            .record_file_read(&file.to_string_lossy());
+        assert!(check_write_allowed(&file, &context).is_ok());
+
+        // 模拟外部修改：把 mtime 推到未来
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        let err = check_write_allowed(&file, &context).unwrap_err();
+        assert!(err.to_string().contains("modified since it was last read"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_cannot_escape_working_dir() {
+        let project = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(outside.path(), project.path().join("escape")).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.txt"),
+            project.path().join("link.txt"),
+        )
+        .unwrap();
+        std::fs::write(project.path().join("real.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(
+            project.path().join("real.txt"),
+            project.path().join("inner.txt"),
+        )
+        .unwrap();
+        let context = ToolContext::new(project.path());
+
+        assert!(!is_within_working_dir(
+            Path::new("escape/new.txt"),
+            &context
+        ));
+        assert!(!is_within_working_dir(Path::new("link.txt"), &context));
+        assert!(is_within_working_dir(
+            Path::new("new/dir/file.txt"),
+            &context
+        ));
+        let err =
+            check_write_allowed(&project.path().join("escape/secret.txt"), &context).unwrap_err();
+        assert!(err.to_string().contains("outside the working directory"));
+
+        // 指向工作目录内的链接同样不能写入
+        let inner = project.path().join("inner.txt");
+        let err = check_write_allowed(&inner, &context).unwrap_err();
+        assert!(err.to_string().contains("symbolic link"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_atomic_refuses_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target.txt");
+        let link = temp_dir.path().join("link.txt");
+        std::fs::write(&target, "original").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let err = write_atomic(&link, "new").await.unwrap_err();
+        assert!(err.to_string().contains("symbolic link"));
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "original");
+    }
+
+    #[test]
+    fn test_check_write_allowed_rejects_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = ToolContext::new(temp_dir.path());
+        let err = check_write_allowed(temp_dir.path(), &context).unwrap_err();
+        assert!(err.to_string().contains("is a directory"));
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_creates_parent_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("nested/dir/file.txt");
+
+        write_atomic(&file, "hello").await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello");
+        // 不应残留临时文件
+        let leftovers: Vec<_> = std::fs::read_dir(file.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains("kode-tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_atomic_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("script.sh");
+        std::fs::write(&file, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        write_atomic(&file, "#!/bin/sh\necho hi\n").await.unwrap();
+
+        let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+}
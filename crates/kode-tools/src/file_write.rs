@@ -0,0 +1,170 @@
+//! FileWrite 工具
+//!
+//! 创建或整体覆盖文件，通过临时文件 + rename 原子写入。
+
+use crate::file_utils::{self, LineEnding};
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use kode_core::permission::PermissionRequest;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const DESCRIPTION: &str = "Write a file to the local filesystem, creating parent directories as \
+needed. Overwrites the existing file if there is one; the line ending style of an existing file \
+is preserved.";
+
+#[derive(Debug, Deserialize)]
+struct FileWriteParams {
+    file_path: String,
+    content: String,
+}
+
+/// FileWrite 工具
+#[derive(Debug, Default)]
+pub struct FileWriteTool;
+
+impl FileWriteTool {
+    /// 创建新的 FileWrite 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for FileWriteTool {
+    fn name(&self) -> &str {
+        "FileWrite"
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The path of the file to write"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The content to write to the file"
+                    }
+                },
+                "required": ["file_path", "content"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: FileWriteParams =
+            serde_json::from_value(params).context("Invalid FileWrite parameters")?;
+        let path = context.resolve_path(&params.file_path);
+
+        file_utils::check_write_allowed(&path, context)?;
+
+        let existing = file_utils::read_existing(&path).await?;
+        let content = match existing.as_deref() {
+            Some(old) => LineEnding::detect(old).apply(&params.content),
+            None => params.content,
+        };
+
+        file_utils::write_atomic(&path, &content).await?;
+        file_utils::record_write(&path, context);
+
+        let message = if existing.is_some() {
+            format!("File updated successfully at: {}", path.display())
+        } else {
+            format!("File created successfully at: {}", path.display())
+        };
+        Ok(ToolResult::new(message))
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    fn permission_request(&self, params: &Value, context: &ToolContext) -> PermissionRequest {
+        file_utils::edit_permission_request(self.name(), params, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_write_new_file_with_parents() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = ToolContext::new(temp_dir.path());
+
+        let result = FileWriteTool::new()
+            .execute(
+                json!({"file_path": "a/b/c.txt", "content": "hello\n"}),
+                &context,
+            )
+            .await
+            .unwrap();
+
+        assert!(result.output.contains("created"));
+        let file = temp_dir.path().join("a/b/c.txt");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello\n");
+        assert!(context.freshness().is_file_tracked(&file.to_string_lossy()));
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_preserves_crlf() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("win.txt");
+        std::fs::write(&file, "old\r\n").unwrap();
+        let context = ToolContext::new(temp_dir.path());
+
+        // 没有读取过的已存在文件不能覆盖
+        let err = FileWriteTool::new()
+            .execute(
+                json!({"file_path": "win.txt", "content": "new\n"}),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has not been read yet"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "old\r\n");
+
+        context
+            .freshness()
+            .record_file_read(&file.to_string_lossy());
+        let result = FileWriteTool::new()
+            .execute(
+                json!({"file_path": "win.txt", "content": "new\nlines\n"}),
+                &context,
+            )
+            .await
+            .unwrap();
+
+        assert!(result.output.contains("updated"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "new\r\nlines\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_params() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = ToolContext::new(temp_dir.path());
+        let result = FileWriteTool::new()
+            .execute(json!({"file_path": "x.txt"}), &context)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_requires_permission() {
+        assert!(FileWriteTool::new().requires_permission());
+        assert_eq!(FileWriteTool::new().schema().name, "FileWrite");
+    }
+}
@@ -0,0 +1,493 @@
+//! Glob 工具
+//!
+//! 按 glob 模式匹配文件路径，遵守 `.gitignore`，结果按修改时间排序。
+
+use crate::file_utils::normalize_path;
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use glob::{MatchOptions, Pattern};
+use ignore::WalkBuilder;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const DESCRIPTION: &str = "Find files by glob pattern (e.g. \"**/*.rs\", \"src/**/*.{ts,tsx}\"). \
+Respects .gitignore and skips hidden files by default. Returns matching paths sorted by \
+modification time, most recent first.";
+
+/// 默认最多返回的文件数
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// Glob 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct GlobParams {
+    /// glob 模式（相对于 `path`）
+    pub pattern: String,
+    /// 搜索的基础目录，默认为工作目录
+    #[serde(default)]
+    pub path: Option<String>,
+    /// 是否包含隐藏文件
+    #[serde(default)]
+    pub include_hidden: bool,
+    /// 是否遵守 `.gitignore`
+    #[serde(default = "default_true")]
+    pub respect_gitignore: bool,
+    /// 最多返回的文件数
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Glob 搜索结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobMatches {
+    /// 匹配的文件（按修改时间倒序）
+    pub files: Vec<PathBuf>,
+    /// 是否因超过上限被截断
+    pub truncated: bool,
+}
+
+/// Glob 工具
+#[derive(Debug, Default)]
+pub struct GlobTool;
+
+impl GlobTool {
+    /// 创建新的 Glob 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 展开 `{a,b}` 形式的花括号（`glob` crate 本身不支持）
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| open + i) else {
+        return vec![pattern.to_string()];
+    };
+
+    let prefix = &pattern[..open];
+    let suffix = &pattern[close + 1..];
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alt| expand_braces(&format!("{}{}{}", prefix, alt, suffix)))
+        .collect()
+}
+
+/// 是否为递归模式
+fn is_recursive(pattern: &str) -> bool {
+    pattern.contains("**")
+}
+
+/// 模式实际遍历的根目录
+///
+/// 基础目录加上模式开头不含通配符的目录部分（不含最后一段文件名），
+/// 按路径组件规范化（处理 `.` 与 `..`）。
+fn crawl_root(base: &Path, pattern: &str) -> PathBuf {
+    let mut root = base.to_path_buf();
+    let parts: Vec<&str> = pattern.split('/').collect();
+    for part in &parts[..parts.len() - 1] {
+        if part.contains(['*', '?', '[', '{']) {
+            break;
+        }
+        root.push(part);
+    }
+    normalize_path(&root)
+}
+
+/// 执行 glob 搜索
+///
+/// # Arguments
+///
+/// * `params` - 搜索参数
+/// * `base` - 已解析的基础目录
+pub async fn find_files(params: GlobParams, base: PathBuf) -> Result<GlobMatches> {
+    tokio::task::spawn_blocking(move || find_files_blocking(&params, &base))
+        .await
+        .map_err(|e| anyhow!("Glob task failed: {}", e))?
+}
+
+fn find_files_blocking(params: &GlobParams, base: &Path) -> Result<GlobMatches> {
+    if !base.is_dir() {
+        bail!("Directory does not exist: {}", base.display());
+    }
+
+    let patterns = expand_braces(&params.pattern)
+        .iter()
+        .map(|p| Pattern::new(p).with_context(|| format!("Invalid glob pattern: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let mut matches: Vec<(SystemTime, PathBuf)> = walker(params, base)
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter(|entry| {
+            entry.path().strip_prefix(base).is_ok_and(|relative| {
+                patterns
+                    .iter()
+                    .any(|p| p.matches_path_with(relative, options))
+            })
+        })
+        .map(|entry| {
+            let modified = entry
+                .metadata()
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, entry.into_path())
+        })
+        .collect();
+
+    // 最近修改的在前，同一时间按路径排序保证结果稳定
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let truncated = matches.len() > limit;
+    let files = matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, path)| path)
+        .collect();
+
+    Ok(GlobMatches { files, truncated })
+}
+
+/// 遍历 [`crawl_root`] 之下的条目
+///
+/// 从基础目录开始遍历以保持 `.gitignore` 与隐藏文件规则不变，但只进入通往
+/// 遍历根目录的上级目录和根目录之下的条目，不会进入模式前缀旁边的其他目录。
+fn walker(params: &GlobParams, base: &Path) -> ignore::Walk {
+    let root = crawl_root(base, &params.pattern);
+    WalkBuilder::new(base)
+        .hidden(!params.include_hidden)
+        .git_ignore(params.respect_gitignore)
+        .git_global(params.respect_gitignore)
+        .git_exclude(params.respect_gitignore)
+        .ignore(params.respect_gitignore)
+        .parents(params.respect_gitignore)
+        .max_depth(if is_recursive(&params.pattern) {
+            None
+        } else {
+            // 非递归模式只需遍历到模式中的目录层级
+            Some(params.pattern.split('/').count())
+        })
+        .filter_entry(move |entry| {
+            let path = normalize_path(entry.path());
+            path.starts_with(&root) || root.starts_with(&path)
+        })
+        .build()
+}
+
+#[async_trait]
+impl Tool for GlobTool {
+    fn name(&self) -> &str {
+        "Glob"
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The glob pattern to match files against"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to search in. Defaults to the working directory"
+                    },
+                    "include_hidden": {
+                        "type": "boolean",
+                        "description": "Include hidden files and directories (default false)"
+                    },
+                    "respect_gitignore": {
+                        "type": "boolean",
+                        "description": "Skip files ignored by .gitignore (default true)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of files to return (default 100)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: GlobParams =
+            serde_json::from_value(params).context("Invalid Glob parameters")?;
+        let base = match &params.path {
+            Some(path) => context.resolve_path(path),
+            None => context.working_dir.clone(),
+        };
+
+        // 项目设置了 dontCrawlDirectory 时，不允许从项目根（或其上级目录）递归遍历
+        if context.project_config.dont_crawl_directory == Some(true)
+            && is_recursive(&params.pattern)
+        {
+            let root = crawl_root(&base, &params.pattern);
+            if normalize_path(&context.working_dir).starts_with(&root) {
+                bail!(
+                    "Recursive search of {} is disabled for this project (dontCrawlDirectory). \
+                     Specify a narrower path.",
+                    root.display()
+                );
+            }
+        }
+
+        let result = find_files(params, base).await?;
+        if result.files.is_empty() {
+            return Ok(ToolResult::new("No files found"));
+        }
+
+        let mut output = result
+            .files
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if result.truncated {
+            output.push_str(
+                "\n(Results are truncated. Consider using a more specific path or pattern.)",
+            );
+        }
+        Ok(ToolResult::new(output))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_core::config::ProjectConfig;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        for file in [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/util/mod.rs",
+            "web/app.ts",
+            "web/app.tsx",
+            "target/debug/build.rs",
+            ".hidden/secret.rs",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    fn params(pattern: &str) -> GlobParams {
+        serde_json::from_value(json!({ "pattern": pattern })).unwrap()
+    }
+
+    fn names(result: &GlobMatches, base: &Path) -> Vec<String> {
+        let mut names: Vec<String> = result
+            .files
+            .iter()
+            .map(|p| p.strip_prefix(base).unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(expand_braces("*.{ts,tsx}"), vec!["*.ts", "*.tsx"]);
+        assert_eq!(expand_braces("*.rs"), vec!["*.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_recursive_pattern_respects_gitignore_and_hidden() {
+        let dir = setup();
+        let result = find_files(params("**/*.rs"), dir.path().to_path_buf())
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&result, dir.path()),
+            vec!["src/main.rs", "src/util/mod.rs"]
+        );
+
+        let mut p = params("**/*.rs");
+        p.include_hidden = true;
+        p.respect_gitignore = false;
+        let result = find_files(p, dir.path().to_path_buf()).await.unwrap();
+        assert_eq!(result.files.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_single_star_does_not_cross_directories() {
+        let dir = setup();
+        let result = find_files(params("*.rs"), dir.path().join("src"))
+            .await
+            .unwrap();
+        assert_eq!(names(&result, &dir.path().join("src")), vec!["main.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_braces() {
+        let dir = setup();
+        let result = find_files(params("web/*.{ts,tsx}"), dir.path().to_path_buf())
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&result, dir.path()),
+            vec!["web/app.ts", "web/app.tsx"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_and_truncation() {
+        let dir = setup();
+        let mut p = params("**/*");
+        p.limit = Some(2);
+        let result = find_files(p, dir.path().to_path_buf()).await.unwrap();
+        assert_eq!(result.files.len(), 2);
+        assert!(result.truncated);
+
+        let context = ToolContext::new(dir.path());
+        let output = GlobTool::new()
+            .execute(json!({"pattern": "**/*", "limit": 2}), &context)
+            .await
+            .unwrap()
+            .output;
+        assert!(output.contains("Results are truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_by_mtime() {
+        let dir = setup();
+        let old = dir.path().join("src/main.rs");
+        let past = SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let result = find_files(params("src/**/*.rs"), dir.path().to_path_buf())
+            .await
+            .unwrap();
+        assert_eq!(result.files.last().unwrap(), &old);
+    }
+
+    #[tokio::test]
+    async fn test_dont_crawl_directory() {
+        let dir = setup();
+        let context = ToolContext::new(dir.path()).with_project_config(ProjectConfig {
+            dont_crawl_directory: Some(true),
+            ..Default::default()
+        });
+
+        let result = GlobTool::new()
+            .execute(json!({"pattern": "**/*.rs"}), &context)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("dontCrawlDirectory"));
+
+        // 指定子目录时允许
+        let result = GlobTool::new()
+            .execute(json!({"pattern": "**/*.rs", "path": "src"}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.contains("main.rs"));
+
+        // 模式本身以子目录开头时同样允许
+        let result = GlobTool::new()
+            .execute(json!({"pattern": "src/**"}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.contains("main.rs"));
+
+        // 通过 `..` 回到项目根或上级目录时仍然禁止
+        for (pattern, path) in [
+            ("**/*.rs", "src/.."),
+            ("**/*.rs", "./"),
+            ("src/../**", "."),
+            ("**/*.rs", ".."),
+        ] {
+            let err = GlobTool::new()
+                .execute(json!({"pattern": pattern, "path": path}), &context)
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("dontCrawlDirectory"),
+                "{} in {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_walk_stays_under_pattern_prefix() {
+        let dir = setup();
+        std::fs::create_dir_all(dir.path().join("excluded/deep")).unwrap();
+        std::fs::write(dir.path().join("excluded/deep/big.bin"), "").unwrap();
+
+        let params: GlobParams = serde_json::from_value(json!({"pattern": "src/**"})).unwrap();
+        let visited: Vec<PathBuf> = walker(&params, dir.path())
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .collect();
+        assert!(visited.contains(&dir.path().join("src/util/mod.rs")));
+        // 模式前缀旁边的目录既不匹配也不进入
+        assert!(
+            !visited
+                .iter()
+                .any(|path| path.starts_with(dir.path().join("excluded"))
+                    || path.starts_with(dir.path().join("web"))),
+            "{:?}",
+            visited
+        );
+
+        let params: GlobParams =
+            serde_json::from_value(json!({"pattern": "src/util/*.rs"})).unwrap();
+        let visited: Vec<PathBuf> = walker(&params, dir.path())
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .collect();
+        assert!(!visited.contains(&dir.path().join("src/main.rs")));
+        assert!(visited.contains(&dir.path().join("src/util/mod.rs")));
+    }
+
+    #[tokio::test]
+    async fn test_no_files_found() {
+        let dir = setup();
+        let context = ToolContext::new(dir.path());
+        let result = GlobTool::new()
+            .execute(json!({"pattern": "*.nothing"}), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "No files found");
+    }
+}
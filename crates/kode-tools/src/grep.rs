@@ -0,0 +1,646 @@
+//! Grep 工具
+//!
+//! 基于 `ignore` + `regex` 的内容搜索，行为接近 ripgrep：
+//! 遵守 `.gitignore`，多线程并行遍历，结果按修改时间排序。
+
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use ignore::overrides::OverrideBuilder;
+use ignore::types::TypesBuilder;
+use ignore::{WalkBuilder, WalkState};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+const DESCRIPTION: &str = "Search file contents with a regular expression. Respects .gitignore. \
+Supports glob and file type filters, context lines, case-insensitive and multiline matching. \
+Output modes: \"files_with_matches\" (default) lists matching files sorted by modification time, \
+\"content\" shows matching lines, \"count\" shows match counts per file.";
+
+/// 单行最大显示长度（超出部分截断）
+const MAX_LINE_LENGTH: usize = 500;
+
+/// 二进制文件检测时读取的字节数
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// 输出模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// 仅列出包含匹配的文件
+    #[default]
+    FilesWithMatches,
+    /// 显示匹配行（可带上下文）
+    Content,
+    /// 显示每个文件的匹配数量
+    Count,
+}
+
+/// Grep 参数
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GrepParams {
+    /// 正则表达式
+    pub pattern: String,
+    /// 搜索路径（文件或目录），默认为工作目录
+    #[serde(default)]
+    pub path: Option<String>,
+    /// 文件名 glob 过滤（例如 `*.rs`、`*.{ts,tsx}`）
+    #[serde(default)]
+    pub glob: Option<String>,
+    /// 文件类型过滤（例如 `rust`、`py`）
+    #[serde(default, rename = "type")]
+    pub file_type: Option<String>,
+    /// 输出模式
+    #[serde(default)]
+    pub output_mode: OutputMode,
+    /// 匹配行之后的上下文行数
+    #[serde(default, rename = "-A")]
+    pub after_context: Option<usize>,
+    /// 匹配行之前的上下文行数
+    #[serde(default, rename = "-B")]
+    pub before_context: Option<usize>,
+    /// 匹配行前后的上下文行数
+    #[serde(default, rename = "-C")]
+    pub context: Option<usize>,
+    /// 忽略大小写
+    #[serde(default, rename = "-i")]
+    pub case_insensitive: bool,
+    /// 显示行号（content 模式，默认开启）
+    #[serde(default, rename = "-n")]
+    pub line_numbers: Option<bool>,
+    /// 多行模式（`.` 匹配换行，模式可跨行）
+    #[serde(default)]
+    pub multiline: bool,
+    /// 最多返回的条目数
+    #[serde(default)]
+    pub head_limit: Option<usize>,
+    /// 跳过前 N 个条目
+    #[serde(default)]
+    pub offset: Option<usize>,
+}
+
+/// 单个文件的搜索结果
+#[derive(Debug, Clone)]
+struct FileMatches {
+    path: PathBuf,
+    modified: SystemTime,
+    match_count: usize,
+    /// 需要显示的行（行号从 1 开始，是否为匹配行）
+    lines: Vec<(usize, String, bool)>,
+}
+
+/// Grep 工具
+#[derive(Debug, Default)]
+pub struct GrepTool;
+
+impl GrepTool {
+    /// 创建新的 Grep 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 执行搜索
+///
+/// 在阻塞线程池中并行遍历目录，返回格式化后的结果文本。
+pub async fn search(params: GrepParams, root: PathBuf) -> Result<String> {
+    tokio::task::spawn_blocking(move || search_blocking(&params, &root))
+        .await
+        .map_err(|e| anyhow!("Grep task failed: {}", e))?
+}
+
+fn build_regex(params: &GrepParams) -> Result<Regex> {
+    RegexBuilder::new(&params.pattern)
+        .case_insensitive(params.case_insensitive)
+        .multi_line(true)
+        .dot_matches_new_line(params.multiline)
+        .build()
+        .with_context(|| format!("Invalid regex pattern: {}", params.pattern))
+}
+
+fn search_blocking(params: &GrepParams, root: &Path) -> Result<String> {
+    if !root.exists() {
+        bail!("Path does not exist: {}", root.display());
+    }
+
+    let regex = build_regex(params)?;
+    let mut builder = WalkBuilder::new(root);
+    builder.threads(0);
+
+    if let Some(glob) = &params.glob {
+        let mut overrides = OverrideBuilder::new(root);
+        overrides
+            .add(glob)
+            .with_context(|| format!("Invalid glob: {}", glob))?;
+        builder.overrides(overrides.build()?);
+    }
+
+    if let Some(file_type) = &params.file_type {
+        let mut types = TypesBuilder::new();
+        types.add_defaults();
+        types.select(file_type);
+        builder.types(
+            types
+                .build()
+                .with_context(|| format!("Unknown file type: {}", file_type))?,
+        );
+    }
+
+    let (before, after) = params.context_lines();
+    let collect_lines = params.output_mode == OutputMode::Content;
+    let results: Arc<Mutex<Vec<FileMatches>>> = Arc::new(Mutex::new(Vec::new()));
+
+    builder.build_parallel().run(|| {
+        let regex = regex.clone();
+        let results = Arc::clone(&results);
+        let multiline = params.multiline;
+        Box::new(move |entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                return WalkState::Continue;
+            }
+            if let Some(found) = search_file(
+                entry.path(),
+                &regex,
+                multiline,
+                collect_lines,
+                before,
+                after,
+            ) {
+                results
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(found);
+            }
+            WalkState::Continue
+        })
+    });
+
+    let mut results = Arc::try_unwrap(results)
+        .map_err(|_| anyhow!("Grep workers still running"))?
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // 最近修改的文件在前，同一时间按路径排序保证结果稳定
+    results.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.path.cmp(&b.path)));
+
+    Ok(format_results(params, &results))
+}
+
+impl GrepParams {
+    /// 计算 (before, after) 上下文行数，`-A`/`-B` 优先于 `-C`
+    fn context_lines(&self) -> (usize, usize) {
+        let context = self.context.unwrap_or(0);
+        (
+            self.before_context.unwrap_or(context),
+            self.after_context.unwrap_or(context),
+        )
+    }
+}
+
+fn search_file(
+    path: &Path,
+    regex: &Regex,
+    multiline: bool,
+    collect_lines: bool,
+    before: usize,
+    after: usize,
+) -> Option<FileMatches> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = content
+        .split('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .collect();
+
+    let (matched, match_count) = if multiline {
+        find_multiline_matches(&content, regex)
+    } else {
+        let matched: BTreeSet<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line))
+            .map(|(idx, _)| idx)
+            .collect();
+        let count = matched.len();
+        (matched, count)
+    };
+
+    if match_count == 0 {
+        return None;
+    }
+
+    let mut shown = Vec::new();
+    if collect_lines {
+        let mut visible = BTreeSet::new();
+        for &idx in &matched {
+            let start = idx.saturating_sub(before);
+            let end = (idx + after).min(lines.len().saturating_sub(1));
+            visible.extend(start..=end);
+        }
+        for idx in visible {
+            // 文件末尾换行产生的空行不显示
+            if idx + 1 == lines.len() && lines[idx].is_empty() {
+                continue;
+            }
+            shown.push((idx + 1, truncate_line(lines[idx]), matched.contains(&idx)));
+        }
+    }
+
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    Some(FileMatches {
+        path: path.to_path_buf(),
+        modified,
+        match_count,
+        lines: shown,
+    })
+}
+
+/// 多行模式：返回被匹配覆盖的行号集合和匹配次数
+fn find_multiline_matches(content: &str, regex: &Regex) -> (BTreeSet<usize>, usize) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| match line_starts.binary_search(&offset) {
+        Ok(idx) => idx,
+        Err(idx) => idx - 1,
+    };
+
+    let mut matched = BTreeSet::new();
+    let mut count = 0;
+    for m in regex.find_iter(content) {
+        count += 1;
+        let first = line_of(m.start());
+        let last = line_of(m.end().saturating_sub(1).max(m.start()));
+        matched.extend(first..=last);
+    }
+    (matched, count)
+}
+
+fn truncate_line(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_LENGTH {
+        line.to_string()
+    } else {
+        let truncated: String = line.chars().take(MAX_LINE_LENGTH).collect();
+        format!("{}...", truncated)
+    }
+}
+
+/// 应用 offset 和 head_limit
+fn paginate<T>(items: Vec<T>, offset: Option<usize>, head_limit: Option<usize>) -> (Vec<T>, bool) {
+    let total = items.len();
+    let offset = offset.unwrap_or(0);
+    let limit = head_limit.unwrap_or(usize::MAX);
+    let page: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+    let truncated = offset.saturating_add(page.len()) < total;
+    (page, truncated)
+}
+
+fn format_results(params: &GrepParams, results: &[FileMatches]) -> String {
+    if results.is_empty() {
+        return "No matches found".to_string();
+    }
+
+    match params.output_mode {
+        OutputMode::FilesWithMatches => {
+            let files: Vec<String> = results
+                .iter()
+                .map(|r| r.path.display().to_string())
+                .collect();
+            let total = files.len();
+            let (page, truncated) = paginate(files, params.offset, params.head_limit);
+            let mut output = format!(
+                "Found {} file{}\n{}",
+                total,
+                if total == 1 { "" } else { "s" },
+                page.join("\n")
+            );
+            if truncated {
+                output.push_str("\n(Results are truncated. Use offset to see more.)");
+            }
+            output
+        }
+        OutputMode::Count => {
+            let total: usize = results.iter().map(|r| r.match_count).sum();
+            let entries: Vec<String> = results
+                .iter()
+                .map(|r| format!("{}:{}", r.path.display(), r.match_count))
+                .collect();
+            let (page, truncated) = paginate(entries, params.offset, params.head_limit);
+            let mut output = page.join("\n");
+            if truncated {
+                output.push_str("\n(Results are truncated. Use offset to see more.)");
+            }
+            output.push_str(&format!(
+                "\n\nFound {} total match{} across {} file{}",
+                total,
+                if total == 1 { "" } else { "es" },
+                results.len(),
+                if results.len() == 1 { "" } else { "s" }
+            ));
+            output
+        }
+        OutputMode::Content => {
+            let show_numbers = params.line_numbers.unwrap_or(true);
+            let mut lines = Vec::new();
+            for result in results {
+                let path = result.path.display().to_string();
+                let mut previous: Option<usize> = None;
+                for (number, text, is_match) in &result.lines {
+                    if previous.is_some_and(|p| number - p > 1) {
+                        lines.push("--".to_string());
+                    }
+                    let separator = if *is_match { ':' } else { '-' };
+                    lines.push(if show_numbers {
+                        format!("{}{}{}{}{}", path, separator, number, separator, text)
+                    } else {
+                        format!("{}{}{}", path, separator, text)
+                    });
+                    previous = Some(*number);
+                }
+            }
+            let (page, truncated) = paginate(lines, params.offset, params.head_limit);
+            let mut output = page.join("\n");
+            if truncated {
+                output.push_str("\n(Results are truncated. Use offset to see more.)");
+            }
+            output
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "Grep"
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The regular expression pattern to search for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory to search in. Defaults to the working directory"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Glob pattern to filter files (e.g. \"*.rs\", \"*.{ts,tsx}\")"
+                    },
+                    "type": {
+                        "type": "string",
+                        "description": "File type to search (e.g. \"rust\", \"py\", \"js\")"
+                    },
+                    "output_mode": {
+                        "type": "string",
+                        "enum": ["files_with_matches", "content", "count"],
+                        "description": "Output mode (default: files_with_matches)"
+                    },
+                    "-A": {
+                        "type": "integer",
+                        "description": "Lines to show after each match (content mode)"
+                    },
+                    "-B": {
+                        "type": "integer",
+                        "description": "Lines to show before each match (content mode)"
+                    },
+                    "-C": {
+                        "type": "integer",
+                        "description": "Lines to show before and after each match (content mode)"
+                    },
+                    "-i": {
+                        "type": "boolean",
+                        "description": "Case insensitive search"
+                    },
+                    "-n": {
+                        "type": "boolean",
+                        "description": "Show line numbers (content mode, default true)"
+                    },
+                    "multiline": {
+                        "type": "boolean",
+                        "description": "Allow patterns to span lines; `.` matches newlines"
+                    },
+                    "head_limit": {
+                        "type": "integer",
+                        "description": "Limit output to the first N lines or entries"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Skip the first N lines or entries"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: GrepParams =
+            serde_json::from_value(params).context("Invalid Grep parameters")?;
+        let root = match &params.path {
+            Some(path) => context.resolve_path(path),
+            None => context.working_dir.clone(),
+        };
+        Ok(ToolResult::new(search(params, root).await?))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        // ignore crate 仅在 git 仓库中读取 .gitignore
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "ignored/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("ignored")).unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    println!(\"Hello\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.py"),
+            "def hello():\n    return 'hello'\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("ignored/skip.rs"), "hello\n").unwrap();
+        dir
+    }
+
+    fn params(pattern: &str) -> GrepParams {
+        GrepParams {
+            pattern: pattern.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_files_with_matches_respects_gitignore() {
+        let dir = setup();
+        let mut p = params("hello");
+        p.case_insensitive = true;
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+
+        assert!(output.starts_with("Found 2 files"));
+        assert!(output.contains("main.rs"));
+        assert!(output.contains("lib.py"));
+        assert!(!output.contains("skip.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_case_sensitive_by_default() {
+        let dir = setup();
+        let output = search(params("Hello"), dir.path().to_path_buf())
+            .await
+            .unwrap();
+        assert!(output.starts_with("Found 1 file\n"));
+        assert!(output.contains("main.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_glob_and_type_filters() {
+        let dir = setup();
+        let mut p = params("(?i)hello");
+        p.glob = Some("*.py".to_string());
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        assert!(output.contains("lib.py"));
+        assert!(!output.contains("main.rs"));
+
+        let mut p = params("(?i)hello");
+        p.file_type = Some("rust".to_string());
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        assert!(output.contains("main.rs"));
+        assert!(!output.contains("lib.py"));
+    }
+
+    #[tokio::test]
+    async fn test_content_mode_with_context() {
+        let dir = setup();
+        let mut p = params("println");
+        p.output_mode = OutputMode::Content;
+        p.context = Some(1);
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("main.rs-1-fn main() {"));
+        assert!(lines[1].ends_with("main.rs:2:    println!(\"Hello\");"));
+        assert!(lines[2].ends_with("main.rs-3-}"));
+    }
+
+    #[tokio::test]
+    async fn test_count_mode() {
+        let dir = setup();
+        let mut p = params("hello");
+        p.output_mode = OutputMode::Count;
+        p.glob = Some("*.py".to_string());
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        assert!(output.contains("lib.py:2"));
+        assert!(output.contains("Found 2 total matches across 1 file"));
+    }
+
+    #[tokio::test]
+    async fn test_multiline() {
+        let dir = setup();
+        let mut p = params(r"main\(\) \{.*println");
+        p.output_mode = OutputMode::Content;
+        let output = search(p.clone(), dir.path().to_path_buf()).await.unwrap();
+        assert_eq!(output, "No matches found");
+
+        p.multiline = true;
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        assert_eq!(output.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_head_limit_and_offset() {
+        let dir = TempDir::new().unwrap();
+        let content: String = (1..=10).map(|i| format!("match {}\n", i)).collect();
+        std::fs::write(dir.path().join("many.txt"), content).unwrap();
+
+        let mut p = params("match");
+        p.output_mode = OutputMode::Content;
+        p.line_numbers = Some(false);
+        p.offset = Some(2);
+        p.head_limit = Some(3);
+        let output = search(p, dir.path().to_path_buf()).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[0].ends_with(":match 3"));
+        assert!(lines[2].ends_with(":match 5"));
+        assert!(lines[3].contains("truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_by_mtime() {
+        let dir = TempDir::new().unwrap();
+        let older = dir.path().join("older.txt");
+        let newer = dir.path().join("newer.txt");
+        std::fs::write(&older, "needle").unwrap();
+        std::fs::write(&newer, "needle").unwrap();
+        let past = SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&older)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let output = search(params("needle"), dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[1].ends_with("newer.txt"));
+        assert!(lines[2].ends_with("older.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex() {
+        let dir = TempDir::new().unwrap();
+        let result = search(params("("), dir.path().to_path_buf()).await;
+        assert!(result.unwrap_err().to_string().contains("Invalid regex"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_uses_working_dir() {
+        let dir = setup();
+        let context = ToolContext::new(dir.path());
+        let result = GrepTool::new()
+            .execute(json!({"pattern": "fn main", "-n": true}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.contains("main.rs"));
+    }
+}
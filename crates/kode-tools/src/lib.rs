@@ -11,6 +11,78 @@ pub mod tool;
 /// 工具注册表
 pub mod registry;
 
+/// 工具编排（并发执行只读工具）
+pub mod orchestrator;
+
+/// 文件操作辅助函数
+pub mod file_utils;
+
+/// MultiEdit 工具
+pub mod multi_edit;
+
+/// FileWrite 工具
+pub mod file_write;
+
+/// Grep 工具
+pub mod grep;
+
+/// Glob 工具
+pub mod glob;
+
+/// 持久 shell 会话
+pub mod shell;
+
+/// Bash 命令安全分析
+pub mod bash_safety;
+
+/// Bash 沙箱
+pub mod sandbox;
+
+/// Bash 工具
+pub mod bash;
+
+/// Task 工具（子 Agent）
+pub mod task;
+
+/// TodoWrite / TodoRead 工具
+pub mod todo;
+
+/// Architect 工具与计划模式
+pub mod architect;
+
+/// WebFetch 工具
+pub mod web_fetch;
+
+/// NotebookRead / NotebookEdit 工具
+pub mod notebook;
+
+/// ApplyPatch 工具
+pub mod apply_patch;
+
+/// MCP 服务器工具
+pub mod mcp;
+
+/// ListMcpResources / ReadMcpResource 工具
+pub mod mcp_resources;
+
+/// MCP 服务器模式（通过 stdio 对外提供工具）
+pub mod mcp_server;
+
 // 重新导出主要类型
-pub use registry::ToolRegistry;
-pub use tool::{Tool, ToolContext, ToolResult, ToolSchema};
+pub use apply_patch::ApplyPatchTool;
+pub use architect::{ArchitectTool, ExitPlanModeTool, ImplementationPlan};
+pub use bash::{BashOutputTool, BashTool, KillBashTool};
+pub use file_write::FileWriteTool;
+pub use glob::GlobTool;
+pub use grep::GrepTool;
+pub use mcp::{McpTool, McpToolSource};
+pub use mcp_resources::{ListMcpResourcesTool, ReadMcpResourceTool};
+pub use mcp_server::{AgentTool, McpServer};
+pub use multi_edit::MultiEditTool;
+pub use notebook::{NotebookEditTool, NotebookReadTool};
+pub use orchestrator::{OrchestratorRunner, ToolOrchestrator};
+pub use registry::{AgentTools, ToolRegistry, ToolSource};
+pub use task::{TaskBudget, TaskTool};
+pub use todo::{TodoReadTool, TodoReminders, TodoWriteTool};
+pub use tool::{ProgressSink, Tool, ToolContext, ToolResult, ToolSchema};
+pub use web_fetch::WebFetchTool;
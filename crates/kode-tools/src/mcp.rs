@@ -0,0 +1,676 @@
+//! MCP 工具
+//!
+//! 把已连接 MCP 服务器提供的工具包装成 [`Tool`]，以 `mcp__<server>__<tool>` 命名注册。
+//! 调用转发给服务器的 `tools/call`，结果中的文本、图片和资源转换为工具结果；
+//! 服务器发出 `notifications/tools/list_changed` 时自动刷新工具列表。
+//! 工具每次调用时从 [`McpClientProvider`] 按服务器名取当前连接，服务器被
+//! `McpManager` 重启后自动换用新连接并重新读取工具列表。
+
+use crate::registry::ToolSource;
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use kode_core::message::ImageBlock;
+use kode_services::mcp::protocol::{JsonRpcNotification, McpTool as McpToolDefinition};
+use kode_services::mcp::{McpClient, McpClientProvider, ToolContent};
+use serde_json::{json, Value};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+/// MCP 工具名前缀
+pub const MCP_TOOL_PREFIX: &str = "mcp__";
+
+/// 工具列表变更通知
+const TOOLS_LIST_CHANGED: &str = "notifications/tools/list_changed";
+
+/// 连接关闭后检查服务器是否已重启的间隔
+const REBIND_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 生成 MCP 工具在注册表中的名称
+///
+/// 服务器名和工具名按 [`sanitize_name`] 规范化。
+pub fn mcp_tool_name(server: &str, tool: &str) -> String {
+    format!("{}{}", mcp_server_prefix(server), sanitize_name(tool))
+}
+
+/// 服务器所有工具共用的名称前缀 `mcp__<server>__`
+pub fn mcp_server_prefix(server: &str) -> String {
+    format!("{}{}__", MCP_TOOL_PREFIX, sanitize_name(server))
+}
+
+/// 规范化工具名片段
+///
+/// 字母、数字、`_`、`-` 以外的字符替换为 `_`，连续的 `_` 合并为一个，并去掉首尾的 `_`，
+/// 保证片段中不含分隔符 `__`：否则服务器 `a__b` 的工具会被 `mcp__a` 的权限规则匹配。
+/// 规范化后为空时使用 `unnamed`。
+pub fn sanitize_name(name: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '-' {
+            c
+        } else {
+            '_'
+        };
+        if c != '_' || !sanitized.ends_with('_') {
+            sanitized.push(c);
+        }
+    }
+    let sanitized = sanitized.trim_matches('_');
+    if sanitized.is_empty() {
+        "unnamed".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// 按服务器名取当前连接
+fn current_client(clients: &dyn McpClientProvider, server: &str) -> Result<Arc<McpClient>> {
+    clients
+        .client(server)
+        .ok_or_else(|| anyhow!("MCP server '{}' is not connected", server))
+}
+
+/// 单个 MCP 服务器工具
+pub struct McpTool {
+    name: String,
+    description: String,
+    definition: McpToolDefinition,
+    server: String,
+    clients: Arc<dyn McpClientProvider>,
+}
+
+impl McpTool {
+    /// 包装服务器返回的工具定义
+    ///
+    /// 调用时从 `clients` 中按 `server` 取当前连接。
+    pub fn new(
+        server: impl Into<String>,
+        clients: Arc<dyn McpClientProvider>,
+        definition: McpToolDefinition,
+    ) -> Self {
+        let server = server.into();
+        Self {
+            name: mcp_tool_name(&server, &definition.name),
+            description: definition.description.clone().unwrap_or_default(),
+            definition,
+            server,
+            clients,
+        }
+    }
+
+    /// 所属服务器名
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// 服务器上的原始工具名
+    pub fn tool_name(&self) -> &str {
+        &self.definition.name
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let parameters = if self.definition.input_schema.is_object() {
+            self.definition.input_schema.clone()
+        } else {
+            json!({ "type": "object", "properties": {} })
+        };
+        ToolSchema {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters,
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let arguments = if params.is_null() { json!({}) } else { params };
+        let client = current_client(self.clients.as_ref(), &self.server)?;
+        let result = tokio::select! {
+            _ = context.cancel.cancelled() => bail!("Call to {} was cancelled", self.name),
+            result = client.call_tool(&self.definition.name, arguments) => result?,
+        };
+
+        let (output, images) = render_content(&result.content);
+        if result.is_error {
+            bail!("{}", output);
+        }
+        let mut result = ToolResult::new(output);
+        result.images = images;
+        Ok(result)
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+}
+
+/// 把工具返回的内容块转换为文本输出和图片
+pub fn render_content(content: &[ToolContent]) -> (String, Vec<ImageBlock>) {
+    let mut parts = Vec::new();
+    let mut images = Vec::new();
+    for block in content {
+        match block {
+            ToolContent::Text { text } => parts.push(text.clone()),
+            ToolContent::Image { data, mime_type } => images.push(ImageBlock {
+                image_type: "base64".to_string(),
+                media_type: mime_type.clone(),
+                data: data.clone(),
+            }),
+            ToolContent::Resource { resource } => {
+                let uri = resource["uri"].as_str().unwrap_or("unknown");
+                let mime_type = resource["mimeType"].as_str().unwrap_or_default();
+                if let Some(text) = resource["text"].as_str() {
+                    parts.push(format!("[Resource: {}]\n{}", uri, text));
+                } else if let (Some(blob), true) =
+                    (resource["blob"].as_str(), mime_type.starts_with("image/"))
+                {
+                    images.push(ImageBlock {
+                        image_type: "base64".to_string(),
+                        media_type: mime_type.to_string(),
+                        data: blob.to_string(),
+                    });
+                } else {
+                    parts.push(format!("[Binary resource: {} ({})]", uri, mime_type));
+                }
+            }
+            ToolContent::Unknown => parts.push("[Unsupported content]".to_string()),
+        }
+    }
+    (parts.join("\n\n"), images)
+}
+
+/// 一个 MCP 服务器的工具来源
+///
+/// 注册到 [`ToolRegistry`](crate::ToolRegistry) 后，服务器工具列表的变化会反映到所有共享该来源的注册表。
+pub struct McpToolSource {
+    server: String,
+    clients: Arc<dyn McpClientProvider>,
+    tools: RwLock<Vec<Arc<dyn Tool>>>,
+    watcher: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl McpToolSource {
+    /// 读取单个连接的工具并开始监听列表变更
+    ///
+    /// 连接关闭后不会换用新连接；可能被重启的服务器使用 [`McpToolSource::from_provider`]。
+    pub async fn connect(client: Arc<McpClient>) -> Result<Arc<Self>> {
+        let server = client.name().to_string();
+        Self::from_provider(server, Arc::new(vec![client])).await
+    }
+
+    /// 读取 `clients` 中名为 `server` 的服务器的工具并开始监听列表变更
+    ///
+    /// `clients` 通常是 `McpManager`：服务器重启后工具调用使用新连接，工具列表重新读取。
+    pub async fn from_provider(
+        server: impl Into<String>,
+        clients: Arc<dyn McpClientProvider>,
+    ) -> Result<Arc<Self>> {
+        let server = server.into();
+        let client = current_client(clients.as_ref(), &server)?;
+        // 先订阅再读取列表，避免遗漏读取期间到达的变更通知
+        let notifications = client.subscribe();
+        let source = Arc::new(Self {
+            server,
+            clients,
+            tools: RwLock::new(Vec::new()),
+            watcher: std::sync::Mutex::new(None),
+        });
+        source.refresh_from(&client).await?;
+
+        let watcher = tokio::spawn(watch_list_changes(
+            Arc::downgrade(&source),
+            client,
+            notifications,
+        ));
+        *source.watcher.lock().unwrap() = Some(watcher);
+        Ok(source)
+    }
+
+    /// 服务器名
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// 重新读取服务器的工具列表
+    pub async fn refresh(&self) -> Result<()> {
+        let client = current_client(self.clients.as_ref(), &self.server)?;
+        self.refresh_from(&client).await
+    }
+
+    async fn refresh_from(&self, client: &McpClient) -> Result<()> {
+        let definitions = client.list_tools().await?;
+        let tools: Vec<Arc<dyn Tool>> = definitions
+            .into_iter()
+            .map(|definition| {
+                Arc::new(McpTool::new(
+                    self.server.clone(),
+                    self.clients.clone(),
+                    definition,
+                )) as _
+            })
+            .collect();
+        *self.tools.write().unwrap() = tools;
+        Ok(())
+    }
+}
+
+impl ToolSource for McpToolSource {
+    fn tools(&self) -> Vec<Arc<dyn Tool>> {
+        self.tools.read().unwrap().clone()
+    }
+
+    /// 服务器名规范化后相同（例如 `my server` 和 `my_server`）的来源不能同时注册
+    fn name_prefix(&self) -> Option<String> {
+        Some(mcp_server_prefix(self.server()))
+    }
+}
+
+impl Drop for McpToolSource {
+    fn drop(&mut self) {
+        if let Some(watcher) = self.watcher.lock().unwrap().take() {
+            watcher.abort();
+        }
+    }
+}
+
+/// 收到工具列表变更通知时刷新来源
+///
+/// 连接关闭后等待服务器重启，换用新连接并刷新一次；来源释放后退出。
+async fn watch_list_changes(
+    source: Weak<McpToolSource>,
+    mut client: Arc<McpClient>,
+    mut notifications: broadcast::Receiver<JsonRpcNotification>,
+) {
+    loop {
+        let received = tokio::select! {
+            received = notifications.recv() => Some(received),
+            _ = client.closed() => None,
+        };
+        let refresh = match received {
+            Some(Ok(notification)) => notification.method == TOOLS_LIST_CHANGED,
+            // 积压时可能漏掉了变更通知，保险起见刷新一次
+            Some(Err(RecvError::Lagged(_))) => true,
+            Some(Err(RecvError::Closed)) | None => {
+                let Some(next) = restarted_client(&source, &client).await else {
+                    return;
+                };
+                notifications = next.subscribe();
+                client = next;
+                true
+            }
+        };
+        if !refresh {
+            continue;
+        }
+        let Some(source) = source.upgrade() else {
+            return;
+        };
+        if let Err(e) = source.refresh_from(&client).await {
+            tracing::warn!(
+                "Failed to refresh tools from MCP server {}: {:#}",
+                source.server(),
+                e
+            );
+        }
+    }
+}
+
+/// 等待提供者给出与 `closed` 不同的可用连接，来源释放后返回 `None`
+async fn restarted_client(
+    source: &Weak<McpToolSource>,
+    closed: &Arc<McpClient>,
+) -> Option<Arc<McpClient>> {
+    loop {
+        tokio::time::sleep(REBIND_INTERVAL).await;
+        let source = source.upgrade()?;
+        if let Some(client) = source.clients.client(&source.server) {
+            if !Arc::ptr_eq(&client, closed) && !client.is_closed() {
+                return Some(client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToolRegistry;
+    use kode_core::agent::{Agent, AgentLocation, ToolFilter};
+    use kode_core::config::{GlobalConfig, ProjectConfig};
+    use kode_core::permission::{
+        AccessKind, PermissionEngine, PermissionRequest, PermissionRule, PermissionRules,
+    };
+    use kode_services::mcp::protocol::{JsonRpcMessage, JsonRpcResponse};
+    use kode_services::mcp::McpTransport;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc;
+
+    /// 内存中的假 MCP 服务器
+    struct FakeServer {
+        tools: Mutex<Vec<Value>>,
+        tx: mpsc::UnboundedSender<JsonRpcMessage>,
+        rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    }
+
+    impl FakeServer {
+        fn new(tools: Vec<Value>) -> Arc<Self> {
+            let (tx, rx) = mpsc::unbounded_channel();
+            Arc::new(Self {
+                tools: Mutex::new(tools),
+                tx,
+                rx: tokio::sync::Mutex::new(rx),
+            })
+        }
+
+        /// 替换工具列表并通知客户端
+        fn set_tools(&self, tools: Vec<Value>) {
+            *self.tools.lock().unwrap() = tools;
+            let _ = self
+                .tx
+                .send(JsonRpcMessage::Notification(JsonRpcNotification::new(
+                    TOOLS_LIST_CHANGED,
+                    None,
+                )));
+        }
+
+        fn call(&self, name: &str, arguments: &Value) -> Value {
+            match name {
+                "echo" => json!({ "content": [{ "type": "text", "text": arguments["text"] }] }),
+                "mixed" => json!({
+                    "content": [
+                        { "type": "text", "text": "chart below" },
+                        { "type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png" },
+                        {
+                            "type": "resource",
+                            "resource": { "uri": "file:///notes.md", "mimeType": "text/markdown", "text": "# Notes" }
+                        },
+                        {
+                            "type": "resource",
+                            "resource": { "uri": "file:///a.bin", "mimeType": "application/octet-stream", "blob": "AAAA" }
+                        }
+                    ]
+                }),
+                _ => json!({ "content": [{ "type": "text", "text": "boom" }], "isError": true }),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl McpTransport for FakeServer {
+        async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+            let JsonRpcMessage::Request(request) = message else {
+                return Ok(());
+            };
+            let params = request.params.clone().unwrap_or_default();
+            let result = match request.method.as_str() {
+                "initialize" => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": { "tools": { "listChanged": true } },
+                    "serverInfo": { "name": "fake", "version": "1.0" }
+                }),
+                "tools/list" => json!({ "tools": *self.tools.lock().unwrap() }),
+                "tools/call" => self.call(params["name"].as_str().unwrap(), &params["arguments"]),
+                _ => json!({}),
+            };
+            let _ = self
+                .tx
+                .send(JsonRpcMessage::Response(JsonRpcResponse::success(
+                    request.id.clone(),
+                    result,
+                )));
+            Ok(())
+        }
+
+        async fn receive(&self) -> Option<JsonRpcMessage> {
+            self.rx.lock().await.recv().await
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn tool_definition(name: &str) -> Value {
+        json!({
+            "name": name,
+            "description": format!("The {} tool", name),
+            "inputSchema": {
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"]
+            }
+        })
+    }
+
+    async fn connect(server: &Arc<FakeServer>) -> (ToolRegistry, Arc<McpToolSource>) {
+        let client = McpClient::connect("my server", server.clone())
+            .await
+            .unwrap();
+        let source = McpToolSource::connect(Arc::new(client)).await.unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.register_source(source.clone()).unwrap();
+        (registry, source)
+    }
+
+    fn context(dir: &TempDir, allowed: &[&str]) -> ToolContext {
+        let project = ProjectConfig {
+            allowed_tools: allowed.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let rules = PermissionRules::from_configs(&GlobalConfig::default(), &project);
+        ToolContext::new(dir.path())
+            .with_permissions(Arc::new(PermissionEngine::new(dir.path(), rules)))
+    }
+
+    #[test]
+    fn test_mcp_tool_name() {
+        assert_eq!(
+            mcp_tool_name("github", "create_issue"),
+            "mcp__github__create_issue"
+        );
+        assert_eq!(mcp_tool_name("my server", "a.b/c"), "mcp__my_server__a_b_c");
+        assert_eq!(mcp_tool_name("a__b", "x__y"), "mcp__a_b__x_y");
+        assert_eq!(mcp_tool_name("_a_ ", "t"), "mcp__a__t");
+        assert_eq!(mcp_tool_name("!!", "t"), "mcp__unnamed__t");
+
+        // 服务器名中的 `__` 不会让其他服务器的规则匹配到
+        let request = PermissionRequest::new(mcp_tool_name("a__b", "x"), AccessKind::Execute);
+        assert!(!PermissionRule::tool("mcp__a").matches(&request, Path::new("/")));
+        assert!(PermissionRule::tool("mcp__a_b").matches(&request, Path::new("/")));
+    }
+
+    #[tokio::test]
+    async fn test_prefix_collision_rejected() {
+        let server = FakeServer::new(vec![tool_definition("echo")]);
+        let (mut registry, _source) = connect(&server).await;
+
+        let other = FakeServer::new(vec![tool_definition("echo")]);
+        let client = McpClient::connect("my_server", other).await.unwrap();
+        let colliding = McpToolSource::connect(Arc::new(client)).await.unwrap();
+        let err = registry.register_source(colliding).unwrap_err();
+        assert!(err.to_string().contains("mcp__my_server__"));
+
+        let third = FakeServer::new(vec![tool_definition("echo")]);
+        let client = McpClient::connect("other", third).await.unwrap();
+        let distinct = McpToolSource::connect(Arc::new(client)).await.unwrap();
+        registry.register_source(distinct).unwrap();
+        assert_eq!(registry.list().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tools_registered_with_schema() {
+        let server = FakeServer::new(vec![tool_definition("echo"), json!({ "name": "bare" })]);
+        let (registry, _source) = connect(&server).await;
+
+        let schemas = registry.schemas();
+        let names: Vec<_> = schemas.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["mcp__my_server__bare", "mcp__my_server__echo"]);
+        assert_eq!(schemas[1].description, "The echo tool");
+        assert_eq!(schemas[1].parameters["required"], json!(["text"]));
+        assert_eq!(schemas[0].parameters["type"], "object");
+
+        let tool = registry.get("mcp__my_server__echo").unwrap();
+        assert!(tool.requires_permission());
+        assert!(!tool.is_read_only());
+    }
+
+    #[tokio::test]
+    async fn test_execute_maps_content() {
+        let dir = TempDir::new().unwrap();
+        let server = FakeServer::new(vec![
+            tool_definition("echo"),
+            tool_definition("mixed"),
+            tool_definition("broken"),
+        ]);
+        let (registry, _source) = connect(&server).await;
+        let context = context(&dir, &["mcp__my_server"]);
+
+        let result = registry
+            .call("mcp__my_server__echo", json!({ "text": "hi" }), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "hi");
+
+        let result = registry
+            .call("mcp__my_server__mixed", json!({}), &context)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.output,
+            "chart below\n\n[Resource: file:///notes.md]\n# Notes\n\n\
+             [Binary resource: file:///a.bin (application/octet-stream)]"
+        );
+        assert_eq!(result.images.len(), 1);
+        assert_eq!(result.images[0].media_type, "image/png");
+
+        let err = registry
+            .call("mcp__my_server__broken", json!({}), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+    }
+
+    #[tokio::test]
+    async fn test_list_changed_refreshes_all_clones() {
+        let server = FakeServer::new(vec![tool_definition("echo")]);
+        let (registry, _source) = connect(&server).await;
+        let shared = registry.clone();
+
+        server.set_tools(vec![tool_definition("search")]);
+        for _ in 0..50 {
+            if shared.get("mcp__my_server__search").is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(shared.list(), vec!["mcp__my_server__search"]);
+        assert!(registry.get("mcp__my_server__echo").is_none());
+    }
+
+    /// 连接可以被替换的客户端提供者，模拟管理器重启服务器
+    struct Restartable(Mutex<Option<Arc<McpClient>>>);
+
+    impl McpClientProvider for Restartable {
+        fn clients(&self) -> Vec<Arc<McpClient>> {
+            self.0.lock().unwrap().iter().cloned().collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restart_rebinds_client() {
+        let dir = TempDir::new().unwrap();
+        let context = context(&dir, &["mcp__my_server"]);
+        let first = Arc::new(
+            McpClient::connect("my server", FakeServer::new(vec![tool_definition("echo")]))
+                .await
+                .unwrap(),
+        );
+        let clients = Arc::new(Restartable(Mutex::new(Some(first.clone()))));
+        let source = McpToolSource::from_provider("my server", clients.clone())
+            .await
+            .unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.register_source(source).unwrap();
+
+        // 服务器退出，重启完成前调用报告未连接
+        first.close().await.unwrap();
+        *clients.0.lock().unwrap() = None;
+        let err = registry
+            .call("mcp__my_server__echo", json!({ "text": "hi" }), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "MCP server 'my server' is not connected");
+
+        // 重启后的连接提供新的工具列表，已注册的工具使用新连接
+        let restarted = FakeServer::new(vec![tool_definition("echo"), tool_definition("mixed")]);
+        let second = McpClient::connect("my server", restarted).await.unwrap();
+        *clients.0.lock().unwrap() = Some(Arc::new(second));
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while registry.get("mcp__my_server__mixed").is_none() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let result = registry
+            .call("mcp__my_server__echo", json!({ "text": "again" }), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "again");
+    }
+
+    #[tokio::test]
+    async fn test_permissions_and_agent_filters() {
+        let dir = TempDir::new().unwrap();
+        let server = FakeServer::new(vec![tool_definition("echo"), tool_definition("mixed")]);
+        let (registry, _source) = connect(&server).await;
+
+        // 没有规则也没有询问回调时拒绝
+        let err = registry
+            .call(
+                "mcp__my_server__echo",
+                json!({ "text": "x" }),
+                &context(&dir, &[]),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Permission denied"));
+
+        // 具体工具名规则只放行该工具
+        let context = context(&dir, &["mcp__my_server__echo"]);
+        assert!(registry
+            .call("mcp__my_server__echo", json!({ "text": "x" }), &context)
+            .await
+            .is_ok());
+        assert!(registry
+            .call("mcp__my_server__mixed", json!({}), &context)
+            .await
+            .is_err());
+
+        let agent = Agent::new(
+            "mcp-only".to_string(),
+            "Uses one MCP tool".to_string(),
+            ToolFilter::Specific(vec!["mcp__my_server__*".to_string()]),
+            "Prompt".to_string(),
+            AgentLocation::Project,
+        )
+        .with_disallowed_tools(vec!["mcp__my_server__mixed".to_string()]);
+        let tools = registry.for_agent(&agent);
+        assert_eq!(tools.registry.list(), vec!["mcp__my_server__echo"]);
+        assert!(tools.warnings.is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+//! ListMcpResources / ReadMcpResource 工具
+//!
+//! 让 Agent 按需浏览和读取已连接 MCP 服务器暴露的资源。
+//! 系统提示词中只注入 `mcp_context_uris` 列出的资源，其余内容由 Agent 通过这两个工具获取。
+
+use crate::mcp::render_content;
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_services::mcp::{McpClient, McpClientProvider, ToolContent};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const LIST_DESCRIPTION: &str = "Lists resources exposed by connected MCP servers. \
+Each entry shows the server name, the resource URI, its name and MIME type. \
+Pass `server` to list a single server. Use ReadMcpResource to fetch a resource's content.";
+
+const READ_DESCRIPTION: &str = "Reads a resource from a connected MCP server by URI. \
+Text resources are returned as text; image resources are returned as images.";
+
+/// ListMcpResources 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListMcpResourcesParams {
+    /// 只列出此服务器的资源
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+/// ReadMcpResource 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReadMcpResourceParams {
+    /// 服务器名
+    pub server: String,
+    /// 资源 URI
+    pub uri: String,
+}
+
+fn find_server(clients: &[Arc<McpClient>], server: &str) -> Result<Arc<McpClient>> {
+    match clients.iter().find(|client| client.name() == server) {
+        Some(client) => Ok(client.clone()),
+        None => {
+            let names: Vec<_> = clients.iter().map(|client| client.name()).collect();
+            if names.is_empty() {
+                bail!(
+                    "MCP server '{}' not found: no MCP servers are connected",
+                    server
+                );
+            }
+            bail!(
+                "MCP server '{}' not found. Connected servers: {}",
+                server,
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// ListMcpResources 工具
+pub struct ListMcpResourcesTool {
+    clients: Arc<dyn McpClientProvider>,
+}
+
+impl ListMcpResourcesTool {
+    /// 创建新的 ListMcpResources 工具
+    pub fn new(clients: Arc<dyn McpClientProvider>) -> Self {
+        Self { clients }
+    }
+}
+
+#[async_trait]
+impl Tool for ListMcpResourcesTool {
+    fn name(&self) -> &str {
+        "ListMcpResources"
+    }
+
+    fn description(&self) -> &str {
+        LIST_DESCRIPTION
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: LIST_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "server": {
+                        "type": "string",
+                        "description": "Only list resources of this MCP server"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: ListMcpResourcesParams = if params.is_null() {
+            ListMcpResourcesParams { server: None }
+        } else {
+            serde_json::from_value(params).context("Invalid ListMcpResources parameters")?
+        };
+        let clients = self.clients.clients();
+        let clients = match &params.server {
+            Some(server) => vec![find_server(&clients, server)?],
+            None => clients,
+        };
+
+        let mut lines = Vec::new();
+        for client in &clients {
+            let resources = tokio::select! {
+                _ = context.cancel.cancelled() => bail!("ListMcpResources was cancelled"),
+                resources = client.list_resources() => resources,
+            };
+            match resources {
+                Ok(resources) => {
+                    for resource in resources {
+                        let mut line = format!("{}: {}", client.name(), resource.uri);
+                        if !resource.name.is_empty() {
+                            line.push_str(&format!(" - {}", resource.name));
+                        }
+                        if let Some(mime_type) = &resource.mime_type {
+                            line.push_str(&format!(" ({})", mime_type));
+                        }
+                        if let Some(description) = &resource.description {
+                            line.push_str(&format!("\n  {}", description));
+                        }
+                        lines.push(line);
+                    }
+                }
+                Err(e) => lines.push(format!(
+                    "{}: failed to list resources: {}",
+                    client.name(),
+                    e
+                )),
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(ToolResult::new("No resources found."));
+        }
+        Ok(ToolResult::new(lines.join("\n")))
+    }
+}
+
+/// ReadMcpResource 工具
+pub struct ReadMcpResourceTool {
+    clients: Arc<dyn McpClientProvider>,
+}
+
+impl ReadMcpResourceTool {
+    /// 创建新的 ReadMcpResource 工具
+    pub fn new(clients: Arc<dyn McpClientProvider>) -> Self {
+        Self { clients }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadMcpResourceTool {
+    fn name(&self) -> &str {
+        "ReadMcpResource"
+    }
+
+    fn description(&self) -> &str {
+        READ_DESCRIPTION
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: READ_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "server": {
+                        "type": "string",
+                        "description": "The MCP server that provides the resource"
+                    },
+                    "uri": {
+                        "type": "string",
+                        "description": "The URI of the resource to read"
+                    }
+                },
+                "required": ["server", "uri"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: ReadMcpResourceParams =
+            serde_json::from_value(params).context("Invalid ReadMcpResource parameters")?;
+        let client = find_server(&self.clients.clients(), &params.server)?;
+        let result = tokio::select! {
+            _ = context.cancel.cancelled() => bail!("ReadMcpResource was cancelled"),
+            result = client.read_resource(&params.uri) => result?,
+        };
+
+        let content = result
+            .contents
+            .iter()
+            .map(|contents| {
+                Ok(ToolContent::Resource {
+                    resource: serde_json::to_value(contents)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (output, images) = render_content(&content);
+        let output = if output.is_empty() && images.is_empty() {
+            format!("Resource {} is empty", params.uri)
+        } else {
+            output
+        };
+        let mut tool_result = ToolResult::new(output);
+        tool_result.images = images;
+        Ok(tool_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_services::mcp::protocol::{JsonRpcMessage, JsonRpcResponse};
+    use kode_services::mcp::McpTransport;
+    use tempfile::TempDir;
+    use tokio::sync::{mpsc, Mutex};
+
+    /// 内存中的假 MCP 服务器，只提供资源
+    struct ResourceServer {
+        resources: bool,
+        tx: mpsc::UnboundedSender<JsonRpcMessage>,
+        rx: Mutex<mpsc::UnboundedReceiver<JsonRpcMessage>>,
+    }
+
+    impl ResourceServer {
+        fn read(uri: &str) -> Value {
+            match uri {
+                "docs://guide" => json!({
+                    "contents": [{ "uri": uri, "mimeType": "text/plain", "text": "Use tabs." }]
+                }),
+                "docs://diagram" => json!({
+                    "contents": [{ "uri": uri, "mimeType": "image/png", "blob": "iVBORw0KGgo=" }]
+                }),
+                _ => json!({ "contents": [] }),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl McpTransport for ResourceServer {
+        async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+            let JsonRpcMessage::Request(request) = message else {
+                return Ok(());
+            };
+            let params = request.params.clone().unwrap_or_default();
+            let capabilities = if self.resources {
+                json!({ "resources": {} })
+            } else {
+                json!({})
+            };
+            let result = match request.method.as_str() {
+                "initialize" => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": capabilities,
+                    "serverInfo": { "name": "fake", "version": "1.0" }
+                }),
+                "resources/list" => json!({
+                    "resources": [
+                        { "uri": "docs://guide", "name": "Style guide", "mimeType": "text/plain",
+                          "description": "Team conventions" },
+                        { "uri": "docs://diagram", "name": "Diagram" }
+                    ]
+                }),
+                "resources/read" => Self::read(params["uri"].as_str().unwrap()),
+                _ => json!({}),
+            };
+            let _ = self
+                .tx
+                .send(JsonRpcMessage::Response(JsonRpcResponse::success(
+                    request.id.clone(),
+                    result,
+                )));
+            Ok(())
+        }
+
+        async fn receive(&self) -> Option<JsonRpcMessage> {
+            self.rx.lock().await.recv().await
+        }
+
+        async fn close(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn client(name: &str, resources: bool) -> Arc<McpClient> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let server = Arc::new(ResourceServer {
+            resources,
+            tx,
+            rx: Mutex::new(rx),
+        });
+        Arc::new(McpClient::connect(name, server).await.unwrap())
+    }
+
+    async fn clients() -> Arc<dyn McpClientProvider> {
+        Arc::new(vec![
+            client("docs", true).await,
+            client("tools-only", false).await,
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_list_resources() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path());
+        let tool = ListMcpResourcesTool::new(clients().await);
+        assert!(tool.is_read_only());
+
+        let result = tool.execute(json!({}), &context).await.unwrap();
+        assert_eq!(
+            result.output,
+            "docs: docs://guide - Style guide (text/plain)\n  Team conventions\n\
+             docs: docs://diagram - Diagram"
+        );
+
+        let result = tool
+            .execute(json!({ "server": "tools-only" }), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "No resources found.");
+
+        let err = tool
+            .execute(json!({ "server": "nope" }), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "MCP server 'nope' not found. Connected servers: docs, tools-only"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_resource() {
+        let dir = TempDir::new().unwrap();
+        let context = ToolContext::new(dir.path());
+        let tool = ReadMcpResourceTool::new(clients().await);
+
+        let result = tool
+            .execute(json!({ "server": "docs", "uri": "docs://guide" }), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "[Resource: docs://guide]\nUse tabs.");
+
+        let result = tool
+            .execute(
+                json!({ "server": "docs", "uri": "docs://diagram" }),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(result.output.is_empty());
+        assert_eq!(result.images.len(), 1);
+        assert_eq!(result.images[0].media_type, "image/png");
+
+        let result = tool
+            .execute(json!({ "server": "docs", "uri": "docs://empty" }), &context)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "Resource docs://empty is empty");
+
+        let err = tool
+            .execute(
+                json!({ "server": "tools-only", "uri": "docs://guide" }),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not provide resources"));
+    }
+}
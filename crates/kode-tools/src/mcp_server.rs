@@ -0,0 +1,707 @@
+//! MCP 服务器模式
+//!
+//! 通过 stdio 以 MCP 协议对外提供 [`ToolRegistry`] 中的工具，供其他 MCP 客户端复用。
+//! 每个请求在独立任务中处理，`notifications/cancelled` 取消对应的工具调用。
+//! 工具调用与会话内一样经过 [`ToolRegistry::call`] 的权限检查；服务器模式下没有
+//! 询问回调，需要询问的调用直接被拒绝，只有配置中允许的操作才会执行。
+//!
+//! 可选地把 Agent 暴露为工具（`agent_<name>`），调用时在子对话中运行完整的查询循环。
+
+use crate::mcp::sanitize_name;
+use crate::task::TaskTool;
+use crate::{Tool, ToolContext, ToolRegistry, ToolResult, ToolSchema};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use kode_core::agent::Agent;
+use kode_core::model::ModelProvider;
+use kode_services::mcp::protocol::{
+    CallToolResult, Implementation, InitializeResult, JsonRpcMessage, JsonRpcRequest,
+    JsonRpcResponse, ListChangedCapability, ListToolsResult, McpTool as McpToolDefinition,
+    RequestId, ServerCapabilities, ToolContent, INVALID_PARAMS, MCP_PROTOCOL_VERSION,
+    METHOD_NOT_FOUND, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Agent 工具名前缀
+pub const AGENT_TOOL_PREFIX: &str = "agent_";
+
+/// 服务器名
+const SERVER_NAME: &str = "kode";
+
+/// tools/call 参数
+#[derive(Debug, Deserialize)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Option<Value>,
+}
+
+/// 以工具形式运行的 Agent
+///
+/// 调用转交给 Task 工具，Agent 在独立的子对话中运行并返回最终报告。
+pub struct AgentTool {
+    name: String,
+    agent: Agent,
+    task: Arc<TaskTool>,
+}
+
+impl AgentTool {
+    /// 包装 Agent
+    ///
+    /// 工具名为 `agent_` 加上按 [`sanitize_name`] 规范化的 Agent 名。
+    pub fn new(agent: Agent, task: Arc<TaskTool>) -> Self {
+        Self {
+            name: format!("{}{}", AGENT_TOOL_PREFIX, sanitize_name(&agent.name)),
+            agent,
+            task,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for AgentTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.agent.description
+    }
+
+    fn is_concurrency_safe(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name.clone(),
+            description: format!(
+                "Run the '{}' agent on a task in its own conversation and return its final report. {}",
+                self.agent.name, self.agent.description
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "prompt": {
+                        "type": "string",
+                        "description": "The task for the agent to perform"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "A short (3-5 word) description of the task"
+                    }
+                },
+                "required": ["prompt"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let mut params = match params {
+            Value::Object(params) => params,
+            Value::Null => serde_json::Map::new(),
+            other => anyhow::bail!("Invalid parameters for {}: {}", self.name, other),
+        };
+        params.insert("subagent_type".to_string(), json!(self.agent.name));
+        self.task.execute(Value::Object(params), context).await
+    }
+}
+
+/// MCP 服务器
+pub struct McpServer {
+    registry: ToolRegistry,
+    context: ToolContext,
+    in_flight: Mutex<HashMap<RequestId, CancellationToken>>,
+}
+
+impl McpServer {
+    /// 创建服务器
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - 对外提供的工具
+    /// * `context` - 工具调用共享的上下文（工作目录、权限引擎等）
+    pub fn new(registry: ToolRegistry, context: ToolContext) -> Self {
+        Self {
+            registry,
+            context,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 只提供 Agent 工具过滤器允许的工具
+    ///
+    /// 返回过滤后的服务器以及过滤器引用了不存在工具时的警告。
+    pub fn with_agent_filter(mut self, agent: &Agent) -> (Self, Vec<String>) {
+        let tools = self.registry.for_agent(agent);
+        self.registry = tools.registry;
+        (self, tools.warnings)
+    }
+
+    /// 把 Agent 暴露为工具
+    ///
+    /// 子 Agent 可用的工具来自当前（已过滤的）工具集。规范化后与已有工具重名的 Agent
+    /// 不会被暴露，返回对应的警告。
+    pub fn with_agents(
+        mut self,
+        agents: Vec<Agent>,
+        models: Arc<dyn ModelProvider>,
+    ) -> (Self, Vec<String>) {
+        let task = Arc::new(TaskTool::new(agents.clone(), self.registry.clone(), models));
+        let mut warnings = Vec::new();
+        for agent in agents {
+            let tool = AgentTool::new(agent, task.clone());
+            if self.registry.get(tool.name()).is_some() {
+                warnings.push(format!(
+                    "Agent '{}' is not exposed: tool name '{}' is already taken",
+                    tool.agent.name,
+                    tool.name()
+                ));
+                continue;
+            }
+            self.registry.register(Arc::new(tool));
+        }
+        (self, warnings)
+    }
+
+    /// 对外提供的工具
+    pub fn tools(&self) -> Vec<ToolSchema> {
+        self.registry.schemas()
+    }
+
+    /// 在 stdin/stdout 上运行服务器，直到客户端关闭输入
+    pub async fn serve_stdio(self: Arc<Self>) -> Result<()> {
+        self.serve(BufReader::new(tokio::io::stdin()), tokio::io::stdout())
+            .await
+    }
+
+    /// 在给定的输入输出上运行服务器
+    ///
+    /// 消息按行分隔。输入结束后等待仍在处理的请求完成并写出响应再返回。
+    pub async fn serve<R, W>(self: Arc<Self>, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<JsonRpcMessage>();
+        let mut lines = reader.lines();
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    match line.context("Failed to read MCP message")? {
+                        Some(line) => self.dispatch(&line, &tx),
+                        None => break,
+                    }
+                }
+                Some(message) = rx.recv() => write_message(&mut writer, &message).await?,
+            }
+        }
+
+        drop(tx);
+        while let Some(message) = rx.recv().await {
+            write_message(&mut writer, &message).await?;
+        }
+        Ok(())
+    }
+
+    fn dispatch(self: &Arc<Self>, line: &str, tx: &mpsc::UnboundedSender<JsonRpcMessage>) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let message: JsonRpcMessage = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("Ignoring invalid MCP message: {}", e);
+                return;
+            }
+        };
+
+        match message {
+            JsonRpcMessage::Request(request) => {
+                let cancel = self.context.cancel.child_token();
+                self.in_flight().insert(request.id.clone(), cancel.clone());
+                let server = self.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let id = request.id.clone();
+                    let response = server.handle_request(request, cancel.clone()).await;
+                    server.in_flight().remove(&id);
+                    // 被取消的请求不再响应
+                    if !cancel.is_cancelled() {
+                        let _ = tx.send(JsonRpcMessage::Response(response));
+                    }
+                });
+            }
+            JsonRpcMessage::Notification(notification) => {
+                if notification.method == "notifications/cancelled" {
+                    let id = notification
+                        .params
+                        .and_then(|params| params.get("requestId").cloned())
+                        .and_then(|id| serde_json::from_value::<RequestId>(id).ok());
+                    if let Some(cancel) = id.and_then(|id| self.in_flight().remove(&id)) {
+                        cancel.cancel();
+                    }
+                }
+            }
+            // 服务器不发请求，客户端的响应直接忽略
+            JsonRpcMessage::Response(_) => {}
+        }
+    }
+
+    fn in_flight(&self) -> std::sync::MutexGuard<'_, HashMap<RequestId, CancellationToken>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 处理单个请求
+    pub async fn handle_request(
+        &self,
+        request: JsonRpcRequest,
+        cancel: CancellationToken,
+    ) -> JsonRpcResponse {
+        let id = request.id.clone();
+        let params = request.params.unwrap_or(Value::Null);
+        let result = match request.method.as_str() {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(params, cancel).await,
+            method => {
+                return JsonRpcResponse::failure(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {}", method),
+                )
+            }
+        };
+        match result {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(message) => JsonRpcResponse::failure(id, INVALID_PARAMS, message),
+        }
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params["protocolVersion"].as_str().unwrap_or_default();
+        let protocol_version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+            requested
+        } else {
+            MCP_PROTOCOL_VERSION
+        };
+        let result = InitializeResult {
+            protocol_version: protocol_version.to_string(),
+            capabilities: ServerCapabilities {
+                tools: Some(ListChangedCapability::default()),
+                ..Default::default()
+            },
+            server_info: Implementation {
+                name: SERVER_NAME.to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            instructions: None,
+        };
+        serde_json::to_value(result).unwrap_or_default()
+    }
+
+    fn list_tools(&self) -> Value {
+        let tools = self
+            .registry
+            .schemas()
+            .into_iter()
+            .map(|schema| McpToolDefinition {
+                name: schema.name,
+                description: Some(schema.description),
+                input_schema: schema.parameters,
+            })
+            .collect();
+        serde_json::to_value(ListToolsResult {
+            tools,
+            next_cursor: None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// 执行工具调用；工具执行失败（包括权限拒绝）作为 `isError` 结果返回
+    async fn call_tool(
+        &self,
+        params: Value,
+        cancel: CancellationToken,
+    ) -> std::result::Result<Value, String> {
+        let params: CallToolParams = serde_json::from_value(params)
+            .map_err(|e| format!("Invalid tools/call parameters: {}", e))?;
+        if self.registry.get(&params.name).is_none() {
+            return Err(format!("Unknown tool: {}", params.name));
+        }
+
+        let context = self.context.clone().with_cancellation(cancel);
+        let arguments = params.arguments.unwrap_or_else(|| json!({}));
+        let result = match self.registry.call(&params.name, arguments, &context).await {
+            Ok(result) => {
+                let mut content = Vec::new();
+                if !result.output.is_empty() || result.images.is_empty() {
+                    content.push(ToolContent::Text {
+                        text: result.output,
+                    });
+                }
+                content.extend(result.images.into_iter().map(|image| ToolContent::Image {
+                    data: image.data,
+                    mime_type: image.media_type,
+                }));
+                CallToolResult {
+                    content,
+                    is_error: false,
+                }
+            }
+            Err(e) => CallToolResult {
+                content: vec![ToolContent::Text {
+                    text: format!("{:#}", e),
+                }],
+                is_error: true,
+            },
+        };
+        Ok(serde_json::to_value(result).unwrap_or_default())
+    }
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &JsonRpcMessage,
+) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .context("Failed to write MCP message")?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BashTool, GlobTool};
+    use kode_core::agent::{AgentLocation, ToolFilter};
+    use kode_core::config::{GlobalConfig, ProjectConfig};
+    use kode_core::message::{Message, MessageContent};
+    use kode_core::model::{ModelAdapter, ModelResponse, StreamChunk, StreamingResponse};
+    use kode_core::permission::{PermissionEngine, PermissionRules};
+    use serial_test::serial;
+    use tempfile::TempDir;
+    use tokio::io::{duplex, DuplexStream, Lines};
+
+    /// 一直等待直到被取消的工具
+    struct Block;
+
+    #[async_trait]
+    impl Tool for Block {
+        fn name(&self) -> &str {
+            "Block"
+        }
+
+        fn description(&self) -> &str {
+            "Wait until cancelled"
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema {
+                name: "Block".to_string(),
+                description: "Wait until cancelled".to_string(),
+                parameters: json!({ "type": "object" }),
+            }
+        }
+
+        async fn execute(&self, _params: Value, context: &ToolContext) -> Result<ToolResult> {
+            context.cancel.cancelled().await;
+            Ok(ToolResult::new("cancelled"))
+        }
+    }
+
+    fn server(dir: &TempDir, allowed: &[&str]) -> McpServer {
+        let project = ProjectConfig {
+            allowed_tools: allowed.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let rules = PermissionRules::from_configs(&GlobalConfig::default(), &project);
+        let context = ToolContext::new(dir.path())
+            .with_permissions(Arc::new(PermissionEngine::new(dir.path(), rules)));
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(GlobTool::new()));
+        registry.register(Arc::new(BashTool::new()));
+        registry.register(Arc::new(Block));
+        McpServer::new(registry, context)
+    }
+
+    struct Client {
+        input: DuplexStream,
+        output: Lines<BufReader<DuplexStream>>,
+        next_id: i64,
+    }
+
+    impl Client {
+        fn start(server: McpServer) -> Self {
+            let (input, server_input) = duplex(64 * 1024);
+            let (server_output, output) = duplex(64 * 1024);
+            tokio::spawn(Arc::new(server).serve(BufReader::new(server_input), server_output));
+            Self {
+                input,
+                output: BufReader::new(output).lines(),
+                next_id: 0,
+            }
+        }
+
+        async fn send(&mut self, message: Value) {
+            let line = format!("{}\n", message);
+            self.input.write_all(line.as_bytes()).await.unwrap();
+        }
+
+        async fn start_request(&mut self, method: &str, params: Value) -> i64 {
+            self.next_id += 1;
+            let id = self.next_id;
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+                .await;
+            id
+        }
+
+        async fn receive(&mut self) -> Value {
+            let line = self.output.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn request(&mut self, method: &str, params: Value) -> Value {
+            let id = self.start_request(method, params).await;
+            let response = self.receive().await;
+            assert_eq!(response["id"], id);
+            response
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let dir = TempDir::new().unwrap();
+        let mut client = Client::start(server(&dir, &[]));
+
+        let response = client
+            .request("initialize", json!({ "protocolVersion": "2025-03-26" }))
+            .await;
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(response["result"]["serverInfo"]["name"], "kode");
+        assert!(response["result"]["capabilities"]["tools"].is_object());
+        client
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+
+        let response = client.request("tools/list", json!({})).await;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["Bash", "Block", "Glob"]);
+        assert_eq!(tools[2]["inputSchema"]["required"], json!(["pattern"]));
+
+        let response = client.request("resources/list", json!({})).await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_enforces_permissions() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn main() {}").unwrap();
+        let mut client = Client::start(server(&dir, &["Bash(echo:*)"]));
+
+        // 只读工具不需要权限
+        let response = client
+            .request(
+                "tools/call",
+                json!({ "name": "Glob", "arguments": { "pattern": "*.rs" } }),
+            )
+            .await;
+        assert_eq!(response["result"]["isError"], false);
+        assert!(response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("a.rs"));
+
+        let response = client
+            .request(
+                "tools/call",
+                json!({ "name": "Bash", "arguments": { "command": "echo hello" } }),
+            )
+            .await;
+        assert_eq!(response["result"]["isError"], false, "{}", response);
+        assert!(response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("hello"));
+
+        // 没有规则放行、也无法询问用户的调用被拒绝
+        let response = client
+            .request(
+                "tools/call",
+                json!({ "name": "Bash", "arguments": { "command": "touch created" } }),
+            )
+            .await;
+        assert_eq!(response["result"]["isError"], true);
+        assert!(response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Permission denied"));
+        assert!(!dir.path().join("created").exists());
+
+        let response = client
+            .request("tools/call", json!({ "name": "Missing" }))
+            .await;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(response["error"]["message"], "Unknown tool: Missing");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_gets_no_response() {
+        let dir = TempDir::new().unwrap();
+        let mut client = Client::start(server(&dir, &[]));
+
+        let blocked = client
+            .start_request("tools/call", json!({ "name": "Block" }))
+            .await;
+        client
+            .send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": blocked, "reason": "user abort" }
+            }))
+            .await;
+
+        // 下一个响应属于后续请求，被取消的请求没有响应
+        let response = client.request("ping", json!({})).await;
+        assert_eq!(response["result"], json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_agent_filter() {
+        let dir = TempDir::new().unwrap();
+        let agent = Agent::new(
+            "searcher".to_string(),
+            "Searches files".to_string(),
+            ToolFilter::Specific(vec!["Glob".to_string(), "Grep".to_string()]),
+            "Prompt".to_string(),
+            AgentLocation::Project,
+        );
+        let (server, warnings) = server(&dir, &[]).with_agent_filter(&agent);
+        let names: Vec<_> = server.tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["Glob"]);
+        assert_eq!(
+            warnings,
+            vec!["Agent 'searcher' references unknown tool 'Grep'"]
+        );
+    }
+
+    /// 回显任务说明的模型
+    struct EchoAdapter;
+
+    #[async_trait]
+    impl ModelAdapter for EchoAdapter {
+        async fn send_message(
+            &self,
+            _messages: Vec<Message>,
+            _system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<ModelResponse> {
+            Err(kode_core::error::Error::ModelRequestError(
+                "not scripted".to_string(),
+            ))
+        }
+
+        async fn stream_message(
+            &self,
+            messages: Vec<Message>,
+            _system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<StreamingResponse> {
+            let MessageContent::Text(prompt) = &messages[0].content else {
+                panic!("expected text prompt");
+            };
+            let chunks = vec![
+                StreamChunk::content_block_delta(0, format!("report for {}", prompt)),
+                StreamChunk::message_stop(kode_core::model::TokenUsage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    total_tokens: Some(15),
+                }),
+            ];
+            Ok(StreamingResponse::new(Box::pin(futures::stream::iter(
+                chunks.into_iter().map(Ok),
+            ))))
+        }
+
+        fn model_name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    struct EchoProvider;
+
+    impl ModelProvider for EchoProvider {
+        fn adapter(&self, _model: Option<&str>) -> kode_core::Result<Arc<dyn ModelAdapter>> {
+            Ok(Arc::new(EchoAdapter))
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_agents_exposed_as_tools() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_var("KODE_CONFIG_DIR", dir.path());
+        let agent = Agent::new(
+            "code reviewer".to_string(),
+            "Reviews code".to_string(),
+            ToolFilter::All,
+            "You review code.".to_string(),
+            AgentLocation::Project,
+        );
+        let duplicate = Agent::new(
+            "code__reviewer".to_string(),
+            "Also reviews code".to_string(),
+            ToolFilter::All,
+            "You review code.".to_string(),
+            AgentLocation::Project,
+        );
+        let (server, warnings) =
+            server(&dir, &[]).with_agents(vec![agent, duplicate], Arc::new(EchoProvider));
+        assert_eq!(
+            warnings,
+            vec![
+                "Agent 'code__reviewer' is not exposed: tool name 'agent_code_reviewer' is already taken"
+            ]
+        );
+        let mut client = Client::start(server);
+
+        let response = client.request("tools/list", json!({})).await;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        let agent_tool = tools
+            .iter()
+            .find(|t| t["name"] == "agent_code_reviewer")
+            .unwrap();
+        assert_eq!(agent_tool["inputSchema"]["required"], json!(["prompt"]));
+        assert!(agent_tool["description"]
+            .as_str()
+            .unwrap()
+            .contains("'code reviewer' agent"));
+
+        let response = client
+            .request(
+                "tools/call",
+                json!({ "name": "agent_code_reviewer", "arguments": { "prompt": "check main.rs" } }),
+            )
+            .await;
+        assert_eq!(response["result"]["isError"], false, "{}", response);
+        assert!(response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("report for check main.rs"));
+        std::env::remove_var("KODE_CONFIG_DIR");
+    }
+}
@@ -0,0 +1,348 @@
+//! MultiEdit 工具
+//!
+//! 对单个文件按顺序应用多处字符串替换，全部成功才写入磁盘。
+
+use crate::file_utils::{self, LineEnding};
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::permission::PermissionRequest;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const DESCRIPTION: &str = "Apply multiple find-and-replace edits to a single file in one atomic \
+operation. Edits are applied in order, each one operating on the result of the previous edit. \
+Either all edits succeed or none are written. To create a new file, use an empty old_string in \
+the first edit.";
+
+/// 单处编辑
+#[derive(Debug, Clone, Deserialize)]
+pub struct EditOperation {
+    /// 要替换的文本
+    pub old_string: String,
+    /// 替换后的文本
+    pub new_string: String,
+    /// 是否替换所有匹配
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct MultiEditParams {
+    file_path: String,
+    edits: Vec<EditOperation>,
+}
+
+/// MultiEdit 工具
+#[derive(Debug, Default)]
+pub struct MultiEditTool;
+
+impl MultiEditTool {
+    /// 创建新的 MultiEdit 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// 在内存中按顺序应用编辑
+///
+/// `original` 为 `None` 表示文件不存在，此时第一处编辑的 `old_string` 必须为空。
+/// 任意一处编辑失败都会返回错误并指出编辑序号（从 1 开始）。
+///
+/// # Returns
+///
+/// 返回编辑后的内容（LF 换行）
+pub fn apply_edits(original: Option<&str>, edits: &[EditOperation]) -> Result<String> {
+    if edits.is_empty() {
+        bail!("At least one edit is required");
+    }
+
+    let mut content = match original {
+        Some(text) => LineEnding::normalize(text),
+        None => {
+            if !edits[0].old_string.is_empty() {
+                bail!(
+                    "File does not exist. Use an empty old_string in the first edit to create it"
+                );
+            }
+            String::new()
+        }
+    };
+
+    for (index, edit) in edits.iter().enumerate() {
+        let number = index + 1;
+        let old_string = LineEnding::normalize(&edit.old_string);
+        let new_string = LineEnding::normalize(&edit.new_string);
+
+        if old_string == new_string {
+            bail!("Edit {}: old_string and new_string are identical", number);
+        }
+
+        if old_string.is_empty() {
+            if index != 0 || original.is_some() {
+                bail!(
+                    "Edit {}: old_string can only be empty when creating a new file",
+                    number
+                );
+            }
+            content = new_string;
+            continue;
+        }
+
+        let occurrences = content.matches(old_string.as_str()).count();
+        if occurrences == 0 {
+            bail!("Edit {}: old_string not found in file", number);
+        }
+        if occurrences > 1 && !edit.replace_all {
+            bail!(
+                "Edit {}: old_string matches {} locations. Provide more context to make it \
+                 unique, or set replace_all to true",
+                number,
+                occurrences
+            );
+        }
+
+        content = if edit.replace_all {
+            content.replace(old_string.as_str(), &new_string)
+        } else {
+            content.replacen(old_string.as_str(), &new_string, 1)
+        };
+    }
+
+    Ok(content)
+}
+
+#[async_trait]
+impl Tool for MultiEditTool {
+    fn name(&self) -> &str {
+        "MultiEdit"
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The path of the file to modify"
+                    },
+                    "edits": {
+                        "type": "array",
+                        "description": "Edits to apply sequentially",
+                        "minItems": 1,
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "old_string": {
+                                    "type": "string",
+                                    "description": "The text to replace"
+                                },
+                                "new_string": {
+                                    "type": "string",
+                                    "description": "The text to replace it with"
+                                },
+                                "replace_all": {
+                                    "type": "boolean",
+                                    "description": "Replace all occurrences (default false)"
+                                }
+                            },
+                            "required": ["old_string", "new_string"]
+                        }
+                    }
+                },
+                "required": ["file_path", "edits"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: MultiEditParams =
+            serde_json::from_value(params).context("Invalid MultiEdit parameters")?;
+        let path = context.resolve_path(&params.file_path);
+        if path.extension().is_some_and(|ext| ext == "ipynb") {
+            bail!(
+                "{} is a Jupyter notebook; use the NotebookEdit tool to edit it",
+                path.display()
+            );
+        }
+
+        file_utils::check_write_allowed(&path, context)?;
+
+        let original = file_utils::read_existing(&path).await?;
+        let line_ending = original
+            .as_deref()
+            .map(LineEnding::detect)
+            .unwrap_or(LineEnding::Lf);
+
+        let updated = apply_edits(original.as_deref(), &params.edits)?;
+        file_utils::write_atomic(&path, &line_ending.apply(&updated)).await?;
+        file_utils::record_write(&path, context);
+
+        let action = if original.is_some() {
+            "Applied"
+        } else {
+            "Created file with"
+        };
+        Ok(ToolResult::new(format!(
+            "{} {} edit(s) to {}",
+            action,
+            params.edits.len(),
+            path.display()
+        )))
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    fn permission_request(&self, params: &Value, context: &ToolContext) -> PermissionRequest {
+        file_utils::edit_permission_request(self.name(), params, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn edit(old: &str, new: &str) -> EditOperation {
+        EditOperation {
+            old_string: old.to_string(),
+            new_string: new.to_string(),
+            replace_all: false,
+        }
+    }
+
+    #[test]
+    fn test_apply_edits_sequential() {
+        let edits = vec![edit("foo", "bar"), edit("bar baz", "qux")];
+        let result = apply_edits(Some("foo baz\n"), &edits).unwrap();
+        assert_eq!(result, "qux\n");
+    }
+
+    #[test]
+    fn test_apply_edits_ambiguous_match() {
+        let err = apply_edits(Some("a a"), &[edit("a", "b")]).unwrap_err();
+        assert!(err.to_string().contains("Edit 1"));
+        assert!(err.to_string().contains("2 locations"));
+
+        let mut replace_all = edit("a", "b");
+        replace_all.replace_all = true;
+        assert_eq!(apply_edits(Some("a a"), &[replace_all]).unwrap(), "b b");
+    }
+
+    #[test]
+    fn test_apply_edits_reports_failing_edit() {
+        let edits = vec![edit("one", "two"), edit("missing", "x")];
+        let err = apply_edits(Some("one"), &edits).unwrap_err();
+        assert!(err.to_string().contains("Edit 2: old_string not found"));
+    }
+
+    #[test]
+    fn test_apply_edits_create_new_file() {
+        let edits = vec![edit("", "hello world"), edit("world", "there")];
+        assert_eq!(apply_edits(None, &edits).unwrap(), "hello there");
+
+        assert!(apply_edits(None, &[edit("x", "y")]).is_err());
+        assert!(apply_edits(Some("existing"), &[edit("", "y")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_is_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.txt");
+        std::fs::write(&file, "alpha beta").unwrap();
+
+        let context = ToolContext::new(temp_dir.path());
+        let result = MultiEditTool::new()
+            .execute(
+                json!({
+                    "file_path": "a.txt",
+                    "edits": [
+                        {"old_string": "alpha", "new_string": "gamma"},
+                        {"old_string": "missing", "new_string": "x"}
+                    ]
+                }),
+                &context,
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "alpha beta");
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_notebooks() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("a.ipynb");
+        std::fs::write(&file, "{}").unwrap();
+
+        let context = ToolContext::new(temp_dir.path());
+        let err = MultiEditTool::new()
+            .execute(
+                json!({
+                    "file_path": "a.ipynb",
+                    "edits": [{"old_string": "{}", "new_string": "[]"}]
+                }),
+                &context,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("NotebookEdit"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "{}");
+    }
+
+    #[tokio::test]
+    async fn test_execute_preserves_crlf() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("win.txt");
+        std::fs::write(&file, "line one\r\nline two\r\n").unwrap();
+
+        let context = ToolContext::new(temp_dir.path());
+        context
+            .freshness()
+            .record_file_read(&file.to_string_lossy());
+        MultiEditTool::new()
+            .execute(
+                json!({
+                    "file_path": file.to_string_lossy(),
+                    "edits": [{"old_string": "one\nline", "new_string": "1\nline"}]
+                }),
+                &context,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "line 1\r\nline two\r\n"
+        );
+        assert!(context.freshness().is_file_tracked(&file.to_string_lossy()));
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_outside_working_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let context = ToolContext::new(temp_dir.path().join("project"));
+        let result = MultiEditTool::new()
+            .execute(
+                json!({
+                    "file_path": "../escape.txt",
+                    "edits": [{"old_string": "", "new_string": "x"}]
+                }),
+                &context,
+            )
+            .await;
+
+        assert!(result.unwrap_err().to_string().contains("outside"));
+        assert!(!temp_dir.path().join("escape.txt").exists());
+    }
+}
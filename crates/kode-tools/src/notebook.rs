@@ -0,0 +1,865 @@
+//! NotebookRead / NotebookEdit 工具
+//!
+//! 按 nbformat v4 解析 Jupyter notebook（`.ipynb`），而不是把它当作普通 JSON 文本编辑。
+//! 读取时渲染每个单元格的源码和输出，图片输出以 [`ImageBlock`] 返回；
+//! 编辑时按单元格 ID 或序号替换、插入、删除单元格，保留 notebook 和单元格的元数据。
+//!
+//! 写入与文件编辑工具走同样的检查：工作目录限制、新鲜度检查和 `Edit` 权限请求。
+
+use crate::file_utils::{self, normalize_path};
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::message::ImageBlock;
+use kode_core::permission::{AccessKind, PermissionRequest, PermissionTarget};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// 单个输出的最大显示长度（字符）
+const MAX_OUTPUT_CHARS: usize = 10_000;
+
+const READ_DESCRIPTION: &str = "Read a Jupyter notebook (.ipynb) and return all of its cells \
+with their outputs. Image outputs are returned as images. Pass `cell_id` to read a single cell. \
+Use this instead of reading the raw JSON.";
+
+const EDIT_DESCRIPTION: &str = "Edit a Jupyter notebook (.ipynb) cell. Identify the cell by \
+`cell_id` or by 0-based `cell_index`. edit_mode \"replace\" (default) replaces the cell source, \
+\"insert\" adds a new cell after `cell_id` (or at `cell_index`, or at the end), \"delete\" \
+removes the cell. Cell and notebook metadata are preserved. Use this instead of editing the \
+raw JSON.";
+
+/// 单元格类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellType {
+    /// 代码单元格
+    Code,
+    /// Markdown 单元格
+    Markdown,
+    /// 原始文本单元格
+    Raw,
+}
+
+impl CellType {
+    fn as_str(self) -> &'static str {
+        match self {
+            CellType::Code => "code",
+            CellType::Markdown => "markdown",
+            CellType::Raw => "raw",
+        }
+    }
+}
+
+/// 编辑方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditMode {
+    /// 替换单元格源码
+    #[default]
+    Replace,
+    /// 插入新单元格
+    Insert,
+    /// 删除单元格
+    Delete,
+}
+
+/// NotebookRead 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotebookReadParams {
+    /// notebook 路径
+    pub notebook_path: String,
+    /// 只读取此 ID 的单元格
+    #[serde(default)]
+    pub cell_id: Option<String>,
+}
+
+/// NotebookEdit 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotebookEditParams {
+    /// notebook 路径
+    pub notebook_path: String,
+    /// 目标单元格 ID
+    #[serde(default)]
+    pub cell_id: Option<String>,
+    /// 目标单元格序号（从 0 开始）
+    #[serde(default)]
+    pub cell_index: Option<usize>,
+    /// 新的源码（替换和插入时必填）
+    #[serde(default)]
+    pub new_source: Option<String>,
+    /// 单元格类型（插入时默认为 code，替换时可用于改变类型）
+    #[serde(default)]
+    pub cell_type: Option<CellType>,
+    /// 编辑方式
+    #[serde(default)]
+    pub edit_mode: EditMode,
+}
+
+/// 解析后的 notebook
+///
+/// 保存完整的 JSON，编辑只修改涉及的字段，其余内容原样写回。
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    json: Value,
+}
+
+impl Notebook {
+    /// 解析 nbformat v4 notebook
+    pub fn parse(content: &str) -> Result<Self> {
+        let json: Value = serde_json::from_str(content).context("Notebook is not valid JSON")?;
+        let format = json.get("nbformat").and_then(Value::as_u64);
+        if format != Some(4) {
+            bail!(
+                "Unsupported notebook format {}; only nbformat v4 is supported",
+                format.map_or_else(|| "(missing)".to_string(), |v| v.to_string())
+            );
+        }
+        if !json.get("cells").is_some_and(Value::is_array) {
+            bail!("Notebook has no cells array");
+        }
+        Ok(Self { json })
+    }
+
+    /// 序列化为 Jupyter 使用的格式（1 空格缩进，键排序，末尾换行）
+    pub fn to_json_string(&self) -> Result<String> {
+        let mut out = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+        self.json.serialize(&mut serializer)?;
+        out.push(b'\n');
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// 所有单元格
+    pub fn cells(&self) -> &[Value] {
+        self.json["cells"].as_array().map_or(&[], Vec::as_slice)
+    }
+
+    fn cells_mut(&mut self) -> &mut Vec<Value> {
+        self.json["cells"]
+            .as_array_mut()
+            .expect("validated in parse")
+    }
+
+    /// 代码单元格的语言
+    pub fn language(&self) -> &str {
+        let metadata = &self.json["metadata"];
+        metadata["language_info"]["name"]
+            .as_str()
+            .or_else(|| metadata["kernelspec"]["language"].as_str())
+            .unwrap_or("python")
+    }
+
+    /// 是否支持单元格 ID（nbformat 4.5 起）
+    fn has_cell_ids(&self) -> bool {
+        self.json["nbformat_minor"].as_u64().unwrap_or(0) >= 5
+    }
+
+    /// 按 ID 查找单元格序号
+    pub fn find(&self, cell_id: &str) -> Option<usize> {
+        self.cells()
+            .iter()
+            .position(|cell| cell["id"].as_str() == Some(cell_id))
+    }
+
+    /// 根据参数定位单元格
+    fn locate(&self, cell_id: Option<&str>, cell_index: Option<usize>) -> Result<usize> {
+        match (cell_id, cell_index) {
+            (Some(id), _) => self
+                .find(id)
+                .with_context(|| format!("Cell with id `{}` not found", id)),
+            (None, Some(index)) if index < self.cells().len() => Ok(index),
+            (None, Some(index)) => bail!(
+                "Cell index {} is out of range; the notebook has {} cells",
+                index,
+                self.cells().len()
+            ),
+            (None, None) => bail!("Either cell_id or cell_index is required"),
+        }
+    }
+
+    /// 应用一次编辑，返回描述
+    pub fn apply(&mut self, params: &NotebookEditParams) -> Result<String> {
+        let cell_id = params.cell_id.as_deref();
+        match params.edit_mode {
+            EditMode::Replace => {
+                let index = self.locate(cell_id, params.cell_index)?;
+                let source = params
+                    .new_source
+                    .as_deref()
+                    .context("new_source is required to replace a cell")?;
+                let cell = &mut self.cells_mut()[index];
+                set_source(cell, source);
+                let cell_type = match params.cell_type {
+                    Some(cell_type) => cell_type.as_str().to_string(),
+                    None => cell["cell_type"].as_str().unwrap_or("code").to_string(),
+                };
+                set_cell_type(cell, &cell_type);
+                Ok(format!("Replaced cell {}", describe(cell, index)))
+            }
+            EditMode::Insert => {
+                let source = params
+                    .new_source
+                    .as_deref()
+                    .context("new_source is required to insert a cell")?;
+                let index = match (cell_id, params.cell_index) {
+                    (Some(id), _) => {
+                        self.find(id)
+                            .with_context(|| format!("Cell with id `{}` not found", id))?
+                            + 1
+                    }
+                    (None, Some(index)) if index <= self.cells().len() => index,
+                    (None, Some(index)) => bail!(
+                        "Cell index {} is out of range; the notebook has {} cells",
+                        index,
+                        self.cells().len()
+                    ),
+                    (None, None) => self.cells().len(),
+                };
+                let mut cell = json!({"metadata": {}});
+                if self.has_cell_ids() {
+                    cell["id"] = json!(self.new_cell_id());
+                }
+                set_source(&mut cell, source);
+                set_cell_type(
+                    &mut cell,
+                    params.cell_type.unwrap_or(CellType::Code).as_str(),
+                );
+                let description = describe(&cell, index);
+                self.cells_mut().insert(index, cell);
+                Ok(format!("Inserted cell {}", description))
+            }
+            EditMode::Delete => {
+                let index = self.locate(cell_id, params.cell_index)?;
+                let cell = self.cells_mut().remove(index);
+                Ok(format!("Deleted cell {}", describe(&cell, index)))
+            }
+        }
+    }
+
+    fn new_cell_id(&self) -> String {
+        loop {
+            let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+            if self.find(&id).is_none() {
+                return id;
+            }
+        }
+    }
+
+    /// 渲染单元格及其输出，图片输出追加到 `images`
+    pub fn render_cell(&self, index: usize, images: &mut Vec<ImageBlock>) -> String {
+        let cell = &self.cells()[index];
+        let cell_type = cell["cell_type"].as_str().unwrap_or("code");
+        let mut out = format!("<cell index=\"{}\"", index);
+        if let Some(id) = cell["id"].as_str() {
+            let _ = write!(out, " id=\"{}\"", id);
+        }
+        let _ = write!(out, " type=\"{}\"", cell_type);
+        if let Some(count) = cell["execution_count"].as_u64() {
+            let _ = write!(out, " execution_count=\"{}\"", count);
+        }
+        out.push_str(">\n");
+
+        let source = multiline(&cell["source"]);
+        if cell_type == "code" {
+            let _ = writeln!(out, "```{}\n{}\n```", self.language(), source);
+        } else {
+            let _ = writeln!(out, "{}", source);
+        }
+
+        let outputs = cell["outputs"].as_array().map_or(&[][..], Vec::as_slice);
+        for output in outputs {
+            if let Some(text) = render_output(output, images) {
+                let _ = writeln!(out, "<output>\n{}\n</output>", truncate(&text));
+            }
+        }
+        out.push_str("</cell>");
+        out
+    }
+}
+
+/// 把源码写入单元格（按 Jupyter 的习惯拆分为行数组）
+fn set_source(cell: &mut Value, source: &str) {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    cell["source"] = json!(lines);
+}
+
+/// 设置单元格类型，并补齐或移除代码单元格特有的字段
+///
+/// 代码单元格的源码变化后，旧输出不再对应，一并清空。
+fn set_cell_type(cell: &mut Value, cell_type: &str) {
+    cell["cell_type"] = json!(cell_type);
+    let Some(fields) = cell.as_object_mut() else {
+        return;
+    };
+    if cell_type == "code" {
+        fields.insert("execution_count".to_string(), Value::Null);
+        fields.insert("outputs".to_string(), json!([]));
+    } else {
+        fields.remove("execution_count");
+        fields.remove("outputs");
+    }
+}
+
+fn describe(cell: &Value, index: usize) -> String {
+    match cell["id"].as_str() {
+        Some(id) => format!("`{}` (index {})", id, index),
+        None => format!("at index {}", index),
+    }
+}
+
+/// 字符串或字符串数组形式的多行文本
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn strip_ansi(text: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap())
+        .replace_all(text, "")
+        .into_owned()
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((end, _)) => format!("{}\n[Output truncated]", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// 渲染单个输出；图片输出追加到 `images`，没有文本时返回 `None`
+fn render_output(output: &Value, images: &mut Vec<ImageBlock>) -> Option<String> {
+    match output["output_type"].as_str()? {
+        "stream" => Some(strip_ansi(&multiline(&output["text"]))),
+        "error" => {
+            let traceback = output["traceback"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            let header = format!(
+                "{}: {}",
+                output["ename"].as_str().unwrap_or("Error"),
+                output["evalue"].as_str().unwrap_or("")
+            );
+            Some(strip_ansi(if traceback.is_empty() {
+                &header
+            } else {
+                &traceback
+            }))
+        }
+        "execute_result" | "display_data" => {
+            let data = output["data"].as_object()?;
+            let mut image = false;
+            for media_type in ["image/png", "image/jpeg", "image/gif", "image/webp"] {
+                if let Some(encoded) = data.get(media_type) {
+                    images.push(ImageBlock {
+                        image_type: "base64".to_string(),
+                        media_type: media_type.to_string(),
+                        data: multiline(encoded)
+                            .chars()
+                            .filter(|c| !c.is_whitespace())
+                            .collect(),
+                    });
+                    image = true;
+                    break;
+                }
+            }
+            match data.get("text/plain") {
+                Some(text) if !image => Some(strip_ansi(&multiline(text))),
+                Some(_) => Some(format!("[image output #{}]", images.len())),
+                None if image => Some(format!("[image output #{}]", images.len())),
+                None => other_mime_types(data),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn other_mime_types(data: &Map<String, Value>) -> Option<String> {
+    let types: Vec<&str> = data.keys().map(String::as_str).collect();
+    (!types.is_empty()).then(|| format!("[{} output]", types.join(", ")))
+}
+
+/// 解析并校验 notebook 路径
+fn notebook_path(path: &str, context: &ToolContext) -> Result<PathBuf> {
+    let path = context.resolve_path(path);
+    if path.extension().and_then(|e| e.to_str()) != Some("ipynb") {
+        bail!("{} is not a Jupyter notebook (.ipynb)", path.display());
+    }
+    Ok(path)
+}
+
+async fn read_notebook(path: &Path) -> Result<Notebook> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Notebook::parse(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// NotebookRead 工具
+#[derive(Debug, Default)]
+pub struct NotebookReadTool;
+
+impl NotebookReadTool {
+    /// 创建新的 NotebookRead 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for NotebookReadTool {
+    fn name(&self) -> &str {
+        "NotebookRead"
+    }
+
+    fn description(&self) -> &str {
+        READ_DESCRIPTION
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: READ_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "notebook_path": {
+                        "type": "string",
+                        "description": "The path of the .ipynb file to read"
+                    },
+                    "cell_id": {
+                        "type": "string",
+                        "description": "Only read the cell with this id"
+                    }
+                },
+                "required": ["notebook_path"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: NotebookReadParams =
+            serde_json::from_value(params).context("Invalid NotebookRead parameters")?;
+        let path = notebook_path(&params.notebook_path, context)?;
+        let notebook = read_notebook(&path).await?;
+        context
+            .freshness()
+            .record_file_read(&path.to_string_lossy());
+
+        let indices: Vec<usize> = match &params.cell_id {
+            Some(id) => vec![notebook
+                .find(id)
+                .with_context(|| format!("Cell with id `{}` not found", id))?],
+            None => (0..notebook.cells().len()).collect(),
+        };
+        if indices.is_empty() {
+            return Ok(ToolResult::new(format!("{} has no cells", path.display())));
+        }
+
+        let mut images = Vec::new();
+        let rendered: Vec<String> = indices
+            .into_iter()
+            .map(|index| notebook.render_cell(index, &mut images))
+            .collect();
+        let mut result = ToolResult::new(rendered.join("\n"));
+        for image in images {
+            result = result.with_image(image);
+        }
+        Ok(result)
+    }
+}
+
+/// NotebookEdit 工具
+#[derive(Debug, Default)]
+pub struct NotebookEditTool;
+
+impl NotebookEditTool {
+    /// 创建新的 NotebookEdit 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for NotebookEditTool {
+    fn name(&self) -> &str {
+        "NotebookEdit"
+    }
+
+    fn description(&self) -> &str {
+        EDIT_DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: EDIT_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "notebook_path": {
+                        "type": "string",
+                        "description": "The path of the .ipynb file to edit"
+                    },
+                    "cell_id": {
+                        "type": "string",
+                        "description": "The id of the cell to edit (insert adds the new cell after it)"
+                    },
+                    "cell_index": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "The 0-based index of the cell, used when cell_id is not given"
+                    },
+                    "new_source": {
+                        "type": "string",
+                        "description": "The new source of the cell (required for replace and insert)"
+                    },
+                    "cell_type": {
+                        "type": "string",
+                        "enum": ["code", "markdown", "raw"],
+                        "description": "The cell type; defaults to code for insert and to the current type for replace"
+                    },
+                    "edit_mode": {
+                        "type": "string",
+                        "enum": ["replace", "insert", "delete"],
+                        "description": "The kind of edit (default replace)"
+                    }
+                },
+                "required": ["notebook_path"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: NotebookEditParams =
+            serde_json::from_value(params).context("Invalid NotebookEdit parameters")?;
+        let path = notebook_path(&params.notebook_path, context)?;
+
+        file_utils::check_write_allowed(&path, context)?;
+
+        let mut notebook = read_notebook(&path).await?;
+        let summary = notebook.apply(&params)?;
+        file_utils::write_atomic(&path, &notebook.to_json_string()?).await?;
+        file_utils::record_write(&path, context);
+
+        Ok(ToolResult::new(format!(
+            "{} in {}",
+            summary,
+            path.display()
+        )))
+    }
+
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    fn permission_request(&self, params: &Value, context: &ToolContext) -> PermissionRequest {
+        let request = PermissionRequest::new(self.name(), AccessKind::Edit);
+        match params.get("notebook_path").and_then(Value::as_str) {
+            Some(path) => request.with_target(PermissionTarget::Path(normalize_path(
+                &context.resolve_path(path),
+            ))),
+            None => request,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "id": "intro",
+   "metadata": {"tags": ["title"]},
+   "source": ["# Analysis\n", "Loading data."]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "id": "load",
+   "metadata": {"collapsed": false},
+   "outputs": [
+    {"name": "stdout", "output_type": "stream", "text": ["rows: 10\n"]},
+    {
+     "data": {"image/png": "iVBORw0K\nGgo=", "text/plain": ["<Figure>"]},
+     "metadata": {},
+     "output_type": "display_data"
+    },
+    {
+     "ename": "KeyError",
+     "evalue": "'x'",
+     "output_type": "error",
+     "traceback": ["\u001b[0;31mKeyError\u001b[0m: 'x'"]
+    }
+   ],
+   "source": "df = load()\ndf.plot()"
+  }
+ ],
+ "metadata": {"kernelspec": {"language": "python", "name": "python3"}, "custom": 1},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    fn setup() -> (TempDir, ToolContext) {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("analysis.ipynb"), NOTEBOOK).unwrap();
+        let context = ToolContext::new(dir.path());
+        context
+            .freshness()
+            .record_file_read(&dir.path().join("analysis.ipynb").to_string_lossy());
+        (dir, context)
+    }
+
+    fn saved(dir: &TempDir) -> Value {
+        let content = std::fs::read_to_string(dir.path().join("analysis.ipynb")).unwrap();
+        serde_json::from_str(&content).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_read_renders_cells_and_images() {
+        let (dir, context) = setup();
+        let result = NotebookReadTool::new()
+            .execute(json!({"notebook_path": "analysis.ipynb"}), &context)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.output,
+            "<cell index=\"0\" id=\"intro\" type=\"markdown\">\n# Analysis\nLoading data.\n</cell>\n\
+<cell index=\"1\" id=\"load\" type=\"code\" execution_count=\"3\">\n\
+```python\ndf = load()\ndf.plot()\n```\n\
+<output>\nrows: 10\n\n</output>\n\
+<output>\n[image output #1]\n</output>\n\
+<output>\nKeyError: 'x'\n</output>\n</cell>"
+        );
+        assert_eq!(
+            result.images,
+            [ImageBlock {
+                image_type: "base64".to_string(),
+                media_type: "image/png".to_string(),
+                data: "iVBORw0KGgo=".to_string(),
+            }]
+        );
+        let path = dir.path().join("analysis.ipynb");
+        assert!(context.freshness().is_file_tracked(&path.to_string_lossy()));
+
+        let single = NotebookReadTool::new()
+            .execute(
+                json!({"notebook_path": "analysis.ipynb", "cell_id": "intro"}),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(single.output.starts_with("<cell index=\"0\""));
+        assert!(single.images.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replace_preserves_metadata() {
+        let (dir, context) = setup();
+        let result = NotebookEditTool::new()
+            .execute(
+                json!({
+                    "notebook_path": "analysis.ipynb",
+                    "cell_id": "load",
+                    "new_source": "df = load()\nprint(df)\n"
+                }),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(result.output.starts_with("Replaced cell `load` (index 1)"));
+
+        let notebook = saved(&dir);
+        let cell = &notebook["cells"][1];
+        assert_eq!(cell["source"], json!(["df = load()\n", "print(df)\n"]));
+        assert_eq!(cell["metadata"], json!({"collapsed": false}));
+        assert_eq!(cell["outputs"], json!([]));
+        assert_eq!(cell["execution_count"], Value::Null);
+        assert_eq!(notebook["metadata"]["custom"], 1);
+        assert_eq!(notebook["cells"][0]["metadata"]["tags"], json!(["title"]));
+
+        // 按序号替换并改变类型
+        NotebookEditTool::new()
+            .execute(
+                json!({
+                    "notebook_path": "analysis.ipynb",
+                    "cell_index": 1,
+                    "new_source": "Notes",
+                    "cell_type": "markdown"
+                }),
+                &context,
+            )
+            .await
+            .unwrap();
+        let cell = &saved(&dir)["cells"][1];
+        assert_eq!(cell["cell_type"], "markdown");
+        assert!(cell.get("outputs").is_none());
+        assert!(cell.get("execution_count").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_delete() {
+        let (dir, context) = setup();
+        let tool = NotebookEditTool::new();
+        tool.execute(
+            json!({
+                "notebook_path": "analysis.ipynb",
+                "cell_id": "intro",
+                "new_source": "import pandas",
+                "edit_mode": "insert"
+            }),
+            &context,
+        )
+        .await
+        .unwrap();
+        tool.execute(
+            json!({
+                "notebook_path": "analysis.ipynb",
+                "new_source": "## Conclusion",
+                "cell_type": "markdown",
+                "edit_mode": "insert"
+            }),
+            &context,
+        )
+        .await
+        .unwrap();
+
+        let notebook = saved(&dir);
+        let cells = notebook["cells"].as_array().unwrap();
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[1]["cell_type"], "code");
+        assert_eq!(cells[1]["source"], json!(["import pandas"]));
+        assert_eq!(cells[1]["outputs"], json!([]));
+        assert_eq!(cells[1]["id"].as_str().unwrap().len(), 8);
+        assert_eq!(cells[2]["id"], "load");
+        assert_eq!(cells[3]["cell_type"], "markdown");
+
+        let result = tool
+            .execute(
+                json!({"notebook_path": "analysis.ipynb", "cell_id": "load", "edit_mode": "delete"}),
+                &context,
+            )
+            .await
+            .unwrap();
+        assert!(result.output.starts_with("Deleted cell `load` (index 2)"));
+        assert!(Notebook::parse(
+            &std::fs::read_to_string(dir.path().join("analysis.ipynb")).unwrap()
+        )
+        .unwrap()
+        .find("load")
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_edit_errors_and_freshness() {
+        let (dir, context) = setup();
+        let tool = NotebookEditTool::new();
+
+        let err = tool
+            .execute(
+                json!({"notebook_path": "analysis.ipynb", "cell_id": "missing", "new_source": "x"}),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`missing` not found"));
+
+        let err = tool
+            .execute(
+                json!({"notebook_path": "analysis.ipynb", "cell_index": 5, "edit_mode": "delete"}),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("out of range"));
+
+        std::fs::write(dir.path().join("notes.txt"), "x").unwrap();
+        let err = tool
+            .execute(
+                json!({"notebook_path": "notes.txt", "cell_index": 0}),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not a Jupyter notebook"));
+
+        std::fs::write(
+            dir.path().join("old.ipynb"),
+            r#"{"nbformat": 3, "worksheets": []}"#,
+        )
+        .unwrap();
+        let err = NotebookReadTool::new()
+            .execute(json!({"notebook_path": "old.ipynb"}), &context)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("only nbformat v4"));
+
+        // 读取后被外部修改，需要重新读取才能编辑
+        NotebookReadTool::new()
+            .execute(json!({"notebook_path": "analysis.ipynb"}), &context)
+            .await
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(
+            dir.path().join("analysis.ipynb"),
+            NOTEBOOK.replace("rows", "cols"),
+        )
+        .unwrap();
+        let err = tool
+            .execute(
+                json!({"notebook_path": "analysis.ipynb", "cell_index": 0, "new_source": "x"}),
+                &context,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("modified since it was last read"));
+    }
+
+    #[test]
+    fn test_permission_request_targets_notebook() {
+        let context = ToolContext::new("/project");
+        let request = NotebookEditTool::new()
+            .permission_request(&json!({"notebook_path": "nb/a.ipynb"}), &context);
+        assert_eq!(request.access, AccessKind::Edit);
+        assert_eq!(
+            request.target,
+            PermissionTarget::Path(PathBuf::from("/project/nb/a.ipynb"))
+        );
+    }
+
+    #[test]
+    fn test_serializes_like_jupyter() {
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        let out = notebook.to_json_string().unwrap();
+        assert!(out.starts_with("{\n \"cells\": [\n  {\n   \"cell_type\": \"markdown\""));
+        assert!(out.ends_with("}\n"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&out).unwrap(),
+            serde_json::from_str::<Value>(NOTEBOOK).unwrap()
+        );
+    }
+}
@@ -0,0 +1,460 @@
+//! 工具编排
+//!
+//! 执行同一条助手消息中的多个工具调用：相邻的并发安全调用（例如 Grep、Glob）
+//! 组成一批并行执行，其余调用按顺序逐个执行。无论执行顺序如何，
+//! 结果都按原始 tool_use 顺序返回。
+//!
+//! [`OrchestratorRunner`] 把编排器和工具上下文绑定在一起，作为查询循环的 [`ToolRunner`]。
+
+use crate::{ProgressSink, ToolContext, ToolRegistry};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use kode_core::message::types::ProgressMessage;
+use kode_core::message::{Message, ToolResultBlock, ToolUseBlock};
+use kode_core::query::{ToolRunner, TOOL_CANCELLED_MESSAGE};
+use std::collections::HashSet;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+/// 默认最大并发数
+pub const DEFAULT_MAX_CONCURRENCY: usize = 10;
+
+/// 工具编排器
+#[derive(Clone)]
+pub struct ToolOrchestrator {
+    registry: ToolRegistry,
+    max_concurrency: usize,
+}
+
+/// 一批工具调用
+#[derive(Debug, PartialEq, Eq)]
+enum Batch<'a> {
+    /// 并发执行
+    Concurrent(&'a [ToolUseBlock]),
+    /// 单个串行执行
+    Serial(&'a ToolUseBlock),
+}
+
+impl ToolOrchestrator {
+    /// 创建工具编排器
+    pub fn new(registry: ToolRegistry) -> Self {
+        Self {
+            registry,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        }
+    }
+
+    /// 设置最大并发数（至少为 1）
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// 工具注册表
+    pub fn registry(&self) -> &ToolRegistry {
+        &self.registry
+    }
+
+    /// 绑定工具上下文，得到查询循环使用的 [`ToolRunner`]
+    pub fn runner(self, context: ToolContext) -> OrchestratorRunner {
+        OrchestratorRunner {
+            orchestrator: self,
+            context,
+            progress: None,
+        }
+    }
+
+    /// 执行一轮中的所有工具调用
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_uses` - 助手消息中的工具调用（按出现顺序）
+    /// * `context` - 工具上下文
+    /// * `progress` - 可选的进度通道，每个调用开始和结束时各发送一条进度消息
+    ///
+    /// # Returns
+    ///
+    /// 按 `tool_uses` 顺序排列的工具结果；执行失败的调用返回 `is_error` 结果
+    pub async fn run(
+        &self,
+        tool_uses: &[ToolUseBlock],
+        context: &ToolContext,
+        progress: Option<&UnboundedSender<ProgressMessage>>,
+    ) -> Vec<ToolResultBlock> {
+        let mut results = Vec::with_capacity(tool_uses.len());
+        for batch in self.partition(tool_uses) {
+            match batch {
+                Batch::Serial(tool_use) => {
+                    let siblings = HashSet::from([tool_use.tool_use_id.clone()]);
+                    results.push(self.execute(tool_use, context, &siblings, progress).await);
+                }
+                Batch::Concurrent(tool_uses) => {
+                    let siblings: HashSet<String> =
+                        tool_uses.iter().map(|t| t.tool_use_id.clone()).collect();
+                    // 先创建 future 再交给 stream，避免闭包的高阶生命周期让 future 不满足 Send；
+                    // buffered 保持输入顺序
+                    let calls: Vec<_> = tool_uses
+                        .iter()
+                        .map(|tool_use| self.execute(tool_use, context, &siblings, progress))
+                        .collect();
+                    let batch_results: Vec<ToolResultBlock> = stream::iter(calls)
+                        .buffered(self.max_concurrency)
+                        .collect()
+                        .await;
+                    results.extend(batch_results);
+                }
+            }
+        }
+        results
+    }
+
+    /// 将工具调用划分为并发批次和串行调用
+    fn partition<'a>(&self, tool_uses: &'a [ToolUseBlock]) -> Vec<Batch<'a>> {
+        let mut batches = Vec::new();
+        let mut start = 0;
+        while start < tool_uses.len() {
+            let safe_len = tool_uses[start..]
+                .iter()
+                .take_while(|t| self.is_concurrency_safe(&t.tool_name))
+                .count();
+            if safe_len == 0 {
+                batches.push(Batch::Serial(&tool_uses[start]));
+                start += 1;
+            } else {
+                batches.push(Batch::Concurrent(&tool_uses[start..start + safe_len]));
+                start += safe_len;
+            }
+        }
+        batches
+    }
+
+    fn is_concurrency_safe(&self, tool_name: &str) -> bool {
+        self.registry
+            .get(tool_name)
+            .is_some_and(|tool| tool.is_concurrency_safe())
+    }
+
+    async fn execute(
+        &self,
+        tool_use: &ToolUseBlock,
+        context: &ToolContext,
+        siblings: &HashSet<String>,
+        progress: Option<&UnboundedSender<ProgressMessage>>,
+    ) -> ToolResultBlock {
+        let sink = progress.map(|sender| {
+            ProgressSink::new(sender.clone(), &tool_use.tool_use_id, siblings.clone())
+        });
+        let report = |text: String| {
+            if let Some(sink) = &sink {
+                sink.report(&Message::assistant(text));
+            }
+        };
+
+        if context.cancel.is_cancelled() {
+            return ToolResultBlock {
+                tool_use_id: tool_use.tool_use_id.clone(),
+                content: TOOL_CANCELLED_MESSAGE.to_string(),
+                is_error: true,
+                images: Vec::new(),
+            };
+        }
+
+        report(format!("Running {}", tool_use.tool_name));
+        let context = match &sink {
+            Some(sink) => context.clone().with_progress(sink.clone()),
+            None => context.clone(),
+        };
+        let result = self
+            .registry
+            .call(&tool_use.tool_name, tool_use.parameters.clone(), &context)
+            .await;
+        let (content, images, is_error) = match result {
+            Ok(result) => (result.output, result.images, false),
+            Err(e) => (format!("Error: {:#}", e), Vec::new(), true),
+        };
+        report(format!(
+            "{} {}",
+            tool_use.tool_name,
+            if is_error { "failed" } else { "completed" }
+        ));
+
+        ToolResultBlock {
+            tool_use_id: tool_use.tool_use_id.clone(),
+            content,
+            is_error,
+            images,
+        }
+    }
+}
+
+/// 绑定了工具上下文的编排器
+///
+/// 每次执行时使用查询循环传入的取消令牌替换上下文中的令牌。
+#[derive(Clone)]
+pub struct OrchestratorRunner {
+    orchestrator: ToolOrchestrator,
+    context: ToolContext,
+    progress: Option<UnboundedSender<ProgressMessage>>,
+}
+
+impl OrchestratorRunner {
+    /// 设置进度通道
+    pub fn with_progress(mut self, progress: UnboundedSender<ProgressMessage>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+#[async_trait]
+impl ToolRunner for OrchestratorRunner {
+    async fn run(
+        &self,
+        tool_uses: &[ToolUseBlock],
+        cancel: &CancellationToken,
+    ) -> Vec<ToolResultBlock> {
+        let context = self.context.clone().with_cancellation(cancel.clone());
+        self.orchestrator
+            .run(tool_uses, &context, self.progress.as_ref())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Tool, ToolResult, ToolSchema};
+    use anyhow::{bail, Result};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// 记录并发度和执行顺序的测试工具
+    struct Probe {
+        name: &'static str,
+        read_only: bool,
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Tool for Probe {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "probe"
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema {
+                name: self.name.to_string(),
+                description: "probe".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn execute(&self, params: Value, _context: &ToolContext) -> Result<ToolResult> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            let delay = params["delay"].as_u64().unwrap_or(10);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            let id = params["id"].as_str().unwrap_or_default().to_string();
+            self.log.lock().unwrap().push(id.clone());
+            if params["fail"].as_bool() == Some(true) {
+                bail!("probe {} failed", id);
+            }
+            Ok(ToolResult::new(format!("done {}", id)))
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+    }
+
+    struct Setup {
+        orchestrator: ToolOrchestrator,
+        peak: Arc<AtomicUsize>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    fn setup(max_concurrency: usize) -> Setup {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ToolRegistry::new();
+        for (name, read_only) in [("Read", true), ("Write", false)] {
+            registry.register(Arc::new(Probe {
+                name,
+                read_only,
+                running: running.clone(),
+                peak: peak.clone(),
+                log: log.clone(),
+            }));
+        }
+        Setup {
+            orchestrator: ToolOrchestrator::new(registry).with_max_concurrency(max_concurrency),
+            peak,
+            log,
+        }
+    }
+
+    fn tool_use(tool: &str, id: &str, delay: u64) -> ToolUseBlock {
+        ToolUseBlock {
+            tool_use_id: id.to_string(),
+            tool_name: tool.to_string(),
+            parameters: json!({"id": id, "delay": delay}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_tools_run_concurrently_in_order() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(DEFAULT_MAX_CONCURRENCY);
+        let uses = vec![
+            tool_use("Read", "a", 60),
+            tool_use("Read", "b", 10),
+            tool_use("Read", "c", 30),
+        ];
+
+        let results = setup
+            .orchestrator
+            .run(&uses, &ToolContext::new(dir.path()), None)
+            .await;
+        let ids: Vec<&str> = results.iter().map(|r| r.tool_use_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(results[0].content, "done a");
+        assert_eq!(setup.peak.load(Ordering::SeqCst), 3);
+        // 完成顺序与结果顺序无关
+        assert_eq!(*setup.log.lock().unwrap(), vec!["b", "c", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_mutating_tools_run_serially() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(DEFAULT_MAX_CONCURRENCY);
+        let uses = vec![
+            tool_use("Read", "r1", 30),
+            tool_use("Read", "r2", 10),
+            tool_use("Write", "w1", 20),
+            tool_use("Write", "w2", 5),
+            tool_use("Read", "r3", 5),
+        ];
+
+        let batches = setup.orchestrator.partition(&uses);
+        assert_eq!(
+            batches,
+            vec![
+                Batch::Concurrent(&uses[0..2]),
+                Batch::Serial(&uses[2]),
+                Batch::Serial(&uses[3]),
+                Batch::Concurrent(&uses[4..5]),
+            ]
+        );
+
+        // 测试上下文没有权限询问回调，改用 bypass 模式
+        let context = ToolContext::new(dir.path()).with_permissions(Arc::new(
+            kode_core::permission::PermissionEngine::new(dir.path(), Default::default())
+                .with_mode(kode_core::permission::PermissionMode::Bypass),
+        ));
+        let results = setup.orchestrator.run(&uses, &context, None).await;
+        assert!(results.iter().all(|r| !r.is_error));
+        assert_eq!(
+            *setup.log.lock().unwrap(),
+            vec!["r2", "r1", "w1", "w2", "r3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_cap() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(2);
+        let uses: Vec<ToolUseBlock> = (0..6)
+            .map(|i| tool_use("Read", &format!("r{}", i), 20))
+            .collect();
+
+        let results = setup
+            .orchestrator
+            .run(&uses, &ToolContext::new(dir.path()), None)
+            .await;
+        assert_eq!(results.len(), 6);
+        assert_eq!(setup.peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_and_progress() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(DEFAULT_MAX_CONCURRENCY);
+        let mut failing = tool_use("Read", "bad", 5);
+        failing.parameters["fail"] = json!(true);
+        let uses = vec![
+            tool_use("Read", "ok", 5),
+            failing,
+            tool_use("Missing", "unknown", 5),
+        ];
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let results = setup
+            .orchestrator
+            .run(&uses, &ToolContext::new(dir.path()), Some(&tx))
+            .await;
+
+        assert!(!results[0].is_error);
+        assert!(results[1].is_error);
+        assert!(results[1].content.contains("probe bad failed"));
+        assert!(results[2].is_error);
+        assert!(results[2].content.contains("Unknown tool"));
+
+        drop(tx);
+        let mut messages = Vec::new();
+        while let Some(message) = rx.recv().await {
+            messages.push(message);
+        }
+        assert_eq!(messages.len(), 6);
+        let first = messages
+            .iter()
+            .find(|m| m.tool_use_id == "ok")
+            .expect("progress for sibling");
+        assert_eq!(
+            first.sibling_tool_use_ids,
+            HashSet::from(["ok".to_string(), "bad".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_context() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(DEFAULT_MAX_CONCURRENCY);
+        let context = ToolContext::new(dir.path());
+        context.cancel.cancel();
+
+        let results = setup
+            .orchestrator
+            .run(&[tool_use("Read", "a", 5)], &context, None)
+            .await;
+        assert!(results[0].is_error);
+        assert!(setup.log.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_runner_uses_query_cancellation() {
+        let dir = TempDir::new().unwrap();
+        let setup = setup(DEFAULT_MAX_CONCURRENCY);
+        let runner = setup.orchestrator.runner(ToolContext::new(dir.path()));
+        let uses = [tool_use("Read", "a", 5)];
+
+        let results = ToolRunner::run(&runner, &uses, &CancellationToken::new()).await;
+        assert!(!results[0].is_error);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let results = ToolRunner::run(&runner, &uses, &cancel).await;
+        assert_eq!(results[0].content, TOOL_CANCELLED_MESSAGE);
+    }
+}
@@ -1,13 +1,34 @@
 //! 工具注册表
 
-use crate::Tool;
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Result};
+use kode_core::agent::Agent;
+use kode_core::permission::PermissionDecision;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// 动态工具来源
+///
+/// 工具集合可能在运行中变化（例如 MCP 服务器通知工具列表变更），
+/// 注册表每次查询时都从来源读取当前工具。
+pub trait ToolSource: Send + Sync {
+    /// 当前提供的工具
+    fn tools(&self) -> Vec<Arc<dyn Tool>>;
+
+    /// 来源中所有工具共用的名称前缀（例如 `mcp__github__`），注册时用于检测冲突
+    fn name_prefix(&self) -> Option<String> {
+        None
+    }
+}
+
 /// 工具注册表
-#[derive(Default)]
+///
+/// 克隆的注册表共享动态工具来源，来源中的变化对所有克隆可见。
+#[derive(Default, Clone)]
 pub struct ToolRegistry {
     tools: HashMap<String, Arc<dyn Tool>>,
+    sources: Vec<Arc<dyn ToolSource>>,
 }
 
 impl ToolRegistry {
@@ -21,13 +42,273 @@ impl ToolRegistry {
         self.tools.insert(tool.name().to_string(), tool);
     }
 
+    /// 注册动态工具来源
+    ///
+    /// 与静态注册的工具重名时，静态工具优先。
+    ///
+    /// # Errors
+    ///
+    /// 与已注册来源的名称前缀相同时返回错误，避免两个来源的工具互相覆盖。
+    pub fn register_source(&mut self, source: Arc<dyn ToolSource>) -> Result<()> {
+        if let Some(prefix) = source.name_prefix() {
+            if self
+                .sources
+                .iter()
+                .any(|existing| existing.name_prefix().as_deref() == Some(prefix.as_str()))
+            {
+                bail!(
+                    "Tool name prefix `{}` is already used by another tool source",
+                    prefix
+                );
+            }
+        }
+        self.sources.push(source);
+        Ok(())
+    }
+
     /// 获取工具
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
-        self.tools.get(name).cloned()
+        self.tools.get(name).cloned().or_else(|| {
+            self.sources
+                .iter()
+                .flat_map(|source| source.tools())
+                .find(|tool| tool.name() == name)
+        })
     }
 
     /// 列出所有工具名称
     pub fn list(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.snapshot().into_keys().collect()
+    }
+
+    /// 所有工具的 schema（按名称排序），用于发送给模型
+    pub fn schemas(&self) -> Vec<ToolSchema> {
+        let mut schemas: Vec<ToolSchema> =
+            self.snapshot().values().map(|tool| tool.schema()).collect();
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+        schemas
+    }
+
+    /// 只包含只读工具的子集
+    ///
+    /// 子集是当前工具的快照，不再跟随动态来源变化。
+    pub fn read_only(&self) -> ToolRegistry {
+        let tools = self
+            .snapshot()
+            .into_iter()
+            .filter(|(_, tool)| tool.is_read_only())
+            .collect();
+        ToolRegistry {
+            tools,
+            sources: Vec::new(),
+        }
+    }
+
+    /// 按 Agent 的工具过滤器生成子集
+    ///
+    /// Agent 文件中引用了未注册的工具时，在结果的 `warnings` 中给出提示。
+    /// 子集是当前工具的快照，不再跟随动态来源变化。
+    pub fn for_agent(&self, agent: &Agent) -> AgentTools {
+        let all = self.snapshot();
+        let warnings = agent
+            .referenced_tools()
+            .filter(|name| !all.contains_key(*name))
+            .map(|name| format!("Agent '{}' references unknown tool '{}'", agent.name, name))
+            .collect();
+
+        let tools = all
+            .into_iter()
+            .filter(|(name, _)| agent.allows_tool(name))
+            .collect();
+
+        AgentTools {
+            registry: ToolRegistry {
+                tools,
+                sources: Vec::new(),
+            },
+            warnings,
+        }
+    }
+
+    /// 静态工具与动态来源当前工具的合并视图
+    fn snapshot(&self) -> HashMap<String, Arc<dyn Tool>> {
+        let mut all: HashMap<String, Arc<dyn Tool>> = HashMap::new();
+        for tool in self.sources.iter().flat_map(|source| source.tools()) {
+            all.entry(tool.name().to_string()).or_insert(tool);
+        }
+        all.extend(
+            self.tools
+                .iter()
+                .map(|(name, tool)| (name.clone(), tool.clone())),
+        );
+        all
+    }
+
+    /// 经过权限检查后执行工具
+    ///
+    /// 所有来自模型的工具调用都应通过此方法执行：先由工具构造权限请求，
+    /// 交给上下文中的权限引擎评估（必要时询问用户），被拒绝时返回错误。
+    pub async fn call(
+        &self,
+        name: &str,
+        params: Value,
+        context: &ToolContext,
+    ) -> Result<ToolResult> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown tool: {}", name))?;
+
+        let request = tool.permission_request(&params, context);
+        if let PermissionDecision::Deny { reason } = context.permissions.check(&request).await? {
+            bail!("Permission denied: {}", reason);
+        }
+        tool.execute(params, context).await
+    }
+}
+
+/// 按 Agent 过滤后的工具集
+pub struct AgentTools {
+    /// Agent 可以使用的工具
+    pub registry: ToolRegistry,
+    /// 配置问题提示（例如引用了未知工具）
+    pub warnings: Vec<String>,
+}
+
+impl AgentTools {
+    /// 发送给模型的工具 schema
+    pub fn schemas(&self) -> Vec<ToolSchema> {
+        self.registry.schemas()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BashTool, FileWriteTool, GrepTool};
+    use kode_core::config::GlobalConfig;
+    use kode_core::config::ProjectConfig;
+    use kode_core::permission::{PermissionEngine, PermissionMode, PermissionRules};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn registry() -> ToolRegistry {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(BashTool::new()));
+        registry.register(Arc::new(FileWriteTool::new()));
+        registry.register(Arc::new(GrepTool::new()));
+        registry
+    }
+
+    fn permission_context(dir: &TempDir, allowed: &[&str], mode: PermissionMode) -> ToolContext {
+        let project = ProjectConfig {
+            allowed_tools: allowed.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let rules = PermissionRules::from_configs(&GlobalConfig::default(), &project);
+        let engine = PermissionEngine::new(dir.path(), rules).with_mode(mode);
+        ToolContext::new(dir.path())
+            .with_project_config(project)
+            .with_permissions(Arc::new(engine))
+    }
+
+    #[test]
+    fn test_for_agent() {
+        use kode_core::agent::{AgentLocation, ToolFilter};
+
+        let agent = Agent::new(
+            "searcher".to_string(),
+            "Searches code".to_string(),
+            ToolFilter::Specific(vec![
+                "Grep".to_string(),
+                "File*".to_string(),
+                "FileRead".to_string(),
+            ]),
+            "Prompt".to_string(),
+            AgentLocation::Project,
+        )
+        .with_disallowed_tools(vec!["FileWrite".to_string(), "Nonexistent".to_string()]);
+
+        let tools = registry().for_agent(&agent);
+        let names: Vec<String> = tools.schemas().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["Grep"]);
+        assert_eq!(
+            tools.warnings,
+            vec![
+                "Agent 'searcher' references unknown tool 'FileRead'",
+                "Agent 'searcher' references unknown tool 'Nonexistent'",
+            ]
+        );
+
+        let all = Agent::new(
+            "all".to_string(),
+            "All tools".to_string(),
+            ToolFilter::All,
+            "Prompt".to_string(),
+            AgentLocation::Builtin,
+        );
+        let tools = registry().for_agent(&all);
+        assert_eq!(tools.registry.list().len(), 3);
+        assert!(tools.warnings.is_empty());
+
+        assert_eq!(registry().read_only().list(), vec!["Grep"]);
+    }
+
+    #[tokio::test]
+    async fn test_call_checks_permissions() {
+        let dir = TempDir::new().unwrap();
+        let registry = registry();
+        let context =
+            permission_context(&dir, &["Bash(touch allowed.txt)"], PermissionMode::Default);
+
+        // 只读命令和被规则允许的命令直接执行
+        assert!(registry
+            .call("Bash", json!({"command": "ls"}), &context)
+            .await
+            .is_ok());
+        registry
+            .call("Bash", json!({"command": "touch allowed.txt"}), &context)
+            .await
+            .unwrap();
+        assert!(dir.path().join("allowed.txt").exists());
+
+        // 未被允许且没有询问回调时拒绝
+        let err = registry
+            .call("Bash", json!({"command": "touch other.txt"}), &context)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Permission denied"));
+        assert!(!dir.path().join("other.txt").exists());
+
+        let err = registry
+            .call("Nope", json!({}), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unknown tool: Nope");
+    }
+
+    #[tokio::test]
+    async fn test_call_in_accept_edits_and_plan_modes() {
+        let dir = TempDir::new().unwrap();
+        let registry = registry();
+        let params = json!({"file_path": "a.txt", "content": "hi"});
+
+        let context = permission_context(&dir, &[], PermissionMode::AcceptEdits);
+        registry
+            .call("FileWrite", params.clone(), &context)
+            .await
+            .unwrap();
+        assert!(dir.path().join("a.txt").exists());
+
+        let context = permission_context(&dir, &[], PermissionMode::Plan);
+        let err = registry
+            .call("FileWrite", params, &context)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("plan mode"));
+        assert!(registry
+            .call("Grep", json!({"pattern": "hi"}), &context)
+            .await
+            .is_ok());
     }
 }
@@ -0,0 +1,858 @@
+//! Bash 沙箱
+//!
+//! 在共享开发机上以自动批准模式运行时，Bash 命令可以放进沙箱执行：
+//! 通过 Linux user/mount/network 命名空间隔离，再用 landlock 把写权限限制在
+//! 项目根目录和临时目录内，并用 seccomp 禁止逃逸相关的系统调用。
+//! 内核不支持时，按项目配置决定退回到不加沙箱执行还是直接报错。
+
+use crate::file_utils::{normalize_path, resolve_existing};
+use crate::shell::CommandOutput;
+use anyhow::{bail, Result};
+use kode_core::config::SandboxConfig;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// 沙箱内允许写入的设备文件
+///
+/// 只列出具体的设备节点，`/dev/shm` 等目录仍然不可写。
+const WRITABLE_DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/tty",
+    "/dev/urandom",
+    "/dev/random",
+];
+
+/// 是否是沙箱内可以写入的设备文件
+///
+/// `/dev/fd/*`、`/dev/stdout` 和 `/dev/stderr` 指向命令自己已经打开的文件描述符。
+fn is_writable_device(path: &Path) -> bool {
+    WRITABLE_DEVICES
+        .iter()
+        .any(|device| path == Path::new(device))
+        || path.starts_with("/dev/fd")
+        || path == Path::new("/dev/stdout")
+        || path == Path::new("/dev/stderr")
+}
+
+/// 沙箱策略
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    /// 允许写入的路径（包含项目根目录和临时目录）
+    pub writable_paths: Vec<PathBuf>,
+    /// 沙箱内使用的临时目录，通过 `TMPDIR` 传给命令
+    pub tmp_dir: PathBuf,
+    /// 是否允许访问网络
+    pub allow_network: bool,
+}
+
+impl SandboxPolicy {
+    /// 根据项目配置构造沙箱策略
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - 项目的沙箱配置
+    /// * `project_root` - 项目根目录，相对路径以此为基准解析
+    pub fn from_config(config: &SandboxConfig, project_root: &Path) -> Self {
+        let tmp_dir = sandbox_tmp_dir().to_path_buf();
+        let mut writable_paths = vec![project_root.to_path_buf(), tmp_dir.clone()];
+        writable_paths.extend(config.writable_paths.iter().map(|path| {
+            let path = Path::new(path);
+            if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                project_root.join(path)
+            }
+        }));
+
+        Self {
+            writable_paths,
+            tmp_dir,
+            allow_network: config.allow_network,
+        }
+    }
+
+    /// 路径是否位于沙箱允许写入的范围内
+    fn allows_write(&self, path: &Path) -> bool {
+        if is_writable_device(path) {
+            return true;
+        }
+        let path = resolve_existing(path);
+        is_writable_device(&path)
+            || self
+                .writable_paths
+                .iter()
+                .any(|writable| path.starts_with(resolve_existing(writable)))
+    }
+
+    /// 供错误信息展示的可写路径列表
+    fn describe_writable_paths(&self) -> String {
+        self.writable_paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Bash 命令的沙箱模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// 未启用沙箱
+    Disabled,
+    /// 在沙箱中执行
+    Enabled(SandboxPolicy),
+    /// 已启用沙箱但系统不支持，退回到不加沙箱执行
+    Fallback {
+        /// 不支持的原因
+        reason: String,
+    },
+}
+
+impl SandboxMode {
+    /// 根据项目配置和系统支持情况确定沙箱模式
+    ///
+    /// 启用了沙箱但系统不支持、且配置不允许退回时返回错误。
+    pub fn resolve(config: Option<&SandboxConfig>, project_root: &Path) -> Result<Self> {
+        let Some(config) = config.filter(|config| config.enabled) else {
+            return Ok(SandboxMode::Disabled);
+        };
+
+        match support() {
+            Ok(()) => Ok(SandboxMode::Enabled(SandboxPolicy::from_config(
+                config,
+                project_root,
+            ))),
+            Err(reason) if config.allow_unsandboxed_fallback => {
+                Ok(SandboxMode::Fallback { reason })
+            }
+            Err(reason) => bail!(
+                "Sandbox is enabled but not supported on this system: {}",
+                reason
+            ),
+        }
+    }
+
+    /// 生效的沙箱策略
+    pub fn policy(&self) -> Option<&SandboxPolicy> {
+        match self {
+            SandboxMode::Enabled(policy) => Some(policy),
+            SandboxMode::Disabled | SandboxMode::Fallback { .. } => None,
+        }
+    }
+}
+
+/// 沙箱使用的临时目录
+///
+/// 目录名随机生成，在进程内保持不变，使持久 shell 会话的策略保持一致。
+fn sandbox_tmp_dir() -> &'static Path {
+    static TMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+    TMP_DIR.get_or_init(|| {
+        std::env::temp_dir().join(format!("kode-sandbox-{}", uuid::Uuid::new_v4().simple()))
+    })
+}
+
+/// 创建只有当前用户可访问的临时目录
+///
+/// 目录已存在时确认它不是符号链接、属于当前用户且权限为 0700，
+/// 防止其他用户抢先创建同名目录。
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let metadata = std::fs::symlink_metadata(path)?;
+            // SAFETY: getuid 总是成功
+            let uid = unsafe { libc::getuid() };
+            if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+                bail!(
+                    "Sandbox temporary directory {} is not a private directory",
+                    path.display()
+                );
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 检查当前系统是否支持沙箱
+///
+/// 实际在沙箱中启动一次 `/bin/true` 来探测，结果在进程内缓存。
+pub fn support() -> std::result::Result<(), String> {
+    static SUPPORT: OnceLock<std::result::Result<(), String>> = OnceLock::new();
+    SUPPORT.get_or_init(probe).clone()
+}
+
+#[cfg(target_os = "linux")]
+fn probe() -> std::result::Result<(), String> {
+    let policy = SandboxPolicy {
+        writable_paths: vec![std::env::temp_dir()],
+        tmp_dir: std::env::temp_dir(),
+        allow_network: false,
+    };
+    let mut command = std::process::Command::new("/bin/true");
+    command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    linux::apply(&mut command, &policy).map_err(|e| e.to_string())?;
+
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("sandbox probe exited with {}", status)),
+        Err(e) => Err(format!("sandbox probe failed: {}", e)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe() -> std::result::Result<(), String> {
+    Err("the sandbox requires Linux".to_string())
+}
+
+/// 为即将启动的进程应用沙箱
+///
+/// 同时创建沙箱的临时目录（权限 0700）。
+#[cfg(target_os = "linux")]
+pub fn apply(command: &mut std::process::Command, policy: &SandboxPolicy) -> Result<()> {
+    create_private_dir(&policy.tmp_dir)?;
+    command.env("TMPDIR", &policy.tmp_dir);
+    linux::apply(command, policy)
+}
+
+/// 为即将启动的进程应用沙箱（非 Linux 系统不支持）
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_command: &mut std::process::Command, _policy: &SandboxPolicy) -> Result<()> {
+    bail!("The sandbox requires Linux")
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::SandboxPolicy;
+    use anyhow::{anyhow, Result};
+    use landlock::{
+        path_beneath_rules, AccessFs, Ruleset, RulesetAttr, RulesetCreated, RulesetCreatedAttr,
+        RulesetStatus, ABI,
+    };
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule, TargetArch,
+    };
+    use std::collections::BTreeMap;
+    use std::ffi::CStr;
+    use std::io;
+    use std::os::unix::process::CommandExt;
+
+    /// 沙箱内禁止的系统调用：重新挂载、切换命名空间、调试其他进程、加载内核模块、
+    /// 绕过 seccomp 的 io_uring 等
+    pub(super) const BLOCKED_SYSCALLS: &[(&str, libc::c_long)] = &[
+        ("ptrace", libc::SYS_ptrace),
+        ("mount", libc::SYS_mount),
+        ("umount2", libc::SYS_umount2),
+        ("pivot_root", libc::SYS_pivot_root),
+        ("setns", libc::SYS_setns),
+        ("unshare", libc::SYS_unshare),
+        ("kexec_load", libc::SYS_kexec_load),
+        ("init_module", libc::SYS_init_module),
+        ("finit_module", libc::SYS_finit_module),
+        ("delete_module", libc::SYS_delete_module),
+        ("bpf", libc::SYS_bpf),
+        ("perf_event_open", libc::SYS_perf_event_open),
+        ("keyctl", libc::SYS_keyctl),
+        ("add_key", libc::SYS_add_key),
+        ("request_key", libc::SYS_request_key),
+        ("process_vm_writev", libc::SYS_process_vm_writev),
+        ("reboot", libc::SYS_reboot),
+        ("swapon", libc::SYS_swapon),
+        ("swapoff", libc::SYS_swapoff),
+        ("io_uring_setup", libc::SYS_io_uring_setup),
+        ("io_uring_enter", libc::SYS_io_uring_enter),
+        ("io_uring_register", libc::SYS_io_uring_register),
+    ];
+
+    /// `clone` 创建新命名空间时使用的标志
+    const NAMESPACE_FLAGS: &[libc::c_int] = &[
+        libc::CLONE_NEWUSER,
+        libc::CLONE_NEWNS,
+        libc::CLONE_NEWNET,
+        libc::CLONE_NEWPID,
+        libc::CLONE_NEWIPC,
+        libc::CLONE_NEWUTS,
+        libc::CLONE_NEWCGROUP,
+    ];
+
+    pub(super) fn landlock_ruleset(policy: &SandboxPolicy) -> Result<RulesetCreated> {
+        let abi = ABI::V3;
+        let writable = policy
+            .writable_paths
+            .iter()
+            .map(|p| p.as_path())
+            .chain(super::WRITABLE_DEVICES.iter().map(std::path::Path::new));
+        Ok(Ruleset::default()
+            .handle_access(AccessFs::from_write(abi))?
+            .create()?
+            .add_rules(path_beneath_rules(writable, AccessFs::from_write(abi)))?)
+    }
+
+    fn target_arch() -> Result<TargetArch> {
+        TargetArch::try_from(std::env::consts::ARCH)
+            .map_err(|e| anyhow!("Unsupported architecture for seccomp: {}", e))
+    }
+
+    /// 禁止 `BLOCKED_SYSCALLS` 以及带命名空间标志的 `clone`
+    // c_long 在 32 位平台上是 i32
+    #[allow(clippy::unnecessary_cast)]
+    fn seccomp_program() -> Result<BpfProgram> {
+        let mut rules: BTreeMap<i64, Vec<_>> = BLOCKED_SYSCALLS
+            .iter()
+            .map(|&(_, syscall)| (syscall as i64, Vec::new()))
+            .collect();
+        // 同一系统调用的多条规则任一匹配即生效
+        let clone_rules = NAMESPACE_FLAGS
+            .iter()
+            .map(|&flag| {
+                let flag = flag as u64;
+                let condition = SeccompCondition::new(
+                    0,
+                    SeccompCmpArgLen::Qword,
+                    SeccompCmpOp::MaskedEq(flag),
+                    flag,
+                )?;
+                Ok(SeccompRule::new(vec![condition])?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rules.insert(libc::SYS_clone as i64, clone_rules);
+
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            target_arch()?,
+        )?;
+        Ok(filter.try_into()?)
+    }
+
+    /// 禁止 `clone3`
+    ///
+    /// `clone3` 的标志位于用户内存中的结构体里，seccomp 无法检查，只能整体禁止。
+    /// 返回 ENOSYS 让 libc 退回到受上面规则约束的 `clone`。
+    #[allow(clippy::unnecessary_cast)]
+    fn clone3_program() -> Result<BpfProgram> {
+        let rules = BTreeMap::from([(libc::SYS_clone3 as i64, Vec::new())]);
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::ENOSYS as u32),
+            target_arch()?,
+        )?;
+        Ok(filter.try_into()?)
+    }
+
+    /// 以只写方式打开文件并写入内容
+    fn write_proc_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        // SAFETY: path 是以 nul 结尾的 C 字符串，contents 在调用期间有效
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+            let result = if written < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            };
+            libc::close(fd);
+            result
+        }
+    }
+
+    fn check(rc: libc::c_int) -> io::Result<()> {
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 在子进程 exec 前进入沙箱
+    ///
+    /// 规则集和 BPF 程序都在父进程中构造好，fork 之后的子进程只执行系统调用。
+    pub(super) fn apply(command: &mut std::process::Command, policy: &SandboxPolicy) -> Result<()> {
+        let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+        if !policy.allow_network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        // SAFETY: getuid/getgid 总是成功
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("{} {} 1", uid, uid).into_bytes();
+        let gid_map = format!("{} {} 1", gid, gid).into_bytes();
+        let mut ruleset = Some(landlock_ruleset(policy)?);
+        let programs = [seccomp_program()?, clone3_program()?];
+
+        // SAFETY: 闭包只调用系统调用，不在 fork 后的子进程中分配内存或获取锁
+        unsafe {
+            command.pre_exec(move || {
+                check(libc::unshare(flags))?;
+
+                // 新 user 命名空间中映射为原来的 uid/gid
+                match write_proc_file(c"/proc/self/setgroups", b"deny") {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+                write_proc_file(c"/proc/self/uid_map", &uid_map)?;
+                write_proc_file(c"/proc/self/gid_map", &gid_map)?;
+
+                // 挂载变更不传播回宿主
+                check(libc::mount(
+                    std::ptr::null(),
+                    c"/".as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    std::ptr::null(),
+                ))?;
+
+                let ruleset = ruleset
+                    .take()
+                    .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))?;
+                let status = ruleset
+                    .restrict_self()
+                    .map_err(|_| io::Error::from_raw_os_error(libc::EPERM))?;
+                if status.ruleset == RulesetStatus::NotEnforced {
+                    // 内核不支持 landlock
+                    return Err(io::Error::from_raw_os_error(libc::ENOSYS));
+                }
+
+                for program in &programs {
+                    seccompiler::apply_filter(program)
+                        .map_err(|_| io::Error::from_raw_os_error(libc::EPERM))?;
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 写入被拒绝时的典型错误信息
+const WRITE_DENIED_MARKERS: &[&str] = &[
+    "Permission denied",
+    "Read-only file system",
+    "Operation not permitted",
+];
+
+/// 网络被禁用时的典型错误信息
+const NETWORK_DENIED_MARKERS: &[&str] = &[
+    "Network is unreachable",
+    "Could not resolve host",
+    "Temporary failure in name resolution",
+    "Name or service not known",
+];
+
+/// 根据失败命令的输出判断是否触发了沙箱限制
+///
+/// 只有被拒绝的路径位于允许写入的范围之外、且不加沙箱时本可写入，
+/// 或者被拒绝的是沙箱禁止的系统调用时，才视为触发了限制；
+/// 普通的权限错误（如读取无权访问的文件）不算。
+///
+/// # Arguments
+///
+/// * `output` - 命令输出
+/// * `policy` - 生效的沙箱策略
+/// * `cwd` - 命令的工作目录，用于解析错误信息中的相对路径
+///
+/// # Returns
+///
+/// 触发限制时返回面向用户的说明
+pub fn detect_violation(
+    output: &CommandOutput,
+    policy: &SandboxPolicy,
+    cwd: &Path,
+) -> Option<String> {
+    if matches!(output.status.exit_code(), Some(0) | None) {
+        return None;
+    }
+    let text = format!("{}\n{}", output.stdout, output.stderr);
+
+    if !policy.allow_network && NETWORK_DENIED_MARKERS.iter().any(|m| text.contains(m)) {
+        return Some("network access is disabled by the project sandbox policy".to_string());
+    }
+
+    for line in text
+        .lines()
+        .filter(|line| WRITE_DENIED_MARKERS.iter().any(|m| line.contains(m)))
+    {
+        if let Some(syscall) = blocked_syscall(line) {
+            return Some(format!(
+                "the project sandbox policy blocks the {} system call",
+                syscall
+            ));
+        }
+        let denied = candidate_paths(line)
+            .into_iter()
+            .map(|path| normalize_path(&cwd.join(path)))
+            .find(|path| !policy.allows_write(path) && writable_without_sandbox(path));
+        if let Some(path) = denied {
+            return Some(format!(
+                "the project sandbox policy only allows writes to: {} (denied: {})",
+                policy.describe_writable_paths(),
+                path.display()
+            ));
+        }
+    }
+    None
+}
+
+/// 错误信息中提到的被沙箱禁止的系统调用
+#[cfg(target_os = "linux")]
+fn blocked_syscall(line: &str) -> Option<&'static str> {
+    line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .find_map(|word| {
+            linux::BLOCKED_SYSCALLS
+                .iter()
+                .find(|(name, _)| *name == word)
+                .map(|(name, _)| *name)
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn blocked_syscall(_line: &str) -> Option<&'static str> {
+    None
+}
+
+/// 从一行错误信息中提取可能的路径
+///
+/// 包括引号内的内容，以及以 `: ` 分隔、包含 `/` 的片段，
+/// 如 `touch: cannot touch '/etc/x': Permission denied`、`bash: /etc/x: Permission denied`。
+fn candidate_paths(line: &str) -> Vec<&str> {
+    let mut paths = Vec::new();
+    for (open, close) in [('\'', '\''), ('"', '"'), ('‘', '’')] {
+        let mut rest = line;
+        while let Some(start) = rest.find(open) {
+            let after = &rest[start + open.len_utf8()..];
+            let Some(end) = after.find(close) else {
+                break;
+            };
+            paths.push(&after[..end]);
+            rest = &after[end + close.len_utf8()..];
+        }
+    }
+    paths.extend(
+        line.split(": ")
+            .map(str::trim)
+            .filter(|part| part.contains('/') && !part.contains(char::is_whitespace)),
+    );
+    paths.retain(|path| !path.is_empty());
+    paths
+}
+
+/// 不加沙箱时当前进程能否写入该路径
+///
+/// 路径不存在时检查最近的已存在祖先目录。
+#[cfg(unix)]
+fn writable_without_sandbox(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return false;
+    };
+    let Ok(c_path) = std::ffi::CString::new(existing.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: c_path 是以 nul 结尾的 C 字符串
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn writable_without_sandbox(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::CommandStatus;
+    use tempfile::TempDir;
+
+    fn failed(stderr: &str) -> CommandOutput {
+        CommandOutput {
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            status: CommandStatus::Exited(1),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_resolve_disabled() {
+        let root = Path::new("/project");
+        assert_eq!(
+            SandboxMode::resolve(None, root).unwrap(),
+            SandboxMode::Disabled
+        );
+        let config = SandboxConfig::default();
+        assert_eq!(
+            SandboxMode::resolve(Some(&config), root).unwrap(),
+            SandboxMode::Disabled
+        );
+    }
+
+    #[test]
+    fn test_policy_paths() {
+        let config = SandboxConfig {
+            enabled: true,
+            writable_paths: vec!["build".to_string(), "/var/cache/kode".to_string()],
+            ..Default::default()
+        };
+        let policy = SandboxPolicy::from_config(&config, Path::new("/project"));
+        assert_eq!(policy.writable_paths[0], Path::new("/project"));
+        assert_eq!(policy.writable_paths[1], policy.tmp_dir);
+        assert_eq!(policy.writable_paths[2], Path::new("/project/build"));
+        assert_eq!(policy.writable_paths[3], Path::new("/var/cache/kode"));
+        assert!(!policy.allow_network);
+    }
+
+    #[test]
+    fn test_detect_violation() {
+        let project = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let cwd = project.path();
+        let policy = SandboxPolicy::from_config(&SandboxConfig::default(), cwd);
+
+        let outside_file = outside.path().join("x");
+        let violation = detect_violation(
+            &failed(&format!(
+                "touch: cannot touch '{}': Permission denied",
+                outside_file.display()
+            )),
+            &policy,
+            cwd,
+        )
+        .unwrap();
+        assert!(violation.contains(&cwd.display().to_string()));
+        assert!(violation.contains(&outside_file.display().to_string()));
+
+        let violation = detect_violation(
+            &failed(&format!(
+                "bash: {}: Read-only file system",
+                outside_file.display()
+            )),
+            &policy,
+            cwd,
+        )
+        .unwrap();
+        assert!(violation.contains("denied"));
+
+        let violation = detect_violation(
+            &failed("unshare: unshare failed: Operation not permitted"),
+            &policy,
+            cwd,
+        )
+        .unwrap();
+        assert!(violation.contains("unshare system call"));
+
+        let violation =
+            detect_violation(&failed("curl: (6) Could not resolve host: x"), &policy, cwd).unwrap();
+        assert!(violation.contains("network"));
+
+        // 允许写入范围内的普通权限错误不算触发沙箱
+        for stderr in [
+            "cat: secret.txt: Permission denied",
+            "chmod: changing permissions of 'src/main.rs': Operation not permitted",
+            "bash: ./run.sh: Permission denied",
+            "Permission denied",
+            "error: no such file",
+        ] {
+            assert!(
+                detect_violation(&failed(stderr), &policy, cwd).is_none(),
+                "{}",
+                stderr
+            );
+        }
+        let tmp_file = policy.tmp_dir.join("x");
+        assert!(detect_violation(
+            &failed(&format!(
+                "cp: cannot create '{}': Permission denied",
+                tmp_file.display()
+            )),
+            &policy,
+            cwd,
+        )
+        .is_none());
+
+        let mut ok = failed("Permission denied");
+        ok.status = CommandStatus::Exited(0);
+        assert!(detect_violation(&ok, &policy, cwd).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_violation_ignores_unwritable_paths() {
+        // SAFETY: geteuid 总是成功
+        if unsafe { libc::geteuid() } == 0 {
+            // root 不受文件权限限制
+            return;
+        }
+        let project = TempDir::new().unwrap();
+        let policy = SandboxPolicy::from_config(&SandboxConfig::default(), project.path());
+        assert!(detect_violation(
+            &failed("touch: cannot touch '/etc/x': Permission denied"),
+            &policy,
+            project.path(),
+        )
+        .is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_private_tmp_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let tmp = dir.path().join("sandbox-tmp");
+        create_private_dir(&tmp).unwrap();
+        let mode = std::fs::metadata(&tmp).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        // 已存在的私有目录可以复用
+        create_private_dir(&tmp).unwrap();
+
+        let shared = dir.path().join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(create_private_dir(&shared).is_err());
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&tmp, &link).unwrap();
+        assert!(create_private_dir(&link).is_err());
+
+        let policy = SandboxPolicy::from_config(&SandboxConfig::default(), dir.path());
+        assert!(!policy
+            .tmp_dir
+            .to_string_lossy()
+            .ends_with(&std::process::id().to_string()));
+        assert_eq!(
+            policy.tmp_dir,
+            SandboxPolicy::from_config(&SandboxConfig::default(), dir.path()).tmp_dir
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_landlock_ruleset() {
+        use std::os::unix::process::CommandExt;
+
+        let project = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let policy = SandboxPolicy::from_config(&SandboxConfig::default(), project.path());
+        let mut ruleset = Some(linux::landlock_ruleset(&policy).unwrap());
+
+        let script = format!("touch inside; touch {}/outside", outside.path().display());
+        let mut command = std::process::Command::new("/bin/sh");
+        command.arg("-c").arg(script).current_dir(project.path());
+        // SAFETY: 闭包只调用 landlock 相关的系统调用
+        unsafe {
+            command.pre_exec(move || {
+                let status = ruleset
+                    .take()
+                    .ok_or_else(|| std::io::Error::from_raw_os_error(libc::EINVAL))?
+                    .restrict_self()
+                    .map_err(|_| std::io::Error::from_raw_os_error(libc::EPERM))?;
+                if status.ruleset == landlock::RulesetStatus::NotEnforced {
+                    return Err(std::io::Error::from_raw_os_error(libc::ENOSYS));
+                }
+                Ok(())
+            });
+        }
+        let status = match command.status() {
+            Ok(status) => status,
+            // 内核不支持 landlock
+            Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert!(!status.success());
+        assert!(project.path().join("inside").exists());
+        assert!(!outside.path().join("outside").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_landlock_ruleset_devices() {
+        use std::os::unix::process::CommandExt;
+
+        let project = TempDir::new().unwrap();
+        let policy = SandboxPolicy::from_config(&SandboxConfig::default(), project.path());
+        let shm = Path::new("/dev/shm").join(format!("kode-{}", uuid::Uuid::new_v4().simple()));
+        assert!(policy.allows_write(Path::new("/dev/null")));
+        assert!(!policy.allows_write(&shm));
+
+        let mut ruleset = Some(linux::landlock_ruleset(&policy).unwrap());
+        let script = format!(
+            "echo ok > /dev/null && touch null-ok; echo x > {}",
+            shm.display()
+        );
+        let mut command = std::process::Command::new("/bin/sh");
+        command.arg("-c").arg(script).current_dir(project.path());
+        // SAFETY: 闭包只调用 landlock 相关的系统调用
+        unsafe {
+            command.pre_exec(move || {
+                let status = ruleset
+                    .take()
+                    .ok_or_else(|| std::io::Error::from_raw_os_error(libc::EINVAL))?
+                    .restrict_self()
+                    .map_err(|_| std::io::Error::from_raw_os_error(libc::EPERM))?;
+                if status.ruleset == landlock::RulesetStatus::NotEnforced {
+                    return Err(std::io::Error::from_raw_os_error(libc::ENOSYS));
+                }
+                Ok(())
+            });
+        }
+        let status = match command.status() {
+            Ok(status) => status,
+            // 内核不支持 landlock
+            Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        let written = shm.exists();
+        let _ = std::fs::remove_file(&shm);
+        assert!(!status.success());
+        assert!(project.path().join("null-ok").exists());
+        assert!(!written);
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_or_fallback() {
+        let dir = TempDir::new().unwrap();
+        let config = SandboxConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let mode = SandboxMode::resolve(Some(&config), dir.path()).unwrap();
+
+        let Some(policy) = mode.policy() else {
+            // 系统不支持沙箱：允许退回时不加沙箱，否则报错
+            assert!(matches!(mode, SandboxMode::Fallback { .. }));
+            let strict = SandboxConfig {
+                allow_unsandboxed_fallback: false,
+                ..config
+            };
+            let err = SandboxMode::resolve(Some(&strict), dir.path()).unwrap_err();
+            assert!(err.to_string().contains("not supported"));
+            return;
+        };
+
+        let outside = TempDir::new().unwrap();
+        let script = format!("touch inside && touch {}/outside", outside.path().display());
+        let mut command = std::process::Command::new("/bin/sh");
+        command.arg("-c").arg(script).current_dir(dir.path());
+        apply(&mut command, policy).unwrap();
+        let status = command.status().unwrap();
+
+        assert!(!status.success());
+        assert!(dir.path().join("inside").exists());
+        assert!(!outside.path().join("outside").exists());
+    }
+}
@@ -0,0 +1,969 @@
+//! 持久 shell 会话
+//!
+//! Bash 工具在同一会话内复用一个 shell 进程，使 `cd` 和 `export` 在多次调用之间保留。
+//! 每条命令执行后 shell 会输出一行带随机标记的结束行，用来界定命令输出，
+//! 并取回退出码和当前目录。超时或取消时会杀掉整个进程组，下次调用时重新启动 shell。
+
+use crate::sandbox::{self, SandboxPolicy};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio_util::sync::CancellationToken;
+
+/// 默认输出上限（字节）
+pub const DEFAULT_MAX_OUTPUT: usize = 30_000;
+
+/// 后台任务未读输出的缓冲上限（字节）
+const MAX_JOB_BUFFER: usize = 1024 * 1024;
+
+/// 输出缓冲区
+///
+/// 超过上限时只保留开头和结尾各一半，中间部分以省略提示代替，
+/// 避免巨量输出占满内存或上下文。
+#[derive(Debug, Clone)]
+pub struct CappedBuffer {
+    head: Vec<u8>,
+    tail: Vec<u8>,
+    omitted: usize,
+    cap: usize,
+}
+
+impl CappedBuffer {
+    /// 创建指定上限的缓冲区
+    pub fn new(cap: usize) -> Self {
+        Self {
+            head: Vec::new(),
+            tail: Vec::new(),
+            omitted: 0,
+            cap,
+        }
+    }
+
+    fn head_cap(&self) -> usize {
+        self.cap / 2
+    }
+
+    fn tail_cap(&self) -> usize {
+        self.cap - self.head_cap()
+    }
+
+    /// 追加数据
+    pub fn push(&mut self, mut bytes: &[u8]) {
+        let head_cap = self.head_cap();
+        if self.head.len() < head_cap {
+            let n = (head_cap - self.head.len()).min(bytes.len());
+            self.head.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+        }
+        self.tail.extend_from_slice(bytes);
+
+        // 尾部允许暂时超出上限一倍，减少频繁搬移
+        let tail_cap = self.tail_cap();
+        if self.tail.len() > tail_cap * 2 {
+            let excess = self.tail.len() - tail_cap;
+            self.tail.drain(..excess);
+            self.omitted += excess;
+        }
+    }
+
+    /// 去掉末尾的一个换行符
+    fn pop_newline(&mut self) {
+        let last = if self.tail.is_empty() {
+            &mut self.head
+        } else {
+            &mut self.tail
+        };
+        if last.last() == Some(&b'\n') {
+            last.pop();
+        }
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.head.is_empty() && self.tail.is_empty()
+    }
+
+    /// 是否发生了截断
+    pub fn is_truncated(&self) -> bool {
+        self.omitted > 0 || self.tail.len() > self.tail_cap()
+    }
+
+    /// 转换为字符串（非 UTF-8 字节按有损方式转换）
+    pub fn to_string_lossy(&self) -> String {
+        let tail_cap = self.tail_cap();
+        let (omitted, tail) = if self.tail.len() > tail_cap {
+            let start = self.tail.len() - tail_cap;
+            (self.omitted + start, &self.tail[start..])
+        } else {
+            (self.omitted, &self.tail[..])
+        };
+
+        if omitted == 0 {
+            let mut bytes = self.head.clone();
+            bytes.extend_from_slice(tail);
+            String::from_utf8_lossy(&bytes).into_owned()
+        } else {
+            format!(
+                "{}\n\n... [{} bytes of output truncated] ...\n\n{}",
+                String::from_utf8_lossy(&self.head),
+                omitted,
+                String::from_utf8_lossy(tail)
+            )
+        }
+    }
+}
+
+/// 单次命令的执行选项
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// 超时时间
+    pub timeout: Duration,
+    /// 是否将 stderr 合并到 stdout
+    pub merge_stderr: bool,
+    /// 每个输出流的字节上限
+    pub max_output: usize,
+    /// 沙箱策略（`None` 表示不加沙箱）
+    pub sandbox: Option<SandboxPolicy>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(120),
+            merge_stderr: false,
+            max_output: DEFAULT_MAX_OUTPUT,
+            sandbox: None,
+        }
+    }
+}
+
+/// 命令结束状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandStatus {
+    /// 命令正常结束
+    Exited(i32),
+    /// 命令导致 shell 本身退出（例如 `exit`）
+    ShellExited(Option<i32>),
+    /// 超时被终止
+    TimedOut,
+    /// 被取消
+    Cancelled,
+}
+
+impl CommandStatus {
+    /// 退出码（被终止时为 `None`）
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            CommandStatus::Exited(code) => Some(*code),
+            CommandStatus::ShellExited(code) => *code,
+            CommandStatus::TimedOut | CommandStatus::Cancelled => None,
+        }
+    }
+
+    /// shell 是否需要重启
+    pub fn shell_restarted(&self) -> bool {
+        !matches!(self, CommandStatus::Exited(_))
+    }
+}
+
+/// 命令输出
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    /// 标准输出（合并模式下包含 stderr）
+    pub stdout: String,
+    /// 标准错误
+    pub stderr: String,
+    /// 结束状态
+    pub status: CommandStatus,
+    /// 输出是否被截断
+    pub truncated: bool,
+}
+
+/// 选择用于执行命令的 shell
+fn shell_program() -> (&'static str, &'static [&'static str]) {
+    if Path::new("/bin/bash").exists() {
+        ("/bin/bash", &["--noprofile", "--norc"])
+    } else {
+        ("/bin/sh", &[])
+    }
+}
+
+/// 杀掉整个进程组（shell 及其派生的子进程）
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    // SAFETY: killpg 只向进程组发送信号，不涉及内存访问
+    unsafe {
+        libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+/// 用单引号包裹字符串，使其可以安全地嵌入 shell 脚本
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// 构造发送给 shell 的脚本
+///
+/// 命令通过 `eval` 执行：语法错误只会让 `eval` 返回非零退出码，
+/// 而不会吞掉后面的结束标记导致会话挂起。
+fn build_script(command: &str, marker: &str, merge_stderr: bool) -> String {
+    let redirect = if merge_stderr { " 2>&1" } else { "" };
+    format!(
+        "eval {} < /dev/null{}\n\
+         __kode_status=$?\n\
+         printf '\\n%s %d %s\\n' '{}' \"$__kode_status\" \"$PWD\"\n\
+         printf '\\n%s\\n' '{}' >&2\n",
+        shell_quote(command),
+        redirect,
+        marker,
+        marker
+    )
+}
+
+/// 读取输出直到遇到结束标记行
+///
+/// 返回标记行中标记之后的内容；shell 提前退出（EOF）时返回 `None`。
+async fn read_until_marker<R>(
+    reader: &mut BufReader<R>,
+    buffer: &mut CappedBuffer,
+    marker: &str,
+) -> Result<Option<String>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(None);
+        }
+        if let Some(rest) = line.strip_prefix(marker.as_bytes()) {
+            // 结束标记前额外输出的换行符不属于命令输出
+            buffer.pop_newline();
+            return Ok(Some(String::from_utf8_lossy(rest).trim().to_string()));
+        }
+        buffer.push(&line);
+    }
+}
+
+/// 持久 shell 进程
+#[derive(Debug)]
+pub struct ShellSession {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    cwd: PathBuf,
+    sandbox: Option<SandboxPolicy>,
+    alive: bool,
+}
+
+impl ShellSession {
+    /// 在指定目录启动 shell
+    ///
+    /// 指定沙箱策略时，shell 及其执行的所有命令都在沙箱中运行。
+    pub fn spawn(cwd: &Path, sandbox: Option<&SandboxPolicy>) -> Result<Self> {
+        let (program, args) = shell_program();
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        if let Some(policy) = sandbox {
+            sandbox::apply(command.as_std_mut(), policy)?;
+        }
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start shell {}", program))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Shell has no stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Shell has no stdout"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Shell has no stderr"))?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            stderr: BufReader::new(stderr),
+            cwd: cwd.to_path_buf(),
+            sandbox: sandbox.cloned(),
+            alive: true,
+        })
+    }
+
+    /// shell 当前所在目录
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// shell 启动时使用的沙箱策略
+    pub fn sandbox(&self) -> Option<&SandboxPolicy> {
+        self.sandbox.as_ref()
+    }
+
+    /// shell 是否仍可用
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// 在 shell 中执行命令
+    ///
+    /// 超时或被取消时杀掉 shell 的整个进程组，返回已收到的部分输出；
+    /// 之后该会话不可再用。
+    pub async fn run(
+        &mut self,
+        command: &str,
+        options: &RunOptions,
+        cancel: &CancellationToken,
+    ) -> Result<CommandOutput> {
+        if !self.alive {
+            bail!("Shell session has exited");
+        }
+
+        let marker = format!("__KODE_CMD_DONE_{}__", uuid::Uuid::new_v4().simple());
+        let script = build_script(command, &marker, options.merge_stderr);
+        self.stdin
+            .write_all(script.as_bytes())
+            .await
+            .context("Failed to send command to shell")?;
+        self.stdin
+            .flush()
+            .await
+            .context("Failed to send command to shell")?;
+
+        let mut stdout = CappedBuffer::new(options.max_output);
+        let mut stderr = CappedBuffer::new(options.max_output);
+        let finished = {
+            let reading = async {
+                tokio::try_join!(
+                    read_until_marker(&mut self.stdout, &mut stdout, &marker),
+                    read_until_marker(&mut self.stderr, &mut stderr, &marker),
+                )
+            };
+            tokio::select! {
+                result = reading => Some(result?),
+                _ = tokio::time::sleep(options.timeout) => None,
+                _ = cancel.cancelled() => None,
+            }
+        };
+
+        let status = match finished {
+            Some((Some(trailer), _)) => {
+                let (code, cwd) = trailer.split_once(' ').unwrap_or((trailer.as_str(), ""));
+                if !cwd.is_empty() {
+                    self.cwd = PathBuf::from(cwd);
+                }
+                CommandStatus::Exited(code.parse().unwrap_or(-1))
+            }
+            Some((None, _)) => {
+                self.alive = false;
+                let code = self.child.wait().await.ok().and_then(|s| s.code());
+                CommandStatus::ShellExited(code)
+            }
+            None => {
+                self.kill();
+                if cancel.is_cancelled() {
+                    CommandStatus::Cancelled
+                } else {
+                    CommandStatus::TimedOut
+                }
+            }
+        };
+
+        Ok(CommandOutput {
+            truncated: stdout.is_truncated() || stderr.is_truncated(),
+            stdout: stdout.to_string_lossy(),
+            stderr: stderr.to_string_lossy(),
+            status,
+        })
+    }
+
+    /// 读取 shell 中导出的环境变量
+    pub async fn environment(&mut self) -> Result<HashMap<String, String>> {
+        let options = RunOptions {
+            timeout: Duration::from_secs(10),
+            max_output: MAX_JOB_BUFFER,
+            ..RunOptions::default()
+        };
+        let output = self
+            .run("env -0", &options, &CancellationToken::new())
+            .await?;
+        if output.status != CommandStatus::Exited(0) {
+            bail!("Failed to read shell environment");
+        }
+
+        Ok(output
+            .stdout
+            .split('\0')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect())
+    }
+
+    /// 杀掉 shell 及其子进程
+    pub fn kill(&mut self) {
+        self.alive = false;
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            kill_process_group(pid);
+        }
+        self.child.start_kill().ok();
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        if self.alive {
+            self.kill();
+        }
+    }
+}
+
+/// 后台任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// 仍在运行
+    Running,
+    /// 已结束
+    Exited(i32),
+    /// 被杀掉或被信号终止
+    Killed,
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Exited(code) => write!(f, "exited with code {}", code),
+            JobStatus::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+/// 后台任务的共享输出
+#[derive(Debug)]
+struct JobOutput {
+    stdout: CappedBuffer,
+    stderr: CappedBuffer,
+    status: JobStatus,
+}
+
+/// 后台任务
+#[derive(Debug)]
+struct BackgroundJob {
+    command: String,
+    pid: Option<u32>,
+    output: Arc<Mutex<JobOutput>>,
+}
+
+/// 后台任务的一次轮询结果
+#[derive(Debug, Clone)]
+pub struct JobSnapshot {
+    /// 任务执行的命令
+    pub command: String,
+    /// 当前状态
+    pub status: JobStatus,
+    /// 自上次轮询以来的新标准输出
+    pub stdout: String,
+    /// 自上次轮询以来的新标准错误
+    pub stderr: String,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// 将子进程的输出流持续拷贝到共享缓冲区
+fn pump<R>(mut reader: R, output: Arc<Mutex<JobOutput>>, is_stderr: bool)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = reader.read(&mut chunk).await {
+            if n == 0 {
+                break;
+            }
+            let mut output = lock(&output);
+            if is_stderr {
+                output.stderr.push(&chunk[..n]);
+            } else {
+                output.stdout.push(&chunk[..n]);
+            }
+        }
+    });
+}
+
+/// 会话级 shell 状态：持久 shell 与后台任务
+///
+/// 通过 [`crate::ToolContext`] 在同一会话的所有工具调用之间共享。
+#[derive(Debug, Default)]
+pub struct ShellState {
+    session: tokio::sync::Mutex<Option<ShellSession>>,
+    jobs: Mutex<HashMap<String, BackgroundJob>>,
+    next_job_id: AtomicU64,
+}
+
+impl ShellState {
+    /// 创建新的 shell 状态（shell 在第一次执行命令时才启动）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在持久 shell 中执行命令
+    ///
+    /// shell 尚未启动、上次已退出或沙箱策略发生变化时，在 `default_dir` 中启动新 shell。
+    pub async fn run(
+        &self,
+        command: &str,
+        default_dir: &Path,
+        options: &RunOptions,
+        cancel: &CancellationToken,
+    ) -> Result<CommandOutput> {
+        let mut session = self.session.lock().await;
+        let reusable = session
+            .as_ref()
+            .is_some_and(|shell| shell.is_alive() && shell.sandbox() == options.sandbox.as_ref());
+        if !reusable {
+            *session = Some(ShellSession::spawn(default_dir, options.sandbox.as_ref())?);
+        }
+
+        let shell = session.as_mut().expect("shell session was just started");
+        let result = shell.run(command, options, cancel).await;
+        if !shell.is_alive() {
+            *session = None;
+        }
+        result
+    }
+
+    /// 持久 shell 当前所在目录（尚未启动时为 `None`）
+    pub async fn cwd(&self) -> Option<PathBuf> {
+        self.session
+            .lock()
+            .await
+            .as_ref()
+            .map(|shell| shell.cwd().to_path_buf())
+    }
+
+    /// 启动后台任务
+    ///
+    /// 任务在持久 shell 的当前目录中运行，并继承其导出的环境变量。
+    /// 指定沙箱策略时任务在沙箱中运行。
+    ///
+    /// # Returns
+    ///
+    /// 返回用于轮询的任务 ID
+    pub async fn spawn_background(
+        &self,
+        command: &str,
+        default_dir: &Path,
+        merge_stderr: bool,
+        sandbox: Option<&SandboxPolicy>,
+    ) -> Result<String> {
+        let (cwd, env) = {
+            let mut session = self.session.lock().await;
+            match session.as_mut().filter(|shell| shell.is_alive()) {
+                Some(shell) => (shell.cwd().to_path_buf(), Some(shell.environment().await?)),
+                None => (default_dir.to_path_buf(), None),
+            }
+        };
+
+        let script = if merge_stderr {
+            format!("exec 2>&1\n{}", command)
+        } else {
+            command.to_string()
+        };
+        let (program, _) = shell_program();
+        let mut process = Command::new(program);
+        process
+            .arg("-c")
+            .arg(script)
+            .current_dir(&cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(env) = env {
+            process.env_clear().envs(env);
+        }
+        #[cfg(unix)]
+        process.process_group(0);
+        if let Some(policy) = sandbox {
+            sandbox::apply(process.as_std_mut(), policy)?;
+        }
+
+        let mut child = process
+            .spawn()
+            .with_context(|| format!("Failed to start background command: {}", command))?;
+        let output = Arc::new(Mutex::new(JobOutput {
+            stdout: CappedBuffer::new(MAX_JOB_BUFFER),
+            stderr: CappedBuffer::new(MAX_JOB_BUFFER),
+            status: JobStatus::Running,
+        }));
+        if let Some(stdout) = child.stdout.take() {
+            pump(stdout, output.clone(), false);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            pump(stderr, output.clone(), true);
+        }
+
+        let pid = child.id();
+        let waiter = output.clone();
+        tokio::spawn(async move {
+            let status = match child.wait().await.ok().and_then(|s| s.code()) {
+                Some(code) => JobStatus::Exited(code),
+                None => JobStatus::Killed,
+            };
+            let mut output = lock(&waiter);
+            if output.status == JobStatus::Running {
+                output.status = status;
+            }
+        });
+
+        let id = format!(
+            "bash_{}",
+            self.next_job_id.fetch_add(1, Ordering::SeqCst) + 1
+        );
+        lock(&self.jobs).insert(
+            id.clone(),
+            BackgroundJob {
+                command: command.to_string(),
+                pid,
+                output,
+            },
+        );
+        Ok(id)
+    }
+
+    /// 轮询后台任务，取走自上次轮询以来的新输出
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - 任务 ID
+    /// * `max_output` - 每个输出流返回的字节上限
+    pub fn poll(&self, id: &str, max_output: usize) -> Option<JobSnapshot> {
+        let jobs = lock(&self.jobs);
+        let job = jobs.get(id)?;
+        let mut output = lock(&job.output);
+
+        let take = |buffer: &mut CappedBuffer| {
+            let text = buffer.to_string_lossy();
+            *buffer = CappedBuffer::new(MAX_JOB_BUFFER);
+            let mut capped = CappedBuffer::new(max_output);
+            capped.push(text.as_bytes());
+            capped.to_string_lossy()
+        };
+        let stdout = take(&mut output.stdout);
+        let stderr = take(&mut output.stderr);
+
+        Some(JobSnapshot {
+            command: job.command.clone(),
+            status: output.status,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// 杀掉后台任务
+    ///
+    /// # Returns
+    ///
+    /// 任务不存在时返回错误；任务已结束时返回其最终状态
+    pub fn kill(&self, id: &str) -> Result<JobStatus> {
+        let jobs = lock(&self.jobs);
+        let job = jobs
+            .get(id)
+            .ok_or_else(|| anyhow!("No background job with ID {}", id))?;
+        let mut output = lock(&job.output);
+        if output.status == JobStatus::Running {
+            #[cfg(unix)]
+            if let Some(pid) = job.pid {
+                kill_process_group(pid);
+            }
+            output.status = JobStatus::Killed;
+        }
+        Ok(output.status)
+    }
+
+    /// 列出所有后台任务及其状态，按创建顺序排列
+    pub fn jobs(&self) -> Vec<(String, String, JobStatus)> {
+        let mut jobs: Vec<_> = lock(&self.jobs)
+            .iter()
+            .map(|(id, job)| (id.clone(), job.command.clone(), lock(&job.output).status))
+            .collect();
+        jobs.sort_by_key(|(id, _, _)| job_number(id));
+        jobs
+    }
+}
+
+/// 任务 ID（`bash_<n>`）中的序号
+fn job_number(id: &str) -> u64 {
+    id.strip_prefix("bash_")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
+impl Drop for ShellState {
+    fn drop(&mut self) {
+        // 会话结束时不留下仍在运行的后台任务
+        let ids: Vec<String> = lock(&self.jobs).keys().cloned().collect();
+        for id in ids {
+            self.kill(&id).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn run(state: &ShellState, dir: &Path, command: &str) -> CommandOutput {
+        state
+            .run(
+                command,
+                dir,
+                &RunOptions::default(),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_capped_buffer_keeps_head_and_tail() {
+        let mut buffer = CappedBuffer::new(10);
+        buffer.push(b"hello");
+        assert!(!buffer.is_truncated());
+        assert_eq!(buffer.to_string_lossy(), "hello");
+
+        for _ in 0..100 {
+            buffer.push(b"xxxxxxxxxx");
+        }
+        buffer.push(b"world");
+        assert!(buffer.is_truncated());
+        let text = buffer.to_string_lossy();
+        assert!(text.starts_with("hello"));
+        assert!(text.ends_with("world"));
+        assert!(text.contains("1000 bytes of output truncated"));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[tokio::test]
+    async fn test_cd_and_export_persist() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let state = ShellState::new();
+
+        run(&state, dir.path(), "cd sub && export KODE_TEST_VAR=42").await;
+        let output = run(&state, dir.path(), "pwd; echo $KODE_TEST_VAR").await;
+
+        let expected_dir = dir.path().join("sub").canonicalize().unwrap();
+        assert_eq!(output.stdout, format!("{}\n42\n", expected_dir.display()));
+        assert_eq!(state.cwd().await.unwrap(), expected_dir);
+    }
+
+    #[tokio::test]
+    async fn test_exit_code_and_stderr() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+
+        let output = run(&state, dir.path(), "echo out; echo err >&2; false").await;
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        assert_eq!(output.status, CommandStatus::Exited(1));
+
+        let options = RunOptions {
+            merge_stderr: true,
+            ..RunOptions::default()
+        };
+        let output = state
+            .run(
+                "echo out; echo err >&2",
+                dir.path(),
+                &options,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, "out\nerr\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[tokio::test]
+    async fn test_output_without_trailing_newline() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        let output = run(&state, dir.path(), "printf abc").await;
+        assert_eq!(output.stdout, "abc");
+    }
+
+    #[tokio::test]
+    async fn test_syntax_error_does_not_hang() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        let output = run(&state, dir.path(), "echo 'unterminated").await;
+        assert_ne!(output.status, CommandStatus::Exited(0));
+        assert!(!output.status.shell_restarted());
+
+        let output = run(&state, dir.path(), "echo ok").await;
+        assert_eq!(output.stdout, "ok\n");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_and_restarts_shell() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let state = ShellState::new();
+        run(&state, dir.path(), "cd sub").await;
+
+        let options = RunOptions {
+            timeout: Duration::from_millis(200),
+            ..RunOptions::default()
+        };
+        let output = state
+            .run(
+                "echo started; sleep 30",
+                dir.path(),
+                &options,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.status, CommandStatus::TimedOut);
+        assert_eq!(output.stdout, "started\n");
+
+        // 新 shell 回到默认目录
+        let output = run(&state, dir.path(), "pwd").await;
+        assert_eq!(
+            output.stdout.trim(),
+            dir.path().canonicalize().unwrap().to_string_lossy()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancellation() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        let cancel = CancellationToken::new();
+
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let output = state
+            .run("sleep 30", dir.path(), &RunOptions::default(), &cancel)
+            .await
+            .unwrap();
+        assert_eq!(output.status, CommandStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_exit_restarts_shell() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        let output = run(&state, dir.path(), "exit 3").await;
+        assert_eq!(output.status, CommandStatus::ShellExited(Some(3)));
+
+        let output = run(&state, dir.path(), "echo alive").await;
+        assert_eq!(output.stdout, "alive\n");
+    }
+
+    #[tokio::test]
+    async fn test_output_is_capped() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        let options = RunOptions {
+            max_output: 100,
+            ..RunOptions::default()
+        };
+        let output = state
+            .run(
+                "seq 1 10000",
+                dir.path(),
+                &options,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        assert!(output.truncated);
+        assert!(output.stdout.starts_with("1\n2\n"));
+        assert!(output.stdout.ends_with("9999\n10000\n"));
+    }
+
+    #[tokio::test]
+    async fn test_background_job_poll_and_kill() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        run(&state, dir.path(), "export KODE_BG=from-shell").await;
+
+        let id = state
+            .spawn_background("echo $KODE_BG; echo err >&2", dir.path(), false, None)
+            .await
+            .unwrap();
+        let mut snapshot = state.poll(&id, DEFAULT_MAX_OUTPUT).unwrap();
+        let mut stdout = snapshot.stdout.clone();
+        for _ in 0..100 {
+            if snapshot.status != JobStatus::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            snapshot = state.poll(&id, DEFAULT_MAX_OUTPUT).unwrap();
+            stdout.push_str(&snapshot.stdout);
+        }
+        // 退出后可能还有残留输出
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stdout.push_str(&state.poll(&id, DEFAULT_MAX_OUTPUT).unwrap().stdout);
+        assert_eq!(snapshot.status, JobStatus::Exited(0));
+        assert_eq!(stdout, "from-shell\n");
+
+        let id = state
+            .spawn_background("sleep 30", dir.path(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(state.kill(&id).unwrap(), JobStatus::Killed);
+        assert_eq!(state.poll(&id, 100).unwrap().status, JobStatus::Killed);
+        assert!(state.kill("bash_missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jobs_listed_in_creation_order() {
+        let dir = TempDir::new().unwrap();
+        let state = ShellState::new();
+        for _ in 0..11 {
+            state
+                .spawn_background("true", dir.path(), false, None)
+                .await
+                .unwrap();
+        }
+        let ids: Vec<_> = state.jobs().into_iter().map(|(id, _, _)| id).collect();
+        let expected: Vec<_> = (1..=11).map(|n| format!("bash_{}", n)).collect();
+        assert_eq!(ids, expected);
+    }
+}
@@ -0,0 +1,930 @@
+//! Task 工具
+//!
+//! 启动子 Agent 处理独立的任务：子 Agent 使用自己的系统提示词、工具过滤器和模型，
+//! 在隔离的对话中运行查询循环，最终回复作为工具结果返回。
+//!
+//! 每个子 Agent 分配一个新的 Agent ID，工具通过 [`ToolContext::agent_id`] 隔离 Agent 数据，
+//! 对话记录在结束后写入 [`storage`]（使用 [`transcript_id`]，不占用 Agent 自己的数据文件）。
+//!
+//! Task 调用可以并发执行：同一轮中的多个 Task 调用由编排器并行启动，结果按调用顺序返回。
+//! 所有子 Agent（包括嵌套的）共享一个 [`TaskBudget`]，限制同时运行的子 Agent 数量、
+//! 累计 token 消耗和美元成本，并汇总每个子 Agent 的用量。
+
+use crate::{Tool, ToolContext, ToolOrchestrator, ToolRegistry, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::agent::{storage, Agent};
+use kode_core::context::MessageContextManager;
+use kode_core::message::types::TokenUsage;
+use kode_core::message::Message;
+use kode_core::model::ModelProvider;
+use kode_core::query::{QueryEngine, QueryEvent, StopReason};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{mpsc, Semaphore};
+
+/// Task 工具名
+pub const TASK_TOOL_NAME: &str = "Task";
+
+/// 默认最大嵌套深度（1 表示子 Agent 不能再启动子 Agent）
+pub const DEFAULT_MAX_DEPTH: usize = 1;
+
+/// 默认同时运行的子 Agent 数量上限
+pub const DEFAULT_MAX_CONCURRENT_AGENTS: usize = 4;
+
+const DESCRIPTION: &str = "Launch a sub-agent to handle a complex, multi-step task autonomously. \
+The sub-agent runs in its own conversation with the tools, system prompt and model of the \
+selected agent type, and returns a single final report. Give it a detailed prompt: it cannot \
+see the current conversation and cannot ask follow-up questions.";
+
+/// Task 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskParams {
+    /// 任务的简短描述（3-5 个词，用于展示）
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 交给子 Agent 的完整任务说明
+    pub prompt: String,
+    /// 子 Agent 类型（Agent 名称）
+    pub subagent_type: String,
+}
+
+/// 子 Agent 对话记录在 Agent 存储中的 ID
+///
+/// Agent 自己的数据文件（例如待办列表）使用 `agent_id`，对话记录单独存放。
+pub fn transcript_id(agent_id: &str) -> String {
+    format!("{}-transcript", agent_id)
+}
+
+/// 子 Agent 的对话记录，写入 Agent 存储
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTranscript {
+    /// 子 Agent 类型
+    pub agent_type: String,
+    /// 任务描述
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 完整对话
+    pub messages: Vec<Message>,
+}
+
+/// 子 Agent 的运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubAgentStatus {
+    /// 正在运行
+    Running,
+    /// 正常结束
+    Completed,
+    /// 达到最大轮数
+    MaxTurns,
+    /// 因累计 token 或成本超出上限被停止
+    BudgetExhausted,
+    /// 被取消
+    Cancelled,
+    /// 执行失败
+    Failed,
+}
+
+/// 单个子 Agent 的用量
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubAgentUsage {
+    /// Agent ID
+    pub agent_id: String,
+    /// 子 Agent 类型
+    pub agent_type: String,
+    /// 任务描述
+    pub description: Option<String>,
+    /// 运行状态
+    pub status: SubAgentStatus,
+    /// 已执行的轮数
+    pub turns: usize,
+    /// token 用量
+    pub usage: TokenUsage,
+    /// 成本（美元，来自助手消息的 `cost_usd`）
+    pub cost_usd: f64,
+}
+
+/// 所有子 Agent 的用量汇总
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskUsageSummary {
+    /// 各子 Agent 的用量（按启动顺序）
+    pub agents: Vec<SubAgentUsage>,
+    /// 合计 token 用量
+    pub total: TokenUsage,
+    /// 合计成本（美元）
+    pub total_cost_usd: f64,
+}
+
+impl fmt::Display for TaskUsageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for agent in &self.agents {
+            writeln!(
+                f,
+                "{} ({}): {:?}, {} turns, {} tokens, ${:.4}",
+                agent.agent_type,
+                agent.description.as_deref().unwrap_or(&agent.agent_id),
+                agent.status,
+                agent.turns,
+                agent.usage.total(),
+                agent.cost_usd
+            )?;
+        }
+        write!(
+            f,
+            "Total: {} agents, {} tokens, ${:.4}",
+            self.agents.len(),
+            self.total.total(),
+            self.total_cost_usd
+        )
+    }
+}
+
+/// 子 Agent 共享的并发、token 与成本上限
+///
+/// 累计 token 达到 `max_total_tokens` 或累计成本达到 `max_total_cost_usd` 后，
+/// 正在运行的子 Agent 会被停止，新的 Task 调用直接失败。
+#[derive(Debug)]
+pub struct TaskBudget {
+    slots: Semaphore,
+    max_total_tokens: Option<usize>,
+    max_total_cost_usd: Option<f64>,
+    agents: Mutex<Vec<SubAgentUsage>>,
+}
+
+impl Default for TaskBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_AGENTS)
+    }
+}
+
+impl TaskBudget {
+    /// 创建预算，`max_concurrent` 为同时运行的子 Agent 数量上限（至少为 1）
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            slots: Semaphore::new(max_concurrent.max(1)),
+            max_total_tokens: None,
+            max_total_cost_usd: None,
+            agents: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 设置所有子 Agent 累计 token 上限
+    pub fn with_max_total_tokens(mut self, max_total_tokens: usize) -> Self {
+        self.max_total_tokens = Some(max_total_tokens);
+        self
+    }
+
+    /// 设置所有子 Agent 累计成本上限（美元）
+    pub fn with_max_total_cost_usd(mut self, max_total_cost_usd: f64) -> Self {
+        self.max_total_cost_usd = Some(max_total_cost_usd);
+        self
+    }
+
+    /// 所有子 Agent 的用量汇总
+    pub fn summary(&self) -> TaskUsageSummary {
+        let agents = self.agents().clone();
+        let mut total = TokenUsage::default();
+        for agent in &agents {
+            add_usage(&mut total, &agent.usage);
+        }
+        let total_cost_usd = agents.iter().map(|a| a.cost_usd).sum();
+        TaskUsageSummary {
+            agents,
+            total,
+            total_cost_usd,
+        }
+    }
+
+    /// 累计 token 用量
+    pub fn total_tokens(&self) -> usize {
+        self.agents().iter().map(|a| a.usage.total()).sum()
+    }
+
+    /// 累计成本（美元）
+    pub fn total_cost_usd(&self) -> f64 {
+        self.agents().iter().map(|a| a.cost_usd).sum()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.max_total_tokens
+            .is_some_and(|max| self.total_tokens() >= max)
+            || self
+                .max_total_cost_usd
+                .is_some_and(|max| self.total_cost_usd() >= max)
+    }
+
+    fn agents(&self) -> MutexGuard<'_, Vec<SubAgentUsage>> {
+        self.agents
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 登记新的子 Agent，返回其序号
+    fn register(&self, usage: SubAgentUsage) -> Result<usize> {
+        if self.is_exhausted() {
+            bail!(
+                "Sub-agent budget exhausted ({} tokens, ${:.4} used)",
+                self.total_tokens(),
+                self.total_cost_usd()
+            );
+        }
+        let mut agents = self.agents();
+        agents.push(usage);
+        Ok(agents.len() - 1)
+    }
+
+    /// 记录一轮助手消息的用量和成本，返回累计用量是否仍在上限内
+    fn record(&self, index: usize, message: &Message) -> bool {
+        {
+            let mut agents = self.agents();
+            let agent = &mut agents[index];
+            if let Some(usage) = &message.usage {
+                add_usage(&mut agent.usage, usage);
+            }
+            agent.cost_usd += message.cost_usd.unwrap_or(0.0);
+        }
+        !self.is_exhausted()
+    }
+
+    fn finish(&self, index: usize, status: SubAgentStatus, turns: usize) {
+        let mut agents = self.agents();
+        agents[index].status = status;
+        agents[index].turns = turns;
+    }
+}
+
+fn add_usage(total: &mut TokenUsage, usage: &TokenUsage) {
+    let add = |a: Option<usize>, b: Option<usize>| match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    };
+    total.input_tokens = add(total.input_tokens, usage.input_tokens);
+    total.output_tokens = add(total.output_tokens, usage.output_tokens);
+    total.cache_creation_input_tokens = add(
+        total.cache_creation_input_tokens,
+        usage.cache_creation_input_tokens,
+    );
+    total.cache_read_input_tokens =
+        add(total.cache_read_input_tokens, usage.cache_read_input_tokens);
+}
+
+/// Task 工具
+pub struct TaskTool {
+    agents: Vec<Agent>,
+    registry: ToolRegistry,
+    models: Arc<dyn ModelProvider>,
+    budget: Arc<TaskBudget>,
+    context_tokens: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl TaskTool {
+    /// 创建 Task 工具
+    ///
+    /// # Arguments
+    ///
+    /// * `agents` - 可用的 Agent（通常来自 `AgentLoader::get_active_agents`）
+    /// * `registry` - 子 Agent 可用的全部工具，按各 Agent 的过滤器取子集
+    /// * `models` - 解析 Agent 的模型覆盖
+    pub fn new(agents: Vec<Agent>, registry: ToolRegistry, models: Arc<dyn ModelProvider>) -> Self {
+        Self {
+            agents,
+            registry,
+            models,
+            budget: Arc::new(TaskBudget::default()),
+            context_tokens: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// 设置共享的子 Agent 预算
+    pub fn with_budget(mut self, budget: Arc<TaskBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// 设置每个子 Agent 上下文的 token 上限（0 表示不限制）
+    pub fn with_context_tokens(mut self, context_tokens: usize) -> Self {
+        self.context_tokens = context_tokens;
+        self
+    }
+
+    /// 共享的子 Agent 预算
+    pub fn budget(&self) -> &Arc<TaskBudget> {
+        &self.budget
+    }
+
+    /// 设置最大嵌套深度
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// 按名称查找 Agent
+    fn agent(&self, name: &str) -> Result<&Agent> {
+        match self.agents.iter().find(|agent| agent.name == name) {
+            Some(agent) => Ok(agent),
+            None => {
+                let available: Vec<&str> = self.agents.iter().map(|a| a.name.as_str()).collect();
+                bail!(
+                    "Agent type '{}' not found. Available agents: {}",
+                    name,
+                    available.join(", ")
+                )
+            }
+        }
+    }
+
+    /// 子 Agent 可用的工具
+    ///
+    /// 未达到最大深度时，子 Agent 可以继续使用 Task 工具（深度加一）。
+    fn agent_registry(&self, agent: &Agent) -> ToolRegistry {
+        let mut registry = self.registry.for_agent(agent).registry;
+        if self.depth + 1 < self.max_depth && agent.allows_tool(TASK_TOOL_NAME) {
+            registry.register(Arc::new(Self {
+                agents: self.agents.clone(),
+                registry: self.registry.clone(),
+                models: self.models.clone(),
+                budget: self.budget.clone(),
+                context_tokens: self.context_tokens,
+                depth: self.depth + 1,
+                max_depth: self.max_depth,
+            }));
+        }
+        registry
+    }
+}
+
+#[async_trait]
+impl Tool for TaskTool {
+    fn name(&self) -> &str {
+        TASK_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    /// 子 Agent 之间互相隔离，同一轮中的多个 Task 调用可以并发执行
+    fn is_concurrency_safe(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        let agent_types: Vec<&str> = self.agents.iter().map(|a| a.name.as_str()).collect();
+        let agent_list = self
+            .agents
+            .iter()
+            .map(|a| format!("- {}: {}", a.name, a.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ToolSchema {
+            name: self.name().to_string(),
+            description: format!("{}\n\nAvailable agent types:\n{}", DESCRIPTION, agent_list),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "description": {
+                        "type": "string",
+                        "description": "A short (3-5 word) description of the task"
+                    },
+                    "prompt": {
+                        "type": "string",
+                        "description": "The task for the agent to perform"
+                    },
+                    "subagent_type": {
+                        "type": "string",
+                        "enum": agent_types,
+                        "description": "The type of specialized agent to use"
+                    }
+                },
+                "required": ["prompt", "subagent_type"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: TaskParams =
+            serde_json::from_value(params).context("Invalid Task parameters")?;
+        let agent = self.agent(&params.subagent_type)?;
+        let adapter = self.models.adapter(agent.model.as_deref())?;
+
+        let agent_id = storage::generate_agent_id();
+        let index = self.budget.register(SubAgentUsage {
+            agent_id: agent_id.clone(),
+            agent_type: agent.name.clone(),
+            description: params.description.clone(),
+            status: SubAgentStatus::Running,
+            turns: 0,
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
+        })?;
+        let _slot = tokio::select! {
+            _ = context.cancel.cancelled() => {
+                self.budget.finish(index, SubAgentStatus::Cancelled, 0);
+                bail!("Agent '{}' was cancelled", agent.name);
+            }
+            slot = self.budget.slots.acquire() => slot.context("Sub-agent slots closed")?,
+        };
+        context.report_progress(&Message::assistant(format!(
+            "Starting agent {}{}",
+            agent.name,
+            params
+                .description
+                .as_deref()
+                .map(|d| format!(": {}", d))
+                .unwrap_or_default()
+        )));
+
+        let cancel = context.cancel.child_token();
+        let mut agent_context = context
+            .clone()
+            .with_agent_id(agent_id.clone())
+            .with_cancellation(cancel.clone());
+        agent_context.progress = None;
+        let runner = ToolOrchestrator::new(self.agent_registry(agent)).runner(agent_context);
+
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let budget = self.budget.clone();
+        let engine = QueryEngine::new(adapter, Arc::new(runner))
+            .with_system_prompt(agent.system_prompt.clone())
+            .with_events(events_tx)
+            .with_cancellation(cancel)
+            .with_usage_limit(Arc::new(move |message| budget.record(index, message)));
+
+        let mut conversation = MessageContextManager::new(self.context_tokens);
+        conversation.add_message(Message::user(params.prompt.clone()));
+        let run = async {
+            let result = engine.run(&mut conversation).await;
+            // 关闭事件通道，结束转发
+            drop(engine);
+            result
+        };
+        let forward = async {
+            while let Some(event) = events.recv().await {
+                if let QueryEvent::Message(message) = event {
+                    context.report_progress(&message);
+                }
+            }
+        };
+        let (result, _) = tokio::join!(run, forward);
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.budget.finish(index, SubAgentStatus::Failed, 0);
+                return Err(e).with_context(|| format!("Agent '{}' failed", agent.name));
+            }
+        };
+
+        let transcript = TaskTranscript {
+            agent_type: agent.name.clone(),
+            description: params.description,
+            messages: conversation.get_messages(),
+        };
+        if let Err(e) = storage::write_agent_data(&transcript_id(&agent_id), &transcript).await {
+            self.budget
+                .finish(index, SubAgentStatus::Failed, result.turns);
+            return Err(e).with_context(|| format!("Agent '{}' failed", agent.name));
+        }
+
+        let status = match result.stop_reason {
+            StopReason::EndTurn => SubAgentStatus::Completed,
+            StopReason::MaxTurns => SubAgentStatus::MaxTurns,
+            StopReason::UsageLimit => SubAgentStatus::BudgetExhausted,
+            StopReason::Cancelled => SubAgentStatus::Cancelled,
+        };
+        self.budget.finish(index, status, result.turns);
+
+        let report = result.final_text();
+        match status {
+            SubAgentStatus::MaxTurns => Ok(ToolResult::new(format!(
+                "{}\n\n[Agent stopped after reaching the maximum of {} turns]",
+                report, result.turns
+            ))),
+            SubAgentStatus::BudgetExhausted => Ok(ToolResult::new(format!(
+                "{}\n\n[Agent stopped: sub-agent budget exhausted]",
+                report
+            ))),
+            SubAgentStatus::Cancelled => bail!("Agent '{}' was cancelled", agent.name),
+            _ => Ok(ToolResult::new(report)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_core::agent::{AgentLocation, ToolFilter};
+    use kode_core::error::Error;
+    use kode_core::message::ToolResultBlock;
+    use kode_core::message::{ContentBlock, MessageContent};
+    use kode_core::model::{ModelAdapter, ModelResponse, StreamChunk, StreamingResponse};
+    use kode_core::testing::ScriptedAdapter;
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// 记录请求的模型名，始终返回同一个脚本模型
+    struct Provider {
+        adapter: Arc<ScriptedAdapter>,
+        requested: Mutex<Vec<Option<String>>>,
+    }
+
+    impl ModelProvider for Provider {
+        fn adapter(&self, model: Option<&str>) -> kode_core::Result<Arc<dyn ModelAdapter>> {
+            self.requested.lock().unwrap().push(model.map(String::from));
+            Ok(self.adapter.clone())
+        }
+    }
+
+    struct Echo(&'static str);
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "echo"
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema {
+                name: self.0.to_string(),
+                description: "echo".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn execute(&self, _params: Value, context: &ToolContext) -> Result<ToolResult> {
+            Ok(ToolResult::new(format!(
+                "{} ran as {}",
+                self.0, context.agent_id
+            )))
+        }
+    }
+
+    fn agent(tools: &[&str]) -> Agent {
+        Agent::new(
+            "searcher".to_string(),
+            "Searches the codebase".to_string(),
+            ToolFilter::Specific(tools.iter().map(|t| t.to_string()).collect()),
+            "You search code.".to_string(),
+            AgentLocation::Project,
+        )
+        .with_model("small-model".to_string())
+    }
+
+    fn setup(agent: Agent, script: Vec<Vec<StreamChunk>>) -> (TaskTool, Arc<Provider>) {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(Echo("Grep")));
+        registry.register(Arc::new(Echo("Bash")));
+        let provider = Arc::new(Provider {
+            adapter: ScriptedAdapter::new(script),
+            requested: Mutex::new(Vec::new()),
+        });
+        let tool = TaskTool::new(vec![agent], registry, provider.clone());
+        (tool, provider)
+    }
+
+    fn tool_results(message: &Message) -> Vec<ToolResultBlock> {
+        match &message.content {
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolResult(r) => Some(r.clone()),
+                    _ => None,
+                })
+                .collect(),
+            MessageContent::Text(_) => Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_runs_sub_agent_in_isolation() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_var("KODE_CONFIG_DIR", dir.path());
+
+        let (tool, provider) = setup(
+            agent(&["Grep"]),
+            vec![
+                vec![
+                    StreamChunk::tool_use("Grep", "g", json!({})),
+                    StreamChunk::tool_use("Bash", "b", json!({})),
+                ],
+                vec![StreamChunk::content_block_delta(0, "Found it in lib.rs")],
+            ],
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let context = ToolContext::new(dir.path()).with_progress(crate::ProgressSink::new(
+            tx,
+            "task-1",
+            Default::default(),
+        ));
+        let result = tool
+            .execute(
+                json!({"prompt": "Find the parser", "subagent_type": "searcher"}),
+                &context,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.output, "Found it in lib.rs");
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            [Some("small-model".to_string())]
+        );
+
+        let requests = provider.adapter.requests();
+        assert_eq!(requests[0].1.as_deref(), Some("You search code."));
+        // 子 Agent 只能使用过滤后的工具，并使用新的 Agent ID
+        let results = tool_results(requests[1].0.last().unwrap());
+        assert!(!results[0].is_error);
+        assert!(!results[0].content.ends_with(&context.agent_id));
+        assert!(results[1].is_error);
+        assert!(results[1].content.contains("Unknown tool: Bash"));
+
+        let agent_id = results[0].content.rsplit(' ').next().unwrap();
+        let transcript: TaskTranscript = storage::read_agent_data(&transcript_id(agent_id))
+            .await
+            .unwrap()
+            .expect("transcript stored");
+        std::env::remove_var("KODE_CONFIG_DIR");
+        assert_eq!(transcript.agent_type, "searcher");
+        assert_eq!(transcript.messages.len(), 4);
+
+        let mut progress = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            progress.push(message);
+        }
+        // 启动提示 + 子 Agent 追加的 3 条消息
+        assert_eq!(progress.len(), 4);
+        assert!(progress.iter().all(|p| p.tool_use_id == "task-1"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_agent_type() {
+        let (tool, _) = setup(agent(&["Grep"]), Vec::new());
+        let dir = TempDir::new().unwrap();
+        let err = tool
+            .execute(
+                json!({"prompt": "x", "subagent_type": "missing"}),
+                &ToolContext::new(dir.path()),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Available agents: searcher"));
+    }
+
+    #[test]
+    fn test_recursion_is_limited() {
+        let agent = agent(&["Grep", "Task"]);
+        let (tool, _) = setup(agent.clone(), Vec::new());
+        assert!(tool.agent_registry(&agent).get(TASK_TOOL_NAME).is_none());
+
+        let tool = tool.with_max_depth(2);
+        let nested = tool.agent_registry(&agent);
+        assert!(nested.get(TASK_TOOL_NAME).is_some());
+
+        // 嵌套的 Task 工具已达到最大深度
+        let nested_task = TaskTool {
+            depth: 1,
+            ..TaskTool::new(
+                vec![agent.clone()],
+                tool.registry.clone(),
+                tool.models.clone(),
+            )
+            .with_max_depth(2)
+        };
+        assert!(nested_task
+            .agent_registry(&agent)
+            .get(TASK_TOOL_NAME)
+            .is_none());
+    }
+
+    /// 回显任务说明的模型，记录同时进行的请求数
+    #[derive(Default)]
+    struct EchoAdapter {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ModelAdapter for EchoAdapter {
+        async fn send_message(
+            &self,
+            _messages: Vec<Message>,
+            _system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<ModelResponse> {
+            Err(Error::ModelRequestError("not scripted".to_string()))
+        }
+
+        async fn stream_message(
+            &self,
+            messages: Vec<Message>,
+            _system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<StreamingResponse> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            let MessageContent::Text(prompt) = &messages[0].content else {
+                panic!("expected text prompt");
+            };
+            let chunks = vec![
+                StreamChunk::content_block_delta(0, format!("report for {}", prompt)),
+                StreamChunk::message_stop(kode_core::model::TokenUsage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    total_tokens: Some(15),
+                }),
+            ];
+            Ok(StreamingResponse::new(Box::pin(futures::stream::iter(
+                chunks.into_iter().map(Ok),
+            ))))
+        }
+
+        fn model_name(&self) -> &str {
+            "echo"
+        }
+    }
+
+    struct EchoProvider(Arc<EchoAdapter>);
+
+    impl ModelProvider for EchoProvider {
+        fn adapter(&self, _model: Option<&str>) -> kode_core::Result<Arc<dyn ModelAdapter>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn task_use(id: &str, dir: &str) -> kode_core::message::ToolUseBlock {
+        kode_core::message::ToolUseBlock {
+            tool_use_id: id.to_string(),
+            tool_name: TASK_TOOL_NAME.to_string(),
+            parameters: json!({
+                "description": dir,
+                "prompt": format!("explore {}", dir),
+                "subagent_type": "searcher"
+            }),
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_parallel_fan_out() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_var("KODE_CONFIG_DIR", dir.path());
+
+        let adapter = Arc::new(EchoAdapter::default());
+        let budget = Arc::new(TaskBudget::new(2));
+        let tool = TaskTool::new(
+            vec![agent(&["Grep"])],
+            ToolRegistry::new(),
+            Arc::new(EchoProvider(adapter.clone())),
+        )
+        .with_budget(budget.clone())
+        .with_context_tokens(50_000);
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(tool));
+
+        let uses: Vec<_> = ["src", "docs", "tests"]
+            .iter()
+            .enumerate()
+            .map(|(i, d)| task_use(&format!("t{}", i), d))
+            .collect();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let results = ToolOrchestrator::new(registry)
+            .run(&uses, &ToolContext::new(dir.path()), Some(&tx))
+            .await;
+        std::env::remove_var("KODE_CONFIG_DIR");
+
+        // 结果按调用顺序返回，并发数受预算限制
+        let outputs: Vec<&str> = results.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(
+            outputs,
+            [
+                "report for explore src",
+                "report for explore docs",
+                "report for explore tests"
+            ]
+        );
+        assert_eq!(adapter.peak.load(Ordering::SeqCst), 2);
+
+        let summary = budget.summary();
+        assert_eq!(summary.agents.len(), 3);
+        assert!(summary
+            .agents
+            .iter()
+            .all(|a| a.status == SubAgentStatus::Completed && a.turns == 1));
+        assert_eq!(summary.total.total(), 45);
+        assert_eq!(summary.total_cost_usd, 0.0);
+        assert!(summary
+            .to_string()
+            .ends_with("Total: 3 agents, 45 tokens, $0.0000"));
+
+        drop(tx);
+        let mut per_child = std::collections::HashMap::new();
+        while let Some(progress) = rx.recv().await {
+            *per_child.entry(progress.tool_use_id).or_insert(0) += 1;
+        }
+        assert_eq!(per_child.len(), 3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_token_ceiling_stops_agents() {
+        let dir = TempDir::new().unwrap();
+        std::env::set_var("KODE_CONFIG_DIR", dir.path());
+
+        let budget = Arc::new(TaskBudget::default().with_max_total_tokens(10));
+        let (tool, _) = setup(
+            agent(&["Grep"]),
+            vec![vec![
+                StreamChunk::content_block_delta(0, "Looking"),
+                StreamChunk::tool_use("Grep", "g", json!({})),
+                StreamChunk::message_stop(kode_core::model::TokenUsage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    total_tokens: Some(15),
+                }),
+            ]],
+        );
+        let tool = tool.with_budget(budget.clone());
+        let context = ToolContext::new(dir.path());
+        let params = json!({"prompt": "Find it", "subagent_type": "searcher"});
+
+        let result = tool.execute(params.clone(), &context).await.unwrap();
+        assert!(result.output.contains("budget exhausted"));
+        assert_eq!(
+            budget.summary().agents[0].status,
+            SubAgentStatus::BudgetExhausted
+        );
+
+        let err = tool.execute(params, &context).await.unwrap_err();
+        std::env::remove_var("KODE_CONFIG_DIR");
+        assert!(err.to_string().contains("budget exhausted"));
+    }
+
+    #[test]
+    fn test_cost_ceiling() {
+        let budget = TaskBudget::default().with_max_total_cost_usd(0.5);
+        let usage = |agent_id: &str| SubAgentUsage {
+            agent_id: agent_id.to_string(),
+            agent_type: "searcher".to_string(),
+            description: None,
+            status: SubAgentStatus::Running,
+            turns: 0,
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
+        };
+        let index = budget.register(usage("a")).unwrap();
+        let turn = || Message::assistant("done").with_cost_tracking(0.3, 10);
+
+        assert!(budget.record(index, &turn()));
+        assert!(!budget.record(index, &turn()));
+        budget.finish(index, SubAgentStatus::BudgetExhausted, 2);
+
+        let summary = budget.summary();
+        assert!((summary.total_cost_usd - 0.6).abs() < 1e-9);
+        assert!((summary.agents[0].cost_usd - 0.6).abs() < 1e-9);
+        assert!(summary.to_string().ends_with("$0.6000"));
+        let err = budget.register(usage("b")).unwrap_err();
+        assert!(err.to_string().contains("$0.6000"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_transcript_write_failure_marks_failed() {
+        let dir = TempDir::new().unwrap();
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, "").unwrap();
+        std::env::set_var("KODE_CONFIG_DIR", blocker.join("config"));
+
+        let (tool, _) = setup(
+            agent(&[]),
+            vec![vec![StreamChunk::content_block_delta(0, "All done")]],
+        );
+        let budget = tool.budget().clone();
+        let result = tool
+            .execute(
+                json!({"prompt": "Do it", "subagent_type": "searcher"}),
+                &ToolContext::new(dir.path()),
+            )
+            .await;
+        std::env::remove_var("KODE_CONFIG_DIR");
+
+        assert!(result.is_err());
+        assert_eq!(budget.summary().agents[0].status, SubAgentStatus::Failed);
+    }
+}
@@ -0,0 +1,539 @@
+//! TodoWrite / TodoRead 工具
+//!
+//! 待办列表按 Agent 隔离，通过 [`storage`] 保存在 `${sessionId}-agent-${agentId}.json` 中。
+//! 写入后由文件新鲜度服务监控该文件，外部修改会在读取时和下一轮提醒中指出。
+
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::agent::storage;
+use kode_core::query::ReminderSource;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+
+/// 待办列表连续多少轮未更新后提醒
+pub const STALE_AFTER_TURNS: usize = 10;
+
+const WRITE_DESCRIPTION: &str = "Create and manage a structured todo list for the current task. \
+Send the complete list every time: items not included are removed. Use it for multi-step work, \
+keep exactly one item in_progress while working, and mark items completed as soon as they are done.";
+
+const READ_DESCRIPTION: &str = "Read the current todo list. Use it to check progress before \
+deciding what to work on next.";
+
+/// 待办状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    /// 未开始
+    Pending,
+    /// 进行中
+    InProgress,
+    /// 已完成
+    Completed,
+}
+
+/// 待办优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoPriority {
+    /// 高
+    High,
+    /// 中
+    #[default]
+    Medium,
+    /// 低
+    Low,
+}
+
+/// 待办事项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    /// 事项 ID
+    pub id: String,
+    /// 事项内容
+    pub content: String,
+    /// 状态
+    pub status: TodoStatus,
+    /// 优先级
+    #[serde(default)]
+    pub priority: TodoPriority,
+}
+
+impl fmt::Display for TodoItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.status {
+            TodoStatus::Pending => "[ ]",
+            TodoStatus::InProgress => "[~]",
+            TodoStatus::Completed => "[x]",
+        };
+        let priority = match self.priority {
+            TodoPriority::High => "high",
+            TodoPriority::Medium => "medium",
+            TodoPriority::Low => "low",
+        };
+        write!(f, "{} {} ({}) {}", mark, self.id, priority, self.content)
+    }
+}
+
+/// 校验待办列表
+///
+/// ID 和内容不能为空，ID 不能重复，最多一个事项处于进行中。
+pub fn validate_todos(todos: &[TodoItem]) -> Result<()> {
+    let mut ids = HashSet::new();
+    for todo in todos {
+        if todo.id.trim().is_empty() {
+            bail!("Todo id must not be empty");
+        }
+        if todo.content.trim().is_empty() {
+            bail!("Todo '{}' has empty content", todo.id);
+        }
+        if !ids.insert(todo.id.as_str()) {
+            bail!("Duplicate todo id '{}'", todo.id);
+        }
+    }
+
+    let in_progress: Vec<&str> = todos
+        .iter()
+        .filter(|t| t.status == TodoStatus::InProgress)
+        .map(|t| t.id.as_str())
+        .collect();
+    if in_progress.len() > 1 {
+        bail!(
+            "Only one todo can be in_progress at a time (found: {})",
+            in_progress.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// 读取 Agent 的待办列表
+pub async fn read_todos(agent_id: &str) -> Result<Vec<TodoItem>> {
+    Ok(storage::read_agent_data(agent_id)
+        .await
+        .context("Failed to read todo list")?
+        .unwrap_or_default())
+}
+
+/// 把待办列表渲染为文本
+fn render(todos: &[TodoItem]) -> String {
+    if todos.is_empty() {
+        return "The todo list is empty.".to_string();
+    }
+    todos
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 开始监控当前 Agent 的待办文件
+fn watch_todo_file(context: &ToolContext) -> Result<()> {
+    let path = storage::get_agent_file_path(&context.agent_id)?;
+    context
+        .freshness()
+        .start_watching_todo_file(&context.agent_id, &path.to_string_lossy());
+    Ok(())
+}
+
+/// TodoWrite 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoWriteParams {
+    /// 完整的待办列表
+    pub todos: Vec<TodoItem>,
+}
+
+/// TodoWrite 工具
+#[derive(Debug, Default)]
+pub struct TodoWriteTool;
+
+impl TodoWriteTool {
+    /// 创建新的 TodoWrite 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for TodoWriteTool {
+    fn name(&self) -> &str {
+        "TodoWrite"
+    }
+
+    fn description(&self) -> &str {
+        WRITE_DESCRIPTION
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: WRITE_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "todos": {
+                        "type": "array",
+                        "description": "The complete, updated todo list",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "id": {"type": "string"},
+                                "content": {"type": "string", "minLength": 1},
+                                "status": {
+                                    "type": "string",
+                                    "enum": ["pending", "in_progress", "completed"]
+                                },
+                                "priority": {
+                                    "type": "string",
+                                    "enum": ["high", "medium", "low"]
+                                }
+                            },
+                            "required": ["id", "content", "status"]
+                        }
+                    }
+                },
+                "required": ["todos"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: TodoWriteParams =
+            serde_json::from_value(params).context("Invalid TodoWrite parameters")?;
+        validate_todos(&params.todos)?;
+
+        storage::write_agent_data(&context.agent_id, &params.todos)
+            .await
+            .context("Failed to save todo list")?;
+        // 重新记录文件状态，自己的写入不算外部修改
+        watch_todo_file(context)?;
+
+        let completed = params
+            .todos
+            .iter()
+            .filter(|t| t.status == TodoStatus::Completed)
+            .count();
+        Ok(ToolResult::new(format!(
+            "Todo list updated ({}/{} completed):\n{}",
+            completed,
+            params.todos.len(),
+            render(&params.todos)
+        )))
+    }
+}
+
+/// TodoRead 工具
+#[derive(Debug, Default)]
+pub struct TodoReadTool;
+
+impl TodoReadTool {
+    /// 创建新的 TodoRead 工具
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for TodoReadTool {
+    fn name(&self) -> &str {
+        "TodoRead"
+    }
+
+    fn description(&self) -> &str {
+        READ_DESCRIPTION
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: READ_DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        }
+    }
+
+    async fn execute(&self, _params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let external_edit = context.freshness().check_todo_file(&context.agent_id);
+        let todos = read_todos(&context.agent_id).await?;
+        watch_todo_file(context)?;
+
+        let mut output = render(&todos);
+        if let Some(note) = external_edit {
+            output = format!("{}\n\n{}", note, output);
+        }
+        Ok(ToolResult::new(output))
+    }
+}
+
+/// 待办列表提醒
+///
+/// 作为查询循环的 [`ReminderSource`]：待办列表被外部修改，或仍有未完成事项但连续
+/// [`STALE_AFTER_TURNS`] 轮未更新时，向上下文插入提醒。
+pub struct TodoReminders {
+    context: ToolContext,
+    state: Mutex<StaleState>,
+}
+
+#[derive(Default)]
+struct StaleState {
+    last_content: Option<String>,
+    idle_turns: usize,
+}
+
+impl TodoReminders {
+    /// 为工具上下文中的 Agent 创建提醒
+    pub fn new(context: ToolContext) -> Self {
+        Self {
+            context,
+            state: Mutex::new(StaleState::default()),
+        }
+    }
+
+    fn stale_reminder(&self, path: &str) -> Option<String> {
+        let content = std::fs::read_to_string(path).ok();
+        let mut state = self.state.lock().unwrap_or_else(|p| p.into_inner());
+        if content != state.last_content {
+            state.last_content = content;
+            state.idle_turns = 0;
+            return None;
+        }
+        state.idle_turns += 1;
+        if state.idle_turns < STALE_AFTER_TURNS {
+            return None;
+        }
+
+        // 只在有未完成事项时提醒，提醒后重新计数
+        let todos: Vec<TodoItem> = content
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let unfinished: Vec<&TodoItem> = todos
+            .iter()
+            .filter(|t| t.status != TodoStatus::Completed)
+            .collect();
+        if unfinished.is_empty() {
+            return None;
+        }
+        state.idle_turns = 0;
+        Some(format!(
+            "The todo list has not been updated for {} turns. If you have made progress, update it \
+with TodoWrite. Unfinished items:\n{}",
+            STALE_AFTER_TURNS,
+            unfinished
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+}
+
+impl ReminderSource for TodoReminders {
+    fn reminders(&self) -> Vec<String> {
+        let Ok(path) = storage::get_agent_file_path(&self.context.agent_id) else {
+            return Vec::new();
+        };
+        let path = path.to_string_lossy();
+        let external_edit = self
+            .context
+            .freshness()
+            .check_todo_file(&self.context.agent_id);
+        external_edit
+            .into_iter()
+            .chain(self.stale_reminder(&path))
+            .map(|text| format!("<system-reminder>\n{}\n</system-reminder>", text))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn item(id: &str, status: TodoStatus) -> TodoItem {
+        TodoItem {
+            id: id.to_string(),
+            content: format!("task {}", id),
+            status,
+            priority: TodoPriority::default(),
+        }
+    }
+
+    /// 设置 KODE_CONFIG_DIR 的测试环境，离开作用域时清理
+    struct Env {
+        dir: TempDir,
+    }
+
+    impl Env {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            std::env::set_var("KODE_CONFIG_DIR", dir.path());
+            Self { dir }
+        }
+
+        fn context(&self, agent_id: &str) -> ToolContext {
+            ToolContext::new(self.dir.path()).with_agent_id(agent_id)
+        }
+    }
+
+    impl Drop for Env {
+        fn drop(&mut self) {
+            std::env::remove_var("KODE_CONFIG_DIR");
+        }
+    }
+
+    async fn write(context: &ToolContext, todos: Value) -> Result<ToolResult> {
+        TodoWriteTool::new()
+            .execute(json!({ "todos": todos }), context)
+            .await
+    }
+
+    #[test]
+    fn test_validate_todos() {
+        use TodoStatus::*;
+        assert!(validate_todos(&[item("1", InProgress), item("2", Pending)]).is_ok());
+
+        let err = validate_todos(&[item("1", InProgress), item("2", InProgress)]).unwrap_err();
+        assert!(err.to_string().contains("Only one todo can be in_progress"));
+        assert!(validate_todos(&[item("1", Pending), item("1", Completed)]).is_err());
+
+        let mut empty = item("1", Pending);
+        empty.content = " ".to_string();
+        assert!(validate_todos(&[empty]).is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_write_and_read_per_agent() {
+        let env = Env::new();
+        let main = env.context("main");
+        let child = env.context("child");
+
+        let result = write(
+            &main,
+            json!([
+                {"id": "1", "content": "Write parser", "status": "completed", "priority": "high"},
+                {"id": "2", "content": "Add tests", "status": "in_progress"}
+            ]),
+        )
+        .await
+        .unwrap();
+        assert!(result
+            .output
+            .starts_with("Todo list updated (1/2 completed)"));
+
+        let todos = read_todos("main").await.unwrap();
+        assert_eq!(todos[1].status, TodoStatus::InProgress);
+        assert_eq!(todos[1].priority, TodoPriority::Medium);
+
+        let read = TodoReadTool::new().execute(json!({}), &main).await.unwrap();
+        assert_eq!(
+            read.output,
+            "[x] 1 (high) Write parser\n[~] 2 (medium) Add tests"
+        );
+        // 其他 Agent 的列表互不影响
+        let read = TodoReadTool::new()
+            .execute(json!({}), &child)
+            .await
+            .unwrap();
+        assert_eq!(read.output, "The todo list is empty.");
+
+        let err = write(
+            &main,
+            json!([
+                {"id": "1", "content": "a", "status": "in_progress"},
+                {"id": "2", "content": "b", "status": "in_progress"}
+            ]),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("in_progress"));
+        assert_eq!(read_todos("main").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_external_edit_is_reported() {
+        let env = Env::new();
+        let context = env.context("main");
+        write(
+            &context,
+            json!([{"id": "1", "content": "a", "status": "pending"}]),
+        )
+        .await
+        .unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        let path = storage::get_agent_file_path("main").unwrap();
+        std::fs::write(
+            &path,
+            r#"[{"id": "9", "content": "edited", "status": "pending"}]"#,
+        )
+        .unwrap();
+
+        let read = TodoReadTool::new()
+            .execute(json!({}), &context)
+            .await
+            .unwrap();
+        assert!(read.output.contains("modified externally"));
+        assert!(read.output.ends_with("[ ] 9 (medium) edited"));
+
+        // 已经提醒过的修改不再重复
+        let read = TodoReadTool::new()
+            .execute(json!({}), &context)
+            .await
+            .unwrap();
+        assert!(!read.output.contains("modified externally"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reminders() {
+        let env = Env::new();
+        let context = env.context("main");
+        let reminders = TodoReminders::new(context.clone());
+        write(
+            &context,
+            json!([{"id": "1", "content": "Refactor", "status": "in_progress"}]),
+        )
+        .await
+        .unwrap();
+
+        let stale: Vec<Vec<String>> = (0..=STALE_AFTER_TURNS)
+            .map(|_| reminders.reminders())
+            .collect();
+        assert!(stale[..STALE_AFTER_TURNS].iter().all(Vec::is_empty));
+        let last = &stale[STALE_AFTER_TURNS];
+        assert_eq!(last.len(), 1);
+        assert!(last[0].starts_with("<system-reminder>"));
+        assert!(last[0].contains("[~] 1 (medium) Refactor"));
+
+        // 全部完成后不再提醒
+        write(
+            &context,
+            json!([{"id": "1", "content": "Refactor", "status": "completed"}]),
+        )
+        .await
+        .unwrap();
+        assert!((0..=STALE_AFTER_TURNS).all(|_| reminders.reminders().is_empty()));
+
+        // 外部修改通过新鲜度服务提醒
+        std::thread::sleep(Duration::from_millis(20));
+        let path = storage::get_agent_file_path("main").unwrap();
+        std::fs::write(&path, "[]").unwrap();
+        let external = reminders.reminders();
+        assert_eq!(external.len(), 1);
+        assert!(external[0].contains("modified externally"));
+    }
+}
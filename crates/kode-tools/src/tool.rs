@@ -1,8 +1,20 @@
 //! Tool trait 定义
 
+use crate::shell::ShellState;
 use anyhow::Result;
 use async_trait::async_trait;
+use kode_core::agent::storage;
+use kode_core::config::ProjectConfig;
+use kode_core::context::FileFreshnessService;
+use kode_core::message::types::ProgressMessage;
+use kode_core::message::{ImageBlock, Message};
+use kode_core::permission::{AccessKind, PermissionEngine, PermissionRequest, PermissionRules};
 use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 
 /// Tool trait
 #[async_trait]
@@ -23,6 +35,31 @@ pub trait Tool: Send + Sync {
     fn requires_permission(&self) -> bool {
         false
     }
+
+    /// 是否为只读工具（不修改文件、不执行有副作用的操作）
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// 是否可以与同一轮中的其他工具调用并发执行
+    ///
+    /// 默认只读工具可以并发执行。
+    fn is_concurrency_safe(&self) -> bool {
+        self.is_read_only()
+    }
+
+    /// 构造本次调用的权限请求
+    ///
+    /// 默认按 [`Tool::requires_permission`] 区分只读和执行；
+    /// 需要按命令或路径匹配权限规则的工具应覆盖此方法。
+    fn permission_request(&self, _params: &Value, _context: &ToolContext) -> PermissionRequest {
+        let access = if self.requires_permission() {
+            AccessKind::Execute
+        } else {
+            AccessKind::ReadOnly
+        };
+        PermissionRequest::new(self.name(), access)
+    }
 }
 
 /// 工具 Schema
@@ -37,9 +74,152 @@ pub struct ToolSchema {
 }
 
 /// 工具上下文
+///
+/// 同一会话内的所有工具调用共享文件新鲜度服务、持久 shell 和权限引擎。
 #[derive(Debug, Clone)]
 pub struct ToolContext {
-    // TODO: 添加上下文字段
+    /// 工作目录（项目根目录）
+    pub working_dir: PathBuf,
+    /// 文件新鲜度服务
+    pub freshness: Arc<Mutex<FileFreshnessService>>,
+    /// 当前项目配置
+    pub project_config: Arc<ProjectConfig>,
+    /// 持久 shell 与后台任务
+    pub shell: Arc<ShellState>,
+    /// 取消令牌，用户中断时触发
+    pub cancel: CancellationToken,
+    /// 权限引擎
+    pub permissions: Arc<PermissionEngine>,
+    /// 当前 Agent 的 ID，用于隔离 [`storage`] 中的 Agent 数据
+    pub agent_id: String,
+    /// 当前工具调用的进度通道（由编排器在执行时设置）
+    pub progress: Option<ProgressSink>,
+}
+
+impl ToolContext {
+    /// 创建新的工具上下文
+    pub fn new(working_dir: impl Into<PathBuf>) -> Self {
+        let working_dir = working_dir.into();
+        Self {
+            permissions: Arc::new(PermissionEngine::new(
+                working_dir.clone(),
+                PermissionRules::default(),
+            )),
+            working_dir,
+            freshness: Arc::new(Mutex::new(FileFreshnessService::new())),
+            project_config: Arc::new(ProjectConfig::default()),
+            shell: Arc::new(ShellState::new()),
+            cancel: CancellationToken::new(),
+            agent_id: storage::get_default_agent_id().to_string(),
+            progress: None,
+        }
+    }
+
+    /// 设置项目配置
+    pub fn with_project_config(mut self, project_config: ProjectConfig) -> Self {
+        self.project_config = Arc::new(project_config);
+        self
+    }
+
+    /// 设置共享的文件新鲜度服务
+    pub fn with_freshness(mut self, freshness: Arc<Mutex<FileFreshnessService>>) -> Self {
+        self.freshness = freshness;
+        self
+    }
+
+    /// 设置取消令牌
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 设置共享的权限引擎
+    pub fn with_permissions(mut self, permissions: Arc<PermissionEngine>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// 设置当前 Agent 的 ID
+    pub fn with_agent_id(mut self, agent_id: impl Into<String>) -> Self {
+        self.agent_id = agent_id.into();
+        self
+    }
+
+    /// 设置当前工具调用的进度通道
+    pub fn with_progress(mut self, progress: ProgressSink) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// 报告当前工具调用的进度；没有进度通道时忽略
+    pub fn report_progress(&self, message: &Message) {
+        if let Some(progress) = &self.progress {
+            progress.report(message);
+        }
+    }
+
+    /// 获取文件新鲜度服务
+    ///
+    /// 锁被毒化时仍返回内部数据（新鲜度记录不会因 panic 而失效）。
+    pub fn freshness(&self) -> MutexGuard<'_, FileFreshnessService> {
+        self.freshness
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 将工具参数中的路径解析为绝对路径
+    ///
+    /// 相对路径基于 `working_dir` 解析。
+    pub fn resolve_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.working_dir.join(path)
+        }
+    }
+}
+
+/// 单个工具调用的进度通道
+#[derive(Debug, Clone)]
+pub struct ProgressSink {
+    sender: UnboundedSender<ProgressMessage>,
+    tool_use_id: String,
+    sibling_tool_use_ids: HashSet<String>,
+}
+
+impl ProgressSink {
+    /// 创建进度通道
+    ///
+    /// `sibling_tool_use_ids` 为同一批并发执行的工具调用 ID。
+    pub fn new(
+        sender: UnboundedSender<ProgressMessage>,
+        tool_use_id: impl Into<String>,
+        sibling_tool_use_ids: HashSet<String>,
+    ) -> Self {
+        Self {
+            sender,
+            tool_use_id: tool_use_id.into(),
+            sibling_tool_use_ids,
+        }
+    }
+
+    /// 工具调用 ID
+    pub fn tool_use_id(&self) -> &str {
+        &self.tool_use_id
+    }
+
+    /// 发送一条进度消息
+    pub fn report(&self, message: &Message) {
+        // 接收端已关闭时忽略进度
+        let _ = self.sender.send(ProgressMessage::new(
+            message,
+            self.tool_use_id.clone(),
+            &self.sibling_tool_use_ids,
+            &[],
+            &[],
+        ));
+    }
 }
 
 /// 工具执行结果
@@ -47,4 +227,22 @@ pub struct ToolContext {
 pub struct ToolResult {
     /// 输出内容
     pub output: String,
+    /// 随结果返回给模型的图片
+    pub images: Vec<ImageBlock>,
+}
+
+impl ToolResult {
+    /// 创建新的工具结果
+    pub fn new(output: impl Into<String>) -> Self {
+        Self {
+            output: output.into(),
+            images: Vec::new(),
+        }
+    }
+
+    /// 附加图片
+    pub fn with_image(mut self, image: ImageBlock) -> Self {
+        self.images.push(image);
+        self
+    }
 }
@@ -0,0 +1,1490 @@
+//! WebFetch 工具
+//!
+//! 通过 HTTP(S) 获取网页，把 HTML 转换为 markdown 返回给模型；
+//! 提供 `prompt` 且配置了 `quick` 模型时，用该模型按提示总结页面内容。
+//!
+//! 每次获取都需要权限，规则按目标主机匹配（`WebFetch(domain:example.com)`）。
+//! 默认拒绝访问回环、链路本地和私有网络地址，域名解析结果同样受此限制。
+//!
+//! 请求遵守全局配置中的代理，限制响应大小和总耗时（包括所有重定向）。重定向由工具自己处理：
+//! 同源（协议、主机、端口均相同）的重定向直接跟随，跳转到其他源需要通过权限引擎获得批准。
+//! 获取到的页面缓存几分钟，重复访问同一 URL 不会再次请求。
+
+use crate::{Tool, ToolContext, ToolResult, ToolSchema};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use kode_core::config::types::ModelPointers;
+use kode_core::config::GlobalConfig;
+use kode_core::message::Message;
+use kode_core::model::ModelProvider;
+use kode_core::permission::{AccessKind, PermissionDecision, PermissionRequest, PermissionTarget};
+use regex::Regex;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+use reqwest::{redirect, Client, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// WebFetch 工具名
+pub const WEB_FETCH_TOOL_NAME: &str = "WebFetch";
+
+/// 默认响应大小上限（字节）
+pub const DEFAULT_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// 默认总超时（包括所有重定向和读取响应体）
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 默认缓存有效期
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// 最多跟随的重定向次数
+pub const MAX_REDIRECTS: usize = 10;
+
+/// 返回给模型的最大内容长度（字符）
+const MAX_CONTENT_CHARS: usize = 100_000;
+
+/// 总结时的最大输出 token 数
+const SUMMARY_MAX_TOKENS: usize = 2048;
+
+const DESCRIPTION: &str = "Fetch a web page and return its content as markdown. HTML is \
+converted to markdown; plain text and JSON are returned as-is. Pass `prompt` to have a fast \
+model extract just the information you need from the page. Redirects to a different origin are \
+not followed without approval. Local and private network addresses cannot be fetched. Results \
+are cached for a few minutes.";
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You extract information from web pages. Answer the \
+request using only the page content provided. Be concise and quote relevant passages verbatim \
+when precision matters.";
+
+/// WebFetch 参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebFetchParams {
+    /// 要获取的 URL
+    pub url: String,
+    /// 对页面内容的提问（需要配置 `quick` 模型）
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// 获取并转换后的页面
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedPage {
+    /// 跟随重定向后的最终 URL
+    pub url: String,
+    /// 转换后的内容
+    pub content: String,
+}
+
+/// 重定向到其他源且未获批准
+#[derive(Debug)]
+struct CrossOriginRedirect {
+    from: Url,
+    to: Url,
+}
+
+/// WebFetch 工具
+pub struct WebFetchTool {
+    proxy: Option<String>,
+    max_bytes: usize,
+    timeout: Duration,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, FetchedPage)>>,
+    summarizer: Option<(Arc<dyn ModelProvider>, Option<String>)>,
+    allow_private_network: bool,
+}
+
+impl Default for WebFetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebFetchTool {
+    /// 创建新的 WebFetch 工具
+    pub fn new() -> Self {
+        Self {
+            proxy: None,
+            max_bytes: DEFAULT_MAX_BYTES,
+            timeout: DEFAULT_TIMEOUT,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            cache: Mutex::new(HashMap::new()),
+            summarizer: None,
+            allow_private_network: false,
+        }
+    }
+
+    /// 使用全局配置中的代理
+    pub fn with_global_config(self, config: &GlobalConfig) -> Self {
+        match &config.proxy {
+            Some(proxy) => self.with_proxy(proxy),
+            None => self,
+        }
+    }
+
+    /// 设置代理
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 设置响应大小上限（字节）
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// 设置总超时（包括所有重定向和读取响应体）
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置缓存有效期
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// 使用模型指针中的 `quick` 模型总结页面
+    pub fn with_summarizer(
+        mut self,
+        models: Arc<dyn ModelProvider>,
+        pointers: &ModelPointers,
+    ) -> Self {
+        self.summarizer = Some((models, pointers.quick.clone()));
+        self
+    }
+
+    /// 是否允许访问回环、链路本地和私有网络地址（默认不允许）
+    pub fn with_private_network(mut self, allow: bool) -> Self {
+        self.allow_private_network = allow;
+        self
+    }
+
+    fn client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(self.timeout)
+            .user_agent(concat!("kode/", env!("CARGO_PKG_VERSION")));
+        let mut proxy_host = None;
+        if let Some(proxy) = &self.proxy {
+            proxy_host = Url::parse(proxy)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?,
+            );
+        }
+        if !self.allow_private_network {
+            builder = builder.dns_resolver(Arc::new(PublicResolver { proxy_host }));
+        }
+        builder.build().context("Failed to build HTTP client")
+    }
+
+    /// 拒绝指向本机或私有网络的 IP 地址和 `localhost`
+    ///
+    /// 其他域名在解析时由 [`PublicResolver`] 检查。
+    fn check_destination(&self, url: &Url) -> Result<()> {
+        if self.allow_private_network {
+            return Ok(());
+        }
+        let host = url.host_str().unwrap_or_default();
+        let blocked = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+            Ok(ip) => !is_public_ip(ip),
+            Err(_) => {
+                let domain = host.trim_end_matches('.').to_ascii_lowercase();
+                domain == "localhost" || domain.ends_with(".localhost")
+            }
+        };
+        if blocked {
+            bail!(
+                "Refusing to fetch {}: local and private network addresses are not allowed",
+                url
+            );
+        }
+        Ok(())
+    }
+
+    /// 在总截止时间前等待 `future` 完成
+    async fn before<T>(
+        &self,
+        deadline: tokio::time::Instant,
+        url: &Url,
+        future: impl Future<Output = T>,
+    ) -> Result<T> {
+        tokio::time::timeout_at(deadline, future)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Failed to fetch {}: timed out after {} ms",
+                    url,
+                    self.timeout.as_millis()
+                )
+            })
+    }
+
+    fn cached(&self, url: &str) -> Option<FetchedPage> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(url)
+            .filter(|(at, _)| at.elapsed() < self.cache_ttl)
+            .map(|(_, page)| page.clone())
+    }
+
+    fn store(&self, url: &str, page: &FetchedPage) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < self.cache_ttl);
+        cache.insert(url.to_string(), (Instant::now(), page.clone()));
+    }
+
+    /// 获取页面，跟随同源的重定向
+    ///
+    /// 跳转到其他源时询问权限，未获批准返回 [`CrossOriginRedirect`]。
+    /// 所有请求和读取共用一个总截止时间，等待用户批准的时间不计入。
+    async fn fetch(
+        &self,
+        url: Url,
+        context: &ToolContext,
+    ) -> Result<std::result::Result<FetchedPage, CrossOriginRedirect>> {
+        let client = self.client()?;
+        let mut deadline = tokio::time::Instant::now() + self.timeout;
+        let mut current = url;
+        let mut redirects = 0;
+        let mut response = loop {
+            self.check_destination(&current)?;
+            let response = self
+                .before(deadline, &current, client.get(current.clone()).send())
+                .await?
+                .with_context(|| format!("Failed to fetch {}", current))?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .with_context(|| format!("Redirect from {} without a Location header", current))?;
+            let next = current
+                .join(location)
+                .with_context(|| format!("Invalid redirect location {}", location))?;
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                bail!("Too many redirects fetching {}", current);
+            }
+            if next.origin() != current.origin() {
+                let asked = tokio::time::Instant::now();
+                let approved = self.approve_redirect(&current, &next, context).await?;
+                deadline += asked.elapsed();
+                if !approved {
+                    return Ok(Err(CrossOriginRedirect {
+                        from: current,
+                        to: next,
+                    }));
+                }
+            }
+            current = next;
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            bail!("HTTP {} fetching {}", status, current);
+        }
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > self.max_bytes)
+        {
+            bail!(
+                "Response from {} exceeds the {} byte limit",
+                current,
+                self.max_bytes
+            );
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        let mut body = Vec::new();
+        while let Some(chunk) = self
+            .before(deadline, &current, response.chunk())
+            .await?
+            .with_context(|| format!("Failed to read {}", current))?
+        {
+            body.extend_from_slice(&chunk);
+            if body.len() > self.max_bytes {
+                bail!(
+                    "Response from {} exceeds the {} byte limit",
+                    current,
+                    self.max_bytes
+                );
+            }
+        }
+        let text = String::from_utf8_lossy(&body);
+
+        let content = if content_type.contains("html")
+            || (content_type.is_empty() && text.trim_start().starts_with('<'))
+        {
+            html_to_markdown(&text)
+        } else if content_type.is_empty()
+            || content_type.starts_with("text/")
+            || content_type.contains("json")
+            || content_type.contains("xml")
+        {
+            text.into_owned()
+        } else {
+            bail!("Unsupported content type `{}` at {}", content_type, current);
+        };
+        Ok(Ok(FetchedPage {
+            url: current.to_string(),
+            content,
+        }))
+    }
+
+    async fn approve_redirect(&self, from: &Url, to: &Url, context: &ToolContext) -> Result<bool> {
+        let mut request = PermissionRequest::new(WEB_FETCH_TOOL_NAME, AccessKind::Execute)
+            .with_reason(format!("{} redirects to a different origin: {}", from, to));
+        if let Some(domain) = to.host_str() {
+            request = request.with_target(PermissionTarget::Domain(domain.to_ascii_lowercase()));
+        }
+        Ok(matches!(
+            context.permissions.check(&request).await?,
+            PermissionDecision::Allow
+        ))
+    }
+
+    async fn summarize(&self, page: &FetchedPage, prompt: &str) -> Result<Option<String>> {
+        let Some((models, model)) = &self.summarizer else {
+            return Ok(None);
+        };
+        let adapter = models.adapter(model.as_deref())?;
+        let request = format!(
+            "Web page content from {}:\n---\n{}\n---\n\n{}",
+            page.url,
+            truncate(&page.content),
+            prompt
+        );
+        let response = adapter
+            .send_message(
+                vec![Message::user(request)],
+                Some(SUMMARY_SYSTEM_PROMPT.to_string()),
+                SUMMARY_MAX_TOKENS,
+            )
+            .await
+            .context("Failed to summarize the page")?;
+        Ok(Some(response.content))
+    }
+}
+
+/// 只解析到公网地址的 DNS 解析器
+///
+/// 在连接时检查解析结果，跟随重定向和 DNS 重绑定都无法绕过。
+/// 代理主机本身不受限制（通常就在本机或内网）。
+struct PublicResolver {
+    proxy_host: Option<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let is_proxy = self.proxy_host.as_deref() == Some(host.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if !is_proxy {
+                if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+                    return Err(format!(
+                        "{} resolves to {}, a local or private network address",
+                        host,
+                        addr.ip()
+                    )
+                    .into());
+                }
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 是否为公网地址（排除回环、链路本地、私有、共享、组播等地址）
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // 运营商级 NAT 共享地址 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // 唯一本地地址 fc00::/7
+                || first & 0xfe00 == 0xfc00
+                // 链路本地地址 fe80::/10
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// 截断过长的内容
+fn truncate(content: &str) -> String {
+    match content.char_indices().nth(MAX_CONTENT_CHARS) {
+        Some((end, _)) => format!("{}\n\n[Content truncated]", &content[..end]),
+        None => content.to_string(),
+    }
+}
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        WEB_FETCH_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        DESCRIPTION
+    }
+
+    /// 获取页面会访问网络，需要按目标主机获得批准
+    fn requires_permission(&self) -> bool {
+        true
+    }
+
+    /// 不同的获取之间互不影响
+    fn is_concurrency_safe(&self) -> bool {
+        true
+    }
+
+    fn permission_request(&self, params: &Value, _context: &ToolContext) -> PermissionRequest {
+        let request = PermissionRequest::new(self.name(), AccessKind::Execute);
+        let domain = params
+            .get("url")
+            .and_then(Value::as_str)
+            .and_then(|url| Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        match domain {
+            Some(domain) => request.with_target(PermissionTarget::Domain(domain)),
+            None => request,
+        }
+    }
+
+    fn schema(&self) -> ToolSchema {
+        ToolSchema {
+            name: self.name().to_string(),
+            description: DESCRIPTION.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The http(s) URL to fetch"
+                    },
+                    "prompt": {
+                        "type": "string",
+                        "description": "Optional question to answer from the page content"
+                    }
+                },
+                "required": ["url"]
+            }),
+        }
+    }
+
+    async fn execute(&self, params: Value, context: &ToolContext) -> Result<ToolResult> {
+        let params: WebFetchParams =
+            serde_json::from_value(params).context("Invalid WebFetch parameters")?;
+        let url = Url::parse(&params.url).with_context(|| format!("Invalid URL {}", params.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Only http and https URLs are supported: {}", params.url);
+        }
+
+        let page = match self.cached(url.as_str()) {
+            Some(page) => page,
+            None => {
+                let fetched = tokio::select! {
+                    _ = context.cancel.cancelled() => bail!("Fetch of {} was cancelled", url),
+                    fetched = self.fetch(url.clone(), context) => fetched?,
+                };
+                match fetched {
+                    Ok(page) => {
+                        self.store(url.as_str(), &page);
+                        page
+                    }
+                    Err(redirect) => {
+                        return Ok(ToolResult::new(format!(
+                            "{} redirected to a different origin: {}\n\
+Call WebFetch with the new URL if you want to follow it.",
+                            redirect.from, redirect.to
+                        )));
+                    }
+                }
+            }
+        };
+
+        if let Some(prompt) = &params.prompt {
+            let summary = tokio::select! {
+                _ = context.cancel.cancelled() => bail!("Fetch of {} was cancelled", url),
+                summary = self.summarize(&page, prompt) => summary?,
+            };
+            if let Some(summary) = summary {
+                return Ok(ToolResult::new(summary));
+            }
+        }
+        Ok(ToolResult::new(format!(
+            "URL: {}\n\n{}",
+            page.url,
+            truncate(&page.content)
+        )))
+    }
+}
+
+/// 把 HTML 转换为 markdown
+///
+/// 支持标题、段落、列表、链接、图片、强调、行内代码和代码块、引用、分隔线；
+/// 忽略 `<head>`、`<script>`、`<style>` 等不可见内容，其他标签只保留文本。
+pub fn html_to_markdown(html: &str) -> String {
+    let mut converter = HtmlConverter::default();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            converter.text(rest);
+            break;
+        };
+        converter.text(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = tag_end(rest) else {
+            converter.text(rest);
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        if !closing
+            && matches!(
+                name.as_str(),
+                "head" | "script" | "style" | "noscript" | "template" | "svg"
+            )
+        {
+            // 跳过整个元素
+            let close = format!("</{}", name);
+            rest = find_ignore_case(rest, &close)
+                .and_then(|at| rest[at..].find('>').map(|end| &rest[at + end + 1..]))
+                .unwrap_or("");
+            continue;
+        }
+        if closing {
+            converter.close(&name);
+        } else {
+            converter.open(&name, &tag[name_end..]);
+        }
+    }
+    converter.finish()
+}
+
+/// 标签结束的 `>` 位置（跳过引号中的内容）
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// 读取标签属性
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let re = ATTR.get_or_init(|| {
+        Regex::new(r#"([A-Za-z_:][-A-Za-z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .unwrap()
+    });
+    re.captures_iter(attrs)
+        .find(|caps| caps[1].eq_ignore_ascii_case(name))
+        .and_then(|caps| caps.get(2).or(caps.get(3)).or(caps.get(4)))
+        .map(|value| decode_entities(value.as_str()))
+}
+
+/// 解码常见的 HTML 实体
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let entity = &rest[1..end + 1];
+                let c = match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => entity
+                        .strip_prefix("#x")
+                        .or_else(|| entity.strip_prefix("#X"))
+                        .map(|hex| u32::from_str_radix(hex, 16))
+                        .or_else(|| entity.strip_prefix('#').map(str::parse))
+                        .and_then(|code| code.ok())
+                        .and_then(char::from_u32),
+                };
+                c.map(|c| (c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// HTML 转 markdown 的状态
+#[derive(Default)]
+struct HtmlConverter {
+    out: String,
+    /// 列表栈：`None` 为无序列表，`Some(n)` 为有序列表的下一个序号
+    lists: Vec<Option<usize>>,
+    /// 未闭合的链接：目标和链接文本的起始位置
+    links: Vec<(Option<String>, usize)>,
+    pre: usize,
+    quote: usize,
+}
+
+impl HtmlConverter {
+    fn text(&mut self, raw: &str) {
+        let text = decode_entities(raw);
+        if self.pre > 0 {
+            self.out.push_str(&text);
+            return;
+        }
+        let mut words = text.split_whitespace().peekable();
+        if words.peek().is_none() {
+            if !text.is_empty() && !self.at_line_start() && !self.out.ends_with(' ') {
+                self.out.push(' ');
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace)
+            && !self.at_line_start()
+            && !self.out.ends_with(' ')
+        {
+            self.out.push(' ');
+        }
+        let words: Vec<&str> = words.collect();
+        self.out.push_str(&words.join(" "));
+        if text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n') || self.out.ends_with("> ")
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+    }
+
+    fn newline(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.quote_prefix();
+    }
+
+    fn blank_line(&mut self) {
+        self.trim_trailing_spaces();
+        if self.out.is_empty() {
+            return;
+        }
+        while !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.quote_prefix();
+    }
+
+    fn quote_prefix(&mut self) {
+        for _ in 0..self.quote {
+            self.out.push_str("> ");
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.blank_line();
+                let level = name[1..].parse().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            }
+            "p" | "div" | "section" | "article" | "header" | "footer" | "main" | "nav"
+            | "table" | "form" | "figure" | "dl" => self.blank_line(),
+            "tr" | "dt" | "dd" => self.newline(),
+            "td" | "th" if !self.at_line_start() => self.out.push_str(" | "),
+            "br" => {
+                self.trim_trailing_spaces();
+                self.out.push('\n');
+                self.quote_prefix();
+            }
+            "hr" => {
+                self.blank_line();
+                self.out.push_str("---");
+                self.blank_line();
+            }
+            "ul" => {
+                self.newline_or_blank();
+                self.lists.push(None);
+            }
+            "ol" => {
+                self.newline_or_blank();
+                let start = attribute(attrs, "start")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(1);
+                self.lists.push(Some(start));
+            }
+            "li" => {
+                self.newline();
+                let depth = self.lists.len().max(1);
+                self.out.push_str(&"  ".repeat(depth - 1));
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => self.out.push_str("- "),
+                }
+            }
+            "blockquote" => {
+                self.blank_line();
+                self.quote += 1;
+                self.out.push_str("> ");
+            }
+            "pre" => {
+                self.blank_line();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            }
+            "code" if self.pre == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('*'),
+            "a" => {
+                let href = attribute(attrs, "href").filter(|href| {
+                    !href.is_empty() && !href.starts_with('#') && !href.starts_with("javascript:")
+                });
+                self.links.push((href, self.out.len()));
+            }
+            "img" => {
+                let alt = attribute(attrs, "alt").unwrap_or_default();
+                if let Some(src) = attribute(attrs, "src") {
+                    self.out.push_str(&format!("![{}]({})", alt, src));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 嵌套列表只换行，顶层列表前空一行
+    fn newline_or_blank(&mut self) {
+        if self.lists.is_empty() {
+            self.blank_line();
+        } else {
+            self.newline();
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "div" | "section" | "article"
+            | "header" | "footer" | "main" | "nav" | "table" | "form" | "figure" | "dl" => {
+                self.blank_line()
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.newline_or_blank();
+            }
+            "blockquote" => {
+                self.quote = self.quote.saturating_sub(1);
+                self.blank_line();
+            }
+            "pre" if self.pre > 0 => {
+                self.pre -= 1;
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```");
+                self.blank_line();
+            }
+            "code" if self.pre == 0 => {
+                self.trim_trailing_spaces();
+                self.out.push('`');
+            }
+            "strong" | "b" => {
+                self.trim_trailing_spaces();
+                self.out.push_str("**");
+            }
+            "em" | "i" => {
+                self.trim_trailing_spaces();
+                self.out.push('*');
+            }
+            "a" => {
+                if let Some((Some(href), start)) = self.links.pop() {
+                    self.trim_trailing_spaces();
+                    let start = start.min(self.out.len());
+                    let text = self.out[start..].trim().to_string();
+                    if !text.is_empty() {
+                        self.out.truncate(start);
+                        self.out.push_str(&format!("[{}]({})", text, href));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 合并多余的空行（代码块内保持原样）
+    fn finish(self) -> String {
+        let mut result = String::with_capacity(self.out.len());
+        let mut blank = false;
+        let mut in_code = false;
+        for line in self.out.lines() {
+            if in_code {
+                result.push('\n');
+                result.push_str(line);
+                in_code = line != "```";
+                continue;
+            }
+            let line = line.trim_end();
+            if line.is_empty() || line == ">" {
+                blank = true;
+                continue;
+            }
+            if !result.is_empty() {
+                result.push_str(if blank { "\n\n" } else { "\n" });
+            }
+            blank = false;
+            result.push_str(line);
+            in_code = line == "```";
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kode_core::config::ProjectConfig;
+    use kode_core::error::Error;
+    use kode_core::model::{ModelAdapter, ModelResponse, StreamingResponse};
+    use kode_core::permission::{
+        PermissionAnswer, PermissionAsk, PermissionCheck, PermissionEngine, PermissionMode,
+        PermissionPrompt, PermissionRule, PermissionRules,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 本地 HTTP 服务器：按路径返回预设的响应，记录请求次数
+    struct TestServer {
+        base: String,
+        hits: Arc<AtomicUsize>,
+    }
+
+    type Route = (
+        &'static str,
+        &'static str,
+        Vec<(&'static str, String)>,
+        String,
+    );
+
+    async fn serve(routes: impl Fn(&str, &str) -> Route + Send + Sync + 'static) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let routes = Arc::new(routes);
+        let server_base = base.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let routes = routes.clone();
+                let base = server_base.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/");
+                    let (status, content_type, headers, body) = routes(path, &base);
+                    let mut response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        status,
+                        content_type,
+                        body.len()
+                    );
+                    for (name, value) in headers {
+                        response.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    response.push_str("\r\n");
+                    response.push_str(&body);
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        TestServer { base, hits }
+    }
+
+    fn site(path: &str, base: &str) -> Route {
+        let html = "text/html; charset=utf-8";
+        match path {
+            "/docs" => (
+                "200 OK",
+                html,
+                Vec::new(),
+                "<html><head><title>Docs</title><style>p{}</style></head>\
+<body><h1>Guide</h1><p>Read the <a href=\"/api\">API</a>.</p></body></html>"
+                    .to_string(),
+            ),
+            "/moved" => (
+                "301 Moved Permanently",
+                html,
+                vec![("Location", "/docs".to_string())],
+                String::new(),
+            ),
+            "/away" => (
+                "302 Found",
+                html,
+                vec![("Location", base.replace("127.0.0.1", "localhost") + "/docs")],
+                String::new(),
+            ),
+            "/big" => ("200 OK", "text/plain", Vec::new(), "x".repeat(4096)),
+            "/data" => (
+                "200 OK",
+                "application/json",
+                Vec::new(),
+                "{\"ok\":true}".to_string(),
+            ),
+            "/image" => ("200 OK", "image/png", Vec::new(), "PNG".to_string()),
+            _ => (
+                "404 Not Found",
+                "text/plain",
+                Vec::new(),
+                "missing".to_string(),
+            ),
+        }
+    }
+
+    struct AllowAll;
+
+    #[async_trait]
+    impl PermissionPrompt for AllowAll {
+        async fn ask(&self, _ask: &PermissionAsk) -> PermissionAnswer {
+            PermissionAnswer::AllowOnce
+        }
+    }
+
+    fn context(dir: &TempDir) -> ToolContext {
+        ToolContext::new(dir.path())
+    }
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<!DOCTYPE html><html><head><title>T</title><script>var x = "<p>";</script></head>
+<body>
+  <h2>Install &amp; run</h2>
+  <p>Use <code>cargo&nbsp;run</code> or <strong>read</strong> the
+     <a href="https://example.com/docs?a=1&amp;b=2">full docs</a>.</p>
+  <!-- hidden -->
+  <ul><li>One</li><li>Two<ol><li>Nested</li></ol></li></ul>
+  <pre><code>fn main() {
+
+    println!("&lt;hi&gt;");
+}</code></pre>
+  <blockquote>Quoted <em>text</em></blockquote>
+  <img src="/logo.png" alt="Logo"><hr>
+  <p>Line<br>break &#169; &#x263A;</p>
+</body></html>"#;
+        assert_eq!(
+            html_to_markdown(html),
+            "## Install & run\n\n\
+Use `cargo run` or **read** the [full docs](https://example.com/docs?a=1&b=2).\n\n\
+- One\n- Two\n  1. Nested\n\n\
+```\nfn main() {\n\n    println!(\"<hi>\");\n}\n```\n\n\
+> Quoted *text*\n\n\
+![Logo](/logo.png)\n\n---\n\n\
+Line\nbreak © ☺"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_html_as_markdown_and_cache() {
+        let server = serve(site).await;
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new().with_private_network(true);
+
+        let url = format!("{}/docs", server.base);
+        let result = tool
+            .execute(json!({"url": url}), &context(&dir))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.output,
+            format!("URL: {}\n\n# Guide\n\nRead the [API](/api).", url)
+        );
+
+        // 第二次命中缓存
+        tool.execute(json!({"url": url}), &context(&dir))
+            .await
+            .unwrap();
+        assert_eq!(server.hits.load(Ordering::SeqCst), 1);
+
+        // 缓存过期后重新请求
+        let tool = WebFetchTool::new()
+            .with_private_network(true)
+            .with_cache_ttl(Duration::ZERO);
+        for _ in 0..2 {
+            tool.execute(json!({"url": url}), &context(&dir))
+                .await
+                .unwrap();
+        }
+        assert_eq!(server.hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_redirects() {
+        let server = serve(site).await;
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new().with_private_network(true);
+
+        // 同一主机内的重定向直接跟随
+        let result = tool
+            .execute(
+                json!({"url": format!("{}/moved", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap();
+        assert!(result
+            .output
+            .starts_with(&format!("URL: {}/docs\n\n# Guide", server.base)));
+
+        // 跳转到其他主机且没有批准时不跟随
+        let result = tool
+            .execute(
+                json!({"url": format!("{}/away", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap();
+        assert!(result.output.contains("redirected to a different origin"));
+        assert!(result.output.contains("http://localhost:"));
+        assert_eq!(server.hits.load(Ordering::SeqCst), 3);
+
+        // 用户批准后跟随
+        let permissions = PermissionEngine::new(dir.path(), PermissionRules::default())
+            .with_prompt(Arc::new(AllowAll));
+        let approved = context(&dir).with_permissions(Arc::new(permissions));
+        let result = tool
+            .execute(json!({"url": format!("{}/away", server.base)}), &approved)
+            .await
+            .unwrap();
+        assert!(result.output.starts_with("URL: http://localhost:"));
+        assert!(result.output.ends_with("# Guide\n\nRead the [API](/api)."));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_limits_and_errors() {
+        let server = serve(site).await;
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new()
+            .with_private_network(true)
+            .with_max_bytes(1024);
+
+        let err = tool
+            .execute(
+                json!({"url": format!("{}/big", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds the 1024 byte limit"));
+
+        let result = tool
+            .execute(
+                json!({"url": format!("{}/data", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap();
+        assert!(result.output.ends_with("{\"ok\":true}"));
+
+        let err = tool
+            .execute(
+                json!({"url": format!("{}/image", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unsupported content type"));
+
+        let err = tool
+            .execute(
+                json!({"url": format!("{}/nope", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("HTTP 404"));
+
+        let err = tool
+            .execute(json!({"url": "file:///etc/passwd"}), &context(&dir))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Only http and https"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // 接受连接但从不响应
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                open.push(stream);
+            }
+        });
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new()
+            .with_private_network(true)
+            .with_timeout(Duration::from_millis(200));
+        let err = tool
+            .execute(json!({"url": format!("http://{}/", addr)}), &context(&dir))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Failed to fetch"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_redirect_to_other_port_requires_approval() {
+        let other = serve(site).await;
+        let target = format!("{}/docs", other.base);
+        let server = serve(move |path, base| match path {
+            "/hop" => (
+                "302 Found",
+                "text/html",
+                vec![("Location", target.clone())],
+                String::new(),
+            ),
+            _ => site(path, base),
+        })
+        .await;
+        let dir = TempDir::new().unwrap();
+
+        let result = WebFetchTool::new()
+            .with_private_network(true)
+            .execute(
+                json!({"url": format!("{}/hop", server.base)}),
+                &context(&dir),
+            )
+            .await
+            .unwrap();
+        assert!(result.output.contains("redirected to a different origin"));
+        assert_eq!(other.hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_overall_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // 每一跳都稍慢地返回同源重定向，单次请求不会超时
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 302 Found\r\nLocation: /next\r\n\
+Content-Length: 0\r\nConnection: close\r\n\r\n",
+                        )
+                        .await;
+                });
+            }
+        });
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new()
+            .with_private_network(true)
+            .with_timeout(Duration::from_millis(350));
+
+        let started = Instant::now();
+        let err = tool
+            .execute(json!({"url": format!("http://{}/", addr)}), &context(&dir))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("timed out after 350 ms"),
+            "{}",
+            err
+        );
+        assert!(started.elapsed() < Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_private_network_by_default() {
+        let server = serve(site).await;
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new();
+
+        for url in [
+            format!("{}/docs", server.base),
+            "http://localhost:1/".to_string(),
+            "http://[::1]:1/".to_string(),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+            "http://10.0.0.1/".to_string(),
+            "http://[::ffff:192.168.1.1]/".to_string(),
+        ] {
+            let err = tool
+                .execute(json!({ "url": url }), &context(&dir))
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("private network addresses"),
+                "{}: {}",
+                url,
+                err
+            );
+        }
+        assert_eq!(server.hits.load(Ordering::SeqCst), 0);
+
+        // 域名解析到本机地址同样被拒绝
+        let resolved = PublicResolver { proxy_host: None }
+            .resolve("localhost".parse().unwrap())
+            .await;
+        assert!(resolved.is_err());
+        let proxy = PublicResolver {
+            proxy_host: Some("localhost".to_string()),
+        }
+        .resolve("localhost".parse().unwrap())
+        .await;
+        assert!(proxy.is_ok());
+
+        for (ip, public) in [
+            ("93.184.216.34", true),
+            ("100.64.0.1", false),
+            ("172.16.0.1", false),
+            ("0.0.0.0", false),
+            ("2606:4700::1111", true),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+        ] {
+            assert_eq!(is_public_ip(ip.parse().unwrap()), public, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_permission_request() {
+        let dir = TempDir::new().unwrap();
+        let tool = WebFetchTool::new();
+        assert!(tool.requires_permission());
+        assert!(!tool.is_read_only());
+
+        let request = tool.permission_request(
+            &json!({"url": "https://Docs.rs/tokio/latest"}),
+            &context(&dir),
+        );
+        assert_eq!(request.access, AccessKind::Execute);
+        assert_eq!(
+            request.target,
+            PermissionTarget::Domain("docs.rs".to_string())
+        );
+
+        let engine = PermissionEngine::new(
+            dir.path(),
+            PermissionRules::from_configs(
+                &GlobalConfig::default(),
+                &ProjectConfig {
+                    allowed_tools: vec!["WebFetch(domain:docs.rs)".to_string()],
+                    ..Default::default()
+                },
+            ),
+        );
+        assert_eq!(engine.evaluate(&request), PermissionCheck::Allow);
+
+        let other =
+            tool.permission_request(&json!({"url": "http://169.254.169.254/"}), &context(&dir));
+        match engine.evaluate(&other) {
+            PermissionCheck::Ask { suggestions, .. } => assert_eq!(
+                suggestions,
+                vec![PermissionRule::with_content(
+                    "WebFetch",
+                    "domain:169.254.169.254"
+                )]
+            ),
+            check => panic!("unexpected {:?}", check),
+        }
+
+        // 计划模式只允许只读工具
+        engine.set_mode(PermissionMode::Plan);
+        assert!(matches!(
+            engine.evaluate(&request),
+            PermissionCheck::Deny { .. }
+        ));
+    }
+
+    struct Summarizer {
+        prompts: Mutex<Vec<(String, Option<String>)>>,
+    }
+
+    #[async_trait]
+    impl ModelAdapter for Summarizer {
+        async fn send_message(
+            &self,
+            messages: Vec<Message>,
+            system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<ModelResponse> {
+            let text = match &messages[0].content {
+                kode_core::message::MessageContent::Text(text) => text.clone(),
+                _ => String::new(),
+            };
+            self.prompts.lock().unwrap().push((text, system_prompt));
+            Ok(ModelResponse {
+                content: "The guide links to the API.".to_string(),
+                usage: kode_core::model::TokenUsage {
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    total_tokens: None,
+                },
+                model: "quick".to_string(),
+            })
+        }
+
+        async fn stream_message(
+            &self,
+            _messages: Vec<Message>,
+            _system_prompt: Option<String>,
+            _max_tokens: usize,
+        ) -> kode_core::Result<StreamingResponse> {
+            Err(Error::ModelRequestError("not supported".to_string()))
+        }
+
+        fn model_name(&self) -> &str {
+            "quick"
+        }
+    }
+
+    struct Provider {
+        adapter: Arc<Summarizer>,
+        requested: Mutex<Vec<Option<String>>>,
+    }
+
+    impl ModelProvider for Provider {
+        fn adapter(&self, model: Option<&str>) -> kode_core::Result<Arc<dyn ModelAdapter>> {
+            self.requested.lock().unwrap().push(model.map(String::from));
+            Ok(self.adapter.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarize_with_quick_model() {
+        let server = serve(site).await;
+        let dir = TempDir::new().unwrap();
+        let provider = Arc::new(Provider {
+            adapter: Arc::new(Summarizer {
+                prompts: Mutex::new(Vec::new()),
+            }),
+            requested: Mutex::new(Vec::new()),
+        });
+        let pointers = ModelPointers {
+            quick: Some("fast-model".to_string()),
+            ..Default::default()
+        };
+        let tool = WebFetchTool::new()
+            .with_private_network(true)
+            .with_summarizer(provider.clone(), &pointers);
+
+        let result = tool
+            .execute(
+                json!({"url": format!("{}/docs", server.base), "prompt": "What does it link to?"}),
+                &context(&dir),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.output, "The guide links to the API.");
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            [Some("fast-model".to_string())]
+        );
+        let prompts = provider.adapter.prompts.lock().unwrap().clone();
+        assert!(prompts[0].0.contains("# Guide"));
+        assert!(prompts[0].0.ends_with("What does it link to?"));
+        assert_eq!(prompts[0].1.as_deref(), Some(SUMMARY_SYSTEM_PROMPT));
+    }
+}